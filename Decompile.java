@@ -0,0 +1,100 @@
+import java.util.ArrayList;
+import java.util.List;
+
+public class Decompile<T extends Comparable<T>> implements Comparable<Decompile<T>> {
+    private final List<T> items = new ArrayList<>();
+    static final String NAME = "decompile";
+
+    public int compareTo(Decompile<T> other) {
+        return Integer.compare(items.size(), other.items.size());
+    }
+
+    public void add(T item) throws IllegalStateException {
+        if (item == null || items.contains(item)) {
+            throw new IllegalStateException("bad " + item);
+        }
+        items.add(item);
+    }
+
+    static int sum(int... values) {
+        int total = 0;
+        for (int value : values) {
+            if (value < 0 && total > 10) {
+                break;
+            }
+            total += value;
+        }
+        return total;
+    }
+
+    static String describe(char grade) {
+        String result;
+        switch (grade) {
+            case 'a':
+            case 'b':
+                result = "good";
+                break;
+            case 'c':
+                result = "fine";
+            default:
+                result = "bad";
+        }
+        return result;
+    }
+
+    static int search(int[][] grid, int target) {
+        int found = -1;
+        outer:
+        for (int row = 0; row < grid.length; row++) {
+            for (int column = 0; column < grid[row].length; column++) {
+                if (grid[row][column] == target) {
+                    found = row;
+                    break outer;
+                }
+            }
+        }
+        return found;
+    }
+
+    static int countdown(int from) {
+        int steps = 0;
+        do {
+            from /= 2;
+            steps++;
+        } while (from > 1);
+        return steps;
+    }
+
+    static String read(String text) {
+        try {
+            return text.trim();
+        } catch (NullPointerException | IllegalArgumentException error) {
+            return "none";
+        } finally {
+            System.out.println("done");
+        }
+    }
+
+    static int nested(int x) {
+        try {
+            try {
+                return 10 / x;
+            } finally {
+                x++;
+            }
+        } catch (ArithmeticException e) {
+            return x;
+        }
+    }
+
+    static int locked(Object lock, int[] counts) {
+        synchronized (lock) {
+            counts[0]++;
+        }
+        return counts[0];
+    }
+
+    static boolean between(double value, double low, double high) {
+        return value >= low && value <= high || Double.isNaN(value);
+    }
+}
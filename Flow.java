@@ -0,0 +1,57 @@
+public class Flow {
+    static int counter;
+
+    static int classify(int value) {
+        switch (value) {
+            case 1: return 10;
+            case 2: return 20;
+            case 3: return 30;
+            default: return -1;
+        }
+    }
+
+    static int sparse(int value) {
+        switch (value) {
+            case 1: return 1;
+            case 100: return 2;
+            case 1000: return 3;
+            default: return 0;
+        }
+    }
+
+    static int loop(int n) {
+        int sum = 0;
+        for (int i = 0; i < n; i++) {
+            sum += i;
+        }
+        return sum;
+    }
+
+    static int parse(String text) {
+        try {
+            return Integer.parseInt(text);
+        } catch (NumberFormatException e) {
+            return -1;
+        } finally {
+            counter++;
+        }
+    }
+
+    static long longs(long value) {
+        return value * 1000000000000L + 1;
+    }
+
+    static double doubles(double value) {
+        return value / 3.5;
+    }
+
+    static int[] array(int length) {
+        int[] values = new int[length];
+        values[0] = length;
+        return values;
+    }
+
+    static Object nullable(Object value) {
+        return value == null ? "none" : value;
+    }
+}
@@ -0,0 +1,14 @@
+public class Handlers {
+    static boolean compare(long a, long b) {
+        boolean r = false;
+        try {
+            r = a >= b;
+        } catch (RuntimeException e) {
+        }
+        return r;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(compare(2, 1));
+    }
+}
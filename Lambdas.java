@@ -0,0 +1,35 @@
+public class Lambdas {
+    static int apply(Operation operation, int left, int right) {
+        return operation.apply(left, right);
+    }
+
+    static int capturing(int offset) {
+        Operation plus = (left, right) -> left + right + offset;
+        return apply(plus, 3, 4) + plus.twice(10);
+    }
+
+    static int maximum(int left, int right) {
+        return left > right ? left : right;
+    }
+
+    static int reference() {
+        return apply(Lambdas::maximum, 3, 8);
+    }
+
+    int combine(int left, int right) {
+        return left * 10 + right;
+    }
+
+    int bound() {
+        return apply(this::combine, 4, 2);
+    }
+
+    static String describe(int count, long total, char grade, boolean passed, String name, double ratio, float share) {
+        return name + " has " + count + "/" + total + " (" + grade + ", " + passed + ") " + ratio + " " + share + " " + null;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(describe(3, 10000000000L, 'b', true, "Ada", 0.5, 1e10f));
+        System.out.println(capturing(1));
+    }
+}
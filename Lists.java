@@ -0,0 +1,17 @@
+import java.util.AbstractList;
+import java.util.ArrayList;
+import java.util.LinkedList;
+
+public class Lists {
+    static AbstractList<String> make(boolean linked) {
+        int size = 1;
+        size = size;
+        AbstractList<String> list = linked ? new LinkedList<>() : new ArrayList<>();
+        list.add("size " + size);
+        return list;
+    }
+
+    public static void main(String[] args) {
+        System.out.println(make(true) + " " + make(false));
+    }
+}
@@ -0,0 +1,7 @@
+public interface Operation {
+    int apply(int left, int right);
+
+    default int twice(int value) {
+        return apply(value, value);
+    }
+}
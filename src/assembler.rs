@@ -0,0 +1,1078 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use crate::bytecode::{Opcode, Operands};
+use crate::constant_pool::ConstantPoolBuilder;
+use crate::disassembler::{CLASS_FLAGS, FIELD_FLAGS, METHOD_FLAGS};
+use crate::instruction::{encode, with_branch_target, with_constant_pool_index, with_local_index, without_operands, ArrayType, BranchTarget, CpIndex, Instruction};
+use crate::visitor::{ClassVisitor, JavaClassBuilder};
+use crate::{Attribute, AttributeCode, AttributeInfo, AttributeLineNumberTable, AttributeUnknown, BootstrapMethod, ConstantPoolClassInfo, ConstantPoolDoubleInfo, ConstantPoolFieldRefInfo, ConstantPoolFloatInfo, ConstantPoolInfo, ConstantPoolIntegerInfo, ConstantPoolInterfaceMethodRefInfo, ConstantPoolLongInfo, ConstantPoolMethodRefInfo, ConstantPoolNameAndTypeInfo, ConstantPoolStringInfo, ConstantPoolUtf8Info, DynamicInfo, ExceptionTableEntry, InvokeDynamicInfo, JavaClass, LineNumberTableEntry, MethodKindInfo, MethodTypeInfo};
+
+/// Why a source could not be assembled, and on which line.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AssembleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AssembleError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, AssembleError> {
+    Err(AssembleError { line, message: message.into() })
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Word(String),
+    /// A double-quoted literal, with its escapes resolved.
+    Str(String),
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Word(word) => word,
+            Token::Str(string) => string,
+        }
+    }
+}
+
+/// The tokens of one statement. Statements end at line ends and at a `;` that doesn't close a
+/// class type in a descriptor, `//` starts a comment, and tokens are separated by whitespace and commas.
+struct Statement {
+    line: usize,
+    tokens: Vec<Token>,
+}
+
+impl Statement {
+    fn word(&self, position: usize) -> Option<&str> {
+        match self.tokens.get(position) {
+            Some(Token::Word(word)) => Some(word),
+            _ => None,
+        }
+    }
+
+    fn token(&self, position: usize) -> Result<&Token, AssembleError> {
+        self.tokens.get(position).ok_or_else(|| AssembleError { line: self.line, message: "Expected more operands".to_string() })
+    }
+
+    fn text(&self, position: usize) -> Result<&str, AssembleError> {
+        Ok(self.token(position)?.text())
+    }
+
+    fn number<T: TryFrom<i64>>(&self, position: usize) -> Result<T, AssembleError> {
+        let text = self.text(position)?;
+        parse_integer(text).and_then(|value| T::try_from(value).ok())
+            .ok_or_else(|| AssembleError { line: self.line, message: format!("{text} is not a valid number here") })
+    }
+
+    fn expect_end(&self, length: usize) -> Result<(), AssembleError> {
+        match self.tokens.get(length) {
+            Some(token) => error(self.line, format!("Unexpected {}", token.text())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Statement>, AssembleError> {
+    let mut statements = Vec::new();
+    for (line_index, text) in source.lines().enumerate() {
+        let line = line_index + 1;
+        let mut tokens = Vec::new();
+        let mut characters = text.chars().peekable();
+        let mut word = String::new();
+        let finish = |word: &mut String, tokens: &mut Vec<Token>| {
+            if !word.is_empty() {
+                tokens.push(Token::Word(std::mem::take(word)));
+            }
+        };
+        while let Some(character) = characters.next() {
+            match character {
+                '/' if characters.peek() == Some(&'/') => break,
+                ';' if !ends_statement(&word, &tokens, characters.peek()) => word.push(';'),
+                ';' => {
+                    finish(&mut word, &mut tokens);
+                    if !tokens.is_empty() {
+                        statements.push(Statement { line, tokens: std::mem::take(&mut tokens) });
+                    }
+                }
+                '"' if word.is_empty() => tokens.push(Token::Str(read_string(&mut characters, line)?)),
+                '"' => {
+                    // A quoted part inside a word, like javap's Foo."<init>":()V, is kept as written
+                    word.push('"');
+                    loop {
+                        match characters.next() {
+                            Some('"') => break,
+                            Some('\\') => {
+                                word.push('\\');
+                                word.extend(characters.next());
+                            }
+                            Some(character) => word.push(character),
+                            None => return error(line, "Unterminated string"),
+                        }
+                    }
+                    word.push('"');
+                }
+                character if character.is_whitespace() || character == ',' => finish(&mut word, &mut tokens),
+                character => word.push(character),
+            }
+        }
+        finish(&mut word, &mut tokens);
+        if !tokens.is_empty() {
+            statements.push(Statement { line, tokens });
+        }
+    }
+    Ok(statements)
+}
+
+/// Whether a `;` ends the statement rather than the object type being written, as in
+/// `getstatic java/lang/System.out:Ljava/io/PrintStream;` or `.field private name Ljava/lang/String;`.
+fn ends_statement(word: &str, tokens: &[Token], next: Option<&char>) -> bool {
+    if next.is_some_and(|next| !next.is_whitespace()) {
+        return false;
+    }
+    let directive = tokens.first().map(Token::text);
+    if matches!(directive, Some(".class" | ".super" | ".implements" | ".constant" | ".constantvalue" | ".bootstrap" | ".attribute" | ".field" | ".method")) {
+        return false;
+    }
+    match word.rfind(['(', ')', ':', '[', ';']) {
+        Some(position) => !word[position + 1..].starts_with('L'),
+        None => true,
+    }
+}
+
+fn read_string(characters: &mut std::iter::Peekable<std::str::Chars>, line: usize) -> Result<String, AssembleError> {
+    let mut string = String::new();
+    loop {
+        match characters.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => match characters.next() {
+                Some('n') => string.push('\n'),
+                Some('r') => string.push('\r'),
+                Some('t') => string.push('\t'),
+                Some('0') => string.push('\0'),
+                Some('u') => {
+                    let mut hex = String::new();
+                    if characters.next() != Some('{') {
+                        return error(line, "Expected { after \\u");
+                    }
+                    for character in characters.by_ref() {
+                        if character == '}' {
+                            break;
+                        }
+                        hex.push(character);
+                    }
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(character) => string.push(character),
+                        None => return error(line, format!("\\u{{{hex}}} is not a valid character")),
+                    }
+                }
+                Some(character) => string.push(character),
+                None => return error(line, "Unterminated string"),
+            },
+            Some(character) => string.push(character),
+            None => return error(line, "Unterminated string"),
+        }
+    }
+}
+
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => i64::from_str_radix(hex, 16).ok()?,
+        None => digits.parse().ok()?,
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_hex(statement: &Statement, position: usize) -> Result<Vec<u8>, AssembleError> {
+    let text = statement.text(position)?;
+    if text.len() % 2 != 0 {
+        return error(statement.line, "Hex bytes need an even number of digits");
+    }
+    (0..text.len()).step_by(2)
+        .map(|start| u8::from_str_radix(&text[start..start + 2], 16).or_else(|_| error(statement.line, format!("{text} is not hex"))))
+        .collect()
+}
+
+fn parse_flags(statement: &Statement, tokens: &[Token], words: &[(u16, &str)]) -> Result<u16, AssembleError> {
+    let mut flags = 0;
+    for token in tokens {
+        let text = token.text();
+        flags |= match words.iter().find(|(_, word)| *word == text) {
+            Some((flag, _)) => *flag,
+            None => match text.strip_prefix("0x").and_then(|hex| u16::from_str_radix(hex, 16).ok()) {
+                Some(flag) => flag,
+                None => return error(statement.line, format!("{text} is not an access flag")),
+            },
+        };
+    }
+    Ok(flags)
+}
+
+/// Splits `Owner.name:descriptor`, `Owner.name(args)result` or javap's `Owner."<init>":()V`.
+fn parse_member(text: &str) -> Option<(String, String, String)> {
+    let text = text.replace('"', "");
+    let (owner_and_name, descriptor) = match text.find(['(', ':']) {
+        Some(position) if text[position..].starts_with(':') => (&text[..position], &text[position + 1..]),
+        Some(position) => (&text[..position], &text[position..]),
+        None => return None,
+    };
+    let (owner, name) = owner_and_name.rsplit_once('.')?;
+    Some((owner.to_string(), name.to_string(), descriptor.to_string()))
+}
+
+/// The number of argument slots a method descriptor takes, `long` and `double` counting twice.
+fn argument_slots(descriptor: &str) -> usize {
+    let arguments = descriptor.trim_start_matches('(').split(')').next().unwrap_or_default();
+    let mut slots = 0;
+    let mut characters = arguments.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            'J' | 'D' => slots += 2,
+            'L' => {
+                characters.by_ref().find(|character| *character == ';');
+                slots += 1;
+            }
+            '[' => {
+                let mut element = characters.next();
+                while element == Some('[') {
+                    element = characters.next();
+                }
+                if element == Some('L') {
+                    characters.by_ref().find(|character| *character == ';');
+                }
+                slots += 1;
+            }
+            _ => slots += 1,
+        }
+    }
+    slots
+}
+
+enum CodeItem {
+    Label(String),
+    Line(usize),
+    Instruction { line: usize, instruction: Instruction, labels: Vec<String> },
+    Catch { line: usize, catch_type: usize, from: String, to: String, using: String },
+    Attribute { line: usize, name: String, info: Vec<u8> },
+}
+
+/// A `tableswitch` or `lookupswitch` whose cases are still being read.
+struct Switch {
+    line: usize,
+    opcode: Opcode,
+    /// Keys and their labels, `None` being the default.
+    cases: Vec<(Option<i32>, String)>,
+}
+
+/// Collects the statements of a code body and turns them into an [`AttributeCode`].
+struct CodeAssembler<'a> {
+    constant_pool: &'a mut ConstantPoolBuilder,
+    max_stack: usize,
+    max_locals: usize,
+    items: Vec<CodeItem>,
+    switch: Option<Switch>,
+    in_exception_table: bool,
+}
+
+impl<'a> CodeAssembler<'a> {
+    fn new(constant_pool: &'a mut ConstantPoolBuilder) -> Self {
+        CodeAssembler {
+            constant_pool,
+            max_stack: 0,
+            max_locals: 0,
+            items: Vec::new(),
+            switch: None,
+            in_exception_table: false,
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), AssembleError> {
+        let line = statement.line;
+        if self.switch.is_some() {
+            return self.switch_case(statement);
+        }
+        let first = statement.text(0)?;
+        if self.in_exception_table {
+            if first == "from" {
+                return Ok(());
+            }
+            if parse_integer(first).is_some() {
+                let catch_type = match statement.tokens.get(3).map(Token::text) {
+                    Some("any") => 0,
+                    Some("Class") => self.constant_pool.class(statement.text(4)?),
+                    _ => self.class_operand(statement, 3)?,
+                };
+                self.items.push(CodeItem::Catch {
+                    line,
+                    catch_type,
+                    from: first.to_string(),
+                    to: statement.text(1)?.to_string(),
+                    using: statement.text(2)?.to_string(),
+                });
+                return Ok(());
+            }
+            self.in_exception_table = false;
+        }
+        match first {
+            "Code:" => Ok(()),
+            "Exception" if statement.word(1) == Some("table:") => {
+                self.in_exception_table = true;
+                Ok(())
+            }
+            ".limit" => {
+                match statement.text(1)? {
+                    "stack" => self.max_stack = statement.number(2)?,
+                    "locals" => self.max_locals = statement.number(2)?,
+                    other => return error(line, format!("Unknown limit {other}")),
+                }
+                statement.expect_end(3)
+            }
+            _ if first.starts_with("stack=") => {
+                for token in &statement.tokens {
+                    match token.text().split_once('=') {
+                        Some(("stack", value)) => self.max_stack = value.parse().or_else(|_| error(line, "Invalid stack size"))?,
+                        Some(("locals", value)) => self.max_locals = value.parse().or_else(|_| error(line, "Invalid number of locals"))?,
+                        _ => {}
+                    }
+                }
+                Ok(())
+            }
+            ".line" => {
+                self.items.push(CodeItem::Line(statement.number(1)?));
+                statement.expect_end(2)
+            }
+            ".catch" => {
+                let catch_type = match statement.text(1)? {
+                    "any" | "all" => 0,
+                    _ => self.class_operand(statement, 1)?,
+                };
+                if statement.word(2) != Some("from") || statement.word(4) != Some("to") || statement.word(6) != Some("using") {
+                    return error(line, "Expected .catch <class> from <label> to <label> using <label>");
+                }
+                self.items.push(CodeItem::Catch {
+                    line,
+                    catch_type,
+                    from: statement.text(3)?.to_string(),
+                    to: statement.text(5)?.to_string(),
+                    using: statement.text(7)?.to_string(),
+                });
+                statement.expect_end(8)
+            }
+            ".attribute" => {
+                self.items.push(CodeItem::Attribute { line, name: statement.text(1)?.to_string(), info: parse_hex(statement, 2)? });
+                statement.expect_end(3)
+            }
+            _ => match first.strip_suffix(':') {
+                Some(label) if matches!(statement.tokens[0], Token::Word(_)) => {
+                    self.items.push(CodeItem::Label(label.to_string()));
+                    if statement.tokens.len() > 1 {
+                        self.instruction(&Statement { line, tokens: statement.tokens[1..].to_vec() })
+                    } else {
+                        Ok(())
+                    }
+                }
+                _ => self.instruction(statement),
+            },
+        }
+    }
+
+    fn switch_case(&mut self, statement: &Statement) -> Result<(), AssembleError> {
+        let first = statement.text(0)?;
+        if first == "}" {
+            let Switch { line, opcode, cases } = self.switch.take().unwrap();
+            return self.finish_switch(line, opcode, cases);
+        }
+        let key = first.strip_suffix(':').ok_or_else(|| AssembleError { line: statement.line, message: "Expected <key>: <label> or default: <label>".to_string() })?;
+        let key = match key {
+            "default" => None,
+            _ => Some(parse_integer(key).and_then(|key| i32::try_from(key).ok()).ok_or_else(|| AssembleError { line: statement.line, message: format!("{key} is not a valid switch key") })?),
+        };
+        let label = statement.text(1)?.to_string();
+        self.switch.as_mut().unwrap().cases.push((key, label));
+        statement.expect_end(2)
+    }
+
+    fn finish_switch(&mut self, line: usize, opcode: Opcode, cases: Vec<(Option<i32>, String)>) -> Result<(), AssembleError> {
+        let mut labels = Vec::new();
+        let default = match cases.iter().find(|(key, _)| key.is_none()) {
+            Some((_, label)) => label.clone(),
+            None => return error(line, "The switch has no default"),
+        };
+        labels.push(default);
+        let keyed: Vec<(i32, String)> = cases.into_iter().filter_map(|(key, label)| key.map(|key| (key, label))).collect();
+        labels.extend(keyed.iter().map(|(_, label)| label.clone()));
+        let placeholder = BranchTarget(0);
+        let instruction = if opcode == Opcode::Tableswitch {
+            // The JVM requires low <= high, so a tableswitch can't be empty.
+            let Some(&(low, _)) = keyed.first() else {
+                return error(line, "tableswitch needs at least one case, use lookupswitch");
+            };
+            let high = low + (keyed.len() as i32 - 1);
+            if keyed.iter().enumerate().any(|(position, (key, _))| *key != low + position as i32) {
+                return error(line, "tableswitch keys have to be consecutive");
+            }
+            Instruction::Tableswitch { default: placeholder, low, high, targets: vec![placeholder; keyed.len()] }
+        } else {
+            Instruction::Lookupswitch { default: placeholder, pairs: keyed.iter().map(|(key, _)| (*key, placeholder)).collect() }
+        };
+        self.items.push(CodeItem::Instruction { line, instruction, labels });
+        Ok(())
+    }
+
+    fn instruction(&mut self, statement: &Statement) -> Result<(), AssembleError> {
+        let line = statement.line;
+        let mnemonic = statement.text(0)?;
+        let opcode = Opcode::from_mnemonic(mnemonic).ok_or_else(|| AssembleError { line, message: format!("Unknown instruction {mnemonic}") })?;
+        let mut labels = Vec::new();
+        let (instruction, length) = match opcode.operands() {
+            Operands::None => (without_operands(opcode), 1),
+            Operands::Byte => (Instruction::Bipush(statement.number(1)?), 2),
+            Operands::Short => (Instruction::Sipush(statement.number(1)?), 2),
+            Operands::LocalIndex => (with_local_index(opcode, statement.number(1)?), 2),
+            Operands::LocalIndexAndConst => (Instruction::Iinc { index: statement.number(1)?, increment: statement.number(2)? }, 3),
+            Operands::ConstantPoolIndexByte | Operands::ConstantPoolIndex => {
+                let index = self.constant_operand(statement, opcode)?;
+                if opcode == Opcode::Ldc && index > u8::MAX as usize {
+                    return error(line, format!("ldc can't reach constant #{index}, use ldc_w"));
+                }
+                let length = if statement.word(1).is_some_and(|word| matches!(word, "class" | "Method" | "InterfaceMethod" | "Field")) { 3 } else { 2 };
+                (with_constant_pool_index(opcode, CpIndex(index as u16)), length)
+            }
+            Operands::InvokeInterface => {
+                let index = self.constant_operand(statement, opcode)?;
+                let (count, length) = match statement.tokens.get(2) {
+                    Some(token) if !matches!(token, Token::Word(word) if word.starts_with('(')) => (statement.number(2)?, 3),
+                    _ => match self.constant_pool.get(index) {
+                        Some(ConstantPoolInfo::InterfaceMethodRef(method_ref)) => (argument_slots(&method_ref.descriptor) as u8 + 1, 2),
+                        _ => return error(line, "invokeinterface needs its argument count"),
+                    },
+                };
+                (Instruction::Invokeinterface { index: CpIndex(index as u16), count }, length)
+            }
+            Operands::InvokeDynamic => {
+                let index = self.constant_operand(statement, opcode)?;
+                let length = if statement.tokens.len() > 2 { 3 } else { 2 };
+                (Instruction::Invokedynamic(CpIndex(index as u16)), length)
+            }
+            Operands::MultiANewArray => {
+                let index = self.class_operand(statement, 1)?;
+                (Instruction::Multianewarray { index: CpIndex(index as u16), dimensions: statement.number(2)? }, 3)
+            }
+            Operands::ArrayType => {
+                let name = statement.text(1)?;
+                let array_type = (4..=11).filter_map(ArrayType::from_u8).find(|array_type| array_type.name() == name)
+                    .ok_or_else(|| AssembleError { line, message: format!("{name} is not a primitive type") })?;
+                (Instruction::Newarray(array_type), 2)
+            }
+            Operands::Branch | Operands::WideBranch => {
+                labels.push(statement.text(1)?.to_string());
+                (with_branch_target(opcode, BranchTarget(0)), 2)
+            }
+            Operands::TableSwitch | Operands::LookupSwitch => {
+                if statement.word(1) != Some("{") {
+                    return error(line, format!("Expected {{ after {mnemonic}"));
+                }
+                self.switch = Some(Switch { line, opcode, cases: Vec::new() });
+                return statement.expect_end(2);
+            }
+            Operands::Wide => return error(line, "wide is implied by the operands of the instruction it modifies"),
+        };
+        self.items.push(CodeItem::Instruction { line, instruction, labels });
+        statement.expect_end(length)
+    }
+
+    /// A `#index` operand, or a class name to look up or add.
+    fn class_operand(&mut self, statement: &Statement, position: usize) -> Result<usize, AssembleError> {
+        let text = statement.text(position)?;
+        match text.strip_prefix('#') {
+            Some(index) => index.parse().or_else(|_| error(statement.line, format!("{text} is not a constant pool index"))),
+            None => Ok(self.constant_pool.class(&text.replace('"', ""))),
+        }
+    }
+
+    /// The constant an instruction refers to: `#index`, or a literal or member reference to look up or add.
+    fn constant_operand(&mut self, statement: &Statement, opcode: Opcode) -> Result<usize, AssembleError> {
+        let line = statement.line;
+        let token = statement.token(1)?.clone();
+        if let Token::Word(word) = &token {
+            if let Some(index) = word.strip_prefix('#') {
+                return index.parse().or_else(|_| error(line, format!("{word} is not a constant pool index")));
+            }
+        }
+        let member = |position: usize| -> Result<(String, String, String), AssembleError> {
+            let text = statement.text(position)?;
+            let text = match statement.tokens.get(position + 1) {
+                Some(Token::Word(descriptor)) if descriptor.starts_with('(') || !text.contains([':', '(']) => format!("{text}:{descriptor}"),
+                _ => text.to_string(),
+            };
+            parse_member(&text).ok_or_else(|| AssembleError { line, message: format!("Expected Owner.name:descriptor, found {text}") })
+        };
+        let kind = statement.word(1).filter(|word| matches!(*word, "class" | "Method" | "InterfaceMethod" | "Field"));
+        let position = if kind.is_some() { 2 } else { 1 };
+        match opcode {
+            Opcode::Ldc | Opcode::LdcW | Opcode::Ldc2W => match (&token, kind) {
+                (Token::Str(string), _) => Ok(self.constant_pool.string(string)),
+                (_, Some("class")) => self.class_operand(statement, 2),
+                (Token::Word(word), _) => self.number_constant(line, word, opcode == Opcode::Ldc2W),
+            },
+            Opcode::Getstatic | Opcode::Putstatic | Opcode::Getfield | Opcode::Putfield => {
+                let (owner, name, descriptor) = member(position)?;
+                Ok(self.constant_pool.field_ref(&owner, &name, &descriptor))
+            }
+            Opcode::Invokeinterface => {
+                let (owner, name, descriptor) = member(position)?;
+                Ok(self.constant_pool.interface_method_ref(&owner, &name, &descriptor))
+            }
+            Opcode::Invokevirtual | Opcode::Invokespecial | Opcode::Invokestatic => {
+                let (owner, name, descriptor) = member(position)?;
+                if kind == Some("InterfaceMethod") {
+                    Ok(self.constant_pool.interface_method_ref(&owner, &name, &descriptor))
+                } else {
+                    Ok(self.constant_pool.method_ref(&owner, &name, &descriptor))
+                }
+            }
+            Opcode::Invokedynamic => error(line, "invokedynamic needs a #index, its bootstrap method can't be written symbolically"),
+            _ => self.class_operand(statement, position),
+        }
+    }
+
+    fn number_constant(&mut self, line: usize, text: &str, wide: bool) -> Result<usize, AssembleError> {
+        let invalid = || AssembleError { line, message: format!("{text} is not a valid constant") };
+        let is_floating = text.contains(['.', 'e', 'E']) && !text.starts_with("0x") || text.ends_with(['f', 'F', 'd', 'D']) && !text.starts_with("0x");
+        if wide {
+            if is_floating {
+                Ok(self.constant_pool.double(text.trim_end_matches(['d', 'D']).parse().map_err(|_| invalid())?))
+            } else {
+                Ok(self.constant_pool.long(parse_integer(text.trim_end_matches(['l', 'L'])).ok_or_else(invalid)?))
+            }
+        } else if is_floating {
+            Ok(self.constant_pool.float(text.trim_end_matches(['f', 'F']).parse().map_err(|_| invalid())?))
+        } else {
+            Ok(self.constant_pool.integer(parse_integer(text).and_then(|value| i32::try_from(value).ok()).ok_or_else(invalid)?))
+        }
+    }
+
+    fn finish(self, line: usize) -> Result<AttributeCode, AssembleError> {
+        if self.switch.is_some() {
+            return error(line, "The switch is missing its closing }");
+        }
+        let mut instructions: Vec<(usize, Instruction, Vec<String>)> = Vec::new();
+        let mut label_positions: HashMap<String, usize> = HashMap::new();
+        let mut line_numbers: Vec<(usize, usize)> = Vec::new();
+        let mut catches = Vec::new();
+        let mut attributes = Vec::new();
+        for item in self.items {
+            match item {
+                CodeItem::Label(label) => {
+                    label_positions.insert(label, instructions.len());
+                }
+                CodeItem::Line(line_number) => line_numbers.push((instructions.len(), line_number)),
+                CodeItem::Instruction { line, instruction, labels } => instructions.push((line, instruction, labels)),
+                CodeItem::Catch { line, catch_type, from, to, using } => catches.push((line, catch_type, from, to, using)),
+                CodeItem::Attribute { line, name, info } => attributes.push((line, name, info)),
+            }
+        }
+
+        let mut pcs = Vec::with_capacity(instructions.len() + 1);
+        let mut pc = 0;
+        for (_, instruction, _) in &instructions {
+            pcs.push(pc);
+            pc += instruction.length(pc);
+        }
+        pcs.push(pc);
+        let code_length = pc;
+
+        // Numeric labels that aren't defined are pcs, which is what javap-style listings branch to
+        let resolve = |line: usize, label: &str| -> Result<usize, AssembleError> {
+            match label_positions.get(label) {
+                Some(position) => Ok(pcs[*position]),
+                None => match label.parse::<usize>() {
+                    Ok(pc) if pc <= code_length => Ok(pc),
+                    _ => error(line, format!("Label {label} is not defined")),
+                },
+            }
+        };
+        let mut resolved = Vec::with_capacity(instructions.len());
+        let mut lines = Vec::with_capacity(instructions.len());
+        for (position, (line, mut instruction, labels)) in instructions.into_iter().enumerate() {
+            for (target, label) in instruction.branch_targets_mut().into_iter().zip(&labels) {
+                *target = BranchTarget(resolve(line, label)?);
+            }
+            if instruction.opcode().operands() == Operands::Branch {
+                let offset = instruction.branch_targets()[0].0 as i64 - pcs[position] as i64;
+                if i16::try_from(offset).is_err() {
+                    return error(line, format!("The branch is too far for {}, use the _w form", instruction.opcode().mnemonic()));
+                }
+            }
+            resolved.push(instruction);
+            lines.push(line);
+        }
+        let code = match encode(&resolved) {
+            Ok(code) => code,
+            Err(encode_error) => return error(lines[pcs.iter().position(|pc| *pc == encode_error.pc).unwrap_or_default()], encode_error.kind.to_string()),
+        };
+
+        let mut exceptions = Vec::new();
+        for (line, catch_type, from, to, using) in catches {
+            exceptions.push(ExceptionTableEntry {
+                start_pc: resolve(line, &from)?,
+                end_pc: resolve(line, &to)?,
+                handler_pc: resolve(line, &using)?,
+                catch_pc: catch_type,
+            });
+        }
+
+        let mut code_attributes = Vec::new();
+        if !line_numbers.is_empty() {
+            let attribute_name_index = self.constant_pool.utf8("LineNumberTable");
+            let line_number_entries: Vec<LineNumberTableEntry> = line_numbers.into_iter()
+                .map(|(position, line_number)| LineNumberTableEntry { start_pc: pcs[position], line_number })
+                .collect();
+            code_attributes.push(AttributeInfo {
+                attribute_name_index,
+                attribute: Attribute::LineNumberTable(AttributeLineNumberTable {
+                    attribute_name_index,
+                    attribute_name: "LineNumberTable".to_string(),
+                    attribute_length: 2 + 4 * line_number_entries.len(),
+                    line_number_entries,
+                }),
+            });
+        }
+        for (_, name, info) in attributes {
+            code_attributes.push(AttributeInfo {
+                attribute_name_index: self.constant_pool.utf8(&name),
+                attribute: Attribute::Unknown(AttributeUnknown { attribute_name: name, info }),
+            });
+        }
+
+        Ok(AttributeCode {
+            attribute_name_index: self.constant_pool.utf8("Code"),
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code_length,
+            code,
+            exceptions,
+            attributes: code_attributes,
+        })
+    }
+}
+
+/// Assembles a method body into a `Code` attribute, adding the constants it refers to to `constant_pool`.
+///
+/// One instruction goes on a line, or several separated by `;`, optionally after a `label:`.
+/// Operands are numbers, labels, `#index` constant pool references or symbolic constants like
+/// `ldc "str"`, `ldc2_w 5l`, `getstatic java/lang/System.out:Ljava/io/PrintStream;` and
+/// `invokestatic Foo.bar(I)I`. Switches list `key: label` cases and a `default: label` between
+/// braces. `.limit stack N`, `.limit locals N`, `.line N` and
+/// `.catch <class|any> from <label> to <label> using <label>` are the directives. The javap-style
+/// listing the disassembler prints is accepted as well, with pcs serving as labels.
+pub fn assemble_code(source: &str, constant_pool: &mut ConstantPoolBuilder) -> Result<AttributeCode, AssembleError> {
+    let statements = tokenize(source)?;
+    let mut code_assembler = CodeAssembler::new(constant_pool);
+    for statement in &statements {
+        code_assembler.statement(statement)?;
+    }
+    code_assembler.finish(statements.last().map(|statement| statement.line).unwrap_or(0))
+}
+
+enum MemberAttribute {
+    ConstantValue(usize),
+    Code(AttributeCode),
+    BootstrapMethods(Vec<BootstrapMethod>),
+    Unknown(String, Vec<u8>),
+}
+
+struct Member {
+    access_flags: u16,
+    name: String,
+    descriptor: String,
+    attributes: Vec<MemberAttribute>,
+}
+
+/// Assembles a whole class. On top of the method body syntax of [`assemble_code`] it reads
+///
+/// ```text
+/// .class public super Foo
+/// .version 49 0
+/// .super java/lang/Object
+/// .implements java/lang/Runnable
+/// .field private static count I
+///   .constantvalue 0
+/// .method public static bar (I)I
+///   .limit stack 2
+///   .limit locals 1
+///   iload_0; iconst_2; iadd; ireturn
+/// .end method
+/// ```
+///
+/// `.version` defaults to 49.0 and `.super` to `java/lang/Object`. `.constant #index <kind> ...`
+/// lists constant pool entries explicitly, `.bootstrap #<method handle> #<argument>...` adds an
+/// entry to the class's bootstrap methods and `.attribute <name> <hex>` adds an attribute we don't
+/// model to the class, field, method or code it appears in. The class listing of
+/// [`disassemble_class`](crate::disassembler::disassemble_class) assembles back to an identical class.
+pub fn assemble(source: &str) -> Result<JavaClass, AssembleError> {
+    let statements = tokenize(source)?;
+    let mut constant_pool = ConstantPoolBuilder::new();
+    // Classes are looked up once the constants listed explicitly are in the pool
+    let mut class: Option<(u16, String)> = None;
+    let mut version = (49, 0);
+    let mut super_class = None;
+    let mut interfaces = Vec::new();
+    let mut class_attributes = Vec::new();
+    let mut fields: Vec<Member> = Vec::new();
+    let mut methods: Vec<Member> = Vec::new();
+    // Where member statements go: 0 for the class, 1 for the last field, 2 for the last method
+    let mut target = 0;
+    let mut code: Option<(usize, Vec<&Statement>)> = None;
+
+    let finish_code = |code: &mut Option<(usize, Vec<&Statement>)>, methods: &mut Vec<Member>, constant_pool: &mut ConstantPoolBuilder| -> Result<(), AssembleError> {
+        if let Some((line, statements)) = code.take() {
+            let mut code_assembler = CodeAssembler::new(constant_pool);
+            for statement in &statements {
+                code_assembler.statement(statement)?;
+            }
+            let attribute_code = code_assembler.finish(statements.last().map(|statement| statement.line).unwrap_or(line))?;
+            methods.last_mut().unwrap().attributes.push(MemberAttribute::Code(attribute_code));
+        }
+        Ok(())
+    };
+
+    for statement in &statements {
+        let line = statement.line;
+        let first = statement.text(0)?;
+        if let Some((_, body)) = &mut code {
+            if !matches!(first, ".end" | ".method" | ".field") {
+                body.push(statement);
+                continue;
+            }
+        }
+        finish_code(&mut code, &mut methods, &mut constant_pool)?;
+        match first {
+            ".class" => {
+                let name = statement.tokens.last().filter(|_| statement.tokens.len() > 1).ok_or_else(|| AssembleError { line, message: "The class needs a name".to_string() })?;
+                let flags = parse_flags(statement, &statement.tokens[1..statement.tokens.len() - 1], CLASS_FLAGS)?;
+                class = Some((flags, name.text().to_string()));
+            }
+            ".version" => {
+                version = (statement.number(1)?, statement.number(2)?);
+                statement.expect_end(3)?;
+            }
+            ".super" => {
+                super_class = Some((line, statement.text(1)?.to_string()));
+                statement.expect_end(2)?;
+            }
+            ".implements" => {
+                interfaces.push((line, statement.text(1)?.to_string()));
+                statement.expect_end(2)?;
+            }
+            ".constant" => read_constant(statement, &mut constant_pool)?,
+            ".attribute" => {
+                let attribute = MemberAttribute::Unknown(statement.text(1)?.to_string(), parse_hex(statement, 2)?);
+                match target {
+                    0 => class_attributes.push(attribute),
+                    1 => fields.last_mut().unwrap().attributes.push(attribute),
+                    _ => methods.last_mut().unwrap().attributes.push(attribute),
+                }
+                statement.expect_end(3)?;
+            }
+            ".field" | ".method" => {
+                if statement.tokens.len() < 3 {
+                    return error(line, format!("Expected {first} <flags> <name> <descriptor>"));
+                }
+                let length = statement.tokens.len();
+                let words = if first == ".field" { FIELD_FLAGS } else { METHOD_FLAGS };
+                let member = Member {
+                    access_flags: parse_flags(statement, &statement.tokens[1..length - 2], words)?,
+                    name: statement.text(length - 2)?.to_string(),
+                    descriptor: statement.text(length - 1)?.to_string(),
+                    attributes: Vec::new(),
+                };
+                if first == ".field" {
+                    fields.push(member);
+                    target = 1;
+                } else {
+                    methods.push(member);
+                    target = 2;
+                }
+            }
+            ".bootstrap" if target == 0 => {
+                let indexes = (1..statement.tokens.len()).map(|position| {
+                    let text = statement.text(position)?;
+                    text.strip_prefix('#').and_then(|index| index.parse().ok()).ok_or_else(|| AssembleError { line, message: format!("{text} is not a constant pool index") })
+                }).collect::<Result<Vec<usize>, AssembleError>>()?;
+                let Some((&method_handle_index, argument_indexes)) = indexes.split_first() else {
+                    return error(line, "Expected .bootstrap #<method handle> #<argument>...");
+                };
+                let bootstrap_method = BootstrapMethod { method_handle_index, argument_indexes: argument_indexes.to_vec() };
+                // Consecutive entries make up one BootstrapMethods attribute
+                match class_attributes.last_mut() {
+                    Some(MemberAttribute::BootstrapMethods(bootstrap_methods)) => bootstrap_methods.push(bootstrap_method),
+                    _ => class_attributes.push(MemberAttribute::BootstrapMethods(vec![bootstrap_method])),
+                }
+            }
+            ".constantvalue" if target == 1 => {
+                let field = fields.last_mut().unwrap();
+                let token = statement.token(1)?;
+                let index = match (token, field.descriptor.as_str()) {
+                    (Token::Word(word), _) if word.starts_with('#') => word[1..].parse().or_else(|_| error(line, "Invalid constant pool index"))?,
+                    (Token::Str(string), _) => constant_pool.string(string),
+                    (Token::Word(word), "J") => constant_pool.long(parse_integer(word).ok_or_else(|| AssembleError { line, message: format!("{word} is not a long") })?),
+                    (Token::Word(word), "F") => constant_pool.float(word.trim_end_matches('f').parse().or_else(|_| error(line, format!("{word} is not a float")))?),
+                    (Token::Word(word), "D") => constant_pool.double(word.trim_end_matches('d').parse().or_else(|_| error(line, format!("{word} is not a double")))?),
+                    (Token::Word(_), _) => constant_pool.integer(statement.number(1)?),
+                };
+                field.attributes.push(MemberAttribute::ConstantValue(index));
+                statement.expect_end(2)?;
+            }
+            "Code:" | ".code" if target == 2 => code = Some((line, Vec::new())),
+            ".end" => {
+                match statement.word(1) {
+                    Some("field") | Some("method") => target = 0,
+                    Some("code") | Some("class") => {}
+                    _ => return error(line, "Expected .end field, .end method, .end code or .end class"),
+                }
+            }
+            _ if target == 2 => {
+                // A method body without a Code: line
+                code = Some((line, vec![statement]));
+            }
+            other => return error(line, format!("Unexpected {other}")),
+        }
+    }
+    finish_code(&mut code, &mut methods, &mut constant_pool)?;
+
+    let (access_flags, this_class) = class.ok_or_else(|| AssembleError { line: 1, message: "The source has no .class directive".to_string() })?;
+    let mut class_index = |line: usize, name: &str| match name.strip_prefix('#') {
+        Some(index) => index.parse().or_else(|_| error(line, format!("{name} is not a constant pool index"))),
+        None => Ok(constant_pool.class(name)),
+    };
+    let this_class = class_index(0, &this_class)?;
+    let (super_line, super_name) = super_class.unwrap_or((0, "java/lang/Object".to_string()));
+    let super_class = class_index(super_line, &super_name)?;
+    let interfaces = interfaces.iter().map(|(line, name)| class_index(*line, name)).collect::<Result<Vec<usize>, AssembleError>>()?;
+    let names = |member: &Member, constant_pool: &mut ConstantPoolBuilder| (constant_pool.utf8(&member.name), constant_pool.utf8(&member.descriptor));
+    let field_names: Vec<(usize, usize)> = fields.iter().map(|field| names(field, &mut constant_pool)).collect();
+    let method_names: Vec<(usize, usize)> = methods.iter().map(|method| names(method, &mut constant_pool)).collect();
+    let mut attribute_name = |attribute: &MemberAttribute| match attribute {
+        MemberAttribute::ConstantValue(_) => constant_pool.utf8("ConstantValue"),
+        MemberAttribute::Code(code) => code.attribute_name_index,
+        MemberAttribute::BootstrapMethods(_) => constant_pool.utf8("BootstrapMethods"),
+        MemberAttribute::Unknown(name, _) => constant_pool.utf8(name),
+    };
+    let class_attribute_names: Vec<usize> = class_attributes.iter().map(&mut attribute_name).collect();
+    let field_attribute_names: Vec<Vec<usize>> = fields.iter().map(|field| field.attributes.iter().map(&mut attribute_name).collect()).collect();
+    let method_attribute_names: Vec<Vec<usize>> = methods.iter().map(|method| method.attributes.iter().map(&mut attribute_name).collect()).collect();
+    let constant_pool_infos = constant_pool.build();
+    if constant_pool_infos.len() >= u16::MAX as usize {
+        return error(statements.last().map(|statement| statement.line).unwrap_or(0), "The constant pool has more than 65534 entries");
+    }
+
+    let mut builder = JavaClassBuilder::new();
+    builder.visit_header(0xCAFEBABE, version.1, version.0);
+    builder.visit_constant_pool(&constant_pool_infos);
+    builder.visit_class(access_flags, this_class, super_class, &interfaces);
+    for ((field, (name_index, descriptor_index)), attribute_names) in fields.iter().zip(field_names).zip(field_attribute_names) {
+        let mut field_visitor = builder.visit_field(field.access_flags, name_index, &field.name, descriptor_index, &field.descriptor).unwrap();
+        for (attribute, attribute_name_index) in field.attributes.iter().zip(attribute_names) {
+            match attribute {
+                MemberAttribute::ConstantValue(index) => field_visitor.visit_constant_value(attribute_name_index, *index),
+                MemberAttribute::Unknown(name, info) => field_visitor.visit_attribute(attribute_name_index, name, info),
+                MemberAttribute::Code(_) | MemberAttribute::BootstrapMethods(_) => {}
+            }
+        }
+        field_visitor.visit_end();
+    }
+    for ((method, (name_index, descriptor_index)), attribute_names) in methods.iter().zip(method_names).zip(method_attribute_names) {
+        let mut method_visitor = builder.visit_method(method.access_flags, name_index, &method.name, descriptor_index, &method.descriptor).unwrap();
+        for (attribute, attribute_name_index) in method.attributes.iter().zip(attribute_names) {
+            match attribute {
+                MemberAttribute::Code(code) => {
+                    let mut code_visitor = method_visitor.visit_code(attribute_name_index, code.max_stack, code.max_locals).unwrap();
+                    code.accept(code_visitor.as_mut());
+                    code_visitor.visit_end();
+                }
+                MemberAttribute::Unknown(name, info) => method_visitor.visit_attribute(attribute_name_index, name, info),
+                MemberAttribute::ConstantValue(_) | MemberAttribute::BootstrapMethods(_) => {}
+            }
+        }
+        method_visitor.visit_end();
+    }
+    for (attribute, attribute_name_index) in class_attributes.iter().zip(class_attribute_names) {
+        match attribute {
+            MemberAttribute::BootstrapMethods(bootstrap_methods) => builder.visit_bootstrap_methods(attribute_name_index, bootstrap_methods),
+            MemberAttribute::Unknown(name, info) => builder.visit_attribute(attribute_name_index, name, info),
+            MemberAttribute::ConstantValue(_) | MemberAttribute::Code(_) => {}
+        }
+    }
+    builder.visit_end();
+    Ok(builder.build())
+}
+
+/// Reads a `.constant #index <kind> <operands>` line into the next constant pool slot.
+fn read_constant(statement: &Statement, constant_pool: &mut ConstantPoolBuilder) -> Result<(), AssembleError> {
+    let line = statement.line;
+    let expected_index = constant_pool.next_index();
+    if statement.text(1)? != format!("#{expected_index}") {
+        return error(line, format!("Expected constant #{expected_index}, constants have to be listed in order"));
+    }
+    let index = |position: usize| -> Result<usize, AssembleError> {
+        let text = statement.text(position)?;
+        text.strip_prefix('#').and_then(|index| index.parse().ok()).ok_or_else(|| AssembleError { line, message: format!("{text} is not a constant pool index") })
+    };
+    let bits = |position: usize| -> Result<u64, AssembleError> {
+        let text = statement.text(position)?;
+        text.strip_prefix("0x").and_then(|hex| u64::from_str_radix(hex, 16).ok()).ok_or_else(|| AssembleError { line, message: format!("{text} is not hex bits") })
+    };
+    let kind = statement.text(2)?;
+    let (constant_pool_info, length) = match kind {
+        "Utf8" => match statement.token(3)? {
+            Token::Str(string) => (ConstantPoolInfo::Utf8(ConstantPoolUtf8Info { tag: 1, string: string.clone() }), 4),
+            Token::Word(word) => return error(line, format!("Expected a quoted string, found {word}")),
+        },
+        "Integer" => (ConstantPoolInfo::Integer(ConstantPoolIntegerInfo { tag: 3, value: statement.number(3)? }), 4),
+        "Float" => (ConstantPoolInfo::Float(ConstantPoolFloatInfo { tag: 4, value: f32::from_bits(bits(3)? as u32) }), 4),
+        "Long" => (ConstantPoolInfo::Long(ConstantPoolLongInfo { tag: 5, value: statement.number(3)? }), 4),
+        "Double" => (ConstantPoolInfo::Double(ConstantPoolDoubleInfo { tag: 6, value: f64::from_bits(bits(3)?) }), 4),
+        "Class" => (ConstantPoolInfo::Class(ConstantPoolClassInfo { tag: 7, name_index: index(3)?, name: String::new() }), 4),
+        "String" => (ConstantPoolInfo::String(ConstantPoolStringInfo { tag: 8, name_index: index(3)?, string: String::new() }), 4),
+        "Fieldref" => (ConstantPoolInfo::FieldRef(ConstantPoolFieldRefInfo {
+            tag: 9,
+            class_index: index(3)?,
+            name_and_type_index: index(4)?,
+            class_name: String::new(),
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "Methodref" => (ConstantPoolInfo::MethodRef(ConstantPoolMethodRefInfo {
+            tag: 10,
+            class_index: index(3)?,
+            name_and_type_index: index(4)?,
+            class_name: String::new(),
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "InterfaceMethodref" => (ConstantPoolInfo::InterfaceMethodRef(ConstantPoolInterfaceMethodRefInfo {
+            tag: 11,
+            class_index: index(3)?,
+            name_and_type_index: index(4)?,
+            class_name: String::new(),
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "NameAndType" => (ConstantPoolInfo::NameAndType(ConstantPoolNameAndTypeInfo {
+            tag: 12,
+            name_index: index(3)?,
+            descriptor_index: index(4)?,
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "MethodHandle" => (ConstantPoolInfo::MethodKind(MethodKindInfo {
+            tag: 15,
+            reference_kind: statement.number(3)?,
+            reference_index: index(4)?,
+            class_name: String::new(),
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "MethodType" => (ConstantPoolInfo::MethodType(MethodTypeInfo { tag: 16, descriptor_index: index(3)?, descriptor: String::new() }), 4),
+        "Dynamic" => (ConstantPoolInfo::Dynamic(DynamicInfo {
+            tag: 17,
+            bootstrap_method_attr_index: statement.number(3)?,
+            name_and_type_index: index(4)?,
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        "InvokeDynamic" => (ConstantPoolInfo::InvokeDynamic(InvokeDynamicInfo {
+            tag: 18,
+            bootstrap_method_attr_index: statement.number(3)?,
+            name_and_type_index: index(4)?,
+            name: String::new(),
+            descriptor: String::new(),
+        }), 5),
+        other => return error(line, format!("Unknown constant kind {other}")),
+    };
+    constant_pool.push(constant_pool_info);
+    statement.expect_end(length)
+}
+
+#[test]
+fn test_assembles_hand_written_code() {
+    let mut constant_pool = ConstantPoolBuilder::new();
+    let code = assemble_code(".limit stack 2; .limit locals 2\n iload_1; iconst_2; iadd; ireturn", &mut constant_pool).unwrap();
+    assert_eq!(vec![0x1b, 0x05, 0x60, 0xac], code.code);
+    assert_eq!((2, 2), (code.max_stack, code.max_locals));
+
+    let code = assemble_code("
+        .limit stack 2
+    Start:
+        ldc \"str\"
+        invokestatic Foo.bar(Ljava/lang/String;)I
+        tableswitch {
+            0: Zero
+            1: One
+            default: Start
+        }
+    Zero:
+        iconst_0; ireturn
+    One:
+        getstatic java/lang/System.out:Ljava/io/PrintStream;
+        goto Zero
+    Handler:
+        athrow
+        .catch java/lang/Exception from Start to Zero using Handler
+    ", &mut constant_pool).unwrap();
+    let constant_pool_infos = constant_pool.build();
+    let listing = crate::disassembler::disassemble(&code, &constant_pool_infos);
+    assert!(listing.contains("       0: ldc           #3                  // String str\n"));
+    assert!(listing.contains("       2: invokestatic  #9                  // Method Foo.bar:(Ljava/lang/String;)I\n"));
+    assert!(listing.contains("       5: tableswitch   { // 0 to 1\n                     0: 28\n                     1: 30\n               default: 0\n          }\n"));
+    assert!(listing.contains("      30: getstatic     #15                 // Field java/lang/System.out:Ljava/io/PrintStream;\n      33: goto          28\n"));
+    assert!(listing.ends_with("           0    28    36   Class java/lang/Exception\n"));
+}
+
+#[test]
+fn test_assembly_errors_point_at_the_line() {
+    let mut constant_pool = ConstantPoolBuilder::new();
+    assert_eq!(Err(AssembleError { line: 2, message: "Unknown instruction iadd2".to_string() }), assemble_code("iconst_1\niadd2", &mut constant_pool));
+    assert_eq!(Err(AssembleError { line: 1, message: "Label Nowhere is not defined".to_string() }), assemble_code("goto Nowhere", &mut constant_pool));
+    assert_eq!(Err(AssembleError { line: 1, message: "tableswitch needs at least one case, use lookupswitch".to_string() }),
+        assemble_code("tableswitch {\n    default: End\n}\nEnd:\nreturn", &mut constant_pool));
+}
+
+#[test]
+fn test_assembles_class() {
+    let java_class = assemble("
+        .class public super Adder
+        .method public static add (II)I
+            .limit stack 2; .limit locals 2
+            iload_0; iload_1; iadd; ireturn
+        .end method
+    ").unwrap();
+    assert_eq!("Adder", java_class.this_class_name);
+    assert_eq!(Some("java/lang/Object".to_string()), java_class.super_class_name);
+    assert_eq!("add", java_class.methods[0].name);
+    assert_eq!(vec![0x1a, 0x1b, 0x60, 0xac], java_class.methods[0].code().unwrap().code);
+    assert_eq!(java_class, crate::JavaClass::from_bytes(java_class.to_bytes()).unwrap());
+}
+
+#[test]
+fn test_disassembled_classes_reassemble_identically() {
+    for class_file in ["MyClass.class", "Flow.class", "Lambdas.class"] {
+        let java_class = crate::JavaClass::load_from_file(class_file).unwrap();
+        let listing = crate::disassembler::disassemble_class(&java_class);
+        let reassembled = assemble(&listing).unwrap();
+        assert_eq!(java_class, reassembled);
+        assert_eq!(std::fs::read(class_file).unwrap(), reassembled.to_bytes());
+    }
+}
@@ -1,52 +1,387 @@
-pub type Operation = u8;
-
-pub const NOP: Operation = 0x0;
-pub const ACONST_NULL: Operation = 0x1;
-pub const ICONST_M1: Operation = 0x2;
-pub const ICONST_0: Operation = 0x3;
-pub const ICONST_1: Operation = 0x4;
-pub const ICONST_2: Operation = 0x5;
-pub const ICONST_3: Operation = 0x6;
-pub const ICONST_4: Operation = 0x7;
-pub const ICONST_5: Operation = 0x8;
-pub const LCONST_0: Operation = 0x9;
-pub const LCONST_1: Operation = 0xa;
-pub const FCONST_0: Operation = 0xb;
-pub const FCONST_1: Operation = 0xc;
-pub const FCONST_2: Operation = 0xd;
-pub const DCONST_0: Operation = 0xe;
-pub const DCONST_1: Operation = 0xf;
-pub const BIPUSH: Operation = 0x10;
-pub const SIPUSH: Operation = 0x11;
-pub const LDC: Operation = 0x12;
-pub const LDC_W: Operation = 0x13;
-pub const LDC2_W: Operation = 0x14;
-pub const ILOAD: Operation = 0x15;
-pub const LLOAD: Operation = 0x16;
-pub const FLOAD: Operation = 0x17;
-pub const DLOAD: Operation = 0x18;
-pub const ALOAD: Operation = 0x19;
-pub const ILOAD_0: Operation = 0x1a;
-pub const ILOAD_1: Operation = 0x1b;
-pub const ILOAD_2: Operation = 0x1c;
-pub const ILOAD_3: Operation = 0x1d;
-pub const LLOAD_0: Operation = 0x1e;
-pub const LLOAD_1: Operation = 0x1f;
-pub const LLOAD_2: Operation = 0x20;
-pub const LLOAD_3: Operation = 0x21;
-pub const FLOAD_0: Operation = 0x22;
-pub const FLOAD_1: Operation = 0x23;
-pub const FLOAD_2: Operation = 0x24;
-pub const FLOAD_3: Operation = 0x25;
-pub const DLOAD_0: Operation = 0x26;
-pub const DLOAD_1: Operation = 0x27;
-pub const DLOAD_2: Operation = 0x28;
-pub const DLOAD_3: Operation = 0x29;
-pub const ALOAD_0: Operation = 0x2a;
-pub const ALOAD_1: Operation = 0x2b;
-pub const ALOAD_2: Operation = 0x2c;
-pub const ALOAD_3: Operation = 0x2d;
-pub const INVOKE_SPECIAL: Operation = 0xb7;
-
-pub const AALOAD: Operation = 0x50;
-pub const IADD: Operation = 0x60;
+/// The computational type category of a value on the operand stack. `long` and `double` are
+/// category 2 and take two slots; everything else is category 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Category {
+    One,
+    Two,
+}
+
+use Category::One as C1;
+use Category::Two as C2;
+
+/// How the operands following an opcode are encoded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operands {
+    None,
+    /// A signed byte, as pushed by `bipush`.
+    Byte,
+    /// A signed short, as pushed by `sipush`.
+    Short,
+    /// An unsigned byte local variable index, or an unsigned short one after `wide`.
+    LocalIndex,
+    /// `iinc`'s local variable index and signed increment, a byte each or a short each after `wide`.
+    LocalIndexAndConst,
+    /// An unsigned byte constant pool index, used only by `ldc`.
+    ConstantPoolIndexByte,
+    ConstantPoolIndex,
+    /// A constant pool index, the argument count and a zero byte.
+    InvokeInterface,
+    /// A constant pool index and two zero bytes.
+    InvokeDynamic,
+    /// A constant pool index and the number of dimensions.
+    MultiANewArray,
+    /// The `atype` of `newarray`.
+    ArrayType,
+    /// A signed short offset from the instruction.
+    Branch,
+    /// A signed int offset from the instruction.
+    WideBranch,
+    /// Padding to a multiple of four from the start of the code, then default, low, high and the offsets.
+    TableSwitch,
+    /// Padding to a multiple of four from the start of the code, then default, the pair count and the pairs.
+    LookupSwitch,
+    /// The opcode being modified, followed by its operands widened to shorts.
+    Wide,
+}
+
+impl Operands {
+    /// The number of bytes the operands take, or `None` when that depends on the code around them.
+    pub fn length(self) -> Option<usize> {
+        match self {
+            Operands::None => Some(0),
+            Operands::Byte | Operands::LocalIndex | Operands::ConstantPoolIndexByte | Operands::ArrayType => Some(1),
+            Operands::Short | Operands::LocalIndexAndConst | Operands::ConstantPoolIndex | Operands::Branch => Some(2),
+            Operands::MultiANewArray => Some(3),
+            Operands::InvokeInterface | Operands::InvokeDynamic | Operands::WideBranch => Some(4),
+            Operands::TableSwitch | Operands::LookupSwitch | Operands::Wide => None,
+        }
+    }
+}
+
+/// What an instruction takes from and leaves on the operand stack.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum StackEffect {
+    /// Pops and pushes values of these categories, listed from the bottom of the stack up.
+    Fixed { pops: &'static [Category], pushes: &'static [Category] },
+    /// Works on slots whatever the categories of the values in them, like `pop2` and `dup2`.
+    Slots { pops: usize, pushes: usize },
+    /// Depends on the operands: the descriptor of the referenced field or method, the dimensions
+    /// of `multianewarray`, or the instruction `wide` modifies.
+    Variable,
+}
+
+const fn fixed(pops: &'static [Category], pushes: &'static [Category]) -> StackEffect {
+    StackEffect::Fixed { pops, pushes }
+}
+
+const NONE: u8 = 0;
+const THROWS: u8 = 1;
+const BRANCHES: u8 = 2;
+/// Execution never continues with the next instruction.
+const ENDS_BLOCK: u8 = 4;
+
+macro_rules! opcodes {
+    ($($name:ident = $code:literal, $mnemonic:literal, $operands:expr, $stack_effect:expr, $flags:expr;)*) => {
+        /// Every opcode the JVMS defines, from `nop` (0x00) to `jsr_w` (0xc9). The reserved
+        /// `breakpoint`, `impdep1` and `impdep2` never appear in class files and are left out.
+        #[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+        #[repr(u8)]
+        pub enum Opcode {
+            $($name = $code,)*
+        }
+
+        impl Opcode {
+            pub fn from_u8(byte: u8) -> Option<Opcode> {
+                match byte {
+                    $($code => Some(Opcode::$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn from_mnemonic(mnemonic: &str) -> Option<Opcode> {
+                match mnemonic {
+                    $($mnemonic => Some(Opcode::$name),)*
+                    _ => None,
+                }
+            }
+
+            pub fn mnemonic(self) -> &'static str {
+                match self {
+                    $(Opcode::$name => $mnemonic,)*
+                }
+            }
+
+            pub fn operands(self) -> Operands {
+                match self {
+                    $(Opcode::$name => $operands,)*
+                }
+            }
+
+            pub fn stack_effect(self) -> StackEffect {
+                match self {
+                    $(Opcode::$name => $stack_effect,)*
+                }
+            }
+
+            fn flags(self) -> u8 {
+                match self {
+                    $(Opcode::$name => $flags,)*
+                }
+            }
+        }
+    };
+}
+
+impl Opcode {
+    /// Whether the instruction can complete abruptly with an exception, counting linkage errors
+    /// from resolution and `IllegalMonitorStateException` from the returns.
+    pub fn can_throw(self) -> bool {
+        self.flags() & THROWS != 0
+    }
+
+    /// Whether the instruction can transfer control somewhere other than the next instruction:
+    /// conditional and unconditional jumps, subroutine calls and returns, and switches.
+    pub fn can_branch(self) -> bool {
+        self.flags() & BRANCHES != 0
+    }
+
+    /// Whether execution can continue with the next instruction. It can't after `goto`, `ret`,
+    /// the switches, the returns and `athrow`.
+    pub fn can_fall_through(self) -> bool {
+        self.flags() & ENDS_BLOCK == 0
+    }
+}
+
+opcodes! {
+    Nop = 0x00, "nop", Operands::None, fixed(&[], &[]), NONE;
+    AconstNull = 0x01, "aconst_null", Operands::None, fixed(&[], &[C1]), NONE;
+    IconstM1 = 0x02, "iconst_m1", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst0 = 0x03, "iconst_0", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst1 = 0x04, "iconst_1", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst2 = 0x05, "iconst_2", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst3 = 0x06, "iconst_3", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst4 = 0x07, "iconst_4", Operands::None, fixed(&[], &[C1]), NONE;
+    Iconst5 = 0x08, "iconst_5", Operands::None, fixed(&[], &[C1]), NONE;
+    Lconst0 = 0x09, "lconst_0", Operands::None, fixed(&[], &[C2]), NONE;
+    Lconst1 = 0x0a, "lconst_1", Operands::None, fixed(&[], &[C2]), NONE;
+    Fconst0 = 0x0b, "fconst_0", Operands::None, fixed(&[], &[C1]), NONE;
+    Fconst1 = 0x0c, "fconst_1", Operands::None, fixed(&[], &[C1]), NONE;
+    Fconst2 = 0x0d, "fconst_2", Operands::None, fixed(&[], &[C1]), NONE;
+    Dconst0 = 0x0e, "dconst_0", Operands::None, fixed(&[], &[C2]), NONE;
+    Dconst1 = 0x0f, "dconst_1", Operands::None, fixed(&[], &[C2]), NONE;
+    Bipush = 0x10, "bipush", Operands::Byte, fixed(&[], &[C1]), NONE;
+    Sipush = 0x11, "sipush", Operands::Short, fixed(&[], &[C1]), NONE;
+    Ldc = 0x12, "ldc", Operands::ConstantPoolIndexByte, fixed(&[], &[C1]), THROWS;
+    LdcW = 0x13, "ldc_w", Operands::ConstantPoolIndex, fixed(&[], &[C1]), THROWS;
+    Ldc2W = 0x14, "ldc2_w", Operands::ConstantPoolIndex, fixed(&[], &[C2]), NONE;
+    Iload = 0x15, "iload", Operands::LocalIndex, fixed(&[], &[C1]), NONE;
+    Lload = 0x16, "lload", Operands::LocalIndex, fixed(&[], &[C2]), NONE;
+    Fload = 0x17, "fload", Operands::LocalIndex, fixed(&[], &[C1]), NONE;
+    Dload = 0x18, "dload", Operands::LocalIndex, fixed(&[], &[C2]), NONE;
+    Aload = 0x19, "aload", Operands::LocalIndex, fixed(&[], &[C1]), NONE;
+    Iload0 = 0x1a, "iload_0", Operands::None, fixed(&[], &[C1]), NONE;
+    Iload1 = 0x1b, "iload_1", Operands::None, fixed(&[], &[C1]), NONE;
+    Iload2 = 0x1c, "iload_2", Operands::None, fixed(&[], &[C1]), NONE;
+    Iload3 = 0x1d, "iload_3", Operands::None, fixed(&[], &[C1]), NONE;
+    Lload0 = 0x1e, "lload_0", Operands::None, fixed(&[], &[C2]), NONE;
+    Lload1 = 0x1f, "lload_1", Operands::None, fixed(&[], &[C2]), NONE;
+    Lload2 = 0x20, "lload_2", Operands::None, fixed(&[], &[C2]), NONE;
+    Lload3 = 0x21, "lload_3", Operands::None, fixed(&[], &[C2]), NONE;
+    Fload0 = 0x22, "fload_0", Operands::None, fixed(&[], &[C1]), NONE;
+    Fload1 = 0x23, "fload_1", Operands::None, fixed(&[], &[C1]), NONE;
+    Fload2 = 0x24, "fload_2", Operands::None, fixed(&[], &[C1]), NONE;
+    Fload3 = 0x25, "fload_3", Operands::None, fixed(&[], &[C1]), NONE;
+    Dload0 = 0x26, "dload_0", Operands::None, fixed(&[], &[C2]), NONE;
+    Dload1 = 0x27, "dload_1", Operands::None, fixed(&[], &[C2]), NONE;
+    Dload2 = 0x28, "dload_2", Operands::None, fixed(&[], &[C2]), NONE;
+    Dload3 = 0x29, "dload_3", Operands::None, fixed(&[], &[C2]), NONE;
+    Aload0 = 0x2a, "aload_0", Operands::None, fixed(&[], &[C1]), NONE;
+    Aload1 = 0x2b, "aload_1", Operands::None, fixed(&[], &[C1]), NONE;
+    Aload2 = 0x2c, "aload_2", Operands::None, fixed(&[], &[C1]), NONE;
+    Aload3 = 0x2d, "aload_3", Operands::None, fixed(&[], &[C1]), NONE;
+    Iaload = 0x2e, "iaload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Laload = 0x2f, "laload", Operands::None, fixed(&[C1, C1], &[C2]), THROWS;
+    Faload = 0x30, "faload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Daload = 0x31, "daload", Operands::None, fixed(&[C1, C1], &[C2]), THROWS;
+    Aaload = 0x32, "aaload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Baload = 0x33, "baload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Caload = 0x34, "caload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Saload = 0x35, "saload", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Istore = 0x36, "istore", Operands::LocalIndex, fixed(&[C1], &[]), NONE;
+    Lstore = 0x37, "lstore", Operands::LocalIndex, fixed(&[C2], &[]), NONE;
+    Fstore = 0x38, "fstore", Operands::LocalIndex, fixed(&[C1], &[]), NONE;
+    Dstore = 0x39, "dstore", Operands::LocalIndex, fixed(&[C2], &[]), NONE;
+    Astore = 0x3a, "astore", Operands::LocalIndex, fixed(&[C1], &[]), NONE;
+    Istore0 = 0x3b, "istore_0", Operands::None, fixed(&[C1], &[]), NONE;
+    Istore1 = 0x3c, "istore_1", Operands::None, fixed(&[C1], &[]), NONE;
+    Istore2 = 0x3d, "istore_2", Operands::None, fixed(&[C1], &[]), NONE;
+    Istore3 = 0x3e, "istore_3", Operands::None, fixed(&[C1], &[]), NONE;
+    Lstore0 = 0x3f, "lstore_0", Operands::None, fixed(&[C2], &[]), NONE;
+    Lstore1 = 0x40, "lstore_1", Operands::None, fixed(&[C2], &[]), NONE;
+    Lstore2 = 0x41, "lstore_2", Operands::None, fixed(&[C2], &[]), NONE;
+    Lstore3 = 0x42, "lstore_3", Operands::None, fixed(&[C2], &[]), NONE;
+    Fstore0 = 0x43, "fstore_0", Operands::None, fixed(&[C1], &[]), NONE;
+    Fstore1 = 0x44, "fstore_1", Operands::None, fixed(&[C1], &[]), NONE;
+    Fstore2 = 0x45, "fstore_2", Operands::None, fixed(&[C1], &[]), NONE;
+    Fstore3 = 0x46, "fstore_3", Operands::None, fixed(&[C1], &[]), NONE;
+    Dstore0 = 0x47, "dstore_0", Operands::None, fixed(&[C2], &[]), NONE;
+    Dstore1 = 0x48, "dstore_1", Operands::None, fixed(&[C2], &[]), NONE;
+    Dstore2 = 0x49, "dstore_2", Operands::None, fixed(&[C2], &[]), NONE;
+    Dstore3 = 0x4a, "dstore_3", Operands::None, fixed(&[C2], &[]), NONE;
+    Astore0 = 0x4b, "astore_0", Operands::None, fixed(&[C1], &[]), NONE;
+    Astore1 = 0x4c, "astore_1", Operands::None, fixed(&[C1], &[]), NONE;
+    Astore2 = 0x4d, "astore_2", Operands::None, fixed(&[C1], &[]), NONE;
+    Astore3 = 0x4e, "astore_3", Operands::None, fixed(&[C1], &[]), NONE;
+    Iastore = 0x4f, "iastore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Lastore = 0x50, "lastore", Operands::None, fixed(&[C1, C1, C2], &[]), THROWS;
+    Fastore = 0x51, "fastore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Dastore = 0x52, "dastore", Operands::None, fixed(&[C1, C1, C2], &[]), THROWS;
+    Aastore = 0x53, "aastore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Bastore = 0x54, "bastore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Castore = 0x55, "castore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Sastore = 0x56, "sastore", Operands::None, fixed(&[C1, C1, C1], &[]), THROWS;
+    Pop = 0x57, "pop", Operands::None, fixed(&[C1], &[]), NONE;
+    Pop2 = 0x58, "pop2", Operands::None, StackEffect::Slots { pops: 2, pushes: 0 }, NONE;
+    Dup = 0x59, "dup", Operands::None, fixed(&[C1], &[C1, C1]), NONE;
+    DupX1 = 0x5a, "dup_x1", Operands::None, fixed(&[C1, C1], &[C1, C1, C1]), NONE;
+    DupX2 = 0x5b, "dup_x2", Operands::None, StackEffect::Slots { pops: 3, pushes: 4 }, NONE;
+    Dup2 = 0x5c, "dup2", Operands::None, StackEffect::Slots { pops: 2, pushes: 4 }, NONE;
+    Dup2X1 = 0x5d, "dup2_x1", Operands::None, StackEffect::Slots { pops: 3, pushes: 5 }, NONE;
+    Dup2X2 = 0x5e, "dup2_x2", Operands::None, StackEffect::Slots { pops: 4, pushes: 6 }, NONE;
+    Swap = 0x5f, "swap", Operands::None, fixed(&[C1, C1], &[C1, C1]), NONE;
+    Iadd = 0x60, "iadd", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Ladd = 0x61, "ladd", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Fadd = 0x62, "fadd", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Dadd = 0x63, "dadd", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Isub = 0x64, "isub", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lsub = 0x65, "lsub", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Fsub = 0x66, "fsub", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Dsub = 0x67, "dsub", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Imul = 0x68, "imul", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lmul = 0x69, "lmul", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Fmul = 0x6a, "fmul", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Dmul = 0x6b, "dmul", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Idiv = 0x6c, "idiv", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Ldiv = 0x6d, "ldiv", Operands::None, fixed(&[C2, C2], &[C2]), THROWS;
+    Fdiv = 0x6e, "fdiv", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Ddiv = 0x6f, "ddiv", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Irem = 0x70, "irem", Operands::None, fixed(&[C1, C1], &[C1]), THROWS;
+    Lrem = 0x71, "lrem", Operands::None, fixed(&[C2, C2], &[C2]), THROWS;
+    Frem = 0x72, "frem", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Drem = 0x73, "drem", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Ineg = 0x74, "ineg", Operands::None, fixed(&[C1], &[C1]), NONE;
+    Lneg = 0x75, "lneg", Operands::None, fixed(&[C2], &[C2]), NONE;
+    Fneg = 0x76, "fneg", Operands::None, fixed(&[C1], &[C1]), NONE;
+    Dneg = 0x77, "dneg", Operands::None, fixed(&[C2], &[C2]), NONE;
+    Ishl = 0x78, "ishl", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lshl = 0x79, "lshl", Operands::None, fixed(&[C2, C1], &[C2]), NONE;
+    Ishr = 0x7a, "ishr", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lshr = 0x7b, "lshr", Operands::None, fixed(&[C2, C1], &[C2]), NONE;
+    Iushr = 0x7c, "iushr", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lushr = 0x7d, "lushr", Operands::None, fixed(&[C2, C1], &[C2]), NONE;
+    Iand = 0x7e, "iand", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Land = 0x7f, "land", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Ior = 0x80, "ior", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lor = 0x81, "lor", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Ixor = 0x82, "ixor", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Lxor = 0x83, "lxor", Operands::None, fixed(&[C2, C2], &[C2]), NONE;
+    Iinc = 0x84, "iinc", Operands::LocalIndexAndConst, fixed(&[], &[]), NONE;
+    I2l = 0x85, "i2l", Operands::None, fixed(&[C1], &[C2]), NONE;
+    I2f = 0x86, "i2f", Operands::None, fixed(&[C1], &[C1]), NONE;
+    I2d = 0x87, "i2d", Operands::None, fixed(&[C1], &[C2]), NONE;
+    L2i = 0x88, "l2i", Operands::None, fixed(&[C2], &[C1]), NONE;
+    L2f = 0x89, "l2f", Operands::None, fixed(&[C2], &[C1]), NONE;
+    L2d = 0x8a, "l2d", Operands::None, fixed(&[C2], &[C2]), NONE;
+    F2i = 0x8b, "f2i", Operands::None, fixed(&[C1], &[C1]), NONE;
+    F2l = 0x8c, "f2l", Operands::None, fixed(&[C1], &[C2]), NONE;
+    F2d = 0x8d, "f2d", Operands::None, fixed(&[C1], &[C2]), NONE;
+    D2i = 0x8e, "d2i", Operands::None, fixed(&[C2], &[C1]), NONE;
+    D2l = 0x8f, "d2l", Operands::None, fixed(&[C2], &[C2]), NONE;
+    D2f = 0x90, "d2f", Operands::None, fixed(&[C2], &[C1]), NONE;
+    I2b = 0x91, "i2b", Operands::None, fixed(&[C1], &[C1]), NONE;
+    I2c = 0x92, "i2c", Operands::None, fixed(&[C1], &[C1]), NONE;
+    I2s = 0x93, "i2s", Operands::None, fixed(&[C1], &[C1]), NONE;
+    Lcmp = 0x94, "lcmp", Operands::None, fixed(&[C2, C2], &[C1]), NONE;
+    Fcmpl = 0x95, "fcmpl", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Fcmpg = 0x96, "fcmpg", Operands::None, fixed(&[C1, C1], &[C1]), NONE;
+    Dcmpl = 0x97, "dcmpl", Operands::None, fixed(&[C2, C2], &[C1]), NONE;
+    Dcmpg = 0x98, "dcmpg", Operands::None, fixed(&[C2, C2], &[C1]), NONE;
+    Ifeq = 0x99, "ifeq", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Ifne = 0x9a, "ifne", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Iflt = 0x9b, "iflt", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Ifge = 0x9c, "ifge", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Ifgt = 0x9d, "ifgt", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Ifle = 0x9e, "ifle", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    IfIcmpeq = 0x9f, "if_icmpeq", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfIcmpne = 0xa0, "if_icmpne", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfIcmplt = 0xa1, "if_icmplt", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfIcmpge = 0xa2, "if_icmpge", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfIcmpgt = 0xa3, "if_icmpgt", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfIcmple = 0xa4, "if_icmple", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfAcmpeq = 0xa5, "if_acmpeq", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    IfAcmpne = 0xa6, "if_acmpne", Operands::Branch, fixed(&[C1, C1], &[]), BRANCHES;
+    Goto = 0xa7, "goto", Operands::Branch, fixed(&[], &[]), BRANCHES | ENDS_BLOCK;
+    Jsr = 0xa8, "jsr", Operands::Branch, fixed(&[], &[C1]), BRANCHES;
+    Ret = 0xa9, "ret", Operands::LocalIndex, fixed(&[], &[]), BRANCHES | ENDS_BLOCK;
+    Tableswitch = 0xaa, "tableswitch", Operands::TableSwitch, fixed(&[C1], &[]), BRANCHES | ENDS_BLOCK;
+    Lookupswitch = 0xab, "lookupswitch", Operands::LookupSwitch, fixed(&[C1], &[]), BRANCHES | ENDS_BLOCK;
+    Ireturn = 0xac, "ireturn", Operands::None, fixed(&[C1], &[]), THROWS | ENDS_BLOCK;
+    Lreturn = 0xad, "lreturn", Operands::None, fixed(&[C2], &[]), THROWS | ENDS_BLOCK;
+    Freturn = 0xae, "freturn", Operands::None, fixed(&[C1], &[]), THROWS | ENDS_BLOCK;
+    Dreturn = 0xaf, "dreturn", Operands::None, fixed(&[C2], &[]), THROWS | ENDS_BLOCK;
+    Areturn = 0xb0, "areturn", Operands::None, fixed(&[C1], &[]), THROWS | ENDS_BLOCK;
+    Return = 0xb1, "return", Operands::None, fixed(&[], &[]), THROWS | ENDS_BLOCK;
+    Getstatic = 0xb2, "getstatic", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Putstatic = 0xb3, "putstatic", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Getfield = 0xb4, "getfield", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Putfield = 0xb5, "putfield", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Invokevirtual = 0xb6, "invokevirtual", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Invokespecial = 0xb7, "invokespecial", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Invokestatic = 0xb8, "invokestatic", Operands::ConstantPoolIndex, StackEffect::Variable, THROWS;
+    Invokeinterface = 0xb9, "invokeinterface", Operands::InvokeInterface, StackEffect::Variable, THROWS;
+    Invokedynamic = 0xba, "invokedynamic", Operands::InvokeDynamic, StackEffect::Variable, THROWS;
+    New = 0xbb, "new", Operands::ConstantPoolIndex, fixed(&[], &[C1]), THROWS;
+    Newarray = 0xbc, "newarray", Operands::ArrayType, fixed(&[C1], &[C1]), THROWS;
+    Anewarray = 0xbd, "anewarray", Operands::ConstantPoolIndex, fixed(&[C1], &[C1]), THROWS;
+    Arraylength = 0xbe, "arraylength", Operands::None, fixed(&[C1], &[C1]), THROWS;
+    Athrow = 0xbf, "athrow", Operands::None, fixed(&[C1], &[]), THROWS | ENDS_BLOCK;
+    Checkcast = 0xc0, "checkcast", Operands::ConstantPoolIndex, fixed(&[C1], &[C1]), THROWS;
+    Instanceof = 0xc1, "instanceof", Operands::ConstantPoolIndex, fixed(&[C1], &[C1]), THROWS;
+    Monitorenter = 0xc2, "monitorenter", Operands::None, fixed(&[C1], &[]), THROWS;
+    Monitorexit = 0xc3, "monitorexit", Operands::None, fixed(&[C1], &[]), THROWS;
+    Wide = 0xc4, "wide", Operands::Wide, StackEffect::Variable, NONE;
+    Multianewarray = 0xc5, "multianewarray", Operands::MultiANewArray, StackEffect::Variable, THROWS;
+    Ifnull = 0xc6, "ifnull", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    Ifnonnull = 0xc7, "ifnonnull", Operands::Branch, fixed(&[C1], &[]), BRANCHES;
+    GotoW = 0xc8, "goto_w", Operands::WideBranch, fixed(&[], &[]), BRANCHES | ENDS_BLOCK;
+    JsrW = 0xc9, "jsr_w", Operands::WideBranch, fixed(&[], &[C1]), BRANCHES;
+}
+
+#[test]
+fn test_every_defined_opcode_is_covered() {
+    let opcodes: Vec<Opcode> = (0..=u8::MAX).filter_map(Opcode::from_u8).collect();
+    assert_eq!(202, opcodes.len());
+    for opcode in opcodes {
+        assert_eq!(Some(opcode), Opcode::from_u8(opcode as u8));
+    }
+    assert_eq!(None, Opcode::from_u8(0xca));
+    assert_eq!(None, Opcode::from_u8(0xff));
+}
+
+#[test]
+fn test_opcode_metadata() {
+    assert_eq!("if_icmplt", Opcode::IfIcmplt.mnemonic());
+    assert_eq!(Some(Opcode::IfIcmplt), Opcode::from_mnemonic("if_icmplt"));
+    assert_eq!(Operands::Branch, Opcode::IfIcmplt.operands());
+    assert!(Opcode::IfIcmplt.can_branch() && Opcode::IfIcmplt.can_fall_through());
+    assert!(!Opcode::Goto.can_fall_through());
+
+    assert_eq!(fixed(&[C2, C1], &[C2]), Opcode::Lshl.stack_effect());
+    assert_eq!(fixed(&[C1, C1, C2], &[]), Opcode::Dastore.stack_effect());
+    assert_eq!(StackEffect::Variable, Opcode::Invokevirtual.stack_effect());
+
+    assert!(Opcode::Idiv.can_throw());
+    assert!(!Opcode::Fdiv.can_throw());
+    assert_eq!(Some(4), Opcode::Invokeinterface.operands().length());
+    assert_eq!(None, Opcode::Tableswitch.operands().length());
+}
@@ -0,0 +1,224 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use crate::class::{Class, Method, MethodRef, RuntimeConstantPool};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::execution::{ExecutionError, InvokeKind};
+use crate::frames::Value;
+use crate::heap::ObjectData;
+use crate::jvm::Jvm;
+use crate::natives::{java_float, java_string};
+use crate::{BootstrapMethod, ClassFlags, ConstantPoolInfo, MethodKindInfo};
+
+/// What an `invokedynamic` instruction is linked to. The JVM implements the bootstrap methods
+/// javac uses itself rather than run them through method handles.
+#[derive(Debug)]
+pub enum CallSite {
+    /// `StringConcatFactory`: concatenates the arguments following a recipe, where `\u{1}` stands
+    /// for the next argument and `\u{2}` for the next constant.
+    Concat { recipe: String, constants: Vec<String>, parameters: Vec<FieldType> },
+    /// `LambdaMetafactory.metafactory`: makes objects of a class spun to implement the functional
+    /// interface, capturing the arguments.
+    Lambda { class: Rc<Class>, target: Rc<LambdaTarget> },
+}
+
+/// The method the functional interface method of a lambda runs.
+#[derive(Debug)]
+pub struct LambdaTarget {
+    pub(crate) kind: InvokeKind,
+    pub(crate) method_ref: MethodRef,
+    /// The class the lambda was made in, which `invokespecial` method handles are relative to.
+    pub(crate) caller: Rc<Class>,
+}
+
+impl CallSite {
+    /// The call site of the `invokedynamic` at `pc` of a method of `class`, linked the first
+    /// time the instruction runs. `index` is the instruction's `InvokeDynamic` constant.
+    pub(crate) fn linked(jvm: &mut Jvm, class: &Rc<Class>, method: &Method, pc: usize, index: usize) -> Result<Rc<CallSite>, ExecutionError> {
+        if let Some(call_site) = method.call_sites.borrow().get(&pc) {
+            return Ok(call_site.clone());
+        }
+        let call_site = Rc::new(link(jvm, class, index)?);
+        method.call_sites.borrow_mut().insert(pc, call_site.clone());
+        Ok(call_site)
+    }
+
+    /// Runs the call site with the arguments popped off the stack, and returns its result.
+    pub(crate) fn invoke(&self, jvm: &mut Jvm, arguments: Vec<Value>) -> Result<Value, ExecutionError> {
+        match self {
+            CallSite::Concat { recipe, constants, parameters } => {
+                let mut arguments = arguments.iter().zip(parameters);
+                let mut constants = constants.iter();
+                let mut string = String::new();
+                for character in recipe.chars() {
+                    match character {
+                        '\u{1}' => {
+                            let Some((argument, parameter)) = arguments.next() else { unreachable!() };
+                            string.push_str(&java_string(jvm, *argument, parameter)?);
+                        }
+                        '\u{2}' => string.push_str(constants.next().map_or("", String::as_str)),
+                        character => string.push(character),
+                    }
+                }
+                jvm.new_string(string)
+            }
+            CallSite::Lambda { class, target } => {
+                let data = ObjectData::Lambda { target: target.clone(), captured: arguments };
+                let reference = jvm.heap.allocate_with(class.clone(), data)?;
+                Ok(Value::Reference(Some(reference)))
+            }
+        }
+    }
+}
+
+fn link(jvm: &mut Jvm, class: &Rc<Class>, index: usize) -> Result<CallSite, ExecutionError> {
+    let constant_pool = &class.constant_pool;
+    let Some(ConstantPoolInfo::InvokeDynamic(invoke_dynamic)) = constant_pool.get(index) else {
+        return Err(ExecutionError::new(format!("#{index} is not an invokedynamic call site")));
+    };
+    let Some(BootstrapMethod { method_handle_index, argument_indexes: arguments }) = class.bootstrap_methods.get(invoke_dynamic.bootstrap_method_attr_index) else {
+        return Err(ExecutionError::new(format!("BootstrapMethodError: {} has no bootstrap method {}", class.name, invoke_dynamic.bootstrap_method_attr_index)));
+    };
+    let bootstrap_method = method_handle_at(constant_pool, *method_handle_index)?;
+    let Some(descriptor) = MethodDescriptor::parse(&invoke_dynamic.descriptor) else {
+        return Err(ExecutionError::new(format!("{} is not a method descriptor", invoke_dynamic.descriptor)));
+    };
+    match (bootstrap_method.class_name.as_str(), bootstrap_method.name.as_str()) {
+        ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+            let Some((recipe, constants)) = arguments.split_first() else {
+                return Err(ExecutionError::new("BootstrapMethodError: makeConcatWithConstants needs a recipe"));
+            };
+            let recipe = match constant_pool.get(*recipe) {
+                Some(ConstantPoolInfo::String(string)) => string.string.clone(),
+                _ => return Err(ExecutionError::new(format!("BootstrapMethodError: #{recipe} is not a recipe"))),
+            };
+            let constants = constants.iter().map(|index| constant_string(constant_pool, *index)).collect::<Result<Vec<_>, _>>()?;
+            let wanted = recipe.chars().filter(|character| *character == '\u{1}').count();
+            if wanted != descriptor.parameters.len() {
+                return Err(ExecutionError::new(format!("StringConcatException: Mismatched number of concat arguments: recipe wants {wanted} arguments, but signature provides {}", descriptor.parameters.len())));
+            }
+            let wanted = recipe.chars().filter(|character| *character == '\u{2}').count();
+            if wanted != constants.len() {
+                return Err(ExecutionError::new(format!("StringConcatException: Mismatched number of concat constants: recipe wants {wanted} constants, but only {} are passed", constants.len())));
+            }
+            Ok(CallSite::Concat { recipe, constants, parameters: descriptor.parameters })
+        }
+        ("java/lang/invoke/StringConcatFactory", "makeConcat") => {
+            let recipe = "\u{1}".repeat(descriptor.parameters.len());
+            Ok(CallSite::Concat { recipe, constants: Vec::new(), parameters: descriptor.parameters })
+        }
+        ("java/lang/invoke/LambdaMetafactory", "metafactory") => link_lambda(jvm, class, &invoke_dynamic.name, &descriptor, arguments),
+        (class_name, name) => Err(ExecutionError::new(format!("BootstrapMethodError: {class_name}.{name} is not supported"))),
+    }
+}
+
+/// Spins a class implementing the functional interface the call site returns, whose method
+/// `name` invokes the target method handle.
+fn link_lambda(jvm: &mut Jvm, class: &Rc<Class>, name: &str, descriptor: &MethodDescriptor, arguments: &[usize]) -> Result<CallSite, ExecutionError> {
+    let constant_pool = &class.constant_pool;
+    let &[interface_type, implementation, instantiated_type] = arguments else {
+        return Err(ExecutionError::new(format!("BootstrapMethodError: LambdaMetafactory.metafactory takes 3 static arguments but was passed {}", arguments.len())));
+    };
+    let interface_type = method_type_at(constant_pool, interface_type)?;
+    let instantiated_type = method_type_at(constant_pool, instantiated_type)?;
+    let implementation = method_handle_at(constant_pool, implementation)?;
+    let Some(FieldType::Object(interface_name)) = &descriptor.return_type else {
+        return Err(ExecutionError::new(format!("LambdaConversionException: {name} doesn't return a functional interface")));
+    };
+    let kind = match implementation.reference_kind {
+        5 => InvokeKind::Virtual,
+        6 => InvokeKind::Static,
+        7 => InvokeKind::Special,
+        9 => InvokeKind::Interface,
+        8 => return Err(ExecutionError::new(format!("LambdaConversionException: constructor references like {}::new are not supported yet", implementation.class_name))),
+        kind => return Err(ExecutionError::new(format!("LambdaConversionException: method handles of kind {kind} can't implement a lambda"))),
+    };
+    let target_descriptor = parse(&implementation.descriptor)?;
+    let instantiated_descriptor = parse(&instantiated_type)?;
+    // Boxing and unboxing would need the wrapper classes, so the types have to match as they are.
+    let receiver = (kind != InvokeKind::Static).then(|| FieldType::Object(implementation.class_name.clone()));
+    let target_parameters: Vec<_> = receiver.iter().chain(&target_descriptor.parameters).collect();
+    let parameters: Vec<_> = descriptor.parameters.iter().chain(&instantiated_descriptor.parameters).collect();
+    let same_parameters = target_parameters.len() == parameters.len()
+        && target_parameters.iter().zip(&parameters).all(|(target, parameter)| same_computational_type(Some(target), Some(parameter)));
+    if !same_parameters || !same_computational_type(target_descriptor.return_type.as_ref(), instantiated_descriptor.return_type.as_ref()) {
+        return Err(ExecutionError::new(format!("LambdaConversionException: {}.{}{} can't implement {interface_name}.{name}{instantiated_type} without boxing, which is not supported yet", implementation.class_name, implementation.name, implementation.descriptor)));
+    }
+    let interface = matches!(constant_pool.get(implementation.reference_index), Some(ConstantPoolInfo::InterfaceMethodRef(_)));
+    let method_ref = MethodRef { class_name: implementation.class_name.clone(), name: implementation.name.clone(), descriptor: implementation.descriptor.clone(), interface };
+    let target = Rc::new(LambdaTarget { kind, method_ref, caller: class.clone() });
+
+    let lambda_name = format!("{}$$Lambda${}", class.name, jvm.lambda_classes);
+    jvm.lambda_classes += 1;
+    let method = Method {
+        class_name: lambda_name.clone(),
+        name: name.to_string(),
+        descriptor: interface_type,
+        // public native: the JVM runs it.
+        access_flags: 0x0101,
+        code: None,
+        index: 0,
+        vtable_index: None,
+        call_sites: RefCell::new(HashMap::new()),
+    };
+    let lambda_class = Class {
+        name: lambda_name,
+        // final super synthetic
+        flags: ClassFlags::new(0x1030),
+        super_class_name: Some("java/lang/Object".to_string()),
+        interface_names: vec![interface_name.clone()],
+        constant_pool: RuntimeConstantPool::new(Vec::new()),
+        fields: Vec::new(),
+        instance_size: 0,
+        static_values: RefCell::new(Vec::new()),
+        methods: vec![Rc::new(method)],
+        bootstrap_methods: Vec::new(),
+        vtable: Vec::new(),
+        itables: BTreeMap::new(),
+    };
+    let lambda_class = jvm.method_area.add(lambda_class)?;
+    Ok(CallSite::Lambda { class: lambda_class, target })
+}
+
+/// Whether values of the two types, `None` being `void`, are the same on the operand stack.
+fn same_computational_type(left: Option<&FieldType>, right: Option<&FieldType>) -> bool {
+    let kind = |field_type: Option<&FieldType>| match field_type {
+        None => 'V',
+        Some(FieldType::Long) => 'J',
+        Some(FieldType::Float) => 'F',
+        Some(FieldType::Double) => 'D',
+        Some(field_type) if field_type.is_reference() => 'L',
+        Some(_) => 'I',
+    };
+    kind(left) == kind(right)
+}
+
+fn parse(descriptor: &str) -> Result<MethodDescriptor, ExecutionError> {
+    MethodDescriptor::parse(descriptor).ok_or_else(|| ExecutionError::new(format!("{descriptor} is not a method descriptor")))
+}
+
+fn method_handle_at(constant_pool: &RuntimeConstantPool, index: usize) -> Result<&MethodKindInfo, ExecutionError> {
+    match constant_pool.get(index) {
+        Some(ConstantPoolInfo::MethodKind(method_handle)) => Ok(method_handle),
+        _ => Err(ExecutionError::new(format!("BootstrapMethodError: #{index} is not a method handle"))),
+    }
+}
+
+fn method_type_at(constant_pool: &RuntimeConstantPool, index: usize) -> Result<String, ExecutionError> {
+    match constant_pool.get(index) {
+        Some(ConstantPoolInfo::MethodType(method_type)) => Ok(method_type.descriptor.clone()),
+        _ => Err(ExecutionError::new(format!("BootstrapMethodError: #{index} is not a method type"))),
+    }
+}
+
+/// A constant of a concatenation recipe, the way Java turns it into a string.
+fn constant_string(constant_pool: &RuntimeConstantPool, index: usize) -> Result<String, ExecutionError> {
+    match constant_pool.get(index) {
+        Some(ConstantPoolInfo::String(string)) => Ok(string.string.clone()),
+        Some(ConstantPoolInfo::Integer(integer)) => Ok(integer.value.to_string()),
+        Some(ConstantPoolInfo::Long(long)) => Ok(long.value.to_string()),
+        Some(ConstantPoolInfo::Float(float)) => Ok(java_float(float.value)),
+        Some(ConstantPoolInfo::Double(double)) => Ok(java_float(double.value)),
+        _ => Err(ExecutionError::new(format!("BootstrapMethodError: #{index} is not a concatenation constant"))),
+    }
+}
@@ -1,14 +1,293 @@
-type Field = ();
-type Method = ();
-type MethodCode = ();
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::rc::Rc;
+use crate::call_site::CallSite;
+use crate::descriptor::FieldType;
+use crate::instruction::{DecodeError, Instruction};
+use crate::execution::ExecutionError;
+use crate::frames::Value;
+use crate::{Attribute, BootstrapMethod, ClassFlags, ConstantPoolInfo, FieldInfo, JavaClass, MethodInfo};
 
+/// The code of a method, decoded once when its class is loaded.
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub max_stack: usize,
+    pub max_locals: usize,
+    /// The instructions with their pcs, in order.
+    pub instructions: Vec<(usize, Instruction)>,
+}
+
+impl Code {
+    /// The position in `instructions` of the one at `pc`.
+    pub fn position(&self, pc: usize) -> Option<usize> {
+        self.instructions.binary_search_by_key(&pc, |(instruction_pc, _)| *instruction_pc).ok()
+    }
+}
+
+/// A method of a loaded class.
+#[derive(Debug, Clone)]
+pub struct Method {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    pub access_flags: u16,
+    /// `None` for abstract and native methods.
+    pub code: Option<Code>,
+    /// Where the class declares the method among its methods, which is also where it is in the
+    /// itables of an interface's methods.
+    pub index: usize,
+    /// Where the method is in the vtables of its class and subclasses, for the instance methods
+    /// of classes once linked.
+    pub vtable_index: Option<usize>,
+    /// The call sites its `invokedynamic` instructions were linked to, by pc.
+    pub call_sites: RefCell<HashMap<usize, Rc<CallSite>>>,
+}
+
+impl Method {
+    fn new(class_name: &str, index: usize, method_info: &MethodInfo) -> Result<Method, DecodeError> {
+        let code = match method_info.code() {
+            Some(code) => Some(Code { max_stack: code.max_stack, max_locals: code.max_locals, instructions: code.instructions()? }),
+            None => None,
+        };
+        Ok(Method {
+            class_name: class_name.to_string(),
+            name: method_info.name.clone(),
+            descriptor: method_info.descriptor.clone(),
+            access_flags: method_info.access_flags,
+            code,
+            index,
+            vtable_index: None,
+            call_sites: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.access_flags & 0x0001 != 0
+    }
+
+    pub fn is_private(&self) -> bool {
+        self.access_flags & 0x0002 != 0
+    }
+
+    pub fn is_protected(&self) -> bool {
+        self.access_flags & 0x0004 != 0
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags & 0x0008 != 0
+    }
+
+    pub fn is_final(&self) -> bool {
+        self.access_flags & 0x0010 != 0
+    }
+
+    pub fn is_abstract(&self) -> bool {
+        self.access_flags & 0x0400 != 0
+    }
+
+    /// The package of the declaring class, like `java/lang`, which package-private access goes by.
+    pub fn package(&self) -> &str {
+        self.class_name.rsplit_once('/').map_or("", |(package, _)| package)
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+/// A field of a loaded class.
+#[derive(Debug, Clone)]
+pub struct Field {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    pub access_flags: u16,
+    /// Where the class declares the field among its fields.
+    pub index: usize,
+    /// The constant pool index of its `ConstantValue`, which a static field starts out with.
+    pub constant_value: Option<usize>,
+    /// Where an instance field is in objects, counting from their header. Set when the class is
+    /// linked.
+    pub offset: usize,
+}
+
+impl Field {
+    fn new(class_name: &str, index: usize, field_info: &FieldInfo) -> Field {
+        let constant_value = field_info.attributes_info.iter().find_map(|attribute_info| match &attribute_info.attribute {
+            Attribute::ConstantValue(constant_value) => Some(constant_value.constant_value_index),
+            _ => None,
+        });
+        Field {
+            class_name: class_name.to_string(),
+            name: field_info.name.clone(),
+            descriptor: field_info.descriptor.clone(),
+            access_flags: field_info.access_flags,
+            index,
+            constant_value,
+            offset: 0,
+        }
+    }
+
+    pub fn is_static(&self) -> bool {
+        self.access_flags & 0x0008 != 0
+    }
+
+    /// The value the field has before anything is stored in it.
+    pub fn default_value(&self) -> Value {
+        match FieldType::parse(&self.descriptor) {
+            Some(FieldType::Long) => Value::Long(0),
+            Some(FieldType::Float) => Value::Float(0.0),
+            Some(FieldType::Double) => Value::Double(0.0),
+            Some(field_type) if field_type.is_reference() => Value::Reference(None),
+            _ => Value::Int(0),
+        }
+    }
+}
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+/// A symbolic reference to a field, as the field instructions make.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FieldRef {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+/// A symbolic reference to a method, as the invoke instructions make.
+#[derive(Debug, PartialEq, Clone)]
+pub struct MethodRef {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    /// Whether it is an `InterfaceMethodref`.
+    pub interface: bool,
+}
+
+/// The run-time constant pool of a loaded class, which its instructions refer to by index.
+#[derive(Debug)]
+pub struct RuntimeConstantPool {
+    constants: Vec<ConstantPoolInfo>,
+}
+
+impl RuntimeConstantPool {
+    pub fn new(constants: Vec<ConstantPoolInfo>) -> Self {
+        RuntimeConstantPool { constants }
+    }
+
+    /// The entry at `index`, counting from 1.
+    pub fn get(&self, index: usize) -> Option<&ConstantPoolInfo> {
+        self.constants.get(index.checked_sub(1)?)
+    }
+
+    pub fn class_name(&self, index: usize) -> Result<&str, ExecutionError> {
+        match self.get(index) {
+            Some(ConstantPoolInfo::Class(class_info)) => Ok(&class_info.name),
+            _ => Err(ExecutionError::new(format!("#{index} is not a class"))),
+        }
+    }
+
+    pub fn field_ref(&self, index: usize) -> Result<FieldRef, ExecutionError> {
+        match self.get(index) {
+            Some(ConstantPoolInfo::FieldRef(field_ref)) => Ok(FieldRef { class_name: field_ref.class_name.clone(), name: field_ref.name.clone(), descriptor: field_ref.descriptor.clone() }),
+            _ => Err(ExecutionError::new(format!("#{index} is not a field"))),
+        }
+    }
+
+    pub fn method_ref(&self, index: usize) -> Result<MethodRef, ExecutionError> {
+        let (class_name, name, descriptor, interface) = match self.get(index) {
+            Some(ConstantPoolInfo::MethodRef(method_ref)) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor, false),
+            Some(ConstantPoolInfo::InterfaceMethodRef(method_ref)) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor, true),
+            _ => return Err(ExecutionError::new(format!("#{index} is not a method"))),
+        };
+        Ok(MethodRef { class_name: class_name.clone(), name: name.clone(), descriptor: descriptor.clone(), interface })
+    }
+}
+
+/// A class loaded into the method area.
+#[derive(Debug)]
 pub struct Class {
-    constant_pool: (),
-    fields: Vec<Field>,
-    methods: Vec<Method>,
-    methods_code: Vec<MethodCode>,
+    pub name: String,
+    pub flags: ClassFlags,
+    /// `None` for `java/lang/Object`.
+    pub super_class_name: Option<String>,
+    pub interface_names: Vec<String>,
+    pub constant_pool: RuntimeConstantPool,
+    pub fields: Vec<Field>,
+    /// What objects of the class take on the heap, header included. Set when the class is linked.
+    pub instance_size: usize,
+    /// The values of the static fields, at the fields' indexes.
+    pub static_values: RefCell<Vec<Value>>,
+    pub methods: Vec<Rc<Method>>,
+    /// The method handle and static arguments of each bootstrap method, as constant pool indexes.
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+    /// The instance methods objects of the class run, by vtable index. Empty for interfaces and
+    /// until the class is linked.
+    pub vtable: Vec<Rc<Method>>,
+    /// For each superinterface, the method objects of the class run for each of its methods,
+    /// `None` when selecting one fails.
+    pub itables: BTreeMap<String, Vec<Option<Rc<Method>>>>,
 }
 
 impl Class {
+    pub fn new(java_class: JavaClass) -> Result<Class, DecodeError> {
+        let methods = java_class.methods.iter().enumerate()
+            .map(|(index, method_info)| Method::new(&java_class.this_class_name, index, method_info).map(Rc::new))
+            .collect::<Result<_, _>>()?;
+        let fields: Vec<Field> = java_class.fields.iter().enumerate()
+            .map(|(index, field_info)| Field::new(&java_class.this_class_name, index, field_info))
+            .collect();
+        let static_values = RefCell::new(fields.iter().map(Field::default_value).collect());
+        let bootstrap_methods = java_class.bootstrap_methods().to_vec();
+        Ok(Class {
+            name: java_class.this_class_name,
+            flags: java_class.flags,
+            super_class_name: java_class.super_class_name,
+            interface_names: java_class.interfaces_names,
+            constant_pool: RuntimeConstantPool::new(java_class.constant_pool_infos),
+            fields,
+            instance_size: 0,
+            static_values,
+            methods,
+            bootstrap_methods,
+            vtable: Vec::new(),
+            itables: BTreeMap::new(),
+        })
+    }
+
+    pub fn is_interface(&self) -> bool {
+        self.flags.is_interface()
+    }
+
+    /// The vtable and itables, one method per line, for debugging dispatch.
+    pub fn dispatch_tables(&self) -> String {
+        let mut tables = format!("vtable of {}:\n", self.name);
+        for (index, method) in self.vtable.iter().enumerate() {
+            tables.push_str(&format!("  {index}: {method}\n"));
+        }
+        for (interface, itable) in &self.itables {
+            tables.push_str(&format!("itable of {} for {interface}:\n", self.name));
+            for method in itable.iter().flatten() {
+                tables.push_str(&format!("  {}{}: {method}\n", method.name, method.descriptor));
+            }
+        }
+        tables
+    }
+
+    /// The field declared by this class with the given name and descriptor.
+    pub fn field(&self, name: &str, descriptor: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name && field.descriptor == descriptor)
+    }
 
+    /// The method declared by this class with the given name and descriptor.
+    pub fn method(&self, name: &str, descriptor: &str) -> Option<&Rc<Method>> {
+        self.methods.iter().find(|method| method.name == name && method.descriptor == descriptor)
+    }
 }
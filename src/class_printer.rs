@@ -1,4 +1,4 @@
-use crate::{Attribute, ConstantPoolInfo, ConstantPoolNameAndTypeInfo, ConstantPoolUtf8Info, JavaClass};
+use crate::{Attribute, ConstantPoolInfo, JavaClass};
 use text_colorizer::*;
 
 pub struct ClassPrinter(JavaClass);
@@ -26,25 +26,15 @@ impl ClassPrinter {
                  class.flags.is_module());
 
 
-        if let ConstantPoolInfo::Class(class_info) = &class.constant_pool_infos[class.this_class - 1] {
-            if let ConstantPoolInfo::Utf8(class_name) = &class.constant_pool_infos[class_info.name_index - 1] {
-                println!("This class: {} ({})", class_name.string, class.this_class);
-            }
-        }
-        if let ConstantPoolInfo::Class(class_info) = &class.constant_pool_infos[class.super_class - 1] {
-            if class_info.name_index > 0 {
-                if let ConstantPoolInfo::Utf8(class_name) = &class.constant_pool_infos[class_info.name_index - 1] {
-                    println!("Super class: {} ({})", class_name.string, class.super_class);
-                }
-            }
+        println!("This class: {} ({})", Self::class_name(&class, class.this_class), class.this_class);
+        if class.super_class != 0 {
+            println!("Super class: {} ({})", Self::class_name(&class, class.super_class), class.super_class);
         }
 
         println!("Number of interfaces: {}", class.interfaces_count);
 
         for interfaces_index in &class.interfaces_indexes {
-            if let Some(class_constant) = Self::get_class_name(&class, &interfaces_index) {
-                println!("  {} ({})", class_constant.string, interfaces_index);
-            }
+            println!("  {} ({})", Self::class_name(&class, *interfaces_index), interfaces_index);
         }
 
         println!("Number of fields: {}", class.fields.len());
@@ -61,7 +51,7 @@ impl ClassPrinter {
                 match attribute.attribute {
                     Attribute::ConstantValue(_) => {}
                     Attribute::Code(c) => {
-                        c.print();
+                        c.print(&class.constant_pool_infos);
                     }
                     Attribute::Exceptions(_) => {}
                     Attribute::SourceFile(_) => {}
@@ -70,6 +60,8 @@ impl ClassPrinter {
                     Attribute::InnerClasses(_) => {}
                     Attribute::Synthetic(_) => {}
                     Attribute::Deprecated(_) => {}
+                    Attribute::BootstrapMethods(_) => {}
+                    Attribute::Unknown(_) => {}
                 }
             }
         }
@@ -99,38 +91,34 @@ impl ClassPrinter {
                     println!("  {}. {}: {}", const_index, Self::constant_name_coloured("Double"), double_info.value)
                 }
                 ConstantPoolInfo::Class(value) => {
-                    let name = Self::get_name_constant(class, &value.name_index).expect("Name should exist");
-                    println!("  {}. {}: {} ({})", const_index, Self::constant_name_coloured("Class"), name.string.bold(), value.name_index)
+                    let name = Self::utf8(class, value.name_index);
+                    println!("  {}. {}: {} ({})", const_index, Self::constant_name_coloured("Class"), name.bold(), value.name_index)
                 }
                 ConstantPoolInfo::String(string_info) => {
-                    let name = Self::get_name_constant(class, &string_info.name_index).expect("Name should exist");
-                    println!("  {}. {}: \"{}\" ({})", const_index, Self::constant_name_coloured("String"), name.string.bold(), string_info.name_index)
+                    let name = Self::utf8(class, string_info.name_index);
+                    println!("  {}. {}: \"{}\" ({})", const_index, Self::constant_name_coloured("String"), name.bold(), string_info.name_index)
                 }
                 ConstantPoolInfo::FieldRef(field_ref_info) => {
-                    let class_name = Self::get_class_name(class, &field_ref_info.class_index).expect("Name should exist");
-                    let name_and_type = Self::get_name_and_type_constant(class, &field_ref_info.name_and_type_index).expect("Name should exist");
-                    let field_name = Self::get_name_constant(class, &name_and_type.name_index).expect("name for method doesn't doesn't exist");
-                    let field_type_name = Self::get_name_constant(class, &name_and_type.descriptor_index).expect("name for descriptor doesn't exist");
+                    let class_name = Self::class_name(class, field_ref_info.class_index);
+                    let (field_name, field_type_name) = Self::name_and_type(class, field_ref_info.name_and_type_index);
                     println!("  {}. {}: {} {}.{}, class index ({}), name and type index ({})",
                              const_index,
                              Self::constant_name_coloured("Field ref"),
-                             field_type_name.string.bold(),
-                             class_name.string.bold(),
-                             field_name.string.bold(),
+                             field_type_name.bold(),
+                             class_name.bold(),
+                             field_name.bold(),
                              field_ref_info.class_index,
                              field_ref_info.name_and_type_index)
                 }
                 ConstantPoolInfo::MethodRef(method_ref_info) => {
-                    let class_name = Self::get_class_name(class, &method_ref_info.class_index).expect("Name should exist");
-                    let name_and_type = Self::get_name_and_type_constant(class, &method_ref_info.name_and_type_index).expect("Name should exist");
-                    let method_name = Self::get_name_constant(class, &name_and_type.name_index).expect("name for method doesn't doesn't exist");
-                    let method_descriptor = Self::get_name_constant(class, &name_and_type.descriptor_index).expect("name for descriptor doesn't exist");
+                    let class_name = Self::class_name(class, method_ref_info.class_index);
+                    let (method_name, method_descriptor) = Self::name_and_type(class, method_ref_info.name_and_type_index);
                     println!("  {}. {}: {}.{} {}, class index: {}, name and type index: {}",
                              const_index,
                              Self::constant_name_coloured("Method ref"),
-                             class_name.string,
-                             method_name.string.bold(),
-                             method_descriptor.string,
+                             class_name,
+                             method_name.bold(),
+                             method_descriptor,
                              method_ref_info.class_index,
                              method_ref_info.name_and_type_index)
                 }
@@ -142,13 +130,13 @@ impl ClassPrinter {
                              interface_and_method_ref_info.name_and_type_index)
                 }
                 ConstantPoolInfo::NameAndType(name_and_type_info) => {
-                    let name = Self::get_name_constant(class, &name_and_type_info.name_index).expect("name for method doesn't doesn't exist");
-                    let descriptor = Self::get_name_constant(class, &name_and_type_info.descriptor_index).expect("name for descriptor doesn't exist");
+                    let name = Self::utf8(class, name_and_type_info.name_index);
+                    let descriptor = Self::utf8(class, name_and_type_info.descriptor_index);
                     println!("  {}. {}: {}{}, name index: {}, descriptor index: {}",
                              const_index,
                              Self::constant_name_coloured("Name and type"),
-                             name.string.bold(),
-                             descriptor.string.bold(),
+                             name.bold(),
+                             descriptor.bold(),
                              name_and_type_info.name_index,
                              name_and_type_info.descriptor_index)
                 }
@@ -159,6 +147,12 @@ impl ClassPrinter {
                              method_kind.reference_kind,
                              method_kind.reference_index)
                 }
+                ConstantPoolInfo::MethodType(method_type) => {
+                    println!("  {}. {}: descriptor_index({})",
+                             const_index,
+                             Self::constant_name_coloured("Method type"),
+                             method_type.descriptor_index)
+                }
                 ConstantPoolInfo::Dynamic(dynamic) => {
                     println!("  {}. {}: bootstrap_method_attr_index({}), name_and_type_index({})",
                              const_index,
@@ -166,15 +160,16 @@ impl ClassPrinter {
                              dynamic.bootstrap_method_attr_index,
                              dynamic.name_and_type_index)
                 }
+                ConstantPoolInfo::Unusable => {
+                    println!("  {}. {}", const_index, Self::constant_name_coloured("(unusable)"))
+                }
                 ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => {
-                    let name_and_type = Self::get_name_and_type_constant(class, &invoke_dynamic.name_and_type_index).expect("Name should exist");
-                    let method_name = Self::get_name_constant(class, &name_and_type.name_index).expect("name for method doesn't doesn't exist");
-                    let method_descriptor = Self::get_name_constant(class, &name_and_type.descriptor_index).expect("name for descriptor doesn't exist");
+                    let (method_name, method_descriptor) = Self::name_and_type(class, invoke_dynamic.name_and_type_index);
                     println!("  {}. {}: {} {} bootstrap_method_attr_index({}), name_and_type_index({})",
                              const_index,
                              Self::constant_name_coloured("Invoke dynamic"),
-                             method_name.string.bold(),
-                             method_descriptor.string.bold(),
+                             method_name.bold(),
+                             method_descriptor.bold(),
                              invoke_dynamic.bootstrap_method_attr_index,
                              invoke_dynamic.name_and_type_index)
                 }
@@ -182,27 +177,30 @@ impl ClassPrinter {
         }
     }
 
-    fn get_class_name<'a>(class: &'a JavaClass, class_index: &usize) -> Option<&'a ConstantPoolUtf8Info> {
-        if let ConstantPoolInfo::Class(class_info) = &class.constant_pool_infos[class_index - 1] {
-            Self::get_name_constant(class, &class_info.name_index)
-        } else {
-            None
+    fn constant(class: &JavaClass, index: usize) -> Option<&ConstantPoolInfo> {
+        class.constant_pool_infos.get(index.checked_sub(1)?)
+    }
+
+    /// The string of a `Utf8` constant, or a placeholder when the index doesn't point at one.
+    fn utf8(class: &JavaClass, index: usize) -> String {
+        match Self::constant(class, index) {
+            Some(ConstantPoolInfo::Utf8(utf8_info)) => utf8_info.string.clone(),
+            _ => format!("<invalid #{index}>"),
         }
     }
 
-    fn get_name_constant<'a>(class: &'a JavaClass, name_index: &usize) -> Option<&'a ConstantPoolUtf8Info> {
-        if let ConstantPoolInfo::Utf8(class_name) = &class.constant_pool_infos[name_index - 1] {
-            Some(class_name)
-        } else {
-            None
+    fn class_name(class: &JavaClass, index: usize) -> String {
+        match Self::constant(class, index) {
+            Some(ConstantPoolInfo::Class(class_info)) => Self::utf8(class, class_info.name_index),
+            _ => format!("<invalid #{index}>"),
         }
     }
 
-    fn get_name_and_type_constant<'a>(class: &'a JavaClass, name_index: &usize) -> Option<&'a ConstantPoolNameAndTypeInfo> {
-        if let ConstantPoolInfo::NameAndType(name_and_type) = &class.constant_pool_infos[name_index - 1] {
-            Some(name_and_type)
-        } else {
-            None
+    /// The name and descriptor of a `NameAndType` constant.
+    fn name_and_type(class: &JavaClass, index: usize) -> (String, String) {
+        match Self::constant(class, index) {
+            Some(ConstantPoolInfo::NameAndType(name_and_type)) => (Self::utf8(class, name_and_type.name_index), Self::utf8(class, name_and_type.descriptor_index)),
+            _ => (format!("<invalid #{index}>"), format!("<invalid #{index}>")),
         }
     }
 }
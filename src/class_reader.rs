@@ -0,0 +1,1056 @@
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::mem::size_of;
+use log::debug;
+use crate::parse_error::{Diagnostic, ParseError, ParseErrorKind, Recovery};
+use crate::{BootstrapMethod, ConstantPoolClassInfo, ConstantPoolDoubleInfo, ConstantPoolFieldRefInfo, ConstantPoolFloatInfo, ConstantPoolInfo, ConstantPoolIntegerInfo, ConstantPoolInterfaceMethodRefInfo, ConstantPoolLongInfo, ConstantPoolMethodRefInfo, ConstantPoolNameAndTypeInfo, ConstantPoolStringInfo, ConstantPoolUtf8Info, DynamicInfo, ExceptionTableEntry, InvokeDynamicInfo, LineNumberTableEntry, MethodKindInfo, MethodTypeInfo};
+use crate::visitor::{ClassVisitor, CodeVisitor, FieldVisitor, MethodVisitor};
+
+/// Upper bounds on the sizes a class file declares, checked while parsing so that a hostile
+/// file fails cleanly instead of making us allocate gigabytes or recurse deeply.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseLimits {
+    pub max_file_size: usize,
+    pub max_constant_pool_size: usize,
+    pub max_code_length: usize,
+    pub max_attribute_nesting_depth: usize,
+    /// Total bytes the parser may set aside for tables and strings whose sizes come from the file.
+    pub max_total_allocation: usize,
+}
+
+impl Default for ParseLimits {
+    fn default() -> Self {
+        ParseLimits {
+            max_file_size: 16 * 1024 * 1024,
+            max_constant_pool_size: 65535,
+            max_code_length: 65535,
+            max_attribute_nesting_depth: 8,
+            max_total_allocation: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// How the parser treats malformed input.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum ParseMode {
+    /// Fail on the first problem.
+    #[default]
+    Strict,
+    /// Work around problems where possible, such as mis-sized attributes, bad name indexes and
+    /// constant kinds the model doesn't cover, and report each as a [`Diagnostic`]. Exceeded
+    /// [`ParseLimits`] still fail. A class read this way may not write back byte for byte.
+    Lenient,
+}
+
+/// Reads a class file and drives a [`ClassVisitor`] through its contents in file order.
+pub struct ClassReader {
+    bytes: Vec<u8>,
+    limits: ParseLimits,
+    mode: ParseMode,
+}
+
+impl ClassReader {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        ClassReader { bytes, limits: ParseLimits::default(), mode: ParseMode::default() }
+    }
+
+    pub fn with_limits(self, limits: ParseLimits) -> Self {
+        ClassReader { limits, ..self }
+    }
+
+    pub fn with_mode(self, mode: ParseMode) -> Self {
+        ClassReader { mode, ..self }
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, io::Error> {
+        Self::from_file_with_limits(path, ParseLimits::default())
+    }
+
+    /// Reads at most one byte past `limits.max_file_size`, which is enough for [`accept`](ClassReader::accept) to reject the file.
+    pub fn from_file_with_limits(path: &str, limits: ParseLimits) -> Result<Self, io::Error> {
+        let mut bytes = Vec::new();
+        File::open(path)?.take(limits.max_file_size as u64 + 1).read_to_end(&mut bytes)?;
+        Ok(Self::new(bytes).with_limits(limits))
+    }
+
+    /// Drives `visitor` through the class and returns the problems lenient parsing recovered from.
+    /// In strict mode the first problem is an error instead, so the diagnostics are always empty.
+    pub fn accept(&self, visitor: &mut dyn ClassVisitor) -> Result<Vec<Diagnostic>, ParseError> {
+        let mut bytes_reader = BytesReader::new(&self.bytes, &self.limits, self.mode);
+        bytes_reader.check_limit("file size", self.bytes.len(), self.limits.max_file_size)?;
+        // Whatever doesn't start like a class file isn't read any further, even leniently.
+        let prelude = bytes_reader.read_u32()?;
+        if let Err(message) = crate::JavaClass::check_prelude(prelude) {
+            return Err(bytes_reader.error_at(0, ParseErrorKind::InvalidPrelude(message)));
+        }
+
+        if let Err(error) = Self::read_class(&mut bytes_reader, prelude, visitor) {
+            bytes_reader.recover(error, Recovery::StoppedReading)?;
+        }
+        visitor.visit_end();
+        Ok(bytes_reader.diagnostics)
+    }
+
+    fn read_class(bytes_reader: &mut BytesReader, prelude: u32, visitor: &mut dyn ClassVisitor) -> Result<(), ParseError> {
+        let minor = bytes_reader.read_u16()?;
+        let major = bytes_reader.read_u16()?;
+        visitor.visit_header(prelude, minor, major);
+
+        let constant_pool_infos = Self::read_constant_pool(bytes_reader)?;
+        visitor.visit_constant_pool(&constant_pool_infos);
+        if bytes_reader.stopped {
+            return Ok(());
+        }
+
+        let access_flags = bytes_reader.read_u16()?;
+        bytes_reader.enter("this_class".to_string());
+        let this_class = bytes_reader.read_class_index(&constant_pool_infos, false)?;
+        bytes_reader.rename("super_class".to_string());
+        let super_class = bytes_reader.read_class_index(&constant_pool_infos, true)?;
+        bytes_reader.leave();
+
+        let interfaces_count: usize = bytes_reader.read_u16()? as usize;
+        let mut interfaces_indexes: Vec<usize> = Vec::with_capacity(bytes_reader.reserve::<usize>(interfaces_count)?);
+
+        for interface_number in 0..interfaces_count {
+            bytes_reader.enter(format!("interfaces[{interface_number}]"));
+            interfaces_indexes.push(bytes_reader.read_class_index(&constant_pool_infos, false)?);
+            bytes_reader.leave();
+        }
+        visitor.visit_class(access_flags, this_class, super_class, &interfaces_indexes);
+
+        let fields_count: usize = bytes_reader.read_u16()? as usize;
+        for field_number in 0..fields_count {
+            bytes_reader.enter(format!("fields[{field_number}]"));
+            let access_flags = bytes_reader.read_u16()?;
+            let (name_index, name) = bytes_reader.read_name(&constant_pool_infos)?;
+            bytes_reader.rename(format!("fields[{field_number}] \"{name}\""));
+            let (descriptor_index, descriptor) = bytes_reader.read_name(&constant_pool_infos)?;
+
+            let mut field_visitor = visitor.visit_field(access_flags, name_index, &name, descriptor_index, &descriptor);
+            Self::read_field_attributes(bytes_reader, &constant_pool_infos, field_visitor.as_deref_mut())?;
+            if let Some(field_visitor) = field_visitor.as_deref_mut() {
+                field_visitor.visit_end();
+            }
+            bytes_reader.leave();
+        }
+
+        let methods_count = bytes_reader.read_u16()? as usize;
+        for method_number in 0..methods_count {
+            bytes_reader.enter(format!("methods[{method_number}]"));
+            let access_flags = bytes_reader.read_u16()?;
+            let (name_index, name) = bytes_reader.read_name(&constant_pool_infos)?;
+            bytes_reader.rename(format!("methods[{method_number}] \"{name}\""));
+            let (descriptor_index, descriptor) = bytes_reader.read_name(&constant_pool_infos)?;
+
+            let mut method_visitor = visitor.visit_method(access_flags, name_index, &name, descriptor_index, &descriptor);
+            Self::read_method_attributes(bytes_reader, &constant_pool_infos, method_visitor.as_deref_mut())?;
+            if let Some(method_visitor) = method_visitor.as_deref_mut() {
+                method_visitor.visit_end();
+            }
+            bytes_reader.leave();
+        }
+
+        let attributes_count = bytes_reader.read_u16()? as usize;
+        debug!("Class has {} attributes", attributes_count);
+        for attribute_number in 0..attributes_count {
+            let (attribute_name_index, attribute_name, attribute_length) = Self::read_attribute_header(bytes_reader, &constant_pool_infos, attribute_number)?;
+            bytes_reader.read_attribute_body(attribute_length, |bytes_reader| {
+                match attribute_name.as_str() {
+                    "BootstrapMethods" => {
+                        let bootstrap_methods_count = bytes_reader.read_u16()? as usize;
+                        let mut bootstrap_methods: Vec<BootstrapMethod> = Vec::with_capacity(bytes_reader.reserve::<BootstrapMethod>(bootstrap_methods_count)?);
+                        for _ in 0..bootstrap_methods_count {
+                            let method_handle_index = bytes_reader.read_u16()? as usize;
+                            let arguments_count = bytes_reader.read_u16()? as usize;
+                            let mut argument_indexes = Vec::with_capacity(bytes_reader.reserve::<usize>(arguments_count)?);
+                            for _ in 0..arguments_count {
+                                argument_indexes.push(bytes_reader.read_u16()? as usize);
+                            }
+                            bootstrap_methods.push(BootstrapMethod { method_handle_index, argument_indexes });
+                        }
+                        visitor.visit_bootstrap_methods(attribute_name_index, &bootstrap_methods);
+                    }
+                    _ => {
+                        let info = bytes_reader.read_allocated_bytes(attribute_length)?;
+                        visitor.visit_attribute(attribute_name_index, &attribute_name, info);
+                    }
+                }
+                Ok(())
+            })?;
+            bytes_reader.leave();
+        }
+        Ok(())
+    }
+
+    fn read_constant_pool(bytes_reader: &mut BytesReader) -> Result<Vec<ConstantPoolInfo>, ParseError> {
+        let constant_pool_count: usize = bytes_reader.read_u16()? as usize;
+        bytes_reader.check_limit("constant pool size", constant_pool_count, bytes_reader.limits.max_constant_pool_size)?;
+        let mut constant_pool_infos: Vec<ConstantPoolInfo> = Vec::with_capacity(bytes_reader.reserve::<ConstantPoolInfo>(constant_pool_count)?);
+
+        while constant_pool_infos.len() + 1 < constant_pool_count {
+            bytes_reader.enter(format!("constant_pool[{}]", constant_pool_infos.len() + 1));
+            let tag = bytes_reader.read_u8()?;
+            match tag {
+                1 => {
+                    let length = bytes_reader.read_u16()? as usize;
+                    let string = bytes_reader.read_string(length)?;
+                    constant_pool_infos.push(ConstantPoolInfo::Utf8(ConstantPoolUtf8Info {
+                        tag,
+                        string,
+                    }));
+                }
+                3 => {
+                    constant_pool_infos.push(ConstantPoolInfo::Integer(ConstantPoolIntegerInfo {
+                        tag,
+                        value: bytes_reader.read_i32()?,
+                    }))
+                }
+                4 => {
+                    constant_pool_infos.push(ConstantPoolInfo::Float(ConstantPoolFloatInfo {
+                        tag,
+                        value: bytes_reader.read_f32()?,
+                    }))
+                }
+                5 | 6 => {
+                    let tag_offset = bytes_reader.last_read_offset;
+                    let constant = match tag {
+                        5 => ConstantPoolInfo::Long(ConstantPoolLongInfo { tag, value: bytes_reader.read_i64()? }),
+                        _ => ConstantPoolInfo::Double(ConstantPoolDoubleInfo { tag, value: bytes_reader.read_f64()? }),
+                    };
+                    // The slot after a Long or Double has to be in the pool too.
+                    if constant_pool_infos.len() + 2 < constant_pool_count {
+                        constant_pool_infos.push(constant);
+                    } else {
+                        let error = bytes_reader.error_at(tag_offset, ParseErrorKind::WideConstantInLastSlot(constant_pool_infos.len() + 1));
+                        bytes_reader.recover(error, Recovery::SkippedConstant)?;
+                    }
+                    constant_pool_infos.push(ConstantPoolInfo::Unusable);
+                }
+                7 => {
+                    constant_pool_infos.push(ConstantPoolInfo::Class(ConstantPoolClassInfo {
+                        tag,
+                        name_index: bytes_reader.read_u16()? as usize,
+                        name: String::new(),
+                    }))
+                }
+                8 => {
+                    constant_pool_infos.push(ConstantPoolInfo::String(ConstantPoolStringInfo {
+                        tag,
+                        name_index: bytes_reader.read_u16()? as usize,
+                        string: String::new(),
+                    }))
+                }
+                9 => {
+                    constant_pool_infos.push(ConstantPoolInfo::FieldRef(ConstantPoolFieldRefInfo {
+                        tag,
+                        class_index: bytes_reader.read_u16()? as usize,
+                        name_and_type_index: bytes_reader.read_u16()? as usize,
+                        class_name: String::new(),
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                10 => {
+                    constant_pool_infos.push(ConstantPoolInfo::MethodRef(ConstantPoolMethodRefInfo {
+                        tag,
+                        class_index: bytes_reader.read_u16()? as usize,
+                        name_and_type_index: bytes_reader.read_u16()? as usize,
+                        class_name: String::new(),
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                11 => {
+                    constant_pool_infos.push(ConstantPoolInfo::InterfaceMethodRef(ConstantPoolInterfaceMethodRefInfo {
+                        tag,
+                        class_index: bytes_reader.read_u16()? as usize,
+                        name_and_type_index: bytes_reader.read_u16()? as usize,
+                        class_name: String::new(),
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                12 => {
+                    constant_pool_infos.push(ConstantPoolInfo::NameAndType(ConstantPoolNameAndTypeInfo {
+                        tag,
+                        name_index: bytes_reader.read_u16()? as usize,
+                        descriptor_index: bytes_reader.read_u16()? as usize,
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                15 => {
+                    constant_pool_infos.push(ConstantPoolInfo::MethodKind(MethodKindInfo {
+                        tag,
+                        reference_kind: bytes_reader.read_u8()?,
+                        reference_index: bytes_reader.read_u16()? as usize,
+                        class_name: String::new(),
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                16 => {
+                    constant_pool_infos.push(ConstantPoolInfo::MethodType(MethodTypeInfo {
+                        tag,
+                        descriptor_index: bytes_reader.read_u16()? as usize,
+                        descriptor: String::new(),
+                    }))
+                }
+                17 => {
+                    constant_pool_infos.push(ConstantPoolInfo::Dynamic(DynamicInfo {
+                        tag,
+                        bootstrap_method_attr_index: bytes_reader.read_u16()? as usize,
+                        name_and_type_index: bytes_reader.read_u16()? as usize,
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+                18 => {
+                    constant_pool_infos.push(ConstantPoolInfo::InvokeDynamic(InvokeDynamicInfo {
+                        tag,
+                        bootstrap_method_attr_index: bytes_reader.read_u16()? as usize,
+                        name_and_type_index: bytes_reader.read_u16()? as usize,
+                        name: String::new(),
+                        descriptor: String::new(),
+                    }))
+                }
+
+                19 | 20 => {
+                    // Module and Package, which the model doesn't represent
+                    let error = bytes_reader.error(ParseErrorKind::UnknownConstantTag(tag));
+                    bytes_reader.read_u16()?;
+                    bytes_reader.recover(error, Recovery::SkippedConstant)?;
+                    constant_pool_infos.push(ConstantPoolInfo::Unusable);
+                }
+                _ => {
+                    // Without knowing the constant's size, nothing after it can be found
+                    let error = bytes_reader.error(ParseErrorKind::UnknownConstantTag(tag));
+                    bytes_reader.recover(error, Recovery::StoppedReading)?;
+                    bytes_reader.stopped = true;
+                    constant_pool_infos.resize(constant_pool_count - 1, ConstantPoolInfo::Unusable);
+                }
+            }
+            bytes_reader.leave();
+        }
+        resolve_constant_pool_names(&mut constant_pool_infos);
+        Ok(constant_pool_infos)
+    }
+
+    fn read_field_attributes(bytes_reader: &mut BytesReader, constant_pool_infos: &[ConstantPoolInfo], mut field_visitor: Option<&mut (dyn FieldVisitor + '_)>) -> Result<(), ParseError> {
+        let attributes_count = bytes_reader.read_u16()? as usize;
+        debug!("It has {} attributes", attributes_count);
+
+        for attribute_number in 0..attributes_count {
+            let (attribute_name_index, attribute_name, attribute_length) = Self::read_attribute_header(bytes_reader, constant_pool_infos, attribute_number)?;
+            bytes_reader.read_attribute_body(attribute_length, |bytes_reader| {
+                match (attribute_name.as_str(), field_visitor.as_deref_mut()) {
+                    ("ConstantValue", Some(field_visitor)) => {
+                        let constant_value_index = bytes_reader.read_u16()? as usize;
+                        field_visitor.visit_constant_value(attribute_name_index, constant_value_index);
+                    }
+                    (_, field_visitor) => {
+                        let info = bytes_reader.read_allocated_bytes(attribute_length)?;
+                        if let Some(field_visitor) = field_visitor {
+                            field_visitor.visit_attribute(attribute_name_index, &attribute_name, info);
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+            bytes_reader.leave();
+        }
+        Ok(())
+    }
+
+    fn read_method_attributes(bytes_reader: &mut BytesReader, constant_pool_infos: &[ConstantPoolInfo], mut method_visitor: Option<&mut (dyn MethodVisitor + '_)>) -> Result<(), ParseError> {
+        let attributes_count = bytes_reader.read_u16()? as usize;
+        debug!("It has {} attributes", attributes_count);
+
+        for attribute_number in 0..attributes_count {
+            let (attribute_name_index, attribute_name, attribute_length) = Self::read_attribute_header(bytes_reader, constant_pool_infos, attribute_number)?;
+            bytes_reader.read_attribute_body(attribute_length, |bytes_reader| {
+                match (attribute_name.as_str(), method_visitor.as_deref_mut()) {
+                    ("Code", Some(method_visitor)) => {
+                        let max_stack = bytes_reader.read_u16()? as usize;
+                        let max_locals = bytes_reader.read_u16()? as usize;
+                        let mut code_visitor = method_visitor.visit_code(attribute_name_index, max_stack, max_locals);
+                        let code_read = Self::read_code(bytes_reader, constant_pool_infos, code_visitor.as_deref_mut());
+                        if let Some(code_visitor) = code_visitor.as_deref_mut() {
+                            code_visitor.visit_end();
+                        }
+                        code_read?;
+                    }
+                    (_, method_visitor) => {
+                        let info = bytes_reader.read_allocated_bytes(attribute_length)?;
+                        if let Some(method_visitor) = method_visitor {
+                            method_visitor.visit_attribute(attribute_name_index, &attribute_name, info);
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+            bytes_reader.leave();
+        }
+        Ok(())
+    }
+
+    fn read_code(bytes_reader: &mut BytesReader, constant_pool_infos: &[ConstantPoolInfo], mut code_visitor: Option<&mut (dyn CodeVisitor + '_)>) -> Result<(), ParseError> {
+        bytes_reader.enter("code".to_string());
+        let code_length = bytes_reader.read_u32()? as usize;
+        bytes_reader.check_limit("code length", code_length, bytes_reader.limits.max_code_length)?;
+        let code = bytes_reader.read_allocated_bytes(code_length)?;
+        bytes_reader.leave();
+        if let Some(code_visitor) = code_visitor.as_deref_mut() {
+            code_visitor.visit_bytecode(code);
+        }
+
+        let exception_table_length = bytes_reader.read_u16()? as usize;
+        for entry_number in 0..exception_table_length {
+            bytes_reader.enter(format!("exception_table[{entry_number}]"));
+            let start_pc = bytes_reader.read_u16()? as usize;
+            let end_pc = bytes_reader.read_u16()? as usize;
+            let handler_pc = bytes_reader.read_u16()? as usize;
+            let catch_pc = bytes_reader.read_u16()? as usize;
+            if let Some(code_visitor) = code_visitor.as_deref_mut() {
+                code_visitor.visit_exception_table_entry(&ExceptionTableEntry {
+                    start_pc,
+                    end_pc,
+                    handler_pc,
+                    catch_pc,
+                });
+            }
+            bytes_reader.leave();
+        }
+
+        let attributes_count = bytes_reader.read_u16()? as usize;
+        debug!("Code has {} attributes", attributes_count);
+        for attribute_number in 0..attributes_count {
+            let (attribute_name_index, attribute_name, attribute_length) = Self::read_attribute_header(bytes_reader, constant_pool_infos, attribute_number)?;
+            bytes_reader.read_attribute_body(attribute_length, |bytes_reader| {
+                match (attribute_name.as_str(), code_visitor.as_deref_mut()) {
+                    ("LineNumberTable", Some(code_visitor)) => {
+                        let line_number_table_length = bytes_reader.read_u16()? as usize;
+                        let mut line_number_entries: Vec<LineNumberTableEntry> = Vec::with_capacity(bytes_reader.reserve::<LineNumberTableEntry>(line_number_table_length)?);
+                        for _ in 0..line_number_table_length {
+                            let start_pc = bytes_reader.read_u16()? as usize;
+                            let line_number = bytes_reader.read_u16()? as usize;
+                            line_number_entries.push(LineNumberTableEntry {
+                                start_pc,
+                                line_number,
+                            })
+                        }
+                        code_visitor.visit_line_number_table(attribute_name_index, &line_number_entries);
+                    }
+                    (_, code_visitor) => {
+                        let info = bytes_reader.read_allocated_bytes(attribute_length)?;
+                        if let Some(code_visitor) = code_visitor {
+                            code_visitor.visit_attribute(attribute_name_index, &attribute_name, info);
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+            bytes_reader.leave();
+        }
+        Ok(())
+    }
+
+    /// Reads `attribute_name_index` and `attribute_length`, entering the attribute in the structural path.
+    /// The caller leaves it once the attribute is read.
+    fn read_attribute_header(bytes_reader: &mut BytesReader, constant_pool_infos: &[ConstantPoolInfo], attribute_number: usize) -> Result<(usize, String, usize), ParseError> {
+        bytes_reader.enter(format!("attributes[{attribute_number}]"));
+        let (attribute_name_index, attribute_name) = bytes_reader.read_name(constant_pool_infos)?;
+        bytes_reader.rename(format!("attributes[{attribute_number}] {attribute_name}"));
+        let attribute_length = bytes_reader.read_u32()? as usize;
+        debug!("attribute {attribute_name} has length {attribute_length}");
+        Ok((attribute_name_index, attribute_name, attribute_length))
+    }
+}
+
+/// Fills in the names and descriptors each constant refers to through its indexes.
+///
+/// Entries may refer forward, so this runs once the whole pool is read. Indexes that don't
+/// lead to the expected constants resolve to empty strings.
+pub(crate) fn resolve_constant_pool_names(constant_pool_infos: &mut [ConstantPoolInfo]) {
+    let utf8 = |index: usize| -> String {
+        match index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::Utf8(utf8_info)) => utf8_info.string.clone(),
+            _ => String::new(),
+        }
+    };
+    let class_name = |index: usize| -> String {
+        match index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::Class(class_info)) => utf8(class_info.name_index),
+            _ => String::new(),
+        }
+    };
+    let name_and_type = |index: usize| -> (String, String) {
+        match index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::NameAndType(name_and_type)) => (utf8(name_and_type.name_index), utf8(name_and_type.descriptor_index)),
+            _ => (String::new(), String::new()),
+        }
+    };
+    let member = |index: usize| -> (String, String, String) {
+        let (class_index, name_and_type_index) = match index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::FieldRef(field_ref)) => (field_ref.class_index, field_ref.name_and_type_index),
+            Some(ConstantPoolInfo::MethodRef(method_ref)) => (method_ref.class_index, method_ref.name_and_type_index),
+            Some(ConstantPoolInfo::InterfaceMethodRef(method_ref)) => (method_ref.class_index, method_ref.name_and_type_index),
+            _ => (0, 0),
+        };
+        let (name, descriptor) = name_and_type(name_and_type_index);
+        (class_name(class_index), name, descriptor)
+    };
+
+    let resolved: Vec<(String, String, String)> = constant_pool_infos.iter().enumerate()
+        .map(|(position, constant_pool_info)| match constant_pool_info {
+            ConstantPoolInfo::Class(class_info) => (utf8(class_info.name_index), String::new(), String::new()),
+            ConstantPoolInfo::String(string_info) => (utf8(string_info.name_index), String::new(), String::new()),
+            ConstantPoolInfo::FieldRef(_) | ConstantPoolInfo::MethodRef(_) | ConstantPoolInfo::InterfaceMethodRef(_) => member(position + 1),
+            ConstantPoolInfo::NameAndType(name_and_type_info) => (String::new(), utf8(name_and_type_info.name_index), utf8(name_and_type_info.descriptor_index)),
+            ConstantPoolInfo::MethodKind(method_kind) => member(method_kind.reference_index),
+            ConstantPoolInfo::MethodType(method_type) => (String::new(), String::new(), utf8(method_type.descriptor_index)),
+            ConstantPoolInfo::Dynamic(dynamic) => {
+                let (name, descriptor) = name_and_type(dynamic.name_and_type_index);
+                (String::new(), name, descriptor)
+            }
+            ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => {
+                let (name, descriptor) = name_and_type(invoke_dynamic.name_and_type_index);
+                (String::new(), name, descriptor)
+            }
+            _ => (String::new(), String::new(), String::new()),
+        })
+        .collect();
+
+    for (constant_pool_info, (class_name, name, descriptor)) in constant_pool_infos.iter_mut().zip(resolved) {
+        match constant_pool_info {
+            ConstantPoolInfo::Class(class_info) => class_info.name = class_name,
+            ConstantPoolInfo::String(string_info) => string_info.string = class_name,
+            ConstantPoolInfo::FieldRef(field_ref) => {
+                field_ref.class_name = class_name;
+                field_ref.name = name;
+                field_ref.descriptor = descriptor;
+            }
+            ConstantPoolInfo::MethodRef(method_ref) => {
+                method_ref.class_name = class_name;
+                method_ref.name = name;
+                method_ref.descriptor = descriptor;
+            }
+            ConstantPoolInfo::InterfaceMethodRef(method_ref) => {
+                method_ref.class_name = class_name;
+                method_ref.name = name;
+                method_ref.descriptor = descriptor;
+            }
+            ConstantPoolInfo::NameAndType(name_and_type_info) => {
+                name_and_type_info.name = name;
+                name_and_type_info.descriptor = descriptor;
+            }
+            ConstantPoolInfo::MethodKind(method_kind) => {
+                method_kind.class_name = class_name;
+                method_kind.name = name;
+                method_kind.descriptor = descriptor;
+            }
+            ConstantPoolInfo::MethodType(method_type) => method_type.descriptor = descriptor,
+            ConstantPoolInfo::Dynamic(dynamic) => {
+                dynamic.name = name;
+                dynamic.descriptor = descriptor;
+            }
+            ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => {
+                invoke_dynamic.name = name;
+                invoke_dynamic.descriptor = descriptor;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Decodes the modified UTF-8 of the class file format: `NUL` is encoded in two bytes and
+/// supplementary characters as surrogate pairs of three bytes each.
+fn decode_modified_utf8(bytes: &[u8]) -> Option<String> {
+    let mut units: Vec<u16> = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let byte = bytes[index] as u16;
+        let continuation = |offset: usize| match bytes.get(index + offset) {
+            Some(byte) if byte & 0xC0 == 0x80 => Some((byte & 0x3F) as u16),
+            _ => None,
+        };
+        if byte & 0x80 == 0 {
+            units.push(byte);
+            index += 1;
+        } else if byte & 0xE0 == 0xC0 {
+            units.push((byte & 0x1F) << 6 | continuation(1)?);
+            index += 2;
+        } else if byte & 0xF0 == 0xE0 {
+            units.push((byte & 0x0F) << 12 | continuation(1)? << 6 | continuation(2)?);
+            index += 3;
+        } else {
+            return None;
+        }
+    }
+    Some(String::from_utf16_lossy(&units))
+}
+
+struct BytesReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    read_bytes: Vec<usize>,
+    limits: &'a ParseLimits,
+    allocated: usize,
+    /// Where the last read started, which is where errors about the value it read point to.
+    last_read_offset: usize,
+    path: Vec<String>,
+    mode: ParseMode,
+    diagnostics: Vec<Diagnostic>,
+    /// Set when lenient parsing can't go on, so the rest of the class is not read.
+    stopped: bool,
+}
+
+impl<'a> BytesReader<'a> {
+    fn new(bytes: &'a [u8], limits: &'a ParseLimits, mode: ParseMode) -> Self {
+        BytesReader {
+            bytes,
+            position: 0,
+            read_bytes: Vec::new(),
+            limits,
+            allocated: 0,
+            last_read_offset: 0,
+            path: Vec::new(),
+            mode,
+            diagnostics: Vec::new(),
+            stopped: false,
+        }
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let mut buffer = [0; N];
+        buffer.copy_from_slice(self.read_bytes(N)?);
+        Ok(buffer)
+    }
+
+    fn read_i64(&mut self) -> Result<i64, ParseError> {
+        Ok(i64::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ParseError> {
+        Ok(u32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, ParseError> {
+        Ok(i32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u16(&mut self) -> Result<u16, ParseError> {
+        Ok(u16::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ParseError> {
+        Ok(u8::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_f32(&mut self) -> Result<f32, ParseError> {
+        Ok(f32::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, ParseError> {
+        Ok(f64::from_be_bytes(self.read_array()?))
+    }
+
+    fn read_bytes(&mut self, bytes_to_read: usize) -> Result<&'a [u8], ParseError> {
+        self.last_read_offset = self.position;
+        let remaining = self.bytes.len() - self.position;
+        if bytes_to_read > remaining {
+            return Err(self.error(ParseErrorKind::UnexpectedEnd { expected: bytes_to_read, remaining }));
+        }
+        let output = &self.bytes[self.position..self.position + bytes_to_read];
+        self.position += bytes_to_read;
+        self.mark_read_bytes(bytes_to_read);
+        Ok(output)
+    }
+
+    /// Reads bytes that visitors are expected to copy, counting them against the allocation limit.
+    fn read_allocated_bytes(&mut self, bytes_to_read: usize) -> Result<&'a [u8], ParseError> {
+        let bytes = self.read_bytes(bytes_to_read)?;
+        self.reserve::<u8>(bytes_to_read)?;
+        Ok(bytes)
+    }
+
+    fn read_string(&mut self, bytes_of_utf8_string: usize) -> Result<String, ParseError> {
+        let bytes = self.read_allocated_bytes(bytes_of_utf8_string)?;
+        match decode_modified_utf8(bytes) {
+            Some(string) => Ok(string),
+            None => {
+                self.recover(self.error(ParseErrorKind::InvalidUtf8), Recovery::Placeholder)?;
+                Ok(String::from_utf8_lossy(bytes).into_owned())
+            }
+        }
+    }
+
+    /// Reads a constant pool index that has to point to a `Utf8` constant.
+    fn read_name(&mut self, constant_pool_infos: &[ConstantPoolInfo]) -> Result<(usize, String), ParseError> {
+        let name_index = self.read_u16()? as usize;
+        match name_index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::Utf8(utf8_info)) => Ok((name_index, utf8_info.string.clone())),
+            _ => {
+                self.recover(self.error(ParseErrorKind::InvalidConstantIndex { index: name_index, expected: "Utf8" }), Recovery::Placeholder)?;
+                Ok((name_index, format!("<invalid #{name_index}>")))
+            }
+        }
+    }
+
+    /// Reads the index of a `Class` constant, or 0 when the class is `optional`. An invalid index
+    /// is kept, for readers to show as one.
+    fn read_class_index(&mut self, constant_pool_infos: &[ConstantPoolInfo], optional: bool) -> Result<usize, ParseError> {
+        let class_index = self.read_u16()? as usize;
+        match class_index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::Class(_)) => {}
+            None if optional && class_index == 0 => {}
+            _ => self.recover(self.error(ParseErrorKind::InvalidConstantIndex { index: class_index, expected: "Class" }), Recovery::Placeholder)?,
+        }
+        Ok(class_index)
+    }
+
+    /// Accounts for a table of `count` elements whose size comes from the file and returns the
+    /// capacity worth allocating up front. That is never more than the remaining bytes could fill,
+    /// so a lying count can't trigger a huge allocation before the reads run out.
+    fn reserve<T>(&mut self, count: usize) -> Result<usize, ParseError> {
+        let size = count.saturating_mul(size_of::<T>());
+        self.allocated = self.allocated.saturating_add(size);
+        self.check_limit("total allocation", self.allocated, self.limits.max_total_allocation)?;
+        Ok(count.min(self.bytes.len() - self.position))
+    }
+
+    fn check_limit(&self, limit: &'static str, value: usize, max: usize) -> Result<(), ParseError> {
+        if value > max {
+            Err(self.error(ParseErrorKind::LimitExceeded { limit, value, max }))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn mark_read_bytes(&mut self, amt: usize) {
+        for num in self.read_bytes.iter_mut() {
+            *num += amt;
+        }
+    }
+
+    fn begin_read_block(&mut self) -> Result<(), ParseError> {
+        self.check_limit("attribute nesting depth", self.read_bytes.len() + 1, self.limits.max_attribute_nesting_depth)?;
+        self.read_bytes.push(0);
+        Ok(())
+    }
+
+    fn assert_read(&mut self, amt: usize) -> Result<(), ParseError> {
+        let read_bytes = self.read_bytes.pop().unwrap_or_default();
+        if read_bytes == amt {
+            Ok(())
+        } else {
+            Err(self.error_at(self.position, ParseErrorKind::AttributeLengthMismatch { expected: amt, read: read_bytes }))
+        }
+    }
+
+    /// Reads the body of an attribute of `attribute_length` bytes with `read` and checks it read
+    /// exactly that much. In lenient mode a failing attribute is skipped instead: reading resumes
+    /// where its declared length says the next one starts.
+    fn read_attribute_body(&mut self, attribute_length: usize, read: impl FnOnce(&mut Self) -> Result<(), ParseError>) -> Result<(), ParseError> {
+        let start = self.position;
+        let path_length = self.path.len();
+        self.begin_read_block()?;
+        let outer_blocks = self.read_bytes.len() - 1;
+        let error = match read(self).and_then(|()| self.assert_read(attribute_length)) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        self.recover(error, Recovery::SkippedAttribute)?;
+
+        self.read_bytes.truncate(outer_blocks);
+        self.path.truncate(path_length);
+        let end = start.saturating_add(attribute_length).min(self.bytes.len());
+        for num in self.read_bytes.iter_mut() {
+            *num = *num + end - self.position;
+        }
+        self.position = end;
+        Ok(())
+    }
+
+    /// Records `error` as a diagnostic when parsing leniently, or returns it when parsing strictly.
+    /// Exceeded limits are never recovered from.
+    fn recover(&mut self, error: ParseError, recovery: Recovery) -> Result<(), ParseError> {
+        if self.mode == ParseMode::Strict || matches!(error.kind, ParseErrorKind::LimitExceeded { .. }) {
+            return Err(error);
+        }
+        debug!("Recovered from {error}");
+        self.diagnostics.push(Diagnostic { error, recovery });
+        Ok(())
+    }
+
+    fn enter(&mut self, segment: String) {
+        self.path.push(segment);
+    }
+
+    fn rename(&mut self, segment: String) {
+        if let Some(last) = self.path.last_mut() {
+            *last = segment;
+        }
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    fn error(&self, kind: ParseErrorKind) -> ParseError {
+        self.error_at(self.last_read_offset, kind)
+    }
+
+    fn error_at(&self, offset: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError::new(kind, offset, self.path.clone(), self.bytes)
+    }
+}
+
+#[cfg(test)]
+use proptest::prelude::*;
+
+#[cfg(test)]
+fn my_class_bytes() -> Vec<u8> {
+    std::fs::read("MyClass.class").unwrap()
+}
+
+#[cfg(test)]
+fn parse_without_panicking(bytes: Vec<u8>) {
+    if let Ok(java_class) = crate::JavaClass::from_bytes(bytes.clone()) {
+        java_class.to_bytes();
+        for code in java_class.methods.iter().filter_map(|method| method.code()) {
+            let _ = code.instructions();
+        }
+    }
+    if let Ok((java_class, _)) = crate::JavaClass::read_with_diagnostics(&ClassReader::new(bytes).with_mode(ParseMode::Lenient)) {
+        java_class.to_bytes();
+    }
+}
+
+#[cfg(test)]
+proptest! {
+    #[test]
+    fn test_parser_survives_random_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        parse_without_panicking(bytes);
+    }
+
+    #[test]
+    fn test_parser_survives_random_bytes_after_prelude(bytes in proptest::collection::vec(any::<u8>(), 0..4096)) {
+        let mut class_bytes = my_class_bytes()[..10].to_vec();
+        class_bytes.extend(bytes);
+        parse_without_panicking(class_bytes);
+    }
+
+    #[test]
+    fn test_parser_survives_overwritten_bytes(mutations in proptest::collection::vec((any::<prop::sample::Index>(), any::<u8>()), 1..16)) {
+        let mut bytes = my_class_bytes();
+        for (index, value) in mutations {
+            let index = index.index(bytes.len());
+            bytes[index] = value;
+        }
+        parse_without_panicking(bytes);
+    }
+
+    #[test]
+    fn test_parser_survives_inserted_and_removed_bytes(index in any::<prop::sample::Index>(), inserted in proptest::collection::vec(any::<u8>(), 0..8), removed in 0..8usize) {
+        let mut bytes = my_class_bytes();
+        let index = index.index(bytes.len());
+        let removed = removed.min(bytes.len() - index);
+        bytes.splice(index..index + removed, inserted);
+        parse_without_panicking(bytes);
+    }
+
+    #[test]
+    fn test_parser_survives_truncation(length in any::<prop::sample::Index>()) {
+        let mut bytes = my_class_bytes();
+        bytes.truncate(length.index(bytes.len()));
+        parse_without_panicking(bytes);
+    }
+}
+
+#[test]
+fn test_limits_are_enforced() {
+    let code_length_limit = ParseLimits { max_code_length: 10, ..ParseLimits::default() };
+    let error = crate::JavaClass::read(&ClassReader::new(my_class_bytes()).with_limits(code_length_limit)).unwrap_err();
+    assert_eq!(ParseErrorKind::LimitExceeded { limit: "code length", value: 22, max: 10 }, error.kind);
+
+    let file_size_limit = ParseLimits { max_file_size: 1000, ..ParseLimits::default() };
+    let error = crate::JavaClass::read(&ClassReader::new(my_class_bytes()).with_limits(file_size_limit)).unwrap_err();
+    assert_eq!(ParseErrorKind::LimitExceeded { limit: "file size", value: 1383, max: 1000 }, error.kind);
+
+    let nesting_limit = ParseLimits { max_attribute_nesting_depth: 1, ..ParseLimits::default() };
+    let error = crate::JavaClass::read(&ClassReader::new(my_class_bytes()).with_limits(nesting_limit)).unwrap_err();
+    assert_eq!(ParseErrorKind::LimitExceeded { limit: "attribute nesting depth", value: 2, max: 1 }, error.kind);
+
+    let allocation_limit = ParseLimits { max_total_allocation: 1000, ..ParseLimits::default() };
+    let error = crate::JavaClass::read(&ClassReader::new(my_class_bytes()).with_limits(allocation_limit)).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::LimitExceeded { limit: "total allocation", .. }));
+}
+
+#[test]
+fn test_lying_code_length_fails_cleanly() {
+    let mut bytes = my_class_bytes();
+    let code_length_offset = bytes.windows(4).position(|window| window == [0, 0, 0, 22]).unwrap();
+    bytes[code_length_offset..code_length_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+    let error = crate::JavaClass::from_bytes(bytes).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::LimitExceeded { limit: "code length", .. }));
+}
+
+#[test]
+fn test_modified_utf8_decodes_nul_and_supplementary_characters() {
+    assert_eq!(Some("a\0b".to_string()), decode_modified_utf8(&[0x61, 0xC0, 0x80, 0x62]));
+    assert_eq!(Some("\u{1F600}".to_string()), decode_modified_utf8(&[0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80]));
+    assert_eq!(None, decode_modified_utf8(&[0xF0, 0x9F, 0x98, 0x80]));
+}
+
+#[test]
+fn test_errors_carry_offset_and_structural_path() {
+    let mut bytes = my_class_bytes();
+    // main's LineNumberTable: attribute_length 22, line_number_table_length 5
+    let table_offset = bytes.windows(6).rposition(|window| window == [0, 0, 0, 22, 0, 5]).unwrap() + 4;
+    bytes[table_offset + 1] = 6;
+    let error = crate::JavaClass::from_bytes(bytes).unwrap_err();
+
+    assert_eq!(ParseErrorKind::AttributeLengthMismatch { expected: 22, read: 26 }, error.kind);
+    assert_eq!(table_offset + 26, error.offset);
+    assert_eq!(vec!["methods[5] \"main\"", "attributes[0] Code", "attributes[0] LineNumberTable"], error.path);
+    let message = error.to_string();
+    assert!(message.starts_with(&format!("Expected to read 22 bytes but read 26 at offset 0x{:x} in methods[5] \"main\" / attributes[0] Code / attributes[0] LineNumberTable\n", table_offset + 26)));
+    assert!(message.contains("^^"));
+}
+
+#[test]
+fn test_rejects_files_that_are_not_class_files() {
+    let mut bytes = vec![0; 24];
+    for mode in [ParseMode::Strict, ParseMode::Lenient] {
+        let error = crate::JavaClass::read_with_diagnostics(&ClassReader::new(bytes.clone()).with_mode(mode)).unwrap_err();
+        assert_eq!(ParseErrorKind::InvalidPrelude("The prelude is 0x0 instead of 0xCAFEBABE".to_string()), error.kind);
+        assert_eq!(0, error.offset);
+    }
+    bytes.truncate(2);
+    assert_eq!(ParseErrorKind::UnexpectedEnd { expected: 4, remaining: 2 }, crate::JavaClass::from_bytes(bytes).unwrap_err().kind);
+}
+
+#[test]
+fn test_wide_constant_in_the_last_slot_is_rejected() {
+    // A pool of count 3 holding an Integer then a Long, whose second slot would be #3
+    let mut bytes = vec![0xCA, 0xFE, 0xBA, 0xBE, 0, 0, 0, 49, 0, 3, 3, 0, 0, 0, 1, 5];
+    bytes.extend(5i64.to_be_bytes());
+    bytes.extend([0; 14]);
+    let error = crate::JavaClass::from_bytes(bytes.clone()).unwrap_err();
+    assert_eq!(ParseErrorKind::WideConstantInLastSlot(2), error.kind);
+    assert_eq!(15, error.offset);
+
+    let (java_class, diagnostics) = read_leniently(bytes);
+    // The pool has no class for this_class to point at
+    assert_eq!(vec![Recovery::SkippedConstant, Recovery::Placeholder], diagnostics.iter().map(|diagnostic| diagnostic.recovery.clone()).collect::<Vec<_>>());
+    assert_eq!(2, java_class.constant_pool_infos.len());
+    assert_eq!(ConstantPoolInfo::Unusable, java_class.constant_pool_infos[1]);
+}
+
+#[test]
+fn test_truncated_file_points_at_end() {
+    let bytes = my_class_bytes()[..100].to_vec();
+    let error = crate::JavaClass::from_bytes(bytes).unwrap_err();
+    assert_eq!(ParseErrorKind::UnexpectedEnd { expected: 2, remaining: 0 }, error.kind);
+    assert_eq!(100, error.offset);
+    assert_eq!(vec!["constant_pool[13]"], error.path);
+}
+
+#[cfg(test)]
+fn read_leniently(bytes: Vec<u8>) -> (crate::JavaClass, Vec<Diagnostic>) {
+    crate::JavaClass::read_with_diagnostics(&ClassReader::new(bytes).with_mode(ParseMode::Lenient)).unwrap()
+}
+
+#[test]
+fn test_lenient_mode_skips_mis_sized_attribute() {
+    let mut bytes = my_class_bytes();
+    let table_offset = bytes.windows(6).rposition(|window| window == [0, 0, 0, 22, 0, 5]).unwrap() + 4;
+    bytes[table_offset + 1] = 6;
+    assert!(crate::JavaClass::from_bytes(bytes.clone()).is_err());
+
+    let (java_class, diagnostics) = read_leniently(bytes);
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(Recovery::SkippedAttribute, diagnostics[0].recovery);
+    assert_eq!(ParseErrorKind::AttributeLengthMismatch { expected: 22, read: 26 }, diagnostics[0].error.kind);
+    let original = crate::JavaClass::from_bytes(my_class_bytes()).unwrap();
+    assert_eq!(original.methods.len(), java_class.methods.len());
+    assert_eq!(original.attributes, java_class.attributes);
+}
+
+#[test]
+fn test_lenient_mode_replaces_invalid_names() {
+    let mut bytes = my_class_bytes();
+    let original = crate::JavaClass::from_bytes(bytes.clone()).unwrap();
+    // private int privateInt: access_flags, name_index #11, descriptor_index #12
+    let name_offset = bytes.windows(6).position(|window| window == [0, 2, 0, 11, 0, 12]).unwrap() + 2;
+    bytes[name_offset..name_offset + 2].copy_from_slice(&[0xFF, 0xFF]);
+
+    let (java_class, diagnostics) = read_leniently(bytes);
+    assert_eq!(vec![Recovery::Placeholder], diagnostics.iter().map(|diagnostic| diagnostic.recovery.clone()).collect::<Vec<_>>());
+    assert_eq!(ParseErrorKind::InvalidConstantIndex { index: 0xFFFF, expected: "Utf8" }, diagnostics[0].error.kind);
+    assert_eq!(original.methods, java_class.methods);
+}
+
+#[test]
+fn test_lenient_mode_keeps_what_precedes_an_unknown_constant() {
+    let mut bytes = my_class_bytes();
+    bytes[10] = 2;
+    assert_eq!(ParseErrorKind::UnknownConstantTag(2), crate::JavaClass::from_bytes(bytes.clone()).unwrap_err().kind);
+
+    let (java_class, diagnostics) = read_leniently(bytes);
+    assert_eq!(1, diagnostics.len());
+    assert_eq!(Recovery::StoppedReading, diagnostics[0].recovery);
+    assert_eq!(63, java_class.major);
+    assert!(java_class.methods.is_empty());
+}
+
+#[test]
+fn test_lenient_mode_still_enforces_limits() {
+    let limits = ParseLimits { max_code_length: 10, ..ParseLimits::default() };
+    let class_reader = ClassReader::new(my_class_bytes()).with_limits(limits).with_mode(ParseMode::Lenient);
+    let error = crate::JavaClass::read_with_diagnostics(&class_reader).unwrap_err();
+    assert!(matches!(error.kind, ParseErrorKind::LimitExceeded { limit: "code length", .. }));
+}
+
+#[test]
+fn test_bootstrap_methods_are_parsed_and_truncation_is_an_error() {
+    let mut bytes = std::fs::read("Lambdas.class").unwrap();
+    let java_class = crate::JavaClass::from_bytes(bytes.clone()).unwrap();
+    let bootstrap_methods = java_class.bootstrap_methods();
+    assert!(!bootstrap_methods.is_empty());
+    for bootstrap_method in bootstrap_methods {
+        assert!(matches!(java_class.constant_pool_infos.get(bootstrap_method.method_handle_index - 1), Some(ConstantPoolInfo::MethodKind(_))));
+    }
+
+    // attribute_length then num_bootstrap_methods: claim one entry more than the attribute holds
+    let attribute_length = 2 + bootstrap_methods.iter().map(|method| 4 + 2 * method.argument_indexes.len()).sum::<usize>();
+    let mut header = (attribute_length as u32).to_be_bytes().to_vec();
+    header.extend((bootstrap_methods.len() as u16).to_be_bytes());
+    let count_offset = bytes.windows(6).rposition(|window| window == header).unwrap() + 4;
+    bytes[count_offset + 1] += 1;
+    let error = crate::JavaClass::from_bytes(bytes).unwrap_err();
+    assert_eq!(vec!["attributes[1] BootstrapMethods"], error.path);
+}
+
+#[test]
+fn test_class_indexes_must_point_at_classes() {
+    let mut bytes = my_class_bytes();
+    let original = crate::JavaClass::from_bytes(bytes.clone()).unwrap();
+    let header = [original.flags.0.to_be_bytes(), (original.this_class as u16).to_be_bytes(), (original.super_class as u16).to_be_bytes()].concat();
+    let this_class_offset = bytes.windows(6).position(|window| window == header).unwrap() + 2;
+    bytes[this_class_offset..this_class_offset + 2].copy_from_slice(&[0, 0]);
+    bytes[this_class_offset + 2..this_class_offset + 4].copy_from_slice(&[0xFF, 0xFF]);
+    let error = crate::JavaClass::from_bytes(bytes.clone()).unwrap_err();
+    assert_eq!(ParseErrorKind::InvalidConstantIndex { index: 0, expected: "Class" }, error.kind);
+    assert_eq!(vec!["this_class"], error.path);
+
+    let (java_class, diagnostics) = read_leniently(bytes);
+    assert_eq!(vec![ParseErrorKind::InvalidConstantIndex { index: 0, expected: "Class" }, ParseErrorKind::InvalidConstantIndex { index: 0xFFFF, expected: "Class" }],
+               diagnostics.iter().map(|diagnostic| diagnostic.error.kind.clone()).collect::<Vec<_>>());
+    assert_eq!((0, 0xFFFF), (java_class.this_class, java_class.super_class));
+    assert_eq!(original.methods, java_class.methods);
+    crate::class_printer::ClassPrinter::new(java_class).print();
+}
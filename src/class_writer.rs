@@ -0,0 +1,344 @@
+use crate::{BootstrapMethod, ConstantPoolInfo, ExceptionTableEntry, JavaClass, LineNumberTableEntry};
+use crate::visitor::{ClassVisitor, CodeVisitor, FieldVisitor, MethodVisitor};
+
+/// A [`ClassVisitor`] that serializes the events it receives into class file bytes.
+///
+/// Putting other visitors between a [`ClassReader`](crate::class_reader::ClassReader) and a
+/// `ClassWriter` turns them into a load-time transform.
+pub struct ClassWriter {
+    header: Vec<u8>,
+    class: Vec<u8>,
+    fields: AttributesWriter,
+    methods: AttributesWriter,
+    attributes: AttributesWriter,
+}
+
+impl ClassWriter {
+    pub fn new() -> Self {
+        ClassWriter {
+            header: Vec::new(),
+            class: Vec::new(),
+            fields: AttributesWriter::new(),
+            methods: AttributesWriter::new(),
+            attributes: AttributesWriter::new(),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.header.clone();
+        bytes.extend_from_slice(&self.class);
+        self.fields.write_to(&mut bytes);
+        self.methods.write_to(&mut bytes);
+        self.attributes.write_to(&mut bytes);
+        bytes
+    }
+
+    fn write_constant_pool_info(bytes: &mut Vec<u8>, constant_pool_info: &ConstantPoolInfo) {
+        match constant_pool_info {
+            ConstantPoolInfo::Utf8(utf8_info) => {
+                write_u8(bytes, utf8_info.tag);
+                let encoded = encode_modified_utf8(&utf8_info.string);
+                write_u16(bytes, encoded.len());
+                bytes.extend_from_slice(&encoded);
+            }
+            ConstantPoolInfo::Integer(integer_info) => {
+                write_u8(bytes, integer_info.tag);
+                bytes.extend_from_slice(&integer_info.value.to_be_bytes());
+            }
+            ConstantPoolInfo::Float(float_info) => {
+                write_u8(bytes, float_info.tag);
+                bytes.extend_from_slice(&float_info.value.to_be_bytes());
+            }
+            ConstantPoolInfo::Long(long_info) => {
+                write_u8(bytes, long_info.tag);
+                bytes.extend_from_slice(&long_info.value.to_be_bytes());
+            }
+            ConstantPoolInfo::Double(double_info) => {
+                write_u8(bytes, double_info.tag);
+                bytes.extend_from_slice(&double_info.value.to_be_bytes());
+            }
+            ConstantPoolInfo::Class(class_info) => {
+                write_u8(bytes, class_info.tag);
+                write_u16(bytes, class_info.name_index);
+            }
+            ConstantPoolInfo::String(string_info) => {
+                write_u8(bytes, string_info.tag);
+                write_u16(bytes, string_info.name_index);
+            }
+            ConstantPoolInfo::FieldRef(field_ref_info) => {
+                write_u8(bytes, field_ref_info.tag);
+                write_u16(bytes, field_ref_info.class_index);
+                write_u16(bytes, field_ref_info.name_and_type_index);
+            }
+            ConstantPoolInfo::MethodRef(method_ref_info) => {
+                write_u8(bytes, method_ref_info.tag);
+                write_u16(bytes, method_ref_info.class_index);
+                write_u16(bytes, method_ref_info.name_and_type_index);
+            }
+            ConstantPoolInfo::InterfaceMethodRef(interface_method_ref_info) => {
+                write_u8(bytes, interface_method_ref_info.tag);
+                write_u16(bytes, interface_method_ref_info.class_index);
+                write_u16(bytes, interface_method_ref_info.name_and_type_index);
+            }
+            ConstantPoolInfo::NameAndType(name_and_type_info) => {
+                write_u8(bytes, name_and_type_info.tag);
+                write_u16(bytes, name_and_type_info.name_index);
+                write_u16(bytes, name_and_type_info.descriptor_index);
+            }
+            ConstantPoolInfo::MethodKind(method_kind) => {
+                write_u8(bytes, method_kind.tag);
+                write_u8(bytes, method_kind.reference_kind);
+                write_u16(bytes, method_kind.reference_index);
+            }
+            ConstantPoolInfo::MethodType(method_type) => {
+                write_u8(bytes, method_type.tag);
+                write_u16(bytes, method_type.descriptor_index);
+            }
+            ConstantPoolInfo::Dynamic(dynamic) => {
+                write_u8(bytes, dynamic.tag);
+                write_u16(bytes, dynamic.bootstrap_method_attr_index);
+                write_u16(bytes, dynamic.name_and_type_index);
+            }
+            ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => {
+                write_u8(bytes, invoke_dynamic.tag);
+                write_u16(bytes, invoke_dynamic.bootstrap_method_attr_index);
+                write_u16(bytes, invoke_dynamic.name_and_type_index);
+            }
+            ConstantPoolInfo::Unusable => {}
+        }
+    }
+}
+
+impl Default for ClassWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassVisitor for ClassWriter {
+    fn visit_header(&mut self, prelude: u32, minor: u16, major: u16) {
+        self.header.extend_from_slice(&prelude.to_be_bytes());
+        self.header.extend_from_slice(&minor.to_be_bytes());
+        self.header.extend_from_slice(&major.to_be_bytes());
+    }
+
+    fn visit_constant_pool(&mut self, constant_pool_infos: &[ConstantPoolInfo]) {
+        write_u16(&mut self.header, constant_pool_infos.len() + 1);
+        for constant_pool_info in constant_pool_infos {
+            Self::write_constant_pool_info(&mut self.header, constant_pool_info);
+        }
+    }
+
+    fn visit_class(&mut self, access_flags: u16, this_class: usize, super_class: usize, interfaces_indexes: &[usize]) {
+        self.class.extend_from_slice(&access_flags.to_be_bytes());
+        write_u16(&mut self.class, this_class);
+        write_u16(&mut self.class, super_class);
+        write_u16(&mut self.class, interfaces_indexes.len());
+        for interface_index in interfaces_indexes {
+            write_u16(&mut self.class, *interface_index);
+        }
+    }
+
+    fn visit_field(&mut self, access_flags: u16, name_index: usize, _name: &str, descriptor_index: usize, _descriptor: &str) -> Option<Box<dyn FieldVisitor + '_>> {
+        Some(Box::new(MemberWriter::new(&mut self.fields, access_flags, name_index, descriptor_index)))
+    }
+
+    fn visit_method(&mut self, access_flags: u16, name_index: usize, _name: &str, descriptor_index: usize, _descriptor: &str) -> Option<Box<dyn MethodVisitor + '_>> {
+        Some(Box::new(MemberWriter::new(&mut self.methods, access_flags, name_index, descriptor_index)))
+    }
+
+    fn visit_bootstrap_methods(&mut self, attribute_name_index: usize, bootstrap_methods: &[BootstrapMethod]) {
+        let mut info = Vec::new();
+        write_u16(&mut info, bootstrap_methods.len());
+        for bootstrap_method in bootstrap_methods {
+            write_u16(&mut info, bootstrap_method.method_handle_index);
+            write_u16(&mut info, bootstrap_method.argument_indexes.len());
+            for argument_index in &bootstrap_method.argument_indexes {
+                write_u16(&mut info, *argument_index);
+            }
+        }
+        self.attributes.write_attribute(attribute_name_index, &info);
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, _attribute_name: &str, info: &[u8]) {
+        self.attributes.write_attribute(attribute_name_index, info);
+    }
+}
+
+/// A counted sequence of `attribute_info` (or `field_info`/`method_info`) entries.
+struct AttributesWriter {
+    count: usize,
+    bytes: Vec<u8>,
+}
+
+impl AttributesWriter {
+    fn new() -> Self {
+        AttributesWriter {
+            count: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    fn write_attribute(&mut self, attribute_name_index: usize, info: &[u8]) {
+        self.count += 1;
+        write_u16(&mut self.bytes, attribute_name_index);
+        self.bytes.extend_from_slice(&(info.len() as u32).to_be_bytes());
+        self.bytes.extend_from_slice(info);
+    }
+
+    fn write_to(&self, bytes: &mut Vec<u8>) {
+        write_u16(bytes, self.count);
+        bytes.extend_from_slice(&self.bytes);
+    }
+}
+
+struct MemberWriter<'a> {
+    members: &'a mut AttributesWriter,
+    header: Vec<u8>,
+    attributes: AttributesWriter,
+}
+
+impl<'a> MemberWriter<'a> {
+    fn new(members: &'a mut AttributesWriter, access_flags: u16, name_index: usize, descriptor_index: usize) -> Self {
+        let mut header = Vec::with_capacity(6);
+        header.extend_from_slice(&access_flags.to_be_bytes());
+        write_u16(&mut header, name_index);
+        write_u16(&mut header, descriptor_index);
+        MemberWriter {
+            members,
+            header,
+            attributes: AttributesWriter::new(),
+        }
+    }
+
+    fn finish(&mut self) {
+        self.members.count += 1;
+        self.members.bytes.extend_from_slice(&self.header);
+        self.attributes.write_to(&mut self.members.bytes);
+    }
+}
+
+impl FieldVisitor for MemberWriter<'_> {
+    fn visit_constant_value(&mut self, attribute_name_index: usize, constant_value_index: usize) {
+        self.attributes.write_attribute(attribute_name_index, &(constant_value_index as u16).to_be_bytes());
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, _attribute_name: &str, info: &[u8]) {
+        self.attributes.write_attribute(attribute_name_index, info);
+    }
+
+    fn visit_end(&mut self) {
+        self.finish();
+    }
+}
+
+impl MethodVisitor for MemberWriter<'_> {
+    fn visit_code(&mut self, attribute_name_index: usize, max_stack: usize, max_locals: usize) -> Option<Box<dyn CodeVisitor + '_>> {
+        Some(Box::new(CodeWriter {
+            method_attributes: &mut self.attributes,
+            attribute_name_index,
+            max_stack,
+            max_locals,
+            code: Vec::new(),
+            exceptions: Vec::new(),
+            attributes: AttributesWriter::new(),
+        }))
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, _attribute_name: &str, info: &[u8]) {
+        self.attributes.write_attribute(attribute_name_index, info);
+    }
+
+    fn visit_end(&mut self) {
+        self.finish();
+    }
+}
+
+struct CodeWriter<'a> {
+    method_attributes: &'a mut AttributesWriter,
+    attribute_name_index: usize,
+    max_stack: usize,
+    max_locals: usize,
+    code: Vec<u8>,
+    exceptions: Vec<ExceptionTableEntry>,
+    attributes: AttributesWriter,
+}
+
+impl CodeVisitor for CodeWriter<'_> {
+    fn visit_bytecode(&mut self, code: &[u8]) {
+        self.code = code.to_vec();
+    }
+
+    fn visit_exception_table_entry(&mut self, entry: &ExceptionTableEntry) {
+        self.exceptions.push(entry.clone());
+    }
+
+    fn visit_line_number_table(&mut self, attribute_name_index: usize, line_number_entries: &[LineNumberTableEntry]) {
+        let mut info = Vec::with_capacity(2 + 4 * line_number_entries.len());
+        write_u16(&mut info, line_number_entries.len());
+        for entry in line_number_entries {
+            write_u16(&mut info, entry.start_pc);
+            write_u16(&mut info, entry.line_number);
+        }
+        self.attributes.write_attribute(attribute_name_index, &info);
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, _attribute_name: &str, info: &[u8]) {
+        self.attributes.write_attribute(attribute_name_index, info);
+    }
+
+    fn visit_end(&mut self) {
+        let mut info = Vec::new();
+        write_u16(&mut info, self.max_stack);
+        write_u16(&mut info, self.max_locals);
+        info.extend_from_slice(&(self.code.len() as u32).to_be_bytes());
+        info.extend_from_slice(&self.code);
+        write_u16(&mut info, self.exceptions.len());
+        for entry in &self.exceptions {
+            write_u16(&mut info, entry.start_pc);
+            write_u16(&mut info, entry.end_pc);
+            write_u16(&mut info, entry.handler_pc);
+            write_u16(&mut info, entry.catch_pc);
+        }
+        self.attributes.write_to(&mut info);
+        self.method_attributes.write_attribute(self.attribute_name_index, &info);
+    }
+}
+
+/// The inverse of the reader's decoding: `NUL` takes two bytes and characters outside the
+/// basic multilingual plane are written as two three-byte surrogates.
+fn encode_modified_utf8(string: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(string.len());
+    for unit in string.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    bytes
+}
+
+fn write_u8(bytes: &mut Vec<u8>, value: u8) {
+    bytes.push(value);
+}
+
+fn write_u16(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend_from_slice(&(value as u16).to_be_bytes());
+}
+
+impl JavaClass {
+    /// Serializes this class back into the class file format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut class_writer = ClassWriter::new();
+        self.accept(&mut class_writer);
+        class_writer.to_bytes()
+    }
+}
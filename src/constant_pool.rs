@@ -0,0 +1,172 @@
+use crate::class_reader::resolve_constant_pool_names;
+use crate::{ConstantPoolClassInfo, ConstantPoolDoubleInfo, ConstantPoolFieldRefInfo, ConstantPoolFloatInfo, ConstantPoolInfo, ConstantPoolIntegerInfo, ConstantPoolInterfaceMethodRefInfo, ConstantPoolLongInfo, ConstantPoolMethodRefInfo, ConstantPoolNameAndTypeInfo, ConstantPoolStringInfo, ConstantPoolUtf8Info};
+
+/// Builds a constant pool, handing out the index of an equal entry when there already is one.
+///
+/// Indexes are 1-based like in the class file, and `Long` and `Double` entries take two slots.
+#[derive(Debug, Default, Clone)]
+pub struct ConstantPoolBuilder {
+    constant_pool_infos: Vec<ConstantPoolInfo>,
+}
+
+impl ConstantPoolBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts from an existing pool, keeping its indexes.
+    pub fn from_constants(constant_pool_infos: Vec<ConstantPoolInfo>) -> Self {
+        ConstantPoolBuilder { constant_pool_infos }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ConstantPoolInfo> {
+        index.checked_sub(1).and_then(|index| self.constant_pool_infos.get(index))
+    }
+
+    /// The index the next entry will get.
+    pub fn next_index(&self) -> usize {
+        self.constant_pool_infos.len() + 1
+    }
+
+    /// Appends an entry as is, without looking for an equal one, and returns its index.
+    pub fn push(&mut self, constant_pool_info: ConstantPoolInfo) -> usize {
+        let index = self.next_index();
+        let two_slots = matches!(constant_pool_info, ConstantPoolInfo::Long(_) | ConstantPoolInfo::Double(_));
+        self.constant_pool_infos.push(constant_pool_info);
+        if two_slots {
+            self.constant_pool_infos.push(ConstantPoolInfo::Unusable);
+        }
+        index
+    }
+
+    fn find_or_push(&mut self, constant_pool_info: ConstantPoolInfo, matches: impl Fn(&ConstantPoolInfo) -> bool) -> usize {
+        match self.constant_pool_infos.iter().position(matches) {
+            Some(position) => position + 1,
+            None => self.push(constant_pool_info),
+        }
+    }
+
+    pub fn utf8(&mut self, string: &str) -> usize {
+        let constant = ConstantPoolInfo::Utf8(ConstantPoolUtf8Info { tag: 1, string: string.to_string() });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Utf8(utf8_info) if utf8_info.string == string))
+    }
+
+    pub fn integer(&mut self, value: i32) -> usize {
+        let constant = ConstantPoolInfo::Integer(ConstantPoolIntegerInfo { tag: 3, value });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Integer(integer_info) if integer_info.value == value))
+    }
+
+    /// Floats are compared by their bits, so `-0.0` and the different NaNs get entries of their own.
+    pub fn float(&mut self, value: f32) -> usize {
+        let constant = ConstantPoolInfo::Float(ConstantPoolFloatInfo { tag: 4, value });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Float(float_info) if float_info.value.to_bits() == value.to_bits()))
+    }
+
+    pub fn long(&mut self, value: i64) -> usize {
+        let constant = ConstantPoolInfo::Long(ConstantPoolLongInfo { tag: 5, value });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Long(long_info) if long_info.value == value))
+    }
+
+    pub fn double(&mut self, value: f64) -> usize {
+        let constant = ConstantPoolInfo::Double(ConstantPoolDoubleInfo { tag: 6, value });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Double(double_info) if double_info.value.to_bits() == value.to_bits()))
+    }
+
+    pub fn class(&mut self, name: &str) -> usize {
+        let name_index = self.utf8(name);
+        let constant = ConstantPoolInfo::Class(ConstantPoolClassInfo { tag: 7, name_index, name: name.to_string() });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::Class(class_info) if class_info.name_index == name_index))
+    }
+
+    pub fn string(&mut self, string: &str) -> usize {
+        let name_index = self.utf8(string);
+        let constant = ConstantPoolInfo::String(ConstantPoolStringInfo { tag: 8, name_index, string: string.to_string() });
+        self.find_or_push(constant, |info| matches!(info, ConstantPoolInfo::String(string_info) if string_info.name_index == name_index))
+    }
+
+    pub fn name_and_type(&mut self, name: &str, descriptor: &str) -> usize {
+        let name_index = self.utf8(name);
+        let descriptor_index = self.utf8(descriptor);
+        let constant = ConstantPoolInfo::NameAndType(ConstantPoolNameAndTypeInfo {
+            tag: 12,
+            name_index,
+            descriptor_index,
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        });
+        self.find_or_push(constant, |info| {
+            matches!(info, ConstantPoolInfo::NameAndType(name_and_type) if name_and_type.name_index == name_index && name_and_type.descriptor_index == descriptor_index)
+        })
+    }
+
+    pub fn field_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> usize {
+        let class_index = self.class(class_name);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        let constant = ConstantPoolInfo::FieldRef(ConstantPoolFieldRefInfo {
+            tag: 9,
+            class_index,
+            name_and_type_index,
+            class_name: class_name.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        });
+        self.find_or_push(constant, |info| {
+            matches!(info, ConstantPoolInfo::FieldRef(field_ref) if field_ref.class_index == class_index && field_ref.name_and_type_index == name_and_type_index)
+        })
+    }
+
+    pub fn method_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> usize {
+        let class_index = self.class(class_name);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        let constant = ConstantPoolInfo::MethodRef(ConstantPoolMethodRefInfo {
+            tag: 10,
+            class_index,
+            name_and_type_index,
+            class_name: class_name.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        });
+        self.find_or_push(constant, |info| {
+            matches!(info, ConstantPoolInfo::MethodRef(method_ref) if method_ref.class_index == class_index && method_ref.name_and_type_index == name_and_type_index)
+        })
+    }
+
+    pub fn interface_method_ref(&mut self, class_name: &str, name: &str, descriptor: &str) -> usize {
+        let class_index = self.class(class_name);
+        let name_and_type_index = self.name_and_type(name, descriptor);
+        let constant = ConstantPoolInfo::InterfaceMethodRef(ConstantPoolInterfaceMethodRefInfo {
+            tag: 11,
+            class_index,
+            name_and_type_index,
+            class_name: class_name.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string(),
+        });
+        self.find_or_push(constant, |info| {
+            matches!(info, ConstantPoolInfo::InterfaceMethodRef(method_ref) if method_ref.class_index == class_index && method_ref.name_and_type_index == name_and_type_index)
+        })
+    }
+
+    /// The finished pool, with the names every entry refers to resolved.
+    pub fn build(self) -> Vec<ConstantPoolInfo> {
+        let mut constant_pool_infos = self.constant_pool_infos;
+        resolve_constant_pool_names(&mut constant_pool_infos);
+        constant_pool_infos
+    }
+}
+
+#[test]
+fn test_equal_entries_are_shared() {
+    let mut constant_pool = ConstantPoolBuilder::new();
+    let method_ref = constant_pool.method_ref("java/lang/Object", "<init>", "()V");
+    assert_eq!(6, method_ref);
+    assert_eq!(method_ref, constant_pool.method_ref("java/lang/Object", "<init>", "()V"));
+    assert_eq!(2, constant_pool.class("java/lang/Object"));
+    assert_eq!(7, constant_pool.long(7));
+    assert_eq!(9, constant_pool.integer(7));
+
+    let constant_pool_infos = constant_pool.build();
+    assert_eq!(9, constant_pool_infos.len());
+    assert_eq!(ConstantPoolInfo::Unusable, constant_pool_infos[7]);
+    assert_eq!("Method java/lang/Object.\"<init>\":()V", crate::disassembler::describe_constant(&constant_pool_infos, method_ref));
+}
@@ -0,0 +1,411 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use crate::bytecode::Opcode;
+use crate::disassembler::{describe_constant, format_instruction};
+use crate::instruction::{DecodeError, Instruction};
+use crate::{AttributeCode, ConstantPoolInfo};
+
+/// A maximal run of instructions that is only entered at its first instruction and only left
+/// after its last one, exceptions aside.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BasicBlock {
+    /// The pc of the first instruction.
+    pub start: usize,
+    /// The pc just past the last instruction.
+    pub end: usize,
+    /// The positions of the block's instructions in [`ControlFlowGraph::instructions`].
+    pub instructions: std::ops::Range<usize>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EdgeKind {
+    /// Falling through, branching or switching.
+    Normal,
+    /// To the handler of an exception table entry covering the block; `catch_type` is the
+    /// constant pool index of the caught class, or 0 for `finally`.
+    Exceptional { catch_type: usize },
+    /// From a `jsr` to the start of its subroutine.
+    Jsr,
+    /// From a `ret` back to the instruction following a `jsr` that called its subroutine.
+    Ret,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// The basic blocks of a method body and the edges between them. Blocks are numbered in pc order,
+/// so block 0 is the entry.
+///
+/// Blocks are split at the boundaries of exception table ranges, which makes every block either
+/// wholly covered by an entry or not covered at all; covered blocks get an exceptional edge to the
+/// handler.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ControlFlowGraph {
+    pub instructions: Vec<(usize, Instruction)>,
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+impl ControlFlowGraph {
+    pub fn new(code: &AttributeCode) -> Result<Self, DecodeError> {
+        let instructions = code.instructions()?;
+        let mut leaders = BTreeSet::from([0]);
+        for (position, (_, instruction)) in instructions.iter().enumerate() {
+            leaders.extend(instruction.branch_targets().iter().map(|target| target.0));
+            let opcode = instruction.opcode();
+            if opcode.can_branch() || !opcode.can_fall_through() {
+                if let Some((next_pc, _)) = instructions.get(position + 1) {
+                    leaders.insert(*next_pc);
+                }
+            }
+        }
+        for entry in &code.exceptions {
+            leaders.extend([entry.start_pc, entry.end_pc, entry.handler_pc]);
+        }
+
+        let code_length = code.code.len();
+        let mut blocks = Vec::new();
+        let mut first = 0;
+        for (position, (pc, _)) in instructions.iter().enumerate().skip(1) {
+            if leaders.contains(pc) {
+                blocks.push(BasicBlock { start: instructions[first].0, end: *pc, instructions: first..position });
+                first = position;
+            }
+        }
+        if !instructions.is_empty() {
+            blocks.push(BasicBlock { start: instructions[first].0, end: code_length, instructions: first..instructions.len() });
+        }
+
+        let mut graph = ControlFlowGraph { instructions, blocks, edges: Vec::new() };
+        let mut return_sites = Vec::new();
+        for block in 0..graph.blocks.len() {
+            let last = graph.blocks[block].instructions.end - 1;
+            let (_, instruction) = &graph.instructions[last];
+            let opcode = instruction.opcode();
+            if matches!(opcode, Opcode::Jsr | Opcode::JsrW) {
+                let subroutine = graph.block_at(instruction.branch_targets()[0].0).unwrap();
+                graph.edges.push(Edge { from: block, to: subroutine, kind: EdgeKind::Jsr });
+                if block + 1 < graph.blocks.len() {
+                    return_sites.push((subroutine, block + 1));
+                }
+                continue;
+            }
+            for target in instruction.branch_targets() {
+                let to = graph.block_at(target.0).unwrap();
+                if !graph.edges.iter().any(|edge| edge.from == block && edge.to == to) {
+                    graph.edges.push(Edge { from: block, to, kind: EdgeKind::Normal });
+                }
+            }
+            if opcode.can_fall_through() && block + 1 < graph.blocks.len() {
+                graph.edges.push(Edge { from: block, to: block + 1, kind: EdgeKind::Normal });
+            }
+        }
+        for entry in &code.exceptions {
+            let Some(handler) = graph.block_at(entry.handler_pc) else { continue };
+            for block in 0..graph.blocks.len() {
+                if graph.blocks[block].start >= entry.start_pc && graph.blocks[block].end <= entry.end_pc {
+                    graph.edges.push(Edge { from: block, to: handler, kind: EdgeKind::Exceptional { catch_type: entry.catch_pc } });
+                }
+            }
+        }
+        graph.connect_subroutines(&return_sites);
+        Ok(graph)
+    }
+
+    /// Adds an edge from every `ret` to the return sites of the subroutine it returns from. The
+    /// blocks of a subroutine are those reachable from its start without entering the subroutines
+    /// it calls itself.
+    fn connect_subroutines(&mut self, return_sites: &[(usize, usize)]) {
+        let subroutines: BTreeSet<usize> = return_sites.iter().map(|(subroutine, _)| *subroutine).collect();
+        for subroutine in subroutines {
+            let mut visited = vec![false; self.blocks.len()];
+            let mut worklist = vec![subroutine];
+            let mut rets = Vec::new();
+            while let Some(block) = worklist.pop() {
+                if std::mem::replace(&mut visited[block], true) {
+                    continue;
+                }
+                let (_, last) = &self.instructions[self.blocks[block].instructions.end - 1];
+                if last.opcode() == Opcode::Ret {
+                    rets.push(block);
+                }
+                for edge in self.edges.iter().filter(|edge| edge.from == block) {
+                    match edge.kind {
+                        EdgeKind::Normal | EdgeKind::Exceptional { .. } => worklist.push(edge.to),
+                        EdgeKind::Jsr => worklist.extend(return_sites.iter().filter(|(_, site)| *site == block + 1).map(|(_, site)| *site)),
+                        EdgeKind::Ret => {}
+                    }
+                }
+            }
+            for ret in rets {
+                for (_, site) in return_sites.iter().filter(|(called, _)| *called == subroutine) {
+                    self.edges.push(Edge { from: ret, to: *site, kind: EdgeKind::Ret });
+                }
+            }
+        }
+    }
+
+    /// The block containing the instruction at `pc`.
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        let block = self.blocks.partition_point(|block| block.end <= pc);
+        (block < self.blocks.len() && self.blocks[block].start <= pc).then_some(block)
+    }
+
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    /// The instructions of a block, each with its pc.
+    pub fn block_instructions(&self, block: usize) -> &[(usize, Instruction)] {
+        &self.instructions[self.blocks[block].instructions.clone()]
+    }
+
+    /// The dominator tree, rooted at the entry block. Every kind of edge counts.
+    pub fn dominators(&self) -> DominatorTree {
+        let successors: Vec<Vec<usize>> = (0..self.blocks.len())
+            .map(|block| self.successors(block).map(|edge| edge.to).collect())
+            .collect();
+        DominatorTree::new(&successors, 0)
+    }
+
+    /// The post-dominator tree. Blocks without successors, which return or throw out of the
+    /// method, lead to a virtual exit that is the root of the tree and numbered `blocks.len()`.
+    pub fn post_dominators(&self) -> DominatorTree {
+        let exit = self.blocks.len();
+        let mut predecessors: Vec<Vec<usize>> = (0..exit)
+            .map(|block| self.predecessors(block).map(|edge| edge.from).collect())
+            .collect();
+        predecessors.push((0..exit).filter(|block| self.successors(*block).next().is_none()).collect());
+        DominatorTree::new(&predecessors, exit)
+    }
+
+    /// Renders the graph in Graphviz DOT. Exceptional edges are dashed and labeled with the caught
+    /// class, subroutine calls and returns are dotted.
+    pub fn to_dot(&self, name: &str, constant_pool_infos: &[ConstantPoolInfo]) -> String {
+        let mut output = String::new();
+        writeln!(output, "digraph \"{}\" {{", dot_escape(name)).unwrap();
+        writeln!(output, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (block, _) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for (pc, instruction) in self.block_instructions(block) {
+                for line in format_instruction(*pc, instruction, constant_pool_infos).lines() {
+                    label.push_str(&dot_escape(line.trim()));
+                    label.push_str("\\l");
+                }
+            }
+            writeln!(output, "    block{block} [label=\"{label}\"];").unwrap();
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Normal => String::new(),
+                EdgeKind::Exceptional { catch_type: 0 } => " [style=dashed, label=\"any\"]".to_string(),
+                EdgeKind::Exceptional { catch_type } => {
+                    let class_name = describe_constant(constant_pool_infos, catch_type);
+                    format!(" [style=dashed, label=\"{}\"]", dot_escape(class_name.trim_start_matches("class ")))
+                }
+                EdgeKind::Jsr => " [style=dotted, label=\"jsr\"]".to_string(),
+                EdgeKind::Ret => " [style=dotted, label=\"ret\"]".to_string(),
+            };
+            writeln!(output, "    block{} -> block{}{attributes};", edge.from, edge.to).unwrap();
+        }
+        writeln!(output, "}}").unwrap();
+        output
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Immediate dominators of the blocks of a graph, computed with the iterative algorithm of
+/// Cooper, Harvey and Kennedy.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DominatorTree {
+    root: usize,
+    /// The immediate dominator of every node; the root's is itself and unreachable nodes have none.
+    immediate_dominators: Vec<Option<usize>>,
+}
+
+impl DominatorTree {
+    pub(crate) fn new(successors: &[Vec<usize>], root: usize) -> Self {
+        let count = successors.len();
+        let mut postorder = Vec::with_capacity(count);
+        let mut visited = vec![false; count];
+        let mut stack = vec![(root, 0)];
+        visited[root] = true;
+        while let Some((node, next)) = stack.pop() {
+            match successors[node].get(next) {
+                Some(&successor) => {
+                    stack.push((node, next + 1));
+                    if !std::mem::replace(&mut visited[successor], true) {
+                        stack.push((successor, 0));
+                    }
+                }
+                None => postorder.push(node),
+            }
+        }
+        let mut postorder_numbers = vec![usize::MAX; count];
+        for (number, node) in postorder.iter().enumerate() {
+            postorder_numbers[*node] = number;
+        }
+        let mut predecessors = vec![Vec::new(); count];
+        for (node, node_successors) in successors.iter().enumerate() {
+            for successor in node_successors {
+                predecessors[*successor].push(node);
+            }
+        }
+
+        let mut immediate_dominators = vec![None; count];
+        immediate_dominators[root] = Some(root);
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &node in postorder.iter().rev().filter(|node| **node != root) {
+                let mut new_dominator: Option<usize> = None;
+                for &predecessor in &predecessors[node] {
+                    if immediate_dominators[predecessor].is_none() {
+                        continue;
+                    }
+                    new_dominator = Some(match new_dominator {
+                        None => predecessor,
+                        Some(mut other) => {
+                            let mut finger = predecessor;
+                            while finger != other {
+                                while postorder_numbers[finger] < postorder_numbers[other] {
+                                    finger = immediate_dominators[finger].unwrap();
+                                }
+                                while postorder_numbers[other] < postorder_numbers[finger] {
+                                    other = immediate_dominators[other].unwrap();
+                                }
+                            }
+                            finger
+                        }
+                    });
+                }
+                if new_dominator.is_some() && immediate_dominators[node] != new_dominator {
+                    immediate_dominators[node] = new_dominator;
+                    changed = true;
+                }
+            }
+        }
+        DominatorTree { root, immediate_dominators }
+    }
+
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    /// The closest strict dominator of `node`, `None` for the root and for unreachable nodes.
+    pub fn immediate_dominator(&self, node: usize) -> Option<usize> {
+        self.immediate_dominators.get(node).copied().flatten().filter(|_| node != self.root)
+    }
+
+    /// Whether every path from the root to `node` goes through `dominator`. Nodes dominate themselves.
+    pub fn dominates(&self, dominator: usize, node: usize) -> bool {
+        if self.immediate_dominators.get(node).copied().flatten().is_none() {
+            return false;
+        }
+        let mut current = node;
+        loop {
+            if current == dominator {
+                return true;
+            }
+            match self.immediate_dominator(current) {
+                Some(parent) => current = parent,
+                None => return false,
+            }
+        }
+    }
+
+    /// The nodes `node` immediately dominates.
+    pub fn children(&self, node: usize) -> Vec<usize> {
+        (0..self.immediate_dominators.len())
+            .filter(|child| *child != self.root && self.immediate_dominators[*child] == Some(node))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn flow_method_graph(name: &str) -> ControlFlowGraph {
+    let java_class = crate::JavaClass::load_from_file("Flow.class").unwrap();
+    let method = java_class.methods.iter().find(|method| method.name == name).unwrap();
+    ControlFlowGraph::new(method.code().unwrap()).unwrap()
+}
+
+#[test]
+fn test_loop_has_back_edge() {
+    let graph = flow_method_graph("loop");
+    let starts: Vec<usize> = graph.blocks.iter().map(|block| block.start).collect();
+    assert_eq!(vec![0, 4, 9, 19], starts);
+    let edges: Vec<(usize, usize)> = graph.edges.iter().map(|edge| (edge.from, edge.to)).collect();
+    assert_eq!(vec![(0, 1), (1, 3), (1, 2), (2, 1)], edges);
+
+    let dominators = graph.dominators();
+    assert_eq!(Some(1), dominators.immediate_dominator(2));
+    assert_eq!(Some(1), dominators.immediate_dominator(3));
+    assert!(dominators.dominates(1, 2));
+    assert!(!dominators.dominates(2, 3));
+    assert_eq!(vec![2, 3], dominators.children(1));
+
+    let post_dominators = graph.post_dominators();
+    assert_eq!(4, post_dominators.root());
+    assert_eq!(Some(1), post_dominators.immediate_dominator(0));
+    assert_eq!(Some(1), post_dominators.immediate_dominator(2));
+    assert_eq!(Some(3), post_dominators.immediate_dominator(1));
+}
+
+#[test]
+fn test_exception_handlers_get_exceptional_edges() {
+    let graph = flow_method_graph("parse");
+    let starts: Vec<usize> = graph.blocks.iter().map(|block| block.start).collect();
+    assert_eq!(vec![0, 5, 15, 18, 28], starts);
+    assert_eq!(vec![
+        Edge { from: 0, to: 1, kind: EdgeKind::Normal },
+        Edge { from: 2, to: 3, kind: EdgeKind::Normal },
+        Edge { from: 0, to: 2, kind: EdgeKind::Exceptional { catch_type: 19 } },
+        Edge { from: 0, to: 4, kind: EdgeKind::Exceptional { catch_type: 0 } },
+        Edge { from: 2, to: 4, kind: EdgeKind::Exceptional { catch_type: 0 } },
+    ], graph.edges);
+
+    let dominators = graph.dominators();
+    assert_eq!(Some(2), dominators.immediate_dominator(3));
+    assert_eq!(Some(0), dominators.immediate_dominator(4));
+    assert_eq!(Some(5), graph.post_dominators().immediate_dominator(2));
+
+    let java_class = crate::JavaClass::load_from_file("Flow.class").unwrap();
+    let dot = graph.to_dot("parse", &java_class.constant_pool_infos);
+    assert!(dot.starts_with("digraph \"parse\" {\n"));
+    assert!(dot.contains("    block1 [label=\"5: getstatic     #13                 // Field Flow.counter:I\\l8: iconst_1\\l"));
+    assert!(dot.contains("    block0 -> block2 [style=dashed, label=\"java/lang/NumberFormatException\"];\n"));
+}
+
+#[test]
+fn test_subroutines_return_to_their_callers() {
+    let mut constant_pool = crate::constant_pool::ConstantPoolBuilder::new();
+    let code = crate::assembler::assemble_code("
+            jsr Finally
+            jsr Finally
+            return
+        Finally:
+            astore_1
+            ret 1
+    ", &mut constant_pool).unwrap();
+    let graph = ControlFlowGraph::new(&code).unwrap();
+    let starts: Vec<usize> = graph.blocks.iter().map(|block| block.start).collect();
+    assert_eq!(vec![0, 3, 6, 7], starts);
+    assert_eq!(vec![
+        Edge { from: 0, to: 3, kind: EdgeKind::Jsr },
+        Edge { from: 1, to: 3, kind: EdgeKind::Jsr },
+        Edge { from: 3, to: 1, kind: EdgeKind::Ret },
+        Edge { from: 3, to: 2, kind: EdgeKind::Ret },
+    ], graph.edges);
+    assert_eq!(Some(3), graph.dominators().immediate_dominator(2));
+}
@@ -0,0 +1,639 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::bytecode::{Category, Opcode, StackEffect};
+use crate::control_flow::{ControlFlowGraph, EdgeKind};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::instruction::{CpIndex, DecodeError, Instruction, Literal};
+use crate::numeric::{self, Number};
+use crate::{ConstantPoolInfo, JavaClass, MethodInfo};
+
+/// The facts an analysis computes, ordered so that joining only ever loses information.
+pub trait Lattice: Clone + PartialEq {
+    /// Joins `other` into `self`, returning whether `self` changed.
+    fn join(&mut self, other: &Self) -> bool;
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Direction {
+    /// Facts flow from the entry along the edges, like reaching definitions.
+    Forward,
+    /// Facts flow from the exits against the edges, like liveness.
+    Backward,
+}
+
+/// What an analysis does to the facts at one instruction.
+pub trait TransferFunction {
+    type Fact: Lattice;
+
+    fn direction(&self) -> Direction {
+        Direction::Forward
+    }
+
+    /// The fact at the method entry going forward, or at the instructions leaving the method
+    /// going backward.
+    fn boundary(&self) -> Self::Fact;
+
+    /// Turns the fact before `instruction` into the one after it going forward, and the other way
+    /// around going backward.
+    fn transfer(&self, pc: usize, instruction: &Instruction, fact: &mut Self::Fact);
+
+    /// The fact reaching a handler catching `catch_type`, 0 for any, from the fact before an
+    /// instruction it covers. Only called going forward.
+    fn exceptional(&self, _pc: usize, _catch_type: usize, fact: &Self::Fact) -> Self::Fact {
+        fact.clone()
+    }
+}
+
+/// The facts before and after every instruction the analysis reached, by pc.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataFlowResult<F> {
+    pub before: BTreeMap<usize, F>,
+    pub after: BTreeMap<usize, F>,
+}
+
+fn join_into<F: Lattice>(slot: &mut Option<F>, fact: &F) -> bool {
+    match slot {
+        Some(existing) => existing.join(fact),
+        None => {
+            *slot = Some(fact.clone());
+            true
+        }
+    }
+}
+
+/// Runs an analysis to its fixed point with a worklist over the blocks of `graph`.
+///
+/// Exceptional edges carry the fact before every covered instruction going forward, and the
+/// handler's fact into the facts before and after every covered instruction going backward. Going backward,
+/// code that can't reach an exit, like an endless loop, gets no facts.
+pub fn solve<T: TransferFunction>(graph: &ControlFlowGraph, transfer: &T) -> DataFlowResult<T::Fact> {
+    let count = graph.blocks.len();
+    let mut block_facts: Vec<Option<T::Fact>> = vec![None; count];
+    let mut result = DataFlowResult { before: BTreeMap::new(), after: BTreeMap::new() };
+    if count == 0 {
+        return result;
+    }
+    let is_exceptional = |kind: EdgeKind| matches!(kind, EdgeKind::Exceptional { .. });
+
+    match transfer.direction() {
+        Direction::Forward => {
+            block_facts[0] = Some(transfer.boundary());
+            let mut worklist = vec![0];
+            while let Some(block) = worklist.pop() {
+                let mut fact = block_facts[block].clone().unwrap();
+                let mut handler_facts = Vec::new();
+                for (pc, instruction) in graph.block_instructions(block) {
+                    result.before.insert(*pc, fact.clone());
+                    for edge in graph.successors(block) {
+                        if let EdgeKind::Exceptional { catch_type } = edge.kind {
+                            handler_facts.push((edge.to, transfer.exceptional(*pc, catch_type, &fact)));
+                        }
+                    }
+                    transfer.transfer(*pc, instruction, &mut fact);
+                    result.after.insert(*pc, fact.clone());
+                }
+                let successors = graph.successors(block).filter(|edge| !is_exceptional(edge.kind)).map(|edge| (edge.to, fact.clone()));
+                for (successor, successor_fact) in handler_facts.into_iter().chain(successors) {
+                    if join_into(&mut block_facts[successor], &successor_fact) && !worklist.contains(&successor) {
+                        worklist.push(successor);
+                    }
+                }
+            }
+        }
+        Direction::Backward => {
+            let mut worklist: Vec<usize> = (0..count).collect();
+            while let Some(block) = worklist.pop() {
+                let mut fact: Option<T::Fact> = None;
+                let mut has_successors = false;
+                for edge in graph.successors(block).filter(|edge| !is_exceptional(edge.kind)) {
+                    has_successors = true;
+                    if let Some(successor_fact) = &block_facts[edge.to] {
+                        join_into(&mut fact, successor_fact);
+                    }
+                }
+                if !has_successors {
+                    fact = Some(transfer.boundary());
+                }
+                let handler_facts: Vec<T::Fact> = graph.successors(block)
+                    .filter(|edge| is_exceptional(edge.kind))
+                    .filter_map(|edge| block_facts[edge.to].clone())
+                    .collect();
+                for (pc, instruction) in graph.block_instructions(block).iter().rev() {
+                    for handler_fact in &handler_facts {
+                        join_into(&mut fact, handler_fact);
+                    }
+                    let Some(current) = &mut fact else { continue };
+                    result.after.insert(*pc, current.clone());
+                    transfer.transfer(*pc, instruction, current);
+                    // The instruction may throw before it has any effect
+                    for handler_fact in &handler_facts {
+                        current.join(handler_fact);
+                    }
+                    result.before.insert(*pc, current.clone());
+                }
+                let Some(fact) = fact else { continue };
+                if block_facts[block].as_ref() != Some(&fact) {
+                    block_facts[block] = Some(fact);
+                    for edge in graph.predecessors(block) {
+                        if !worklist.contains(&edge.from) {
+                            worklist.push(edge.from);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+/// The values of the locals and of the operand stack, one per slot. The second slot of a `long`
+/// or `double` holds an unknown value.
+#[derive(Debug, PartialEq, Clone)]
+pub struct AbstractFrame<V> {
+    pub locals: Vec<V>,
+    pub stack: Vec<V>,
+}
+
+/// Frames join slot by slot. Their stacks have the same height wherever they meet in verifiable code.
+impl<V: Lattice> Lattice for AbstractFrame<V> {
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (value, other_value) in self.locals.iter_mut().zip(&other.locals).chain(self.stack.iter_mut().zip(&other.stack)) {
+            changed |= value.join(other_value);
+        }
+        changed
+    }
+}
+
+impl<V: fmt::Display> fmt::Display for AbstractFrame<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |values: &[V]| values.iter().map(ToString::to_string).collect::<Vec<String>>().join(", ");
+        write!(f, "locals [{}] stack [{}]", list(&self.locals), list(&self.stack))
+    }
+}
+
+/// The values an analysis tracks for locals and stack slots, and how instructions compute them.
+/// [`StackMachine`] does the moving around: loads, stores and stack shuffles copy values, and
+/// everything else asks [`ValueAnalysis::operation`].
+pub trait ValueAnalysis {
+    type Value: Lattice + fmt::Debug;
+
+    /// A value nothing is known about, also held by unset locals.
+    fn unknown(&self) -> Self::Value;
+
+    /// The value of a parameter at the method entry, `None` being the type of `this`.
+    fn parameter(&self, _field_type: Option<&FieldType>) -> Self::Value {
+        self.unknown()
+    }
+
+    /// The exception a handler starts with.
+    fn caught_exception(&self, _catch_type: usize) -> Self::Value {
+        self.unknown()
+    }
+
+    /// The value an instruction pushes, given the values it pops listed from the bottom of the
+    /// stack up. `iinc` gets the local it increments and gives its new value.
+    fn operation(&self, pc: usize, instruction: &Instruction, operands: &[Self::Value]) -> Self::Value;
+}
+
+/// A forward [`TransferFunction`] over [`AbstractFrame`]s that simulates the operand stack and
+/// locals of a method for a [`ValueAnalysis`].
+pub struct StackMachine<'a, A: ValueAnalysis> {
+    pub analysis: A,
+    constant_pool_infos: &'a [ConstantPoolInfo],
+    entry: AbstractFrame<A::Value>,
+}
+
+impl<'a, A: ValueAnalysis> StackMachine<'a, A> {
+    pub fn new(analysis: A, java_class: &'a JavaClass, method: &MethodInfo) -> Self {
+        let max_locals = method.code().map(|code| code.max_locals).unwrap_or_default();
+        let mut locals = Vec::with_capacity(max_locals);
+        if method.access_flags & 0x0008 == 0 {
+            locals.push(analysis.parameter(None));
+        }
+        for parameter in MethodDescriptor::parse(&method.descriptor).map(|descriptor| descriptor.parameters).unwrap_or_default() {
+            locals.push(analysis.parameter(Some(&parameter)));
+            if parameter.is_wide() {
+                locals.push(analysis.unknown());
+            }
+        }
+        while locals.len() < max_locals {
+            locals.push(analysis.unknown());
+        }
+        StackMachine { entry: AbstractFrame { locals, stack: Vec::new() }, analysis, constant_pool_infos: &java_class.constant_pool_infos }
+    }
+
+    fn constant(&self, CpIndex(index): CpIndex) -> Option<&ConstantPoolInfo> {
+        (index as usize).checked_sub(1).and_then(|position| self.constant_pool_infos.get(position))
+    }
+
+    /// The categories a field or method instruction pops and pushes, from its descriptor.
+    fn variable_effect(&self, instruction: &Instruction) -> (Vec<Category>, Vec<Category>) {
+        let category = |field_type: &FieldType| if field_type.is_wide() { Category::Two } else { Category::One };
+        let opcode = instruction.opcode();
+        if let Instruction::Multianewarray { dimensions, .. } = instruction {
+            return (vec![Category::One; *dimensions as usize], vec![Category::One]);
+        }
+        let index = instruction.constant_pool_index().unwrap_or(CpIndex(0));
+        match (opcode, self.constant(index)) {
+            (Opcode::Getstatic | Opcode::Putstatic | Opcode::Getfield | Opcode::Putfield, Some(ConstantPoolInfo::FieldRef(field_ref))) => {
+                let field_category = FieldType::parse(&field_ref.descriptor).map(|field_type| category(&field_type)).unwrap_or(Category::One);
+                match opcode {
+                    Opcode::Getstatic => (vec![], vec![field_category]),
+                    Opcode::Putstatic => (vec![field_category], vec![]),
+                    Opcode::Getfield => (vec![Category::One], vec![field_category]),
+                    _ => (vec![Category::One, field_category], vec![]),
+                }
+            }
+            (_, Some(constant)) => {
+                let descriptor = match constant {
+                    ConstantPoolInfo::MethodRef(method_ref) => &method_ref.descriptor,
+                    ConstantPoolInfo::InterfaceMethodRef(method_ref) => &method_ref.descriptor,
+                    ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => &invoke_dynamic.descriptor,
+                    _ => return (vec![], vec![]),
+                };
+                let Some(descriptor) = MethodDescriptor::parse(descriptor) else { return (vec![], vec![]) };
+                let mut pops = Vec::new();
+                if !matches!(opcode, Opcode::Invokestatic | Opcode::Invokedynamic) {
+                    pops.push(Category::One);
+                }
+                pops.extend(descriptor.parameters.iter().map(category));
+                (pops, descriptor.return_type.iter().map(category).collect())
+            }
+            _ => (vec![], vec![]),
+        }
+    }
+}
+
+impl<A: ValueAnalysis> TransferFunction for StackMachine<'_, A> {
+    type Fact = AbstractFrame<A::Value>;
+
+    fn boundary(&self) -> Self::Fact {
+        self.entry.clone()
+    }
+
+    fn exceptional(&self, _pc: usize, catch_type: usize, fact: &Self::Fact) -> Self::Fact {
+        AbstractFrame { locals: fact.locals.clone(), stack: vec![self.analysis.caught_exception(catch_type)] }
+    }
+
+    fn transfer(&self, pc: usize, instruction: &Instruction, frame: &mut Self::Fact) {
+        let opcode = instruction.opcode();
+        let stack = &mut frame.stack;
+        let mut pop_slots = |slots: usize| stack.split_off(stack.len().saturating_sub(slots));
+        if let Some((ty, store, index)) = instruction.local_access() {
+            let index = index as usize;
+            let slots = if ty.is_wide() { 2 } else { 1 };
+            if store {
+                let values = pop_slots(slots);
+                frame.locals.splice(index..index + slots, values);
+            } else {
+                frame.stack.extend(frame.locals[index..index + slots].iter().cloned());
+            }
+            return;
+        }
+        match (instruction, opcode.stack_effect()) {
+            (Instruction::Iinc { index, .. }, _) => {
+                let index = *index as usize;
+                frame.locals[index] = self.analysis.operation(pc, instruction, std::slice::from_ref(&frame.locals[index]));
+            }
+            (Instruction::Dup | Instruction::DupX1 | Instruction::Swap, _) | (_, StackEffect::Slots { .. }) => {
+                let pops = match opcode.stack_effect() {
+                    StackEffect::Slots { pops, .. } => pops,
+                    _ if opcode == Opcode::Dup => 1,
+                    _ => 2,
+                };
+                let values = pop_slots(pops);
+                let shuffled: Vec<&A::Value> = match opcode {
+                    Opcode::Pop2 => vec![],
+                    Opcode::Dup => vec![&values[0], &values[0]],
+                    Opcode::DupX1 => vec![&values[1], &values[0], &values[1]],
+                    Opcode::DupX2 => vec![&values[2], &values[0], &values[1], &values[2]],
+                    Opcode::Dup2 => vec![&values[0], &values[1], &values[0], &values[1]],
+                    Opcode::Dup2X1 => vec![&values[1], &values[2], &values[0], &values[1], &values[2]],
+                    Opcode::Dup2X2 => vec![&values[2], &values[3], &values[0], &values[1], &values[2], &values[3]],
+                    _ => vec![&values[1], &values[0]],
+                };
+                let shuffled: Vec<A::Value> = shuffled.into_iter().cloned().collect();
+                frame.stack.extend(shuffled);
+            }
+            (_, effect) => {
+                let (pops, pushes) = match effect {
+                    StackEffect::Fixed { pops, pushes } => (pops.to_vec(), pushes.to_vec()),
+                    _ => self.variable_effect(instruction),
+                };
+                let mut operands = Vec::with_capacity(pops.len());
+                for category in pops.iter().rev() {
+                    let mut slots = pop_slots(if *category == Category::Two { 2 } else { 1 });
+                    if !slots.is_empty() {
+                        operands.push(slots.swap_remove(0));
+                    }
+                }
+                operands.reverse();
+                if let Some(category) = pushes.first() {
+                    let value = self.analysis.operation(pc, instruction, &operands);
+                    frame.stack.push(value);
+                    if *category == Category::Two {
+                        frame.stack.push(self.analysis.unknown());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Runs a [`ValueAnalysis`] over a method, giving the frame before and after every reachable instruction.
+pub fn analyze_values<A: ValueAnalysis>(java_class: &JavaClass, method: &MethodInfo, analysis: A) -> Result<DataFlowResult<AbstractFrame<A::Value>>, DecodeError> {
+    let Some(code) = method.code() else {
+        return Ok(DataFlowResult { before: BTreeMap::new(), after: BTreeMap::new() });
+    };
+    let graph = ControlFlowGraph::new(code)?;
+    Ok(solve(&graph, &StackMachine::new(analysis, java_class, method)))
+}
+
+/// A value constant propagation knows exactly, or [`Constant::Varying`] when it doesn't.
+/// Floating point values are kept as bits so that `NaN`s compare equal.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Constant {
+    Int(i32),
+    Long(i64),
+    Float(u32),
+    Double(u64),
+    Null,
+    String(String),
+    Varying,
+}
+
+impl Constant {
+    /// The constant a literal is, [`Constant::Varying`] for classes and the constants left in the
+    /// constant pool, which aren't followed.
+    pub fn from_literal(literal: &Literal) -> Constant {
+        match literal {
+            Literal::Int(value) => Constant::Int(*value),
+            Literal::Long(value) => Constant::Long(*value),
+            Literal::Float(value) => Constant::Float(value.to_bits()),
+            Literal::Double(value) => Constant::Double(value.to_bits()),
+            Literal::Null => Constant::Null,
+            Literal::String(string) => Constant::String(string.clone()),
+            Literal::Class(_) | Literal::Pooled { .. } => Constant::Varying,
+        }
+    }
+
+    /// The literal pushing the constant, unless it is [`Constant::Varying`].
+    pub fn literal(&self) -> Option<Literal> {
+        match self {
+            Constant::Int(value) => Some(Literal::Int(*value)),
+            Constant::Long(value) => Some(Literal::Long(*value)),
+            Constant::Float(bits) => Some(Literal::Float(f32::from_bits(*bits))),
+            Constant::Double(bits) => Some(Literal::Double(f64::from_bits(*bits))),
+            Constant::Null => Some(Literal::Null),
+            Constant::String(string) => Some(Literal::String(string.clone())),
+            Constant::Varying => None,
+        }
+    }
+}
+
+impl Lattice for Constant {
+    fn join(&mut self, other: &Self) -> bool {
+        if self != other && *self != Constant::Varying {
+            *self = Constant::Varying;
+            return true;
+        }
+        false
+    }
+}
+
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Int(value) => write!(f, "{value}"),
+            Constant::Long(value) => write!(f, "{value}L"),
+            Constant::Float(bits) => write!(f, "{:?}f", f32::from_bits(*bits)),
+            Constant::Double(bits) => write!(f, "{:?}d", f64::from_bits(*bits)),
+            Constant::Null => write!(f, "null"),
+            Constant::String(string) => write!(f, "{string:?}"),
+            Constant::Varying => write!(f, "?"),
+        }
+    }
+}
+
+/// Folds constants through arithmetic, conversions and comparisons with Java's semantics.
+pub struct ConstantPropagation<'a> {
+    pub constant_pool_infos: &'a [ConstantPoolInfo],
+}
+
+impl ConstantPropagation<'_> {
+    pub(crate) fn fold(instruction: &Instruction, operands: &[Constant]) -> Option<Constant> {
+        let numbers = operands.iter().map(|operand| match *operand {
+            Constant::Int(value) => Some(Number::Int(value)),
+            Constant::Long(value) => Some(Number::Long(value)),
+            Constant::Float(bits) => Some(Number::Float(f32::from_bits(bits))),
+            Constant::Double(bits) => Some(Number::Double(f64::from_bits(bits))),
+            _ => None,
+        }).collect::<Option<Vec<_>>>()?;
+        Some(match numeric::compute(instruction, &numbers).ok()? {
+            Number::Int(value) => Constant::Int(value),
+            Number::Long(value) => Constant::Long(value),
+            Number::Float(value) => Constant::Float(value.to_bits()),
+            Number::Double(value) => Constant::Double(value.to_bits()),
+        })
+    }
+}
+
+impl ValueAnalysis for ConstantPropagation<'_> {
+    type Value = Constant;
+
+    fn unknown(&self) -> Constant {
+        Constant::Varying
+    }
+
+    fn operation(&self, _pc: usize, instruction: &Instruction, operands: &[Constant]) -> Constant {
+        let constant = match instruction {
+            Instruction::Ldc(CpIndex(index)) | Instruction::LdcW(CpIndex(index)) | Instruction::Ldc2W(CpIndex(index)) => {
+                match (*index as usize).checked_sub(1).and_then(|position| self.constant_pool_infos.get(position)) {
+                    Some(ConstantPoolInfo::Integer(integer)) => Some(Constant::Int(integer.value)),
+                    Some(ConstantPoolInfo::Float(float)) => Some(Constant::Float(float.value.to_bits())),
+                    Some(ConstantPoolInfo::Long(long)) => Some(Constant::Long(long.value)),
+                    Some(ConstantPoolInfo::Double(double)) => Some(Constant::Double(double.value.to_bits())),
+                    Some(ConstantPoolInfo::String(string)) => Some(Constant::String(string.string.clone())),
+                    _ => None,
+                }
+            }
+            Instruction::Checkcast(_) => operands.first().cloned(),
+            Instruction::Iinc { increment, .. } => match operands {
+                [Constant::Int(value)] => Some(Constant::Int(value.wrapping_add(*increment as i32))),
+                _ => None,
+            },
+            _ => match instruction.pushed_literal() {
+                Some(literal) => Some(Constant::from_literal(&literal)),
+                None => ConstantPropagation::fold(instruction, operands),
+            },
+        };
+        constant.unwrap_or(Constant::Varying)
+    }
+}
+
+/// Whether a reference is `null` on every path, on none, or depends on the path taken.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Nullness {
+    Null,
+    NonNull,
+    /// Maybe `null`, and what primitive values are.
+    Unknown,
+}
+
+impl Lattice for Nullness {
+    fn join(&mut self, other: &Self) -> bool {
+        if self != other && *self != Nullness::Unknown {
+            *self = Nullness::Unknown;
+            return true;
+        }
+        false
+    }
+}
+
+impl fmt::Display for Nullness {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Nullness::Null => write!(f, "null"),
+            Nullness::NonNull => write!(f, "non-null"),
+            Nullness::Unknown => write!(f, "?"),
+        }
+    }
+}
+
+/// Definite nullness: `this`, new objects and arrays, constants and caught exceptions are never
+/// `null`. Parameters, fields and method results may be.
+pub struct NullnessAnalysis;
+
+impl ValueAnalysis for NullnessAnalysis {
+    type Value = Nullness;
+
+    fn unknown(&self) -> Nullness {
+        Nullness::Unknown
+    }
+
+    fn parameter(&self, field_type: Option<&FieldType>) -> Nullness {
+        match field_type {
+            None => Nullness::NonNull,
+            Some(_) => Nullness::Unknown,
+        }
+    }
+
+    fn caught_exception(&self, _catch_type: usize) -> Nullness {
+        Nullness::NonNull
+    }
+
+    fn operation(&self, _pc: usize, instruction: &Instruction, operands: &[Nullness]) -> Nullness {
+        match instruction {
+            Instruction::AconstNull => Nullness::Null,
+            Instruction::New(_) | Instruction::Newarray(_) | Instruction::Anewarray(_) | Instruction::Multianewarray { .. }
+            | Instruction::Ldc(_) | Instruction::LdcW(_) => Nullness::NonNull,
+            Instruction::Checkcast(_) => operands.first().copied().unwrap_or(Nullness::Unknown),
+            _ => Nullness::Unknown,
+        }
+    }
+}
+
+/// The locals read before being written again on some path, one flag per slot.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LiveLocals(pub Vec<bool>);
+
+impl Lattice for LiveLocals {
+    fn join(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (live, other_live) in self.0.iter_mut().zip(&other.0) {
+            changed |= !*live && *other_live;
+            *live |= other_live;
+        }
+        changed
+    }
+}
+
+/// Live local variables, a backward analysis. Nothing is live when leaving the method.
+pub struct Liveness {
+    pub max_locals: usize,
+}
+
+impl TransferFunction for Liveness {
+    type Fact = LiveLocals;
+
+    fn direction(&self) -> Direction {
+        Direction::Backward
+    }
+
+    fn boundary(&self) -> LiveLocals {
+        LiveLocals(vec![false; self.max_locals])
+    }
+
+    fn transfer(&self, _pc: usize, instruction: &Instruction, fact: &mut LiveLocals) {
+        let Some(index) = instruction.local_index().map(usize::from).filter(|index| *index < self.max_locals) else { return };
+        if let Some((ty, true, _)) = instruction.local_access() {
+            fact.0[index] = false;
+            if ty.is_wide() && index + 1 < self.max_locals {
+                fact.0[index + 1] = false;
+            }
+        } else {
+            fact.0[index] = true;
+        }
+    }
+}
+
+#[cfg(test)]
+fn flow_method(name: &str) -> (JavaClass, MethodInfo) {
+    let java_class = JavaClass::load_from_file("Flow.class").unwrap();
+    let method = java_class.methods.iter().find(|method| method.name == name).unwrap().clone();
+    (java_class, method)
+}
+
+#[test]
+fn test_constant_propagation_folds_and_joins() {
+    let java_class = JavaClass::load_from_file("MyClass.class").unwrap();
+    let main = java_class.methods.iter().find(|method| method.name == "main").unwrap();
+    let result = analyze_values(&java_class, main, ConstantPropagation { constant_pool_infos: &java_class.constant_pool_infos }).unwrap();
+    assert_eq!("locals [?, 9, 11, 20] stack [?, 20]", result.before[&14].to_string());
+
+    let (java_class, method) = flow_method("loop");
+    let result = analyze_values(&java_class, &method, ConstantPropagation { constant_pool_infos: &java_class.constant_pool_infos }).unwrap();
+    assert_eq!("locals [?, 0, 0] stack []", result.after[&3].to_string());
+    assert_eq!("locals [?, ?, ?] stack []", result.before[&4].to_string());
+
+    let (java_class, method) = flow_method("parse");
+    let result = analyze_values(&java_class, &method, ConstantPropagation { constant_pool_infos: &java_class.constant_pool_infos }).unwrap();
+    assert_eq!(vec![Constant::Varying, Constant::Int(1)], result.before[&9].stack);
+    assert_eq!(Constant::Int(-1), result.before[&26].locals[2]);
+
+    let (java_class, method) = flow_method("longs");
+    let result = analyze_values(&java_class, &method, ConstantPropagation { constant_pool_infos: &java_class.constant_pool_infos }).unwrap();
+    assert_eq!(vec![Constant::Varying, Constant::Varying, Constant::Long(1_000_000_000_000), Constant::Varying], result.after[&1].stack);
+}
+
+#[test]
+fn test_nullness() {
+    let (java_class, method) = flow_method("nullable");
+    let result = analyze_values(&java_class, &method, NullnessAnalysis).unwrap();
+    assert_eq!(vec![Nullness::Unknown], result.before[&1].stack);
+    assert_eq!(vec![Nullness::NonNull], result.after[&4].stack);
+    assert_eq!(vec![Nullness::Unknown], result.before[&10].stack);
+
+    let (java_class, method) = flow_method("parse");
+    let result = analyze_values(&java_class, &method, NullnessAnalysis).unwrap();
+    assert_eq!(vec![Nullness::NonNull], result.before[&15].stack);
+    assert_eq!(Nullness::NonNull, result.after[&15].locals[1]);
+}
+
+#[test]
+fn test_liveness() {
+    let (_, method) = flow_method("loop");
+    let graph = ControlFlowGraph::new(method.code().unwrap()).unwrap();
+    let result = solve(&graph, &Liveness { max_locals: 3 });
+    assert_eq!(LiveLocals(vec![true, false, false]), result.before[&0]);
+    assert_eq!(LiveLocals(vec![true, true, true]), result.before[&4]);
+    assert_eq!(LiveLocals(vec![false, true, false]), result.before[&19]);
+    assert_eq!(LiveLocals(vec![false, false, false]), result.after[&20]);
+
+    let (_, method) = flow_method("parse");
+    let graph = ControlFlowGraph::new(method.code().unwrap()).unwrap();
+    let result = solve(&graph, &Liveness { max_locals: 4 });
+    assert_eq!(LiveLocals(vec![true, false, false, false]), result.before[&0]);
+    assert_eq!(LiveLocals(vec![false, false, false, true]), result.before[&29]);
+}
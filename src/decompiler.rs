@@ -0,0 +1,2871 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::error::Error;
+use std::fmt::{self, Write};
+use crate::bytecode::Opcode;
+use crate::control_flow::{ControlFlowGraph, DominatorTree, EdgeKind};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::disassembler::describe_constant;
+use crate::instruction::{ArrayType, CpIndex, DecodeError, Instruction, Literal, Type};
+use crate::signature::{java_class_name, java_type_name, type_parameters_to_string, ClassSignature, MethodSignature, TypeSignature};
+use crate::{Attribute, AttributeCode, ExceptionTableEntry, AttributeInfo, ConstantPoolInfo, FieldInfo, JavaClass, MethodInfo};
+
+/// Why a method body couldn't be decompiled, and at which pc.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DecompileError {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl fmt::Display for DecompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}: {}", self.pc, self.message)
+    }
+}
+
+impl Error for DecompileError {}
+
+impl From<DecodeError> for DecompileError {
+    fn from(error: DecodeError) -> Self {
+        DecompileError { pc: error.pc, message: error.to_string() }
+    }
+}
+
+fn error<T>(pc: usize, message: impl Into<String>) -> Result<T, DecompileError> {
+    Err(DecompileError { pc, message: message.into() })
+}
+
+/// A Java expression. Names and types are already in source form.
+#[derive(Debug, PartialEq, Clone)]
+enum Expr {
+    Literal(String),
+    Var(String),
+    /// A class qualifying a static member.
+    Class(String),
+    Binary { op: &'static str, left: Box<Expr>, right: Box<Expr> },
+    /// A comparison; `floating` ones can't be negated by flipping the operator because of NaN.
+    Compare { op: &'static str, left: Box<Expr>, right: Box<Expr>, floating: bool },
+    /// `lcmp`, `fcmpl` and friends when their result isn't branched on directly.
+    ThreeWay { instruction: &'static str, left: Box<Expr>, right: Box<Expr> },
+    Not(Box<Expr>),
+    Negate(Box<Expr>),
+    Cast { ty: String, value: Box<Expr> },
+    Field { owner: Box<Expr>, name: String },
+    Call { owner: Option<Box<Expr>>, name: String, arguments: Vec<Expr> },
+    New { class: String, arguments: Vec<Expr> },
+    /// `new base[lengths]` followed by `extra` pairs of empty brackets.
+    NewArray { base: String, lengths: Vec<Expr>, extra: usize },
+    Index { array: Box<Expr>, index: Box<Expr> },
+    Length(Box<Expr>),
+    InstanceOf { value: Box<Expr>, class: String },
+    Concat(Vec<Expr>),
+    Conditional { condition: Box<Expr>, then: Box<Expr>, otherwise: Box<Expr> },
+    Lambda { parameters: Vec<String>, body: Box<Expr> },
+    MethodReference { owner: Box<Expr>, name: String },
+    /// `new ty{values}`, where `ty` is the array type.
+    ArrayInitializer { ty: String, values: Vec<Expr> },
+    /// `name++` or `name--` used as a value.
+    Postfix { name: String, op: &'static str },
+    /// An `invokedynamic` with a bootstrap method we don't know.
+    Dynamic { name: String, arguments: Vec<Expr> },
+    /// The result of a `new` whose constructor hasn't run yet, by the pc of the `new`.
+    Uninitialized(usize),
+}
+
+impl Expr {
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Literal(text) if text.starts_with('-') => 13,
+            Expr::Lambda { .. } => 1,
+            Expr::Conditional { .. } => 2,
+            Expr::Binary { op, .. } => match *op {
+                "||" => 3,
+                "&&" => 4,
+                "|" => 5,
+                "^" => 6,
+                "&" => 7,
+                "<<" | ">>" | ">>>" => 10,
+                "+" | "-" => 11,
+                _ => 12,
+            },
+            Expr::Compare { op, .. } => if matches!(*op, "==" | "!=") { 8 } else { 9 },
+            Expr::InstanceOf { .. } => 9,
+            Expr::Concat(_) => 11,
+            Expr::Not(_) | Expr::Negate(_) | Expr::Cast { .. } | Expr::NewArray { .. } => 13,
+            _ => 14,
+        }
+    }
+
+    /// Whether evaluating the expression can neither fail nor change anything, so it can be
+    /// copied or dropped.
+    fn is_trivial(&self) -> bool {
+        matches!(self, Expr::Literal(_) | Expr::Var(_) | Expr::Class(_) | Expr::Uninitialized(_))
+    }
+
+    fn mentions(&self, name: &str) -> bool {
+        let mut found = false;
+        self.visit(&mut |expr| found |= matches!(expr, Expr::Var(var) if var == name));
+        found
+    }
+
+    fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Literal(_) | Expr::Var(_) | Expr::Class(_) | Expr::Uninitialized(_) | Expr::Postfix { .. } => Vec::new(),
+            Expr::Binary { left, right, .. } | Expr::Compare { left, right, .. } | Expr::ThreeWay { left, right, .. } => vec![left, right],
+            Expr::Not(value) | Expr::Negate(value) | Expr::Cast { value, .. } | Expr::Length(value) | Expr::InstanceOf { value, .. } => vec![value],
+            Expr::Field { owner, .. } | Expr::MethodReference { owner, .. } => vec![owner],
+            Expr::Call { owner, arguments, .. } => owner.iter().map(|owner| &**owner).chain(arguments).collect(),
+            Expr::New { arguments, .. } | Expr::Dynamic { arguments, .. } | Expr::Concat(arguments)
+            | Expr::ArrayInitializer { values: arguments, .. } => arguments.iter().collect(),
+            Expr::NewArray { lengths, .. } => lengths.iter().collect(),
+            Expr::Index { array, index } => vec![array, index],
+            Expr::Conditional { condition, then, otherwise } => vec![condition, then, otherwise],
+            Expr::Lambda { body, .. } => vec![body],
+        }
+    }
+
+    fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Literal(_) | Expr::Var(_) | Expr::Class(_) | Expr::Uninitialized(_) | Expr::Postfix { .. } => Vec::new(),
+            Expr::Binary { left, right, .. } | Expr::Compare { left, right, .. } | Expr::ThreeWay { left, right, .. } => vec![left, right],
+            Expr::Not(value) | Expr::Negate(value) | Expr::Cast { value, .. } | Expr::Length(value) | Expr::InstanceOf { value, .. } => vec![value],
+            Expr::Field { owner, .. } | Expr::MethodReference { owner, .. } => vec![owner],
+            Expr::Call { owner, arguments, .. } => owner.iter_mut().map(|owner| &mut **owner).chain(arguments).collect(),
+            Expr::New { arguments, .. } | Expr::Dynamic { arguments, .. } | Expr::Concat(arguments)
+            | Expr::ArrayInitializer { values: arguments, .. } => arguments.iter_mut().collect(),
+            Expr::NewArray { lengths, .. } => lengths.iter_mut().collect(),
+            Expr::Index { array, index } => vec![array, index],
+            Expr::Conditional { condition, then, otherwise } => vec![condition, then, otherwise],
+            Expr::Lambda { body, .. } => vec![body],
+        }
+    }
+
+    fn visit(&self, visitor: &mut impl FnMut(&Expr)) {
+        visitor(self);
+        for child in self.children() {
+            child.visit(visitor);
+        }
+    }
+
+    fn visit_mut(&mut self, visitor: &mut impl FnMut(&mut Expr)) {
+        visitor(self);
+        for child in self.children_mut() {
+            child.visit_mut(visitor);
+        }
+    }
+
+    /// Replaces the read of `name` with `value` if nothing that could fail or observe side
+    /// effects is evaluated before it. Errs when something is, or when the read is only
+    /// conditionally evaluated.
+    fn replace_first_read(&mut self, name: &str, value: &mut Option<Expr>) -> Result<bool, ()> {
+        match self {
+            Expr::Var(var) if var == name => {
+                *self = value.take().unwrap();
+                return Ok(true);
+            }
+            Expr::Conditional { condition, .. } => return if condition.replace_first_read(name, value)? { Ok(true) } else { Err(()) },
+            Expr::Lambda { .. } => return Err(()),
+            _ => {}
+        }
+        for child in self.children_mut() {
+            if child.replace_first_read(name, value)? {
+                return Ok(true);
+            }
+        }
+        let has_effect = match self {
+            Expr::Binary { op, .. } => matches!(*op, "/" | "%"),
+            Expr::Cast { .. } | Expr::Field { .. } | Expr::Call { .. } | Expr::New { .. } | Expr::NewArray { .. }
+            | Expr::Index { .. } | Expr::Length(_) | Expr::Dynamic { .. } | Expr::ArrayInitializer { .. } | Expr::Postfix { .. } => true,
+            _ => false,
+        };
+        if has_effect { Err(()) } else { Ok(false) }
+    }
+}
+
+fn operand(expr: &Expr, minimum: u8) -> String {
+    if expr.precedence() < minimum {
+        format!("({expr})")
+    } else {
+        expr.to_string()
+    }
+}
+
+fn arguments(arguments: &[Expr]) -> String {
+    arguments.iter().map(ToString::to_string).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let precedence = self.precedence();
+        match self {
+            Expr::Literal(text) | Expr::Var(text) | Expr::Class(text) => write!(f, "{text}"),
+            Expr::Binary { op, left, right } | Expr::Compare { op, left, right, .. } => {
+                write!(f, "{} {op} {}", operand(left, precedence), operand(right, precedence + 1))
+            }
+            Expr::ThreeWay { instruction, left, right } => match *instruction {
+                "lcmp" => write!(f, "Long.compare({left}, {right})"),
+                _ => write!(f, "{instruction}({left}, {right})"),
+            },
+            Expr::Not(value) => write!(f, "!{}", operand(value, 13)),
+            Expr::Negate(value) => {
+                let value = operand(value, 13);
+                if value.starts_with('-') { write!(f, "-({value})") } else { write!(f, "-{value}") }
+            }
+            Expr::Cast { ty, value } => write!(f, "({ty}) {}", operand(value, 13)),
+            Expr::Field { owner, name } => write!(f, "{}.{name}", operand(owner, 14)),
+            Expr::Call { owner: Some(owner), name, arguments: values } => write!(f, "{}.{name}({})", operand(owner, 14), arguments(values)),
+            Expr::Call { owner: None, name, arguments: values } => write!(f, "{name}({})", arguments(values)),
+            Expr::New { class, arguments: values } => write!(f, "new {class}({})", arguments(values)),
+            Expr::NewArray { base, lengths, extra } => {
+                write!(f, "new {base}")?;
+                for length in lengths {
+                    write!(f, "[{length}]")?;
+                }
+                write!(f, "{}", "[]".repeat(*extra))
+            }
+            Expr::Index { array, index } => write!(f, "{}[{index}]", operand(array, 14)),
+            Expr::Length(array) => write!(f, "{}.length", operand(array, 14)),
+            Expr::InstanceOf { value, class } => write!(f, "{} instanceof {class}", operand(value, 9)),
+            Expr::Concat(parts) => {
+                let parts: Vec<String> = parts.iter().map(|part| operand(part, 12)).collect();
+                write!(f, "{}", parts.join(" + "))
+            }
+            Expr::Conditional { condition, then, otherwise } => {
+                write!(f, "{} ? {} : {}", operand(condition, 3), operand(then, 1), operand(otherwise, 2))
+            }
+            Expr::Lambda { parameters, body } => write!(f, "({}) -> {body}", parameters.join(", ")),
+            Expr::MethodReference { owner, name } => write!(f, "{}::{name}", operand(owner, 14)),
+            Expr::ArrayInitializer { ty, values } => write!(f, "new {ty}{{{}}}", arguments(values)),
+            Expr::Postfix { name, op } => write!(f, "{name}{op}"),
+            Expr::Dynamic { name, arguments: values } => write!(f, "/* invokedynamic */ {name}({})", arguments(values)),
+            Expr::Uninitialized(pc) => write!(f, "/* uninitialized from pc {pc} */"),
+        }
+    }
+}
+
+/// The condition that holds exactly when `condition` doesn't.
+fn negate(condition: Expr) -> Expr {
+    match condition {
+        Expr::Not(value) => *value,
+        Expr::Literal(text) if text == "true" => Expr::Literal("false".to_string()),
+        Expr::Literal(text) if text == "false" => Expr::Literal("true".to_string()),
+        Expr::Binary { op: "&&", left, right } => Expr::Binary { op: "||", left: Box::new(negate(*left)), right: Box::new(negate(*right)) },
+        Expr::Binary { op: "||", left, right } => Expr::Binary { op: "&&", left: Box::new(negate(*left)), right: Box::new(negate(*right)) },
+        Expr::Compare { op, left, right, floating } if !floating || matches!(op, "==" | "!=") => {
+            let op = match op {
+                "==" => "!=",
+                "!=" => "==",
+                "<" => ">=",
+                ">=" => "<",
+                ">" => "<=",
+                _ => ">",
+            };
+            Expr::Compare { op, left, right, floating }
+        }
+        condition => Expr::Not(Box::new(condition)),
+    }
+}
+
+fn string_literal(string: &str) -> String {
+    let mut output = String::from("\"");
+    for character in string.chars() {
+        escape_character(&mut output, character, '"');
+    }
+    output.push('"');
+    output
+}
+
+fn escape_character(output: &mut String, character: char, quote: char) {
+    match character {
+        '\\' => output.push_str("\\\\"),
+        '\n' => output.push_str("\\n"),
+        '\r' => output.push_str("\\r"),
+        '\t' => output.push_str("\\t"),
+        '\u{8}' => output.push_str("\\b"),
+        '\u{c}' => output.push_str("\\f"),
+        character if character == quote => {
+            output.push('\\');
+            output.push(character);
+        }
+        character if character.is_control() => write!(output, "\\u{:04x}", character as u32).unwrap(),
+        character => output.push(character),
+    }
+}
+
+fn char_literal(value: u16) -> String {
+    let mut output = String::from("'");
+    match char::from_u32(value as u32) {
+        Some(character) => escape_character(&mut output, character, '\''),
+        None => write!(output, "\\u{value:04x}").unwrap(),
+    }
+    output.push('\'');
+    output
+}
+
+fn float_literal(value: f32) -> String {
+    match value {
+        value if value.is_nan() => "Float.NaN".to_string(),
+        f32::INFINITY => "Float.POSITIVE_INFINITY".to_string(),
+        f32::NEG_INFINITY => "Float.NEGATIVE_INFINITY".to_string(),
+        value => format!("{value:?}f"),
+    }
+}
+
+fn double_literal(value: f64) -> String {
+    match value {
+        value if value.is_nan() => "Double.NaN".to_string(),
+        f64::INFINITY => "Double.POSITIVE_INFINITY".to_string(),
+        f64::NEG_INFINITY => "Double.NEGATIVE_INFINITY".to_string(),
+        value => format!("{value:?}"),
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Statement {
+    Expression(Expr),
+    /// `declare` is the type of the local being assigned, declared the first time it is.
+    Assign { target: Expr, value: Expr, declare: Option<String> },
+    Increment { name: String, amount: i32 },
+    Return(Option<Expr>),
+    Throw(Expr),
+    If { condition: Expr, then: Vec<Statement>, otherwise: Vec<Statement> },
+    /// `while (true)` unless a condition was recovered. Loops, switches, labels and gotos are
+    /// identified by the pc of the block they start at.
+    Loop { id: usize, condition: Option<Expr>, body: Vec<Statement>, do_while: bool },
+    Switch { id: usize, value: Expr, cases: Vec<(Vec<String>, Vec<Statement>)> },
+    Try { body: Vec<Statement>, catches: Vec<Catch> },
+    Synchronized { lock: Expr, body: Vec<Statement> },
+    Break(usize),
+    Continue(usize),
+    /// A jump no construct expresses.
+    Goto(usize),
+    /// Where a block starts, which only shows when a `goto` jumps there.
+    Label(usize),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Catch {
+    types: Vec<String>,
+    variable: String,
+    body: Vec<Statement>,
+}
+
+impl Statement {
+    /// Whether control never continues past the statement.
+    fn jumps(&self) -> bool {
+        match self {
+            Statement::Return(_) | Statement::Throw(_) | Statement::Break(_) | Statement::Continue(_) | Statement::Goto(_) => true,
+            Statement::If { then, otherwise, .. } => ends_with_jump(then) && ends_with_jump(otherwise),
+            _ => false,
+        }
+    }
+
+    fn expressions_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Statement::Expression(expr) | Statement::Return(Some(expr)) | Statement::Throw(expr) => vec![expr],
+            Statement::Assign { target, value, .. } => vec![target, value],
+            Statement::If { condition, .. } | Statement::Switch { value: condition, .. } | Statement::Synchronized { lock: condition, .. } => vec![condition],
+            Statement::Loop { condition: Some(condition), .. } => vec![condition],
+            _ => Vec::new(),
+        }
+    }
+
+    fn declared(&self) -> Option<String> {
+        match self {
+            Statement::Assign { declare, .. } => declare.clone(),
+            _ => None,
+        }
+    }
+
+    fn bodies_mut(&mut self) -> Vec<&mut Vec<Statement>> {
+        match self {
+            Statement::If { then, otherwise, .. } => vec![then, otherwise],
+            Statement::Loop { body, .. } | Statement::Synchronized { body, .. } => vec![body],
+            Statement::Switch { cases, .. } => cases.iter_mut().map(|(_, body)| body).collect(),
+            Statement::Try { body, catches } => std::iter::once(body).chain(catches.iter_mut().map(|catch| &mut catch.body)).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn visit(&mut self, visitor: &mut impl FnMut(&mut Statement)) {
+        visitor(self);
+        for body in self.bodies_mut() {
+            for statement in body {
+                statement.visit(visitor);
+            }
+        }
+    }
+}
+
+fn ends_with_jump(statements: &[Statement]) -> bool {
+    statements.last().is_some_and(Statement::jumps)
+}
+
+/// A local variable from a `LocalVariableTable`, which javac writes with `-g`.
+#[derive(Debug, PartialEq, Clone)]
+struct LocalVariable {
+    start_pc: usize,
+    length: usize,
+    name: String,
+    descriptor: String,
+    index: usize,
+}
+
+fn unknown_attributes<'a>(attributes: &'a [AttributeInfo], name: &'a str) -> impl Iterator<Item = &'a [u8]> {
+    attributes.iter().filter_map(move |attribute_info| match &attribute_info.attribute {
+        Attribute::Unknown(unknown) if unknown.attribute_name == name => Some(unknown.info.as_slice()),
+        _ => None,
+    })
+}
+
+fn u2(bytes: &[u8], offset: usize) -> Option<usize> {
+    Some(u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]) as usize)
+}
+
+fn constant(java_class: &JavaClass, index: usize) -> Option<&ConstantPoolInfo> {
+    java_class.constant_pool_infos.get(index.checked_sub(1)?)
+}
+
+fn utf8(java_class: &JavaClass, index: usize) -> Option<&str> {
+    match constant(java_class, index)? {
+        ConstantPoolInfo::Utf8(utf8_info) => Some(&utf8_info.string),
+        _ => None,
+    }
+}
+
+fn local_variables(java_class: &JavaClass, code: &AttributeCode) -> Vec<LocalVariable> {
+    let mut locals = Vec::new();
+    for bytes in unknown_attributes(&code.attributes, "LocalVariableTable") {
+        let count = u2(bytes, 0).unwrap_or(0);
+        for offset in (0..count).map(|entry| 2 + entry * 10) {
+            let (Some(start_pc), Some(length), Some(name), Some(descriptor), Some(index)) = (
+                u2(bytes, offset),
+                u2(bytes, offset + 2),
+                u2(bytes, offset + 4).and_then(|index| utf8(java_class, index)),
+                u2(bytes, offset + 6).and_then(|index| utf8(java_class, index)),
+                u2(bytes, offset + 8),
+            ) else { break };
+            locals.push(LocalVariable { start_pc, length, name: name.to_string(), descriptor: descriptor.to_string(), index });
+        }
+    }
+    locals
+}
+
+fn signature<'a>(java_class: &'a JavaClass, attributes: &[AttributeInfo]) -> Option<&'a str> {
+    let bytes = unknown_attributes(attributes, "Signature").next()?;
+    utf8(java_class, u2(bytes, 0)?)
+}
+
+/// The classes an `Exceptions` attribute declares a method to throw.
+fn thrown_classes(java_class: &JavaClass, attributes: &[AttributeInfo]) -> Vec<String> {
+    let Some(bytes) = unknown_attributes(attributes, "Exceptions").next() else { return Vec::new() };
+    (0..u2(bytes, 0).unwrap_or(0))
+        .filter_map(|position| match constant(java_class, u2(bytes, 2 + position * 2)?)? {
+            ConstantPoolInfo::Class(class_info) => Some(java_class_name(&class_info.name)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Value {
+    expr: Expr,
+    ty: FieldType,
+}
+
+impl Value {
+    fn new(expr: Expr, ty: FieldType) -> Value {
+        Value { expr, ty }
+    }
+
+    fn int(value: i32) -> Value {
+        Value::new(Expr::Literal(value.to_string()), FieldType::Int)
+    }
+}
+
+/// The operand stack while decompiling a block, one entry per slot. The second slot of a `long`
+/// or `double` is `None`.
+type Stack = Vec<Option<Value>>;
+
+#[derive(Debug, PartialEq, Clone)]
+enum End {
+    Next(usize),
+    /// Jumps to block `target` when `condition` holds.
+    If { condition: Expr, target: usize, next: usize },
+    /// The cases are labeled the way Java writes them, like `'a'` when switching on a `char`.
+    Switch { value: Expr, cases: Vec<(String, usize)>, default: usize },
+    Return(Option<Expr>),
+    Throw(Expr),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct BlockCode {
+    statements: Vec<Statement>,
+    end: End,
+}
+
+fn object_type(class_name: &str) -> FieldType {
+    FieldType::Object(class_name.to_string())
+}
+
+fn array_element(array: &FieldType) -> Option<FieldType> {
+    match array {
+        FieldType::Array(element) => Some((**element).clone()),
+        _ => None,
+    }
+}
+
+fn primitive_type(array_type: ArrayType) -> FieldType {
+    match array_type {
+        ArrayType::Boolean => FieldType::Boolean,
+        ArrayType::Char => FieldType::Char,
+        ArrayType::Float => FieldType::Float,
+        ArrayType::Double => FieldType::Double,
+        ArrayType::Byte => FieldType::Byte,
+        ArrayType::Short => FieldType::Short,
+        ArrayType::Int => FieldType::Int,
+        ArrayType::Long => FieldType::Long,
+    }
+}
+
+/// The letter locals without a name are told apart by, so a slot reused with another type gets
+/// another variable.
+fn kind_letter(ty: &FieldType) -> char {
+    match ty {
+        FieldType::Long => 'l',
+        FieldType::Float => 'f',
+        FieldType::Double => 'd',
+        FieldType::Object(_) | FieldType::Array(_) => 'a',
+        _ => 'i',
+    }
+}
+
+/// The type of the values an instruction moves, for the typed loads, stores and array accesses.
+fn opcode_type(mnemonic: &str) -> FieldType {
+    match mnemonic.as_bytes()[0] {
+        b'l' => FieldType::Long,
+        b'f' => FieldType::Float,
+        b'd' => FieldType::Double,
+        b'a' => object_type("java/lang/Object"),
+        b'b' => FieldType::Byte,
+        b'c' => FieldType::Char,
+        b's' => FieldType::Short,
+        _ => FieldType::Int,
+    }
+}
+
+const BINARY_OPERATORS: &[(&str, &str)] = &[
+    ("add", "+"), ("sub", "-"), ("mul", "*"), ("div", "/"), ("rem", "%"), ("shl", "<<"), ("shr", ">>"), ("ushr", ">>>"),
+    ("and", "&"), ("or", "|"), ("xor", "^"),
+];
+
+const CONVERSIONS: &[(Opcode, FieldType)] = &[
+    (Opcode::I2l, FieldType::Long), (Opcode::I2f, FieldType::Float), (Opcode::I2d, FieldType::Double),
+    (Opcode::L2i, FieldType::Int), (Opcode::L2f, FieldType::Float), (Opcode::L2d, FieldType::Double),
+    (Opcode::F2i, FieldType::Int), (Opcode::F2l, FieldType::Long), (Opcode::F2d, FieldType::Double),
+    (Opcode::D2i, FieldType::Int), (Opcode::D2l, FieldType::Long), (Opcode::D2f, FieldType::Float),
+    (Opcode::I2b, FieldType::Byte), (Opcode::I2c, FieldType::Char), (Opcode::I2s, FieldType::Short),
+];
+
+/// A loop or switch that `break` can leave, by the block it starts at.
+#[derive(Debug, PartialEq, Clone)]
+struct Breakable {
+    block: usize,
+    is_loop: bool,
+    exit: Option<usize>,
+}
+
+/// The exception table entries a `try` statement stands for, which all cover the same range.
+#[derive(Debug, PartialEq, Clone)]
+struct TryGroup {
+    entries: Vec<usize>,
+    start_pc: usize,
+    end_pc: usize,
+}
+
+/// Where a region being structured is, from the outside in.
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Context {
+    /// The blocks where the enclosing constructs continue, which end the region.
+    stops: Vec<usize>,
+    breakables: Vec<Breakable>,
+    tries: Vec<TryGroup>,
+}
+
+/// How a region ended.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Exit {
+    Reached(usize),
+    Terminated,
+}
+
+/// Decompiles a method in two steps. Each reachable block is first turned into statements by
+/// running its instructions on a stack of expressions; values still on the stack at the end of a
+/// block are handed to its successors in `$stack` variables. The blocks are then structured into
+/// loops from the back edges of the graph, `if`s and `switch`es that merge at their immediate
+/// post-dominator, and `try`s from the exception table, falling back to `goto` where nothing fits.
+struct Decompiler<'a> {
+    java_class: &'a JavaClass,
+    is_static: bool,
+    descriptor: MethodDescriptor,
+    return_type: Option<FieldType>,
+    graph: ControlFlowGraph,
+    /// The exception table, with the ranges javac splits around `return`s joined again.
+    exceptions: Vec<ExceptionTableEntry>,
+    locals: Vec<LocalVariable>,
+    slot_names: HashMap<(usize, char), String>,
+    variable_types: HashMap<String, FieldType>,
+    temporaries: usize,
+    /// The types `$stack` variables are used as, which turn `1` into `true` in their assignments.
+    hints: HashMap<String, FieldType>,
+    /// The blocks the stack of a block is handed to, grouped so that all their predecessors agree
+    /// on the `$stack` variables, by a representative block of each group.
+    stack_groups: Vec<usize>,
+    stack_names: HashMap<(usize, usize), String>,
+    blocks: Vec<Option<BlockCode>>,
+    catch_variables: HashMap<usize, String>,
+    post_dominators: DominatorTree,
+    loops: Vec<Option<Vec<bool>>>,
+    coverage: Vec<Vec<usize>>,
+    throws: Vec<bool>,
+    chain: Vec<usize>,
+    breaks: HashSet<usize>,
+    goto_targets: BTreeSet<usize>,
+    emitted: Vec<bool>,
+    budget: usize,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(java_class: &'a JavaClass, method: &'a MethodInfo, code: &'a AttributeCode) -> Result<Self, DecompileError> {
+        let Some(descriptor) = MethodDescriptor::parse(&method.descriptor) else {
+            return error(0, format!("{} is not a method descriptor", method.descriptor));
+        };
+        let graph = ControlFlowGraph::new(code)?;
+        let count = graph.blocks.len();
+        let mut decompiler = Decompiler {
+            java_class,
+            is_static: method.access_flags & 0x0008 != 0,
+            return_type: descriptor.return_type.clone(),
+            descriptor,
+            exceptions: joined_exceptions(code, &graph),
+            locals: local_variables(java_class, code),
+            slot_names: HashMap::new(),
+            variable_types: HashMap::new(),
+            temporaries: 0,
+            hints: HashMap::new(),
+            stack_groups: (0..count).collect(),
+            stack_names: HashMap::new(),
+            blocks: vec![None; count],
+            catch_variables: HashMap::new(),
+            post_dominators: DominatorTree::new(&[Vec::new()], 0),
+            loops: vec![None; count],
+            coverage: vec![Vec::new(); count],
+            throws: vec![false; count],
+            chain: Vec::new(),
+            breaks: HashSet::new(),
+            goto_targets: BTreeSet::new(),
+            emitted: vec![false; count],
+            budget: count * 20 + 100,
+            graph,
+        };
+        decompiler.analyze();
+        Ok(decompiler)
+    }
+
+    /// The names of the parameters, `this` first for instance methods.
+    fn parameters(&mut self) -> Vec<(String, FieldType)> {
+        let mut parameters = Vec::new();
+        let mut slot = 0;
+        if !self.is_static {
+            parameters.push(self.local(0, 0, &object_type(&self.java_class.this_class_name)));
+            slot += 1;
+        }
+        for parameter in self.descriptor.parameters.clone() {
+            let (name, _) = self.local(slot, 0, &parameter);
+            self.variable_types.insert(name.clone(), parameter.clone());
+            parameters.push((name, parameter.clone()));
+            slot += parameter.slots();
+        }
+        parameters
+    }
+
+    /// The name and type of the local in `slot` at `pc`, from the `LocalVariableTable` when
+    /// there is one.
+    fn local(&mut self, slot: usize, pc: usize, ty: &FieldType) -> (String, FieldType) {
+        let named = self.locals.iter()
+            .find(|local| local.index == slot && local.start_pc <= pc && pc < local.start_pc + local.length)
+            .and_then(|local| Some((local.name.clone(), FieldType::parse(&local.descriptor)?)));
+        if let Some(named) = named {
+            return named;
+        }
+        if slot == 0 && !self.is_static {
+            return ("this".to_string(), object_type(&self.java_class.this_class_name));
+        }
+        let letter = kind_letter(ty);
+        // javac leaves stores nothing reads out of the table, so they take the name of the next
+        // variable in the same slot when the slot isn't read before that one starts.
+        let next = self.locals.iter()
+            .filter(|local| local.index == slot && local.start_pc > pc)
+            .min_by_key(|local| local.start_pc)
+            .filter(|local| !self.graph.instructions.iter().any(|(load_pc, instruction)| {
+                (pc..local.start_pc).contains(load_pc) && instruction.local_index() == Some(slot as u16) && !matches!(instruction.local_access(), Some((_, true, _)))
+            }))
+            .and_then(|local| Some((local.name.clone(), FieldType::parse(&local.descriptor)?)))
+            .filter(|(_, local_type)| kind_letter(local_type) == letter);
+        if let Some(next) = next {
+            return next;
+        }
+        let taken = self.slot_names.keys().any(|(taken_slot, _)| *taken_slot == slot);
+        let name = self.slot_names.entry((slot, letter))
+            .or_insert_with(|| if taken { format!("var{slot}{letter}") } else { format!("var{slot}") })
+            .clone();
+        let ty = self.variable_types.entry(name.clone()).or_insert_with(|| ty.clone()).clone();
+        (name, ty)
+    }
+
+    fn class_name(&self, internal_name: &str) -> String {
+        if internal_name == self.java_class.this_class_name {
+            internal_name.rsplit('/').next().unwrap_or(internal_name).to_string()
+        } else if internal_name.starts_with('[') {
+            FieldType::parse(internal_name).map_or_else(|| internal_name.to_string(), |ty| self.type_name(&ty))
+        } else {
+            java_class_name(internal_name)
+        }
+    }
+
+    fn type_name(&self, ty: &FieldType) -> String {
+        match ty {
+            FieldType::Object(class_name) => self.class_name(class_name),
+            FieldType::Array(element) => format!("{}[]", self.type_name(element)),
+            _ => java_type_name(ty),
+        }
+    }
+
+    fn constant(&self, pc: usize, CpIndex(index): CpIndex) -> Result<&'a ConstantPoolInfo, DecompileError> {
+        match constant(self.java_class, index as usize) {
+            Some(constant) => Ok(constant),
+            None => error(pc, format!("#{index} is not in the constant pool")),
+        }
+    }
+
+    fn constant_class(&self, pc: usize, index: CpIndex) -> Result<&'a str, DecompileError> {
+        match self.constant(pc, index)? {
+            ConstantPoolInfo::Class(class_info) => Ok(&class_info.name),
+            _ => error(pc, format!("#{} is not a class", index.0)),
+        }
+    }
+
+    /// Like [`retype`], also remembering when a `$stack` variable is used as a `boolean` or
+    /// `char`, so that the values assigned to it can be retyped once they are all known.
+    fn typed(&mut self, expr: Expr, ty: &FieldType) -> Expr {
+        if let (FieldType::Boolean | FieldType::Char, Expr::Var(name)) = (ty, &expr) {
+            if name.starts_with("$stack") {
+                self.hints.insert(name.clone(), ty.clone());
+            }
+        }
+        retype(expr, ty)
+    }
+
+    /// Computes what structuring needs: the loops, the post-dominators over normal edges, and
+    /// which exception table entries cover which blocks.
+    fn analyze(&mut self) {
+        let count = self.graph.blocks.len();
+        let dominators = self.graph.dominators();
+        let mut normal_predecessors = vec![Vec::new(); count + 1];
+        for edge in &self.graph.edges {
+            if edge.kind == EdgeKind::Normal {
+                normal_predecessors[edge.to].push(edge.from);
+            }
+        }
+        normal_predecessors[count] = (0..count)
+            .filter(|block| !self.graph.successors(*block).any(|edge| edge.kind == EdgeKind::Normal))
+            .collect();
+        self.post_dominators = DominatorTree::new(&normal_predecessors, count);
+
+        for edge in &self.graph.edges {
+            if edge.kind != EdgeKind::Normal || !dominators.dominates(edge.to, edge.from) {
+                continue;
+            }
+            let header = edge.to;
+            let mut body = self.loops[header].take().unwrap_or_else(|| {
+                let mut body = vec![false; count];
+                body[header] = true;
+                body
+            });
+            let mut worklist = vec![edge.from];
+            while let Some(block) = worklist.pop() {
+                if !dominators.dominates(header, block) || std::mem::replace(&mut body[block], true) {
+                    continue;
+                }
+                worklist.extend(self.graph.predecessors(block)
+                    .filter(|edge| matches!(edge.kind, EdgeKind::Normal | EdgeKind::Exceptional { .. }))
+                    .map(|edge| edge.from));
+            }
+            self.loops[header] = Some(body);
+        }
+
+        for block in 0..count {
+            let successors: Vec<usize> = self.graph.successors(block).filter(|edge| edge.kind == EdgeKind::Normal).map(|edge| edge.to).collect();
+            for pair in successors.windows(2) {
+                let (first, second) = (self.stack_group(pair[0]), self.stack_group(pair[1]));
+                self.stack_groups[first.max(second)] = first.min(second);
+            }
+        }
+
+        for (block, basic_block) in self.graph.blocks.iter().enumerate() {
+            // Entries whose handler is in their own range, like the ones javac adds to release the
+            // monitor of a `synchronized` block, have no `try` in the source.
+            self.coverage[block] = self.exceptions.iter().enumerate()
+                .filter(|(_, entry)| entry.start_pc <= basic_block.start && basic_block.end <= entry.end_pc)
+                .filter(|(_, entry)| !(entry.start_pc..entry.end_pc).contains(&entry.handler_pc))
+                .map(|(position, _)| position)
+                .collect();
+            self.throws[block] = self.graph.block_instructions(block).iter().any(|(_, instruction)| instruction.opcode().can_throw());
+        }
+    }
+}
+
+impl Decompiler<'_> {
+    fn stack_group(&self, mut block: usize) -> usize {
+        while self.stack_groups[block] != block {
+            block = self.stack_groups[block];
+        }
+        block
+    }
+}
+
+/// Joins exception table entries for the same handler when only `return`s, `goto`s and the loads
+/// of what is returned lie between their ranges, which is how javac compiles a `try` containing
+/// `return`, inside a `finally` too. Entries are only joined across others for the same handler,
+/// so that which entry applies first doesn't change.
+fn joined_exceptions(code: &AttributeCode, graph: &ControlFlowGraph) -> Vec<ExceptionTableEntry> {
+    let mut joined: Vec<ExceptionTableEntry> = Vec::new();
+    'entries: for entry in &code.exceptions {
+        for earlier in joined.iter_mut().rev().take_while(|earlier| earlier.handler_pc == entry.handler_pc) {
+            let only_jumps = earlier.end_pc <= entry.start_pc && graph.instructions.iter()
+                .filter(|(pc, _)| (earlier.end_pc..entry.start_pc).contains(pc))
+                .all(|(_, instruction)| matches!(instruction.local_access(), Some((_, false, _))) || matches!(instruction,
+                    Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn
+                    | Instruction::Dreturn | Instruction::Areturn | Instruction::Return));
+            let covers_handler = (earlier.start_pc..entry.end_pc).contains(&entry.handler_pc);
+            if earlier.catch_pc == entry.catch_pc && only_jumps && !covers_handler {
+                earlier.end_pc = entry.end_pc;
+                continue 'entries;
+            }
+        }
+        joined.push(entry.clone());
+    }
+    joined
+}
+
+/// A block being decompiled.
+struct Frame {
+    stack: Stack,
+    statements: Vec<Statement>,
+}
+
+impl Frame {
+    fn pop(&mut self, pc: usize) -> Result<Value, DecompileError> {
+        loop {
+            match self.stack.pop() {
+                Some(Some(value)) => return Ok(value),
+                Some(None) => continue,
+                None => return error(pc, "The operand stack underflows"),
+            }
+        }
+    }
+
+    fn pop_many(&mut self, pc: usize, count: usize) -> Result<Vec<Value>, DecompileError> {
+        let mut values = (0..count).map(|_| self.pop(pc)).collect::<Result<Vec<Value>, DecompileError>>()?;
+        values.reverse();
+        Ok(values)
+    }
+
+    fn push(&mut self, value: Value) {
+        let wide = value.ty.is_wide();
+        self.stack.push(Some(value));
+        if wide {
+            self.stack.push(None);
+        }
+    }
+}
+
+impl Decompiler<'_> {
+    /// Decompiles the reachable blocks in reverse postorder, so that the stack a block starts
+    /// with is known from a predecessor.
+    fn translate_blocks(&mut self) -> Result<(), DecompileError> {
+        let count = self.graph.blocks.len();
+        let mut postorder = Vec::with_capacity(count);
+        let mut visited = vec![false; count];
+        let mut worklist = vec![(0, 0)];
+        visited[0] = true;
+        while let Some((block, next)) = worklist.pop() {
+            let successors: Vec<usize> = self.graph.successors(block)
+                .filter(|edge| matches!(edge.kind, EdgeKind::Normal | EdgeKind::Exceptional { .. }))
+                .map(|edge| edge.to)
+                .collect();
+            match successors.get(next) {
+                Some(&successor) => {
+                    worklist.push((block, next + 1));
+                    if !std::mem::replace(&mut visited[successor], true) {
+                        worklist.push((successor, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+
+        let mut entries: Vec<Option<Stack>> = vec![None; count];
+        entries[0] = Some(Vec::new());
+        for entry in self.exceptions.clone() {
+            let Some(handler) = self.graph.block_at(entry.handler_pc) else { continue };
+            if entries[handler].is_some() {
+                continue;
+            }
+            let ty = match constant(self.java_class, entry.catch_pc) {
+                Some(ConstantPoolInfo::Class(class_info)) => object_type(&class_info.name),
+                _ => object_type("java/lang/Throwable"),
+            };
+            let instructions = self.graph.block_instructions(handler);
+            let (name, ty) = match instructions.first().and_then(|(_, instruction)| instruction.local_access()) {
+                Some((Type::Reference, true, slot)) => {
+                    let next_pc = instructions.get(1).map_or(self.graph.blocks[handler].end, |(pc, _)| *pc);
+                    self.local(slot as usize, next_pc, &ty)
+                }
+                _ => ("$exception".to_string(), ty),
+            };
+            self.catch_variables.insert(handler, name.clone());
+            entries[handler] = Some(vec![Some(Value::new(Expr::Var(name), ty))]);
+        }
+
+        for block in postorder.into_iter().rev() {
+            let Some(stack) = entries[block].clone() else { continue };
+            let (code, exit) = self.translate(block, stack)?;
+            let successors: Vec<usize> = self.graph.successors(block).filter(|edge| edge.kind == EdgeKind::Normal).map(|edge| edge.to).collect();
+            for successor in successors {
+                if entries[successor].is_none() {
+                    entries[successor] = Some(exit.clone());
+                }
+            }
+            self.blocks[block] = Some(code);
+        }
+        Ok(())
+    }
+
+    /// Decompiles one block, giving its statements and the stack it leaves to its successors.
+    fn translate(&mut self, block: usize, stack: Stack) -> Result<(BlockCode, Stack), DecompileError> {
+        let entry: Vec<Option<Expr>> = stack.iter().map(|slot| slot.as_ref().map(|value| value.expr.clone())).collect();
+        let mut frame = Frame { stack, statements: Vec::new() };
+        let instructions = self.graph.block_instructions(block).to_vec();
+        let end_pc = self.graph.blocks[block].end;
+        let mut end = None;
+        for (position, (pc, instruction)) in instructions.iter().enumerate() {
+            let next_pc = instructions.get(position + 1).map_or(end_pc, |(next_pc, _)| *next_pc);
+            end = self.instruction(&mut frame, block, *pc, next_pc, instruction)?;
+        }
+        let end = match end {
+            Some(end) => end,
+            None if block + 1 < self.graph.blocks.len() => End::Next(block + 1),
+            None => return error(end_pc, "Execution falls off the end of the code"),
+        };
+        if matches!(end, End::Return(_) | End::Throw(_)) {
+            for value in std::mem::take(&mut frame.stack).into_iter().flatten() {
+                if !value.expr.is_trivial() {
+                    frame.statements.push(Statement::Expression(value.expr));
+                }
+            }
+        } else {
+            let group = match &end {
+                End::Next(next) | End::If { next, .. } => self.stack_group(*next),
+                End::Switch { default, .. } => self.stack_group(*default),
+                _ => unreachable!(),
+            };
+            // A value passed on unchanged keeps its variable when it's the first to get there.
+            let mut names = Vec::new();
+            for (position, slot) in frame.stack.iter().enumerate() {
+                let Some(value) = slot.as_ref().filter(|value| !matches!(value.expr, Expr::Uninitialized(_))) else { continue };
+                let unchanged = matches!(&value.expr, Expr::Var(name) if name.starts_with("$stack") && entry.get(position) == Some(&Some(value.expr.clone())));
+                let count = self.stack_names.len();
+                let name = self.stack_names.entry((group, position))
+                    .or_insert_with(|| if unchanged { value.expr.to_string() } else { format!("$stack{count}") })
+                    .clone();
+                names.push((position, name));
+            }
+            let overwritten = names.iter().any(|(position, name)| {
+                frame.stack.iter().enumerate().any(|(other, slot)| other != *position && slot.as_ref().is_some_and(|value| value.expr.mentions(name)))
+            });
+            if overwritten {
+                self.spill(&mut frame, |_| true);
+            }
+            for (position, name) in names {
+                let Some(value) = &mut frame.stack[position] else { continue };
+                if value.expr != Expr::Var(name.clone()) {
+                    let expr = std::mem::replace(&mut value.expr, Expr::Var(name.clone()));
+                    frame.statements.push(Statement::Assign { target: Expr::Var(name), value: expr, declare: Some(self.type_name(&value.ty)) });
+                }
+            }
+        }
+        Ok((BlockCode { statements: frame.statements, end }, frame.stack))
+    }
+
+    /// Moves the values on the stack that aren't trivial, or that `also` picks, into temporaries,
+    /// so that they are evaluated before whatever comes next.
+    fn spill(&mut self, frame: &mut Frame, also: impl Fn(&Expr) -> bool) {
+        for slot in frame.stack.iter_mut() {
+            let Some(value) = slot else { continue };
+            if matches!(value.expr, Expr::Uninitialized(_)) || (value.expr.is_trivial() && !also(&value.expr)) {
+                continue;
+            }
+            let name = format!("$tmp{}", self.temporaries);
+            self.temporaries += 1;
+            let expr = std::mem::replace(&mut value.expr, Expr::Var(name.clone()));
+            frame.statements.push(Statement::Assign { target: Expr::Var(name), value: expr, declare: Some(self.type_name(&value.ty)) });
+        }
+    }
+
+    /// Adds a statement with side effects, after evaluating what is on the stack.
+    fn emit(&mut self, frame: &mut Frame, statement: Statement) {
+        self.spill(frame, |_| false);
+        frame.statements.push(statement);
+    }
+
+    fn constant_value(&self, pc: usize, index: CpIndex) -> Result<Value, DecompileError> {
+        Ok(match self.constant(pc, index)? {
+            ConstantPoolInfo::Integer(integer) => Value::int(integer.value),
+            ConstantPoolInfo::Float(float) => Value::new(Expr::Literal(float_literal(float.value)), FieldType::Float),
+            ConstantPoolInfo::Long(long) => Value::new(Expr::Literal(format!("{}L", long.value)), FieldType::Long),
+            ConstantPoolInfo::Double(double) => Value::new(Expr::Literal(double_literal(double.value)), FieldType::Double),
+            ConstantPoolInfo::String(string) => Value::new(Expr::Literal(string_literal(&string.string)), object_type("java/lang/String")),
+            ConstantPoolInfo::Class(class) => {
+                Value::new(Expr::Literal(format!("{}.class", self.class_name(&class.name))), object_type("java/lang/Class"))
+            }
+            ConstantPoolInfo::Dynamic(dynamic) => {
+                let ty = FieldType::parse(&dynamic.descriptor).unwrap_or_else(|| object_type("java/lang/Object"));
+                Value::new(Expr::Literal(format!("/* {} */", describe_constant(&self.java_class.constant_pool_infos, index.0 as usize))), ty)
+            }
+            _ => Value::new(
+                Expr::Literal(format!("/* {} */", describe_constant(&self.java_class.constant_pool_infos, index.0 as usize))),
+                object_type("java/lang/Object"),
+            ),
+        })
+    }
+
+    fn block_at(&self, pc: usize) -> Result<usize, DecompileError> {
+        match self.graph.block_at(pc) {
+            Some(block) => Ok(block),
+            None => error(pc, "Branch target is outside the code"),
+        }
+    }
+
+    /// Decompiles one instruction, giving how the block ends if it is the last one.
+    fn instruction(&mut self, frame: &mut Frame, block: usize, pc: usize, next_pc: usize, instruction: &Instruction) -> Result<Option<End>, DecompileError> {
+        let opcode = instruction.opcode();
+        let mnemonic = opcode.mnemonic();
+        let literal = match instruction {
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => Some(self.constant_value(pc, *index)?),
+            _ => instruction.pushed_literal().map(|literal| match literal {
+                Literal::Int(value) => Value::int(value),
+                Literal::Long(value) => Value::new(Expr::Literal(format!("{value}L")), FieldType::Long),
+                Literal::Float(value) => Value::new(Expr::Literal(float_literal(value)), FieldType::Float),
+                Literal::Double(value) => Value::new(Expr::Literal(double_literal(value)), FieldType::Double),
+                _ => Value::new(Expr::Literal("null".to_string()), object_type("java/lang/Object")),
+            }),
+        };
+        if let Some(value) = literal {
+            frame.push(value);
+            return Ok(None);
+        }
+
+        if let Some((_, store, slot)) = instruction.local_access() {
+            let slot = slot as usize;
+            if !store {
+                let (name, ty) = self.local(slot, pc, &opcode_type(mnemonic));
+                frame.push(Value::new(Expr::Var(name), ty));
+                return Ok(None);
+            }
+            let value = frame.pop(pc)?;
+            let in_scope_after = self.locals.iter().any(|local| local.index == slot && local.start_pc <= next_pc && next_pc < local.start_pc + local.length);
+            let (name, declared) = self.local(slot, if in_scope_after { next_pc } else { pc }, &value.ty);
+            self.spill(frame, |expr| expr.mentions(&name));
+            let expr = self.typed(value.expr, &declared);
+            if expr != Expr::Var(name.clone()) {
+                frame.statements.push(Statement::Assign { target: Expr::Var(name), value: expr, declare: Some(self.type_name(&declared)) });
+            }
+            return Ok(None);
+        }
+
+        let arithmetic = mnemonic.len() > 1 && matches!(mnemonic.as_bytes()[0], b'i' | b'l' | b'f' | b'd');
+        if let Some((_, op)) = BINARY_OPERATORS.iter().find(|(name, _)| arithmetic && mnemonic[1..] == **name) {
+            let [left, right] = <[Value; 2]>::try_from(frame.pop_many(pc, 2)?).unwrap();
+            let ty = if matches!(*op, "&" | "|" | "^") && left.ty == FieldType::Boolean && right.ty == FieldType::Boolean {
+                FieldType::Boolean
+            } else {
+                opcode_type(mnemonic)
+            };
+            frame.push(Value::new(Expr::Binary { op, left: Box::new(left.expr), right: Box::new(right.expr) }, ty));
+            return Ok(None);
+        }
+        if arithmetic && mnemonic[1..] == *"neg" {
+            let value = frame.pop(pc)?;
+            frame.push(Value::new(Expr::Negate(Box::new(value.expr)), opcode_type(mnemonic)));
+            return Ok(None);
+        }
+        if let Some((_, ty)) = CONVERSIONS.iter().find(|(conversion, _)| *conversion == opcode) {
+            let value = frame.pop(pc)?;
+            frame.push(Value::new(Expr::Cast { ty: java_type_name(ty), value: Box::new(value.expr) }, ty.clone()));
+            return Ok(None);
+        }
+        if matches!(opcode, Opcode::Lcmp | Opcode::Fcmpl | Opcode::Fcmpg | Opcode::Dcmpl | Opcode::Dcmpg) {
+            let [left, right] = <[Value; 2]>::try_from(frame.pop_many(pc, 2)?).unwrap();
+            frame.push(Value::new(Expr::ThreeWay { instruction: mnemonic, left: Box::new(left.expr), right: Box::new(right.expr) }, FieldType::Int));
+            return Ok(None);
+        }
+        if mnemonic.ends_with("aload") {
+            let [array, index] = <[Value; 2]>::try_from(frame.pop_many(pc, 2)?).unwrap();
+            let ty = array_element(&array.ty).unwrap_or_else(|| opcode_type(mnemonic));
+            frame.push(Value::new(Expr::Index { array: Box::new(array.expr), index: Box::new(index.expr) }, ty));
+            return Ok(None);
+        }
+        if mnemonic.ends_with("astore") {
+            let [array, index, value] = <[Value; 3]>::try_from(frame.pop_many(pc, 3)?).unwrap();
+            let element = array_element(&array.ty).unwrap_or_else(|| opcode_type(mnemonic));
+            let value = self.typed(value.expr, &element);
+            let target = Expr::Index { array: Box::new(array.expr), index: Box::new(index.expr) };
+            self.emit(frame, Statement::Assign { target, value, declare: None });
+            return Ok(None);
+        }
+
+        let branch = instruction.branch_targets().first().map(|target| target.0);
+        let condition = match instruction {
+            Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) => {
+                let value = frame.pop(pc)?;
+                Some(self.zero_condition(opcode, value))
+            }
+            Instruction::IfIcmpeq(_) | Instruction::IfIcmpne(_) | Instruction::IfIcmplt(_) | Instruction::IfIcmpge(_)
+            | Instruction::IfIcmpgt(_) | Instruction::IfIcmple(_) | Instruction::IfAcmpeq(_) | Instruction::IfAcmpne(_) => {
+                let [left, right] = <[Value; 2]>::try_from(frame.pop_many(pc, 2)?).unwrap();
+                let op = match &mnemonic[mnemonic.len() - 2..] {
+                    "eq" => "==",
+                    "ne" => "!=",
+                    "lt" => "<",
+                    "ge" => ">=",
+                    "gt" => ">",
+                    _ => "<=",
+                };
+                let right_expr = self.typed(right.expr, &left.ty);
+                let left_expr = self.typed(left.expr, &right.ty);
+                Some(Expr::Compare { op, left: Box::new(left_expr), right: Box::new(right_expr), floating: false })
+            }
+            Instruction::Ifnull(_) | Instruction::Ifnonnull(_) => {
+                let value = frame.pop(pc)?;
+                let op = if opcode == Opcode::Ifnull { "==" } else { "!=" };
+                Some(Expr::Compare { op, left: Box::new(value.expr), right: Box::new(Expr::Literal("null".to_string())), floating: false })
+            }
+            _ => None,
+        };
+        if let (Some(condition), Some(target)) = (condition, branch) {
+            return Ok(Some(End::If { condition, target: self.block_at(target)?, next: block + 1 }));
+        }
+
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::Iinc { index, increment } => {
+                let (name, _) = self.local(*index as usize, pc, &FieldType::Int);
+                self.spill(frame, |expr| expr.mentions(&name));
+                frame.statements.push(Statement::Increment { name, amount: *increment as i32 });
+            }
+            Instruction::Pop | Instruction::Pop2 => {
+                let slots = if *instruction == Instruction::Pop { 1 } else { 2 };
+                if frame.stack.len() < slots {
+                    return error(pc, "The operand stack underflows");
+                }
+                let values = frame.stack.split_off(frame.stack.len() - slots);
+                for value in values.into_iter().flatten() {
+                    if !value.expr.is_trivial() {
+                        self.emit(frame, Statement::Expression(value.expr));
+                    }
+                }
+            }
+            Instruction::Dup | Instruction::DupX1 | Instruction::DupX2 | Instruction::Dup2 | Instruction::Dup2X1 | Instruction::Dup2X2 | Instruction::Swap => {
+                let slots = match instruction {
+                    Instruction::Dup => 1,
+                    Instruction::DupX1 | Instruction::Dup2 | Instruction::Swap => 2,
+                    Instruction::DupX2 | Instruction::Dup2X1 => 3,
+                    _ => 4,
+                };
+                if frame.stack.len() < slots {
+                    return error(pc, "The operand stack underflows");
+                }
+                self.spill(frame, |_| false);
+                let values = frame.stack.split_off(frame.stack.len() - slots);
+                let order: &[usize] = match instruction {
+                    Instruction::Dup => &[0, 0],
+                    Instruction::DupX1 => &[1, 0, 1],
+                    Instruction::DupX2 => &[2, 0, 1, 2],
+                    Instruction::Dup2 => &[0, 1, 0, 1],
+                    Instruction::Dup2X1 => &[1, 2, 0, 1, 2],
+                    Instruction::Dup2X2 => &[2, 3, 0, 1, 2, 3],
+                    _ => &[1, 0],
+                };
+                frame.stack.extend(order.iter().map(|position| values[*position].clone()));
+            }
+            Instruction::Goto(target) | Instruction::GotoW(target) => return Ok(Some(End::Next(self.block_at(target.0)?))),
+            Instruction::Tableswitch { default, .. } | Instruction::Lookupswitch { default, .. } => {
+                let value = frame.pop(pc)?;
+                let keys: Vec<(i32, usize)> = match instruction {
+                    Instruction::Tableswitch { low, targets, .. } => targets.iter().enumerate().map(|(position, target)| (low + position as i32, target.0)).collect(),
+                    Instruction::Lookupswitch { pairs, .. } => pairs.iter().map(|(key, target)| (*key, target.0)).collect(),
+                    _ => unreachable!(),
+                };
+                let mut cases = Vec::new();
+                for (key, target) in keys {
+                    let label = self.typed(Expr::Literal(key.to_string()), &value.ty).to_string();
+                    cases.push((label, self.block_at(target)?));
+                }
+                return Ok(Some(End::Switch { value: value.expr, cases, default: self.block_at(default.0)? }));
+            }
+            Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn | Instruction::Areturn => {
+                let value = frame.pop(pc)?;
+                let return_type = self.return_type.clone().unwrap_or(value.ty);
+                return Ok(Some(End::Return(Some(self.typed(value.expr, &return_type)))));
+            }
+            Instruction::Return => return Ok(Some(End::Return(None))),
+            Instruction::Athrow => return Ok(Some(End::Throw(frame.pop(pc)?.expr))),
+            Instruction::Getstatic(index) | Instruction::Putstatic(index) | Instruction::Getfield(index) | Instruction::Putfield(index) => {
+                let ConstantPoolInfo::FieldRef(field_ref) = self.constant(pc, *index)? else {
+                    return error(pc, format!("#{} is not a field reference", index.0));
+                };
+                let Some(ty) = FieldType::parse(&field_ref.descriptor) else {
+                    return error(pc, format!("{} is not a field descriptor", field_ref.descriptor));
+                };
+                let value = if matches!(instruction, Instruction::Putstatic(_) | Instruction::Putfield(_)) { Some(frame.pop(pc)?) } else { None };
+                let owner = if matches!(instruction, Instruction::Getfield(_) | Instruction::Putfield(_)) {
+                    frame.pop(pc)?.expr
+                } else {
+                    Expr::Class(self.class_name(&field_ref.class_name))
+                };
+                let field = Expr::Field { owner: Box::new(owner), name: field_ref.name.clone() };
+                match value {
+                    Some(value) => {
+                        let value = self.typed(value.expr, &ty);
+                        self.emit(frame, Statement::Assign { target: field, value, declare: None });
+                    }
+                    None => frame.push(Value::new(field, ty)),
+                }
+            }
+            Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) | Instruction::Invokestatic(index) | Instruction::Invokeinterface { index, .. } => {
+                let (class_name, name, descriptor) = match self.constant(pc, *index)? {
+                    ConstantPoolInfo::MethodRef(method_ref) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor),
+                    ConstantPoolInfo::InterfaceMethodRef(method_ref) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor),
+                    _ => return error(pc, format!("#{} is not a method reference", index.0)),
+                };
+                let Some(method_descriptor) = MethodDescriptor::parse(descriptor) else {
+                    return error(pc, format!("{descriptor} is not a method descriptor"));
+                };
+                let mut arguments = Vec::new();
+                for (value, ty) in frame.pop_many(pc, method_descriptor.parameters.len())?.into_iter().zip(&method_descriptor.parameters) {
+                    arguments.push(self.typed(value.expr, ty));
+                }
+                let receiver = if opcode == Opcode::Invokestatic { None } else { Some(frame.pop(pc)?.expr) };
+                if name == "<init>" {
+                    self.constructor(frame, class_name, receiver.unwrap(), arguments);
+                    return Ok(None);
+                }
+                let owner = match receiver {
+                    None => Expr::Class(self.class_name(class_name)),
+                    Some(Expr::Var(this)) if this == "this" && opcode == Opcode::Invokespecial && *class_name != self.java_class.this_class_name => {
+                        Expr::Var("super".to_string())
+                    }
+                    Some(receiver) => receiver,
+                };
+                let call = Expr::Call { owner: Some(Box::new(owner)), name: name.clone(), arguments };
+                match method_descriptor.return_type {
+                    Some(ty) => frame.push(Value::new(call, ty)),
+                    None => self.emit(frame, Statement::Expression(call)),
+                }
+            }
+            Instruction::Invokedynamic(index) => {
+                let ConstantPoolInfo::InvokeDynamic(invoke_dynamic) = self.constant(pc, *index)? else {
+                    return error(pc, format!("#{} is not an invokedynamic constant", index.0));
+                };
+                let Some(method_descriptor) = MethodDescriptor::parse(&invoke_dynamic.descriptor) else {
+                    return error(pc, format!("{} is not a method descriptor", invoke_dynamic.descriptor));
+                };
+                let arguments = frame.pop_many(pc, method_descriptor.parameters.len())?;
+                let expr = self.dynamic(pc, invoke_dynamic.bootstrap_method_attr_index, &invoke_dynamic.name, arguments)?;
+                match method_descriptor.return_type {
+                    Some(ty) => frame.push(Value::new(expr, ty)),
+                    None => self.emit(frame, Statement::Expression(expr)),
+                }
+            }
+            Instruction::New(index) => {
+                let class_name = self.constant_class(pc, *index)?;
+                frame.push(Value::new(Expr::Uninitialized(pc), object_type(class_name)));
+            }
+            Instruction::Newarray(_) | Instruction::Anewarray(_) | Instruction::Multianewarray { .. } => {
+                let (array_type, dimensions) = match instruction {
+                    Instruction::Newarray(array_type) => (FieldType::Array(Box::new(primitive_type(*array_type))), 1),
+                    Instruction::Anewarray(index) => {
+                        let class_name = self.constant_class(pc, *index)?;
+                        let Some(element) = FieldType::from_class_name(class_name) else {
+                            return error(pc, format!("{class_name} is not a class name"));
+                        };
+                        (FieldType::Array(Box::new(element)), 1)
+                    }
+                    Instruction::Multianewarray { index, dimensions } => {
+                        let class_name = self.constant_class(pc, *index)?;
+                        let Some(array_type) = FieldType::parse(class_name) else {
+                            return error(pc, format!("{class_name} is not an array type"));
+                        };
+                        (array_type, *dimensions as usize)
+                    }
+                    _ => unreachable!(),
+                };
+                let lengths: Vec<Expr> = frame.pop_many(pc, dimensions)?.into_iter().map(|value| value.expr).collect();
+                let mut base = &array_type;
+                let mut depth = 0;
+                while let FieldType::Array(element) = base {
+                    base = element;
+                    depth += 1;
+                }
+                let expr = Expr::NewArray { base: self.type_name(base), extra: depth - lengths.len().min(depth), lengths };
+                frame.push(Value::new(expr, array_type));
+            }
+            Instruction::Arraylength => {
+                let array = frame.pop(pc)?;
+                frame.push(Value::new(Expr::Length(Box::new(array.expr)), FieldType::Int));
+            }
+            Instruction::Checkcast(index) => {
+                let class_name = self.constant_class(pc, *index)?;
+                let value = frame.pop(pc)?;
+                let ty = FieldType::from_class_name(class_name).unwrap_or_else(|| object_type(class_name));
+                frame.push(Value::new(Expr::Cast { ty: self.class_name(class_name), value: Box::new(value.expr) }, ty));
+            }
+            Instruction::Instanceof(index) => {
+                let class = self.class_name(self.constant_class(pc, *index)?);
+                let value = frame.pop(pc)?;
+                frame.push(Value::new(Expr::InstanceOf { value: Box::new(value.expr), class }, FieldType::Boolean));
+            }
+            Instruction::Monitorenter | Instruction::Monitorexit => {
+                let value = frame.pop(pc)?;
+                let name = if opcode == Opcode::Monitorenter { "monitorenter" } else { "monitorexit" };
+                self.emit(frame, Statement::Expression(Expr::Call { owner: None, name: name.to_string(), arguments: vec![value.expr] }));
+            }
+            _ => return error(pc, format!("{mnemonic} is not supported")),
+        }
+        Ok(None)
+    }
+
+    /// The condition of `ifeq` and friends, which compare with zero. After `lcmp` and the
+    /// floating point comparisons they compare the operands of those instead; `fcmpl` and `dcmpl`
+    /// give -1 for NaN and the `g` variants 1, so some conditions need a `!` to be exact.
+    fn zero_condition(&self, opcode: Opcode, value: Value) -> Expr {
+        let op = match opcode {
+            Opcode::Ifeq => "==",
+            Opcode::Ifne => "!=",
+            Opcode::Iflt => "<",
+            Opcode::Ifge => ">=",
+            Opcode::Ifgt => ">",
+            _ => "<=",
+        };
+        match value.expr {
+            Expr::ThreeWay { instruction, left, right } => {
+                let floating = instruction != "lcmp";
+                let nan_is_less = floating && instruction.ends_with('l');
+                let inverse = match op {
+                    "<" if nan_is_less => ">=",
+                    "<=" if nan_is_less => ">",
+                    ">" if floating && !nan_is_less => "<=",
+                    ">=" if floating && !nan_is_less => "<",
+                    _ => return Expr::Compare { op, left, right, floating },
+                };
+                Expr::Not(Box::new(Expr::Compare { op: inverse, left, right, floating }))
+            }
+            expr if value.ty == FieldType::Boolean && op == "==" => negate(expr),
+            expr if value.ty == FieldType::Boolean && op == "!=" => expr,
+            expr => Expr::Compare { op, left: Box::new(expr), right: Box::new(Expr::Literal("0".to_string())), floating: false },
+        }
+    }
+
+    /// Turns `new`, `dup` and a constructor call into a `new` expression, and constructor calls on
+    /// `this` into `super(...)` or `this(...)`.
+    fn constructor(&mut self, frame: &mut Frame, class_name: &str, receiver: Expr, arguments: Vec<Expr>) {
+        match receiver {
+            Expr::Uninitialized(new_pc) => {
+                let expr = Expr::New { class: self.class_name(class_name), arguments };
+                let copies = frame.stack.iter().flatten().filter(|value| value.expr == Expr::Uninitialized(new_pc)).count();
+                let replacement = match copies {
+                    0 => return self.emit(frame, Statement::Expression(expr)),
+                    1 => expr,
+                    _ => {
+                        let name = format!("$tmp{}", self.temporaries);
+                        self.temporaries += 1;
+                        let declare = Some(self.class_name(class_name));
+                        self.emit(frame, Statement::Assign { target: Expr::Var(name.clone()), value: expr, declare });
+                        Expr::Var(name)
+                    }
+                };
+                for value in frame.stack.iter_mut().flatten() {
+                    if value.expr == Expr::Uninitialized(new_pc) {
+                        value.expr = replacement.clone();
+                    }
+                }
+            }
+            Expr::Var(this) if this == "this" => {
+                let name = if class_name == self.java_class.this_class_name { "this" } else { "super" };
+                if name == "this" || !arguments.is_empty() {
+                    self.emit(frame, Statement::Expression(Expr::Call { owner: None, name: name.to_string(), arguments }));
+                }
+            }
+            receiver => self.emit(frame, Statement::Expression(Expr::Call { owner: Some(Box::new(receiver)), name: "<init>".to_string(), arguments })),
+        }
+    }
+
+    /// An `invokedynamic`: string concatenation and lambdas are written the way Java source does.
+    fn dynamic(&mut self, pc: usize, bootstrap_method: usize, name: &str, arguments: Vec<Value>) -> Result<Expr, DecompileError> {
+        let java_class = self.java_class;
+        let (handle, static_arguments) = match java_class.bootstrap_methods().get(bootstrap_method) {
+            Some(method) => (method.method_handle_index, method.argument_indexes.as_slice()),
+            None => (0, &[][..]),
+        };
+        let bootstrap = match constant(self.java_class, handle) {
+            Some(ConstantPoolInfo::MethodKind(method_kind)) => (method_kind.class_name.as_str(), method_kind.name.as_str()),
+            _ => ("", ""),
+        };
+        match bootstrap {
+            ("java/lang/invoke/StringConcatFactory", "makeConcatWithConstants") => {
+                let recipe = match static_arguments.first().and_then(|index| constant(self.java_class, *index)) {
+                    Some(ConstantPoolInfo::String(string)) => string.string.clone(),
+                    _ => return error(pc, "makeConcatWithConstants needs a recipe"),
+                };
+                let mut parts = Vec::new();
+                let mut text = String::new();
+                let mut arguments = arguments.into_iter();
+                let mut constants = static_arguments[1..].iter();
+                for character in recipe.chars() {
+                    let part = match character {
+                        '\u{1}' => arguments.next(),
+                        '\u{2}' => constants.next().map(|index| self.constant_value(pc, CpIndex(*index as u16))).transpose()?,
+                        _ => {
+                            text.push(character);
+                            continue;
+                        }
+                    };
+                    if !text.is_empty() {
+                        parts.push(Value::new(Expr::Literal(string_literal(&std::mem::take(&mut text))), object_type("java/lang/String")));
+                    }
+                    match part {
+                        Some(part) => parts.push(part),
+                        None => return error(pc, "The concatenation recipe has more parts than arguments"),
+                    }
+                }
+                if !text.is_empty() {
+                    parts.push(Value::new(Expr::Literal(string_literal(&text)), object_type("java/lang/String")));
+                }
+                Ok(concatenation(parts))
+            }
+            ("java/lang/invoke/StringConcatFactory", "makeConcat") => Ok(concatenation(arguments)),
+            ("java/lang/invoke/LambdaMetafactory", _) => {
+                let implementation = match static_arguments.get(1).and_then(|index| constant(self.java_class, *index)) {
+                    Some(ConstantPoolInfo::MethodKind(method_kind)) => method_kind,
+                    _ => return Ok(Expr::Dynamic { name: name.to_string(), arguments: arguments.into_iter().map(|value| value.expr).collect() }),
+                };
+                let parameters = MethodDescriptor::parse(&implementation.descriptor).map_or(0, |descriptor| descriptor.parameters.len());
+                let has_receiver = matches!(implementation.reference_kind, 5 | 7 | 9);
+                let class = self.class_name(&implementation.class_name);
+                let mut captured: Vec<Expr> = arguments.into_iter().map(|value| value.expr).collect();
+                if captured.is_empty() {
+                    let name = if implementation.reference_kind == 8 { "new".to_string() } else { implementation.name.clone() };
+                    return Ok(Expr::MethodReference { owner: Box::new(Expr::Class(class)), name });
+                }
+                if has_receiver && captured.len() == 1 {
+                    return Ok(Expr::MethodReference { owner: Box::new(captured.remove(0)), name: implementation.name.clone() });
+                }
+                let count = (parameters + has_receiver as usize).saturating_sub(captured.len());
+                let parameters: Vec<String> = (0..count).map(|position| format!("p{position}")).collect();
+                captured.extend(parameters.iter().map(|parameter| Expr::Var(parameter.clone())));
+                let body = match implementation.reference_kind {
+                    8 => Expr::New { class, arguments: captured },
+                    _ if has_receiver => {
+                        let receiver = captured.remove(0);
+                        Expr::Call { owner: Some(Box::new(receiver)), name: implementation.name.clone(), arguments: captured }
+                    }
+                    _ => Expr::Call { owner: Some(Box::new(Expr::Class(class))), name: implementation.name.clone(), arguments: captured },
+                };
+                Ok(Expr::Lambda { parameters, body: Box::new(body) })
+            }
+            _ => Ok(Expr::Dynamic { name: name.to_string(), arguments: arguments.into_iter().map(|value| value.expr).collect() }),
+        }
+    }
+}
+
+/// Converts an `int` expression to how Java writes a `boolean` or `char` of the same value.
+fn retype(expr: Expr, ty: &FieldType) -> Expr {
+    match (ty, expr) {
+        (FieldType::Boolean, Expr::Literal(text)) if text == "0" => Expr::Literal("false".to_string()),
+        (FieldType::Boolean, Expr::Literal(text)) if text == "1" => Expr::Literal("true".to_string()),
+        (FieldType::Char, Expr::Literal(text)) => match text.parse::<u16>() {
+            Ok(value) => Expr::Literal(char_literal(value)),
+            Err(_) => Expr::Literal(text),
+        },
+        (FieldType::Boolean | FieldType::Char, Expr::Conditional { condition, then, otherwise }) => {
+            conditional(*condition, retype(*then, ty), retype(*otherwise, ty))
+        }
+        (_, expr) => expr,
+    }
+}
+
+/// `condition ? then : otherwise`, written with `&&` and `||` instead when either side is `true`
+/// or `false`.
+fn conditional(condition: Expr, then: Expr, otherwise: Expr) -> Expr {
+    let boolean = |expr: &Expr| match expr {
+        Expr::Literal(text) if text == "true" => Some(true),
+        Expr::Literal(text) if text == "false" => Some(false),
+        _ => None,
+    };
+    match (boolean(&then), boolean(&otherwise)) {
+        (Some(true), Some(false)) => condition,
+        (Some(false), Some(true)) => negate(condition),
+        (Some(true), None) => or(condition, otherwise),
+        (Some(false), None) => and(negate(condition), otherwise),
+        (None, Some(true)) => or(negate(condition), then),
+        (None, Some(false)) => and(condition, then),
+        _ => Expr::Conditional { condition: Box::new(condition), then: Box::new(then), otherwise: Box::new(otherwise) },
+    }
+}
+
+/// Joins the parts of a string concatenation with `+`, starting with `""` unless one of the first
+/// two parts is a string, which makes Java concatenate rather than add.
+fn concatenation(parts: Vec<Value>) -> Expr {
+    let string = object_type("java/lang/String");
+    let starts_with_string = parts.len() > 1 && parts.iter().take(2).any(|part| part.ty == string);
+    let mut exprs: Vec<Expr> = parts.into_iter().map(|part| part.expr).collect();
+    if !starts_with_string {
+        exprs.insert(0, Expr::Literal("\"\"".to_string()));
+    }
+    Expr::Concat(exprs)
+}
+
+impl Decompiler<'_> {
+    /// Folds blocks that only test a condition into the test of their single predecessor, which
+    /// is how `&&` and `||` compile.
+    fn merge_conditions(&mut self) {
+        let mut predecessors = vec![0usize; self.blocks.len()];
+        for edge in &self.graph.edges {
+            predecessors[edge.to] += 1;
+        }
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in 0..self.blocks.len() {
+                let Some(BlockCode { end: End::If { target, next, .. }, .. }) = &self.blocks[block] else { continue };
+                let (target, next) = (*target, *next);
+                for (other, is_next) in [(next, true), (target, false)] {
+                    if other == block || predecessors[other] != 1 || self.loops[other].is_some() || self.coverage[other] != self.coverage[block] {
+                        continue;
+                    }
+                    let Some(BlockCode { statements, end: End::If { target: other_target, next: other_next, .. } }) = &self.blocks[other] else { continue };
+                    if !statements.is_empty() {
+                        continue;
+                    }
+                    let (other_target, other_next) = (*other_target, *other_next);
+                    // How the two conditions combine: whether with `&&`, and which is negated.
+                    let (is_and, negate_first, negate_other, new_target, new_next) = match is_next {
+                        true if other_target == target => (false, false, false, target, other_next),
+                        true if other_next == target => (true, true, false, other_target, target),
+                        false if other_next == next => (true, false, false, other_target, next),
+                        false if other_target == next => (true, false, true, other_next, next),
+                        _ => continue,
+                    };
+                    let Some(BlockCode { end: End::If { condition: other_condition, .. }, .. }) = self.blocks[other].take() else { unreachable!() };
+                    let Some(BlockCode { statements, end: End::If { condition: first, .. } }) = self.blocks[block].take() else { unreachable!() };
+                    let first = if negate_first { negate(first) } else { first };
+                    let other_condition = if negate_other { negate(other_condition) } else { other_condition };
+                    let merged = if is_and { and(first, other_condition) } else { or(first, other_condition) };
+                    self.blocks[block] = Some(BlockCode { statements, end: End::If { condition: merged, target: new_target, next: new_next } });
+                    changed = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn start(&self, block: usize) -> usize {
+        self.graph.blocks[block].start
+    }
+
+    /// Structures the whole method, then the blocks only reached by `goto`.
+    fn structure(&mut self) -> Result<Vec<Statement>, DecompileError> {
+        let mut statements = Vec::new();
+        let context = Context::default();
+        self.region(0, &context, &mut statements, false);
+        while let Some(block) = self.goto_targets.iter().copied().find(|block| !self.emitted[*block]) {
+            if self.budget == 0 {
+                return error(self.start(block), "Too many blocks would have to be duplicated to structure the method");
+            }
+            self.region(block, &context, &mut statements, false);
+        }
+        Ok(statements)
+    }
+
+    /// Structures the blocks from `start` on into `output` until the region reaches one of the
+    /// stops of `context` or control leaves it. `check_start` says whether `start` itself may be
+    /// a stop or a jump, which it can't be where a construct begins.
+    fn region(&mut self, start: usize, context: &Context, output: &mut Vec<Statement>, check_start: bool) -> Exit {
+        let chain_length = self.chain.len();
+        let mut current = start;
+        let mut check = check_start;
+        let exit = loop {
+            if check {
+                if context.stops.contains(&current) {
+                    break Exit::Reached(current);
+                }
+                if let Some(jump) = self.jump(current, context) {
+                    output.push(jump);
+                    break Exit::Terminated;
+                }
+            }
+            check = true;
+            if !context.tries.iter().all(|group| self.inside_group(current, group)) || self.chain.contains(&current) || self.budget == 0 {
+                output.push(self.goto(current));
+                break Exit::Terminated;
+            }
+            if let Some(group) = self.try_group(current, context) {
+                match self.try_statement(current, group, context, output) {
+                    Some(next) => {
+                        current = next;
+                        continue;
+                    }
+                    None => break Exit::Terminated,
+                }
+            }
+            if self.loops[current].is_some() && !context.breakables.iter().any(|breakable| breakable.is_loop && breakable.block == current) {
+                match self.loop_statement(current, context, output) {
+                    Some(next) => {
+                        current = next;
+                        continue;
+                    }
+                    None => break Exit::Terminated,
+                }
+            }
+
+            self.budget -= 1;
+            self.chain.push(current);
+            self.emitted[current] = true;
+            output.push(Statement::Label(self.start(current)));
+            let Some(code) = self.blocks[current].clone() else {
+                output.push(self.goto(current));
+                break Exit::Terminated;
+            };
+            output.extend(code.statements);
+            let next = match code.end {
+                End::Next(next) => Some(next),
+                End::Return(value) => {
+                    output.push(Statement::Return(value));
+                    None
+                }
+                End::Throw(value) => {
+                    output.push(Statement::Throw(value));
+                    None
+                }
+                End::If { target, next, .. } if target == next => Some(next),
+                End::If { condition, target, next } => self.if_statement(current, condition, target, next, context, output),
+                End::Switch { value, cases, default } => self.switch_statement(current, value, cases, default, context, output),
+            };
+            match next {
+                Some(next) => current = next,
+                None => break Exit::Terminated,
+            }
+        };
+        self.chain.truncate(chain_length);
+        exit
+    }
+
+    fn branch(&mut self, start: usize, context: &Context) -> (Vec<Statement>, Exit) {
+        let mut statements = Vec::new();
+        let exit = self.region(start, context, &mut statements, true);
+        (statements, exit)
+    }
+
+    /// The `break` or `continue` that goes to `block` from inside `context`, if any does.
+    fn jump(&mut self, block: usize, context: &Context) -> Option<Statement> {
+        for breakable in context.breakables.iter().rev() {
+            if breakable.exit == Some(block) {
+                self.breaks.insert(breakable.block);
+                return Some(Statement::Break(self.start(breakable.block)));
+            }
+            if breakable.is_loop && breakable.block == block {
+                return Some(Statement::Continue(self.start(block)));
+            }
+        }
+        None
+    }
+
+    fn goto(&mut self, block: usize) -> Statement {
+        self.goto_targets.insert(block);
+        Statement::Goto(self.start(block))
+    }
+
+    fn jump_to(&mut self, block: usize, context: &Context) -> Statement {
+        self.jump(block, context).unwrap_or_else(|| self.goto(block))
+    }
+
+    /// Where the branches of the `if` or `switch` ending `block` meet again, staying inside the
+    /// innermost loop. That's the immediate post-dominator, unless some branch leaves the method
+    /// or the loop, in which case it's the first block after `block` that two branches reach.
+    fn merge(&self, block: usize, context: &Context) -> Option<usize> {
+        let innermost = context.breakables.iter().rev().find(|breakable| breakable.is_loop).map(|breakable| breakable.block);
+        let in_scope = |other: usize| innermost.is_none_or(|header| self.loops[header].as_ref().is_some_and(|body| body[other]));
+        let post_dominator = self.post_dominators.immediate_dominator(block).filter(|merge| *merge < self.blocks.len());
+        if let Some(merge) = post_dominator.filter(|merge| in_scope(*merge)) {
+            return Some(merge);
+        }
+        let mut reached_from = vec![0usize; self.blocks.len()];
+        let mut successors: Vec<usize> = self.graph.successors(block).filter(|edge| edge.kind == EdgeKind::Normal).map(|edge| edge.to).collect();
+        successors.sort_unstable();
+        successors.dedup();
+        for successor in successors {
+            let mut visited = vec![false; self.blocks.len()];
+            let mut worklist = vec![successor];
+            while let Some(other) = worklist.pop() {
+                if !in_scope(other) || Some(other) == innermost || std::mem::replace(&mut visited[other], true) {
+                    continue;
+                }
+                reached_from[other] += 1;
+                worklist.extend(self.graph.successors(other).filter(|edge| edge.kind == EdgeKind::Normal).map(|edge| edge.to));
+            }
+        }
+        (0..self.blocks.len()).filter(|other| reached_from[*other] > 1 && self.start(*other) > self.start(block)).min_by_key(|other| self.start(*other))
+    }
+
+    /// Ends the branches of a construct that reached a stop other than `continuation` with a jump
+    /// there.
+    fn finish_branches(&mut self, branches: &mut [(&mut Vec<Statement>, Exit)], continuation: Option<usize>, context: &Context) {
+        for (statements, exit) in branches.iter_mut() {
+            if let Exit::Reached(stop) = exit {
+                if Some(*stop) != continuation {
+                    let jump = self.jump_to(*stop, context);
+                    statements.push(jump);
+                }
+            }
+        }
+    }
+
+    fn if_statement(&mut self, block: usize, condition: Expr, target: usize, next: usize, context: &Context, output: &mut Vec<Statement>) -> Option<usize> {
+        let merge = self.merge(block, context);
+        let mut inner = context.clone();
+        inner.stops.extend(merge);
+        let (mut then, then_exit) = self.branch(next, &inner);
+        let (mut otherwise, otherwise_exit) = self.branch(target, &inner);
+        let continuation = continuation(merge, &[then_exit, otherwise_exit]);
+        self.finish_branches(&mut [(&mut then, then_exit), (&mut otherwise, otherwise_exit)], continuation, context);
+        output.push(Statement::If { condition: negate(condition), then, otherwise });
+        continuation
+    }
+
+    fn switch_statement(&mut self, block: usize, value: Expr, cases: Vec<(String, usize)>, default: usize, context: &Context, output: &mut Vec<Statement>) -> Option<usize> {
+        let merge = self.merge(block, context);
+        let id = self.start(block);
+        let mut targets: Vec<usize> = cases.iter().map(|(_, target)| *target).collect();
+        if Some(default) != merge {
+            targets.push(default);
+        }
+        targets.sort_unstable();
+        targets.dedup();
+        let mut inner = context.clone();
+        inner.breakables.push(Breakable { block, is_loop: false, exit: merge });
+        inner.stops.extend(merge);
+        let mut reached_merge = Some(default) == merge;
+        let mut statement_cases = Vec::new();
+        for (position, target) in targets.iter().enumerate() {
+            let mut labels: Vec<String> = cases.iter().filter(|(_, case)| case == target).map(|(label, _)| format!("case {label}")).collect();
+            if *target == default {
+                labels.push("default".to_string());
+            }
+            let following = targets.get(position + 1).copied();
+            let mut case_context = inner.clone();
+            case_context.stops.extend(following);
+            let (mut body, exit) = self.branch(*target, &case_context);
+            match exit {
+                Exit::Reached(stop) if Some(stop) == merge => {
+                    reached_merge = true;
+                    if following.is_some() {
+                        self.breaks.insert(block);
+                        body.push(Statement::Break(id));
+                    }
+                }
+                Exit::Reached(stop) if Some(stop) == following => {}
+                Exit::Reached(stop) => {
+                    let jump = self.jump_to(stop, context);
+                    body.push(jump);
+                }
+                Exit::Terminated => {}
+            }
+            statement_cases.push((labels, body));
+        }
+        output.push(Statement::Switch { id, value, cases: statement_cases });
+        merge.filter(|_| reached_merge || self.breaks.contains(&block))
+    }
+
+    fn loop_statement(&mut self, header: usize, context: &Context, output: &mut Vec<Statement>) -> Option<usize> {
+        let body = self.loops[header].clone().unwrap();
+        let header_pc = self.start(header);
+        let exits: BTreeSet<usize> = self.graph.edges.iter()
+            .filter(|edge| edge.kind == EdgeKind::Normal && body[edge.from] && !body[edge.to])
+            .map(|edge| edge.to)
+            .collect();
+        let exit = match self.post_dominators.immediate_dominator(header) {
+            Some(merge) if exits.contains(&merge) => Some(merge),
+            _ => self.graph.successors(header)
+                .find(|edge| edge.kind == EdgeKind::Normal && exits.contains(&edge.to))
+                .map(|edge| edge.to)
+                .or_else(|| exits.iter().copied().min_by_key(|exit| (self.start(*exit) < header_pc, self.start(*exit)))),
+        };
+        let mut inner = Context { stops: Vec::new(), breakables: context.breakables.clone(), tries: context.tries.clone() };
+        inner.breakables.push(Breakable { block: header, is_loop: true, exit });
+        let mut statements = Vec::new();
+        self.region(header, &inner, &mut statements, false);
+        output.push(Statement::Loop { id: header_pc, condition: None, body: statements, do_while: false });
+        exit.filter(|_| self.breaks.contains(&header))
+    }
+
+    /// Whether `block` belongs in a `try` for `group`. Blocks that can't throw are placed by
+    /// their pc, since no exception table entry needs to cover them.
+    fn inside_group(&self, block: usize, group: &TryGroup) -> bool {
+        if self.throws[block] {
+            group.entries.iter().all(|entry| self.coverage[block].contains(entry))
+        } else {
+            let start = self.start(block);
+            group.start_pc <= start && start <= group.end_pc
+        }
+    }
+
+    /// The outermost `try` that starts at `block`, unless `block` starts a loop that the `try`
+    /// doesn't wholly contain, which is then opened first.
+    fn try_group(&self, block: usize, context: &Context) -> Option<TryGroup> {
+        if !self.throws[block] {
+            return None;
+        }
+        let active: HashSet<usize> = context.tries.iter().flat_map(|group| group.entries.iter().copied()).collect();
+        let extra: Vec<usize> = self.coverage[block].iter().copied().filter(|entry| !active.contains(entry)).collect();
+        let outermost = extra.iter()
+            .map(|entry| &self.exceptions[*entry])
+            .max_by_key(|entry| (entry.end_pc - entry.start_pc, std::cmp::Reverse(entry.start_pc)))?;
+        let (start_pc, end_pc) = (outermost.start_pc, outermost.end_pc);
+        let entries = extra.into_iter()
+            .filter(|entry| self.exceptions[*entry].start_pc == start_pc && self.exceptions[*entry].end_pc == end_pc)
+            .collect();
+        let group = TryGroup { entries, start_pc, end_pc };
+        if let Some(body) = &self.loops[block] {
+            let opened = context.breakables.iter().any(|breakable| breakable.is_loop && breakable.block == block);
+            if !opened && !(0..body.len()).all(|member| !body[member] || !self.throws[member] || self.inside_group(member, &group)) {
+                return None;
+            }
+        }
+        Some(group)
+    }
+
+    fn try_statement(&mut self, block: usize, group: TryGroup, context: &Context, output: &mut Vec<Statement>) -> Option<usize> {
+        let handlers: Vec<usize> = group.entries.iter()
+            .filter_map(|entry| self.graph.block_at(self.exceptions[*entry].handler_pc))
+            .fold(Vec::new(), |mut handlers, handler| {
+                if !handlers.contains(&handler) {
+                    handlers.push(handler);
+                }
+                handlers
+            });
+        let follow = self.try_follow(block, &group, &handlers);
+        let mut inner = context.clone();
+        inner.tries.push(group.clone());
+        inner.stops.extend(follow);
+        let mut body = Vec::new();
+        let body_exit = self.region(block, &inner, &mut body, false);
+
+        let mut handler_context = context.clone();
+        handler_context.stops.extend(follow);
+        let mut catches = Vec::new();
+        let mut exits = vec![body_exit];
+        for handler in handlers {
+            let types = group.entries.iter()
+                .map(|entry| &self.exceptions[*entry])
+                .filter(|entry| self.graph.block_at(entry.handler_pc) == Some(handler))
+                .map(|entry| match constant(self.java_class, entry.catch_pc) {
+                    Some(ConstantPoolInfo::Class(class_info)) => self.class_name(&class_info.name),
+                    _ => "Throwable".to_string(),
+                })
+                .collect();
+            let variable = self.catch_variables.get(&handler).cloned().unwrap_or_else(|| "$exception".to_string());
+            let (statements, exit) = self.branch(handler, &handler_context);
+            catches.push(Catch { types, variable, body: statements });
+            exits.push(exit);
+        }
+        let continuation = continuation(follow, &exits);
+        let mut branches: Vec<(&mut Vec<Statement>, Exit)> = vec![(&mut body, body_exit)];
+        branches.extend(catches.iter_mut().zip(&exits[1..]).map(|(catch, exit)| (&mut catch.body, *exit)));
+        self.finish_branches(&mut branches, continuation, context);
+        output.push(Statement::Try { body, catches });
+        continuation
+    }
+
+    /// Where a `try` continues: the first block outside it that its body reaches, preferring
+    /// blocks after it.
+    fn try_follow(&self, block: usize, group: &TryGroup, handlers: &[usize]) -> Option<usize> {
+        let mut visited = HashSet::new();
+        let mut worklist = vec![block];
+        let mut exits = BTreeSet::new();
+        while let Some(member) = worklist.pop() {
+            if !visited.insert(member) {
+                continue;
+            }
+            for edge in self.graph.successors(member) {
+                if handlers.contains(&edge.to) {
+                    continue;
+                }
+                let inside = self.inside_group(edge.to, group);
+                match edge.kind {
+                    EdgeKind::Normal if inside => worklist.push(edge.to),
+                    EdgeKind::Normal => {
+                        exits.insert(edge.to);
+                    }
+                    EdgeKind::Exceptional { .. } if inside => worklist.push(edge.to),
+                    _ => {}
+                }
+            }
+        }
+        exits.into_iter().min_by_key(|exit| (self.start(*exit) < group.start_pc, self.start(*exit)))
+    }
+}
+
+fn or(left: Expr, right: Expr) -> Expr {
+    Expr::Binary { op: "||", left: Box::new(left), right: Box::new(right) }
+}
+
+fn and(left: Expr, right: Expr) -> Expr {
+    Expr::Binary { op: "&&", left: Box::new(left), right: Box::new(right) }
+}
+
+/// Where a construct continues: where its branches merge if any reached it, otherwise the one
+/// stop they reached.
+fn continuation(merge: Option<usize>, exits: &[Exit]) -> Option<usize> {
+    let reached: BTreeSet<usize> = exits.iter().filter_map(|exit| match exit {
+        Exit::Reached(stop) => Some(*stop),
+        Exit::Terminated => None,
+    }).collect();
+    match merge {
+        Some(merge) if reached.contains(&merge) => Some(merge),
+        _ if reached.len() == 1 => reached.first().copied(),
+        _ => None,
+    }
+}
+
+/// Collects the pcs that `goto`s jump to.
+fn goto_targets(statements: &mut [Statement], targets: &mut HashSet<usize>) {
+    for statement in statements {
+        statement.visit(&mut |statement| {
+            if let Statement::Goto(pc) = statement {
+                targets.insert(*pc);
+            }
+        });
+    }
+}
+
+fn remove_labels(statements: &mut Vec<Statement>, targets: &HashSet<usize>) {
+    statements.retain(|statement| !matches!(statement, Statement::Label(pc) if !targets.contains(pc)));
+    for statement in statements {
+        for body in statement.bodies_mut() {
+            remove_labels(body, targets);
+        }
+    }
+}
+
+/// Retypes the values assigned to `$stack` variables that are used as a `boolean` or `char`, and
+/// the tests of those booleans against 0 made before that was known.
+fn apply_hints(statements: &mut [Statement], hints: &HashMap<String, FieldType>) {
+    for statement in statements {
+        statement.visit(&mut |statement| {
+            if let Statement::Assign { target: Expr::Var(name), value, declare } = statement {
+                if let Some(ty) = hints.get(name) {
+                    *value = retype(std::mem::replace(value, Expr::Literal(String::new())), ty);
+                    *declare = Some(java_type_name(ty));
+                }
+            }
+            for expr in statement.expressions_mut() {
+                expr.visit_mut(&mut |expr| {
+                    let Expr::Compare { op: op @ ("==" | "!="), left, right, .. } = expr else { return };
+                    let Expr::Var(name) = &**left else { return };
+                    if hints.get(name) == Some(&FieldType::Boolean) && **right == Expr::Literal("0".to_string()) {
+                        let value = Expr::Var(name.clone());
+                        *expr = if *op == "!=" { value } else { Expr::Not(Box::new(value)) };
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Removes `remove` where it's the last thing to run before control reaches the end of
+/// `statements` anyway.
+fn strip_trailing(statements: &mut Vec<Statement>, remove: &Statement) {
+    match statements.last_mut() {
+        Some(last) if last == remove => {
+            statements.pop();
+        }
+        Some(Statement::If { then, otherwise, .. }) => {
+            strip_trailing(then, remove);
+            strip_trailing(otherwise, remove);
+        }
+        _ => {}
+    }
+}
+
+fn continues(statements: &mut [Statement], id: usize) -> bool {
+    let mut found = false;
+    for statement in statements {
+        statement.visit(&mut |statement| found |= *statement == Statement::Continue(id));
+    }
+    found
+}
+
+/// Tidies up structured statements: empty branches, ternaries, branches that end in a jump,
+/// and loop conditions.
+fn simplify(statements: &mut Vec<Statement>) {
+    for statement in statements.iter_mut() {
+        for body in statement.bodies_mut() {
+            simplify(body);
+        }
+    }
+    let mut index = 0;
+    while index < statements.len() {
+        match &mut statements[index] {
+            Statement::If { condition, then, otherwise } => {
+                if then.is_empty() && !otherwise.is_empty() {
+                    *condition = negate(std::mem::replace(condition, Expr::Literal(String::new())));
+                    std::mem::swap(then, otherwise);
+                }
+                if let ([Statement::Assign { target: Expr::Var(first), value: then_value, declare }], [Statement::Assign { target: Expr::Var(second), value: otherwise_value, .. }]) = (then.as_slice(), otherwise.as_slice()) {
+                    if first == second && first.starts_with('$') {
+                        let value = conditional(condition.clone(), then_value.clone(), otherwise_value.clone());
+                        statements[index] = Statement::Assign { target: Expr::Var(first.clone()), value, declare: declare.clone() };
+                        continue;
+                    }
+                }
+                if ends_with_jump(otherwise) && (!ends_with_jump(then) || otherwise.len() < then.len()) {
+                    *condition = negate(std::mem::replace(condition, Expr::Literal(String::new())));
+                    std::mem::swap(then, otherwise);
+                }
+                if ends_with_jump(then) && !otherwise.is_empty() {
+                    let rest = std::mem::take(otherwise);
+                    statements.splice(index + 1..index + 1, rest);
+                }
+            }
+            Statement::Loop { id, condition, body, do_while } => {
+                let id = *id;
+                strip_trailing(body, &Statement::Continue(id));
+                let exits = |statement: Option<&Statement>| match statement {
+                    Some(Statement::If { condition, then, otherwise }) if *then == [Statement::Break(id)] && otherwise.is_empty() => Some(condition.clone()),
+                    _ => None,
+                };
+                if condition.is_none() {
+                    if let Some(exit) = exits(body.first()) {
+                        *condition = Some(negate(exit));
+                        body.remove(0);
+                    } else if let Some(exit) = exits(body.last()).filter(|_| body.len() > 1) {
+                        body.pop();
+                        if continues(body, id) {
+                            body.push(Statement::If { condition: exit, then: vec![Statement::Break(id)], otherwise: Vec::new() });
+                        } else {
+                            *condition = Some(negate(exit));
+                            *do_while = true;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+}
+
+/// Turns the statements javac compiles some expressions to back into them: array initializers,
+/// and `x++` used as a value.
+fn collapse(statements: &mut Vec<Statement>) {
+    for statement in statements.iter_mut() {
+        for body in statement.bodies_mut() {
+            collapse(body);
+        }
+    }
+    let mut index = 0;
+    while index < statements.len() {
+        let Statement::Assign { target: Expr::Var(name), value, .. } = &statements[index] else {
+            index += 1;
+            continue;
+        };
+        let name = name.clone();
+        match value {
+            Expr::NewArray { base, lengths, extra } if name.starts_with('$') && lengths.len() == 1 => {
+                let ty = format!("{base}[]{}", "[]".repeat(*extra));
+                let Expr::Literal(length) = &lengths[0] else {
+                    index += 1;
+                    continue;
+                };
+                let length = length.parse::<usize>().unwrap_or(0);
+                let stores = statements.get(index + 1..index + 1 + length).unwrap_or_default();
+                let values: Vec<Expr> = stores.iter().enumerate().map_while(|(position, store)| match store {
+                    Statement::Assign { target: Expr::Index { array, index }, value, .. }
+                        if **array == Expr::Var(name.clone()) && **index == Expr::Literal(position.to_string()) && !value.mentions(&name) => Some(value.clone()),
+                    _ => None,
+                }).collect();
+                if length > 0 && values.len() == length {
+                    statements.drain(index + 1..index + 1 + length);
+                    if let Statement::Assign { value, .. } = &mut statements[index] {
+                        *value = Expr::ArrayInitializer { ty, values };
+                    }
+                }
+            }
+            Expr::Var(variable) if name.starts_with('$') => {
+                if let Some(Statement::Increment { name: incremented, amount: amount @ (1 | -1) }) = statements.get(index + 1) {
+                    if incremented == variable {
+                        let op = if *amount == 1 { "++" } else { "--" };
+                        let postfix = Expr::Postfix { name: variable.clone(), op };
+                        statements.remove(index + 1);
+                        if let Statement::Assign { value, .. } = &mut statements[index] {
+                            *value = postfix;
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+}
+
+fn is_monitor(statement: &Statement, monitor: &str) -> Option<Expr> {
+    match statement {
+        Statement::Expression(Expr::Call { owner: None, name, arguments }) if name == monitor && arguments.len() == 1 => Some(arguments[0].clone()),
+        _ => None,
+    }
+}
+
+fn remove_monitor_exits(statements: &mut Vec<Statement>, lock: &Expr) {
+    statements.retain(|statement| is_monitor(statement, "monitorexit").as_ref() != Some(lock));
+    for statement in statements {
+        for body in statement.bodies_mut() {
+            remove_monitor_exits(body, lock);
+        }
+    }
+}
+
+/// Turns `monitorenter`, then a `try` whose handler releases the monitor and rethrows, into the
+/// `synchronized` statement javac compiled them from.
+fn synchronize(statements: &mut Vec<Statement>) {
+    for statement in statements.iter_mut() {
+        for body in statement.bodies_mut() {
+            synchronize(body);
+        }
+    }
+    let mut index = 0;
+    while index + 1 < statements.len() {
+        let Some(lock) = is_monitor(&statements[index], "monitorenter") else {
+            index += 1;
+            continue;
+        };
+        let Statement::Try { catches, .. } = &statements[index + 1] else {
+            index += 1;
+            continue;
+        };
+        let held = match catches.as_slice() {
+            [Catch { variable, body, .. }] if body.len() == 2 && body[1] == Statement::Throw(Expr::Var(variable.clone())) => is_monitor(&body[0], "monitorexit"),
+            _ => None,
+        };
+        let Some(held) = held else {
+            index += 1;
+            continue;
+        };
+        let Statement::Try { mut body, .. } = statements.remove(index + 1) else { unreachable!() };
+        remove_monitor_exits(&mut body, &held);
+        statements[index] = Statement::Synchronized { lock: lock.clone(), body };
+        if index > 0 && statements[index - 1] == (Statement::Assign { target: held.clone(), value: lock.clone(), declare: statements[index - 1].declared() }) {
+            statements.remove(index - 1);
+            index -= 1;
+        }
+        index += 1;
+    }
+}
+
+/// Counts the assignments to and the reads of each variable.
+fn count_uses(statements: &[Statement], assignments: &mut HashMap<String, usize>, reads: &mut HashMap<String, usize>) {
+    fn read(expr: &Expr, reads: &mut HashMap<String, usize>) {
+        expr.visit(&mut |expr| {
+            if let Expr::Var(name) = expr {
+                *reads.entry(name.clone()).or_default() += 1;
+            }
+        });
+    }
+    for statement in statements {
+        match statement {
+            Statement::Assign { target: Expr::Var(name), value, .. } => {
+                *assignments.entry(name.clone()).or_default() += 1;
+                read(value, reads);
+            }
+            statement => {
+                for expr in statement.clone().expressions_mut() {
+                    read(expr, reads);
+                }
+            }
+        }
+        let mut statement = statement.clone();
+        for body in statement.bodies_mut() {
+            count_uses(body, assignments, reads);
+        }
+    }
+}
+
+/// Replaces the first read of `name` in what `statement` evaluates first, if nothing before it
+/// would notice.
+fn inline_into(statement: &mut Statement, name: &str, value: &mut Option<Expr>) -> bool {
+    let exprs: Vec<&mut Expr> = match statement {
+        Statement::Expression(expr) | Statement::Return(Some(expr)) | Statement::Throw(expr) => vec![expr],
+        Statement::Assign { target: Expr::Var(_), value, .. } => vec![value],
+        Statement::Assign { target: Expr::Field { owner, .. }, value, .. } => vec![owner, value],
+        Statement::Assign { target: Expr::Index { array, index }, value, .. } => vec![array, index, value],
+        Statement::If { condition, .. } | Statement::Switch { value: condition, .. } | Statement::Synchronized { lock: condition, .. } => vec![condition],
+        _ => return false,
+    };
+    for expr in exprs {
+        match expr.replace_first_read(name, value) {
+            Ok(true) => return true,
+            Ok(false) => {}
+            Err(()) => return false,
+        }
+    }
+    false
+}
+
+/// Moves the values of `$` variables assigned and read once into the statement right after.
+fn inline(statements: &mut Vec<Statement>, assignments: &HashMap<String, usize>, reads: &HashMap<String, usize>) -> bool {
+    let mut changed = false;
+    let mut index = 0;
+    while index < statements.len() {
+        for body in statements[index].bodies_mut() {
+            changed |= inline(body, assignments, reads);
+        }
+        if let Statement::Assign { target: Expr::Var(name), value, .. } = &statements[index] {
+            let single = |counts: &HashMap<String, usize>| counts.get(name).copied() == Some(1);
+            if name.starts_with('$') && single(assignments) && single(reads) && index + 1 < statements.len() {
+                let name = name.clone();
+                let mut value = Some(value.clone());
+                if inline_into(&mut statements[index + 1], &name, &mut value) {
+                    statements.remove(index);
+                    changed = true;
+                    continue;
+                }
+            }
+        }
+        index += 1;
+    }
+    changed
+}
+
+/// Turns `$t = value; x = $t; use($t)`, which is how javac compiles an assignment used as a
+/// value, into `x = value; use(x)`.
+fn forward_copies(statements: &mut Vec<Statement>, reads: &HashMap<String, usize>) -> bool {
+    let mut changed = false;
+    for statement in statements.iter_mut() {
+        for body in statement.bodies_mut() {
+            changed |= forward_copies(body, reads);
+        }
+    }
+    let mut index = 0;
+    while index + 2 < statements.len() {
+        if let [Statement::Assign { target: Expr::Var(temporary), value, .. }, Statement::Assign { target: Expr::Var(variable), value: Expr::Var(copied), declare }, _] = &statements[index..index + 3] {
+            if temporary.starts_with('$') && !variable.starts_with('$') && copied == temporary && reads.get(temporary) == Some(&2) {
+                let (temporary, variable) = (temporary.clone(), variable.clone());
+                let assignment = Statement::Assign { target: Expr::Var(variable.clone()), value: value.clone(), declare: declare.clone() };
+                let mut replaced = false;
+                for expr in statements[index + 2].expressions_mut() {
+                    expr.visit_mut(&mut |expr| {
+                        if *expr == Expr::Var(temporary.clone()) && !replaced {
+                            *expr = Expr::Var(variable.clone());
+                            replaced = true;
+                        }
+                    });
+                }
+                if replaced {
+                    statements[index] = assignment;
+                    statements.remove(index + 1);
+                    changed = true;
+                }
+            }
+        }
+        index += 1;
+    }
+    changed
+}
+
+fn inline_all(statements: &mut Vec<Statement>) {
+    loop {
+        let mut assignments = HashMap::new();
+        let mut reads = HashMap::new();
+        count_uses(statements, &mut assignments, &mut reads);
+        if !inline(statements, &assignments, &reads) && !forward_copies(statements, &reads) {
+            break;
+        }
+    }
+}
+
+/// The variables that must be declared before the statements rather than where they're first
+/// assigned, because they're used outside the block that assignment is in.
+fn hoisted_declarations(statements: &[Statement]) -> Vec<(String, String)> {
+    fn walk(statements: &[Statement], path: &mut Vec<usize>, first: &mut Vec<(String, String, Vec<usize>)>, uses: &mut Vec<(String, Vec<usize>)>) {
+        for (position, statement) in statements.iter().enumerate() {
+            let mut names = Vec::new();
+            let mut statement = statement.clone();
+            for expr in statement.expressions_mut() {
+                expr.visit(&mut |expr| {
+                    if let Expr::Var(name) = expr {
+                        names.push(name.clone());
+                    }
+                });
+            }
+            if let Statement::Increment { name, .. } = &statement {
+                names.push(name.clone());
+            }
+            if let Statement::Assign { target: Expr::Var(name), declare: Some(ty), .. } = &statement {
+                if !first.iter().any(|(declared, _, _)| declared == name) {
+                    first.push((name.clone(), ty.clone(), path.clone()));
+                }
+            }
+            uses.extend(names.into_iter().map(|name| (name, path.clone())));
+            for (body_position, body) in statement.bodies_mut().into_iter().enumerate() {
+                path.extend([position, body_position]);
+                walk(body, path, first, uses);
+                path.truncate(path.len() - 2);
+            }
+        }
+    }
+    let mut first = Vec::new();
+    let mut uses = Vec::new();
+    walk(statements, &mut Vec::new(), &mut first, &mut uses);
+    first.into_iter()
+        .filter(|(name, _, scope)| uses.iter().any(|(used, path)| used == name && !path.starts_with(scope)))
+        .map(|(name, ty, _)| (ty, name))
+        .collect()
+}
+
+/// The loops and switches that `break` or `continue` has to name, because they aren't the
+/// innermost one.
+fn labeled(statements: &[Statement], enclosing: &mut Vec<(usize, bool)>, labels: &mut HashSet<usize>) {
+    for statement in statements {
+        match statement {
+            Statement::Break(id) if enclosing.last().map(|(innermost, _)| innermost) != Some(id) => {
+                labels.insert(*id);
+            }
+            Statement::Continue(id) if enclosing.iter().rev().find(|(_, is_loop)| *is_loop).map(|(innermost, _)| innermost) != Some(id) => {
+                labels.insert(*id);
+            }
+            Statement::Loop { id, body, .. } => {
+                enclosing.push((*id, true));
+                labeled(body, enclosing, labels);
+                enclosing.pop();
+            }
+            Statement::Switch { id, cases, .. } => {
+                enclosing.push((*id, false));
+                for (_, body) in cases {
+                    labeled(body, enclosing, labels);
+                }
+                enclosing.pop();
+            }
+            _ => {
+                for body in statement.clone().bodies_mut() {
+                    labeled(body, enclosing, labels);
+                }
+            }
+        }
+    }
+}
+
+struct Printer {
+    output: String,
+    declared: HashSet<String>,
+    labels: HashSet<usize>,
+}
+
+impl Printer {
+    fn line(&mut self, indent: usize, text: &str) {
+        writeln!(self.output, "{}{text}", "    ".repeat(indent)).unwrap();
+    }
+
+    fn block(&mut self, statements: &[Statement], indent: usize) {
+        for statement in statements {
+            self.statement(statement, indent);
+        }
+    }
+
+    fn label(&self, id: usize) -> String {
+        if self.labels.contains(&id) { format!("label{id}: ") } else { String::new() }
+    }
+
+    fn jump(&self, keyword: &str, id: usize) -> String {
+        if self.labels.contains(&id) { format!("{keyword} label{id};") } else { format!("{keyword};") }
+    }
+
+    fn statement(&mut self, statement: &Statement, indent: usize) {
+        match statement {
+            Statement::Expression(expr) => self.line(indent, &format!("{expr};")),
+            Statement::Assign { target, value, declare } => {
+                let text = match (target, declare) {
+                    (Expr::Var(name), Some(ty)) if self.declared.insert(name.clone()) => format!("{ty} {name} = {value};"),
+                    _ => assignment(target, value),
+                };
+                self.line(indent, &text);
+            }
+            Statement::Increment { name, amount } => {
+                let text = match amount {
+                    1 => format!("{name}++;"),
+                    -1 => format!("{name}--;"),
+                    amount if *amount < 0 => format!("{name} -= {};", -amount),
+                    amount => format!("{name} += {amount};"),
+                };
+                self.line(indent, &text);
+            }
+            Statement::Return(None) => self.line(indent, "return;"),
+            Statement::Return(Some(value)) => self.line(indent, &format!("return {value};")),
+            Statement::Throw(value) => self.line(indent, &format!("throw {value};")),
+            Statement::If { condition, then, otherwise } => {
+                self.line(indent, &format!("if ({condition}) {{"));
+                self.block(then, indent + 1);
+                let mut otherwise = otherwise;
+                while let [Statement::If { condition, then, otherwise: rest }] = otherwise.as_slice() {
+                    self.line(indent, &format!("}} else if ({condition}) {{"));
+                    self.block(then, indent + 1);
+                    otherwise = rest;
+                }
+                if !otherwise.is_empty() {
+                    self.line(indent, "} else {");
+                    self.block(otherwise, indent + 1);
+                }
+                self.line(indent, "}");
+            }
+            Statement::Loop { id, condition, body, do_while: true } => {
+                self.line(indent, &format!("{}do {{", self.label(*id)));
+                self.block(body, indent + 1);
+                let condition = condition.as_ref().map_or_else(|| "true".to_string(), ToString::to_string);
+                self.line(indent, &format!("}} while ({condition});"));
+            }
+            Statement::Loop { id, condition, body, .. } => {
+                let condition = condition.as_ref().map_or_else(|| "true".to_string(), ToString::to_string);
+                self.line(indent, &format!("{}while ({condition}) {{", self.label(*id)));
+                self.block(body, indent + 1);
+                self.line(indent, "}");
+            }
+            Statement::Switch { id, value, cases } => {
+                self.line(indent, &format!("{}switch ({value}) {{", self.label(*id)));
+                for (labels, body) in cases {
+                    for label in labels {
+                        self.line(indent + 1, &format!("{label}:"));
+                    }
+                    self.block(body, indent + 2);
+                }
+                self.line(indent, "}");
+            }
+            Statement::Try { body, catches } => {
+                self.line(indent, "try {");
+                self.block(body, indent + 1);
+                for catch in catches {
+                    self.declared.insert(catch.variable.clone());
+                    self.line(indent, &format!("}} catch ({} {}) {{", catch.types.join(" | "), catch.variable));
+                    self.block(&catch.body, indent + 1);
+                }
+                self.line(indent, "}");
+            }
+            Statement::Synchronized { lock, body } => {
+                self.line(indent, &format!("synchronized ({lock}) {{"));
+                self.block(body, indent + 1);
+                self.line(indent, "}");
+            }
+            Statement::Break(id) => self.line(indent, &self.jump("break", *id)),
+            Statement::Continue(id) => self.line(indent, &self.jump("continue", *id)),
+            Statement::Goto(pc) => self.line(indent, &format!("goto block{pc};")),
+            Statement::Label(pc) => self.line(indent, &format!("block{pc}:")),
+        }
+    }
+}
+
+/// Writes `target = value`, as `target += right` or `target++` when that's what it amounts to.
+fn assignment(target: &Expr, value: &Expr) -> String {
+    let compound = match target {
+        Expr::Var(_) => true,
+        Expr::Field { owner, .. } => owner.is_trivial(),
+        Expr::Index { array, index } => array.is_trivial() && index.is_trivial(),
+        _ => false,
+    };
+    if let Expr::Binary { op, left, right } = value {
+        if compound && **left == *target && !matches!(*op, "&&" | "||") {
+            return match (*op, &**right) {
+                ("+", Expr::Literal(one)) if one == "1" => format!("{target}++;"),
+                ("-", Expr::Literal(one)) if one == "1" => format!("{target}--;"),
+                _ => format!("{target} {op}= {right};"),
+            };
+        }
+    }
+    format!("{target} = {value};")
+}
+
+fn flags(access_flags: u16, table: &[(u16, &str)]) -> String {
+    table.iter()
+        .filter(|(flag, _)| access_flags & flag != 0)
+        .map(|(_, name)| format!("{name} "))
+        .collect()
+}
+
+const SOURCE_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0400, "abstract"), (0x0008, "static"), (0x0010, "final"),
+    (0x0040, "volatile"), (0x0080, "transient"),
+];
+
+const SOURCE_METHOD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0400, "abstract"), (0x0008, "static"), (0x0010, "final"),
+    (0x0020, "synchronized"), (0x0100, "native"), (0x0800, "strictfp"),
+];
+
+fn simple_name(internal_name: &str) -> &str {
+    internal_name.rsplit('/').next().unwrap_or(internal_name)
+}
+
+/// The method's declaration up to, not including, its body.
+fn method_header(java_class: &JavaClass, method: &MethodInfo, parameter_names: &[String]) -> String {
+    if method.name == "<clinit>" {
+        return "static".to_string();
+    }
+    let mut header = flags(method.access_flags, SOURCE_METHOD_FLAGS);
+    if java_class.flags.is_interface() && method.access_flags & 0x040a == 0 {
+        header.push_str("default ");
+    }
+    let descriptor = MethodDescriptor::parse(&method.descriptor).unwrap_or(MethodDescriptor { parameters: Vec::new(), return_type: None });
+    let signature = signature(java_class, &method.attributes_info)
+        .and_then(MethodSignature::parse)
+        .filter(|signature| signature.parameters.len() == descriptor.parameters.len());
+    let (type_parameters, mut parameters, return_type, mut throws) = match signature {
+        Some(signature) => (
+            type_parameters_to_string(&signature.type_parameters),
+            signature.parameters.iter().map(ToString::to_string).collect::<Vec<String>>(),
+            signature.return_type.map_or_else(|| "void".to_string(), |ty| ty.to_string()),
+            signature.throws.iter().map(ToString::to_string).collect::<Vec<String>>(),
+        ),
+        None => (
+            String::new(),
+            descriptor.parameters.iter().map(java_type_name).collect(),
+            descriptor.return_type.as_ref().map_or_else(|| "void".to_string(), java_type_name),
+            Vec::new(),
+        ),
+    };
+    if throws.is_empty() {
+        throws = thrown_classes(java_class, &method.attributes_info);
+    }
+    if method.access_flags & 0x0080 != 0 {
+        if let Some(last) = parameters.last_mut().filter(|last| last.ends_with("[]")) {
+            last.truncate(last.len() - 2);
+            last.push_str("...");
+        }
+    }
+    if !type_parameters.is_empty() {
+        write!(header, "{type_parameters} ").unwrap();
+    }
+    if method.name == "<init>" {
+        header.push_str(simple_name(&java_class.this_class_name));
+    } else {
+        write!(header, "{return_type} {}", method.name).unwrap();
+    }
+    let parameters: Vec<String> = parameters.iter().zip(parameter_names).map(|(ty, name)| format!("{ty} {name}")).collect();
+    write!(header, "({})", parameters.join(", ")).unwrap();
+    if !throws.is_empty() {
+        write!(header, " throws {}", throws.join(", ")).unwrap();
+    }
+    header
+}
+
+/// Decompiles one method to Java-like source, its declaration included.
+///
+/// Method bodies that don't fit Java's structured statements keep `goto`s to labeled blocks under a
+/// comment saying so, and values javac would never leave on the stack across branches show up as
+/// `$stack` variables.
+pub fn decompile_method(java_class: &JavaClass, method: &MethodInfo) -> Result<String, DecompileError> {
+    let Some(code) = method.code() else {
+        let descriptor = MethodDescriptor::parse(&method.descriptor);
+        let names: Vec<String> = (1..=descriptor.map_or(0, |descriptor| descriptor.parameters.len())).map(|position| format!("arg{position}")).collect();
+        return Ok(format!("{};\n", method_header(java_class, method, &names)));
+    };
+    let mut decompiler = Decompiler::new(java_class, method, code)?;
+    let parameters = decompiler.parameters();
+    decompiler.translate_blocks()?;
+    decompiler.merge_conditions();
+    let mut statements = decompiler.structure()?;
+
+    let mut targets = HashSet::new();
+    goto_targets(&mut statements, &mut targets);
+    remove_labels(&mut statements, &targets);
+    apply_hints(&mut statements, &decompiler.hints);
+    simplify(&mut statements);
+    collapse(&mut statements);
+    synchronize(&mut statements);
+    inline_all(&mut statements);
+    simplify(&mut statements);
+    if decompiler.return_type.is_none() {
+        strip_trailing(&mut statements, &Statement::Return(None));
+    }
+
+    let mut printer = Printer { output: String::new(), declared: parameters.iter().map(|(name, _)| name.clone()).collect(), labels: HashSet::new() };
+    labeled(&statements, &mut Vec::new(), &mut printer.labels);
+    if !targets.is_empty() {
+        printer.line(1, "// Unstructured: the control flow doesn't fit Java's statements, so blocks are reached by goto");
+    }
+    for (ty, name) in hoisted_declarations(&statements) {
+        if printer.declared.insert(name.clone()) {
+            printer.line(1, &format!("{ty} {name};"));
+        }
+    }
+    printer.block(&statements, 1);
+
+    let names: Vec<String> = parameters.into_iter().skip(usize::from(!decompiler.is_static)).map(|(name, _)| name).collect();
+    Ok(format!("{} {{\n{}}}\n", method_header(java_class, method, &names), printer.output))
+}
+
+/// The initializer of a constant field, from its `ConstantValue` attribute.
+fn field_constant(java_class: &JavaClass, field: &FieldInfo) -> Option<String> {
+    let index = field.attributes_info.iter().find_map(|attribute_info| match &attribute_info.attribute {
+        Attribute::ConstantValue(constant_value) => Some(constant_value.constant_value_index),
+        _ => None,
+    })?;
+    let ty = FieldType::parse(&field.descriptor)?;
+    let literal = match constant(java_class, index)? {
+        ConstantPoolInfo::Integer(integer) => retype(Expr::Literal(integer.value.to_string()), &ty).to_string(),
+        ConstantPoolInfo::Float(float) => float_literal(float.value),
+        ConstantPoolInfo::Long(long) => format!("{}L", long.value),
+        ConstantPoolInfo::Double(double) => double_literal(double.value),
+        ConstantPoolInfo::String(string) => string_literal(&string.string),
+        _ => return None,
+    };
+    Some(literal)
+}
+
+/// Decompiles a whole class to Java-like source. Methods that can't be decompiled are replaced
+/// by a comment saying why.
+pub fn decompile_class(java_class: &JavaClass) -> String {
+    let mut output = String::new();
+    let name = &java_class.this_class_name;
+    if let Some((package, _)) = name.rsplit_once('/') {
+        writeln!(output, "package {};\n", package.replace('/', ".")).unwrap();
+    }
+    let class_flags = &java_class.flags;
+    let (kind, mut modifiers) = if class_flags.is_annotation() {
+        ("@interface", flags(class_flags.0, &[(0x0001, "public")]))
+    } else if class_flags.is_interface() {
+        ("interface", flags(class_flags.0, &[(0x0001, "public")]))
+    } else if class_flags.is_enum() {
+        ("enum", flags(class_flags.0, &[(0x0001, "public")]))
+    } else {
+        ("class", flags(class_flags.0, &[(0x0001, "public"), (0x0400, "abstract"), (0x0010, "final")]))
+    };
+    write!(modifiers, "{kind} {}", simple_name(name)).unwrap();
+
+    let class_signature = signature(java_class, &java_class.attributes).and_then(ClassSignature::parse);
+    let (super_class, interfaces) = match class_signature {
+        Some(signature) => {
+            modifiers.push_str(&type_parameters_to_string(&signature.type_parameters));
+            (Some(signature.super_class.to_string()), signature.interfaces.iter().map(ToString::to_string).collect())
+        }
+        None => (
+            java_class.super_class_name.as_deref().map(java_class_name),
+            java_class.interfaces_names.iter().map(|interface| java_class_name(interface)).collect::<Vec<String>>(),
+        ),
+    };
+    if let Some(super_class) = super_class.filter(|super_class| !class_flags.is_interface() && super_class != "Object" && !super_class.starts_with("Enum<") && super_class != "Enum" && super_class != "Record") {
+        write!(modifiers, " extends {super_class}").unwrap();
+    }
+    let interfaces: Vec<String> = interfaces.into_iter().filter(|interface| !(class_flags.is_annotation() && interface == "java.lang.annotation.Annotation")).collect();
+    if !interfaces.is_empty() {
+        let keyword = if class_flags.is_interface() { "extends" } else { "implements" };
+        write!(modifiers, " {keyword} {}", interfaces.join(", ")).unwrap();
+    }
+    writeln!(output, "{modifiers} {{").unwrap();
+
+    for field in &java_class.fields {
+        let ty = signature(java_class, &field.attributes_info)
+            .and_then(TypeSignature::parse)
+            .map(|ty| ty.to_string())
+            .or_else(|| FieldType::parse(&field.descriptor).map(|ty| java_type_name(&ty)))
+            .unwrap_or_else(|| field.descriptor.clone());
+        let initializer = field_constant(java_class, field).map(|value| format!(" = {value}")).unwrap_or_default();
+        writeln!(output, "    {}{ty} {}{initializer};", flags(field.access_flags, SOURCE_FLAGS), field.name).unwrap();
+    }
+    for (position, method) in java_class.methods.iter().enumerate() {
+        if position > 0 || !java_class.fields.is_empty() {
+            output.push('\n');
+        }
+        let text = decompile_method(java_class, method)
+            .unwrap_or_else(|error| format!("// Could not decompile {}{}: {error}\n", method.name, method.descriptor));
+        for line in text.lines() {
+            if line.is_empty() {
+                output.push('\n');
+            } else {
+                writeln!(output, "    {line}").unwrap();
+            }
+        }
+    }
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+fn decompile_fixture(class_file: &str, name: &str) -> String {
+    let java_class = JavaClass::load_from_file(class_file).unwrap();
+    let method = java_class.methods.iter().find(|method| method.name == name).unwrap();
+    decompile_method(&java_class, method).unwrap()
+}
+
+#[test]
+fn test_recovers_loops_and_switches() {
+    assert_eq!("\
+static int search(int[][] grid, int target) {
+    int found = -1;
+    int row = 0;
+    label4: while (row < grid.length) {
+        int column = 0;
+        while (column < grid[row].length) {
+            if (grid[row][column] == target) {
+                found = row;
+                break label4;
+            }
+            column++;
+        }
+        row++;
+    }
+    return found;
+}
+", decompile_fixture("Decompile.class", "search"));
+    assert_eq!("\
+static String describe(char grade) {
+    String result;
+    switch (grade) {
+        case 'a':
+        case 'b':
+            result = \"good\";
+            break;
+        case 'c':
+            result = \"fine\";
+        default:
+            result = \"bad\";
+    }
+    return result;
+}
+", decompile_fixture("Decompile.class", "describe"));
+    assert_eq!("\
+static int countdown(int from) {
+    int steps = 0;
+    do {
+        from /= 2;
+        steps++;
+    } while (from > 1);
+    return steps;
+}
+", decompile_fixture("Decompile.class", "countdown"));
+}
+
+#[test]
+fn test_recovers_conditions_and_exception_handlers() {
+    assert_eq!("\
+static boolean between(double value, double low, double high) {
+    return value >= low && value <= high || Double.isNaN(value);
+}
+", decompile_fixture("Decompile.class", "between"));
+    assert_eq!("\
+static int locked(Object lock, int[] counts) {
+    synchronized (lock) {
+        counts[0]++;
+    }
+    return counts[0];
+}
+", decompile_fixture("Decompile.class", "locked"));
+    assert_eq!("\
+static int parse(String var0) {
+    int var1i;
+    try {
+        var1i = Integer.parseInt(var0);
+    } catch (NumberFormatException var1) {
+        int var2 = -1;
+        Flow.counter++;
+        return var2;
+    } catch (Throwable var3) {
+        Flow.counter++;
+        throw var3;
+    }
+    Flow.counter++;
+    return var1i;
+}
+", decompile_fixture("Flow.class", "parse"));
+    assert_eq!("\
+static int nested(int x) {
+    int var1;
+    try {
+        try {
+            var1 = 10 / x;
+            x++;
+        } catch (Throwable var2) {
+            x++;
+            throw var2;
+        }
+        return var1;
+    } catch (ArithmeticException e) {
+        return x;
+    }
+}
+", decompile_fixture("Decompile.class", "nested"));
+}
+
+#[test]
+fn test_flags_unstructured_control_flow() {
+    let java_class = crate::assembler::assemble("
+        .class public super Irreducible
+        .version 52 0
+        .method static f (I)I
+            .limit stack 2; .limit locals 1
+            iload_0; ifeq Second
+        First:
+            iinc 0 1
+            iload_0; bipush 10; if_icmplt Second
+            iload_0; ireturn
+        Second:
+            iinc 0 2
+            goto First
+        .end method
+    ").unwrap();
+    let source = decompile_method(&java_class, &java_class.methods[0]).unwrap();
+    assert!(source.starts_with("static int f(int var0) {\n    // Unstructured: "), "{source}");
+    assert!(source.contains("goto block4;"), "{source}");
+}
+
+#[test]
+fn test_decompiles_class_with_signatures() {
+    let java_class = JavaClass::load_from_file("Decompile.class").unwrap();
+    let source = decompile_class(&java_class);
+    assert!(source.starts_with("\
+public class Decompile<T extends Comparable<T>> implements Comparable<Decompile<T>> {
+    private final java.util.List<T> items;
+    static final String NAME = \"decompile\";
+
+    public Decompile() {
+        this.items = new java.util.ArrayList();
+    }
+"), "{source}");
+    assert!(source.contains("    public void add(T item) throws IllegalStateException {\n"), "{source}");
+    assert!(source.contains("    static int sum(int... values) {\n"), "{source}");
+
+    assert_eq!("\
+public static void main(String[] var0) {
+    int var1 = 9;
+    int var2 = 11;
+    int var3 = var1 + var2;
+    System.out.println(\"Value is \" + var3);
+}
+", decompile_fixture("MyClass.class", "main"));
+}
@@ -0,0 +1,149 @@
+use std::fmt;
+
+/// A type as it appears in field and method descriptors, e.g. `I`, `Ljava/lang/String;` or `[[D`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// A class or interface, by its internal name like `java/lang/String`.
+    Object(String),
+    Array(Box<FieldType>),
+}
+
+impl FieldType {
+    pub fn parse(descriptor: &str) -> Option<FieldType> {
+        match FieldType::parse_prefix(descriptor)? {
+            (field_type, "") => Some(field_type),
+            _ => None,
+        }
+    }
+
+    /// Parses the type at the start of `descriptor`, returning it and what follows.
+    fn parse_prefix(descriptor: &str) -> Option<(FieldType, &str)> {
+        let mut characters = descriptor.chars();
+        let field_type = match characters.next()? {
+            'B' => FieldType::Byte,
+            'C' => FieldType::Char,
+            'D' => FieldType::Double,
+            'F' => FieldType::Float,
+            'I' => FieldType::Int,
+            'J' => FieldType::Long,
+            'S' => FieldType::Short,
+            'Z' => FieldType::Boolean,
+            'L' => {
+                let end = descriptor.find(';')?;
+                if end == 1 {
+                    return None;
+                }
+                return Some((FieldType::Object(descriptor[1..end].to_string()), &descriptor[end + 1..]));
+            }
+            '[' => {
+                let (element, rest) = FieldType::parse_prefix(&descriptor[1..])?;
+                return Some((FieldType::Array(Box::new(element)), rest));
+            }
+            _ => return None,
+        };
+        Some((field_type, characters.as_str()))
+    }
+
+    /// The type of a `CONSTANT_Class` name, which is an internal name for classes and a
+    /// descriptor for arrays.
+    pub fn from_class_name(class_name: &str) -> Option<FieldType> {
+        if class_name.starts_with('[') {
+            FieldType::parse(class_name)
+        } else {
+            Some(FieldType::Object(class_name.to_string()))
+        }
+    }
+
+    /// `long` and `double` take two local variable slots and two operand stack slots.
+    pub fn is_wide(&self) -> bool {
+        matches!(self, FieldType::Long | FieldType::Double)
+    }
+
+    pub fn slots(&self) -> usize {
+        if self.is_wide() { 2 } else { 1 }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, FieldType::Object(_) | FieldType::Array(_))
+    }
+
+    /// The name a `CONSTANT_Class` entry uses for this type, if it is a reference type.
+    pub fn class_name(&self) -> Option<String> {
+        match self {
+            FieldType::Object(class_name) => Some(class_name.clone()),
+            FieldType::Array(_) => Some(self.to_string()),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    /// Writes the type back as a descriptor.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(class_name) => write!(f, "L{class_name};"),
+            FieldType::Array(element) => write!(f, "[{element}"),
+        }
+    }
+}
+
+/// The parameter and return types of a method descriptor like `(ILjava/lang/String;)V`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct MethodDescriptor {
+    pub parameters: Vec<FieldType>,
+    /// `None` for `void`.
+    pub return_type: Option<FieldType>,
+}
+
+impl MethodDescriptor {
+    pub fn parse(descriptor: &str) -> Option<MethodDescriptor> {
+        let mut rest = descriptor.strip_prefix('(')?;
+        let mut parameters = Vec::new();
+        while !rest.starts_with(')') {
+            let (parameter, after) = FieldType::parse_prefix(rest)?;
+            parameters.push(parameter);
+            rest = after;
+        }
+        let return_type = match &rest[1..] {
+            "V" => None,
+            return_descriptor => Some(FieldType::parse(return_descriptor)?),
+        };
+        Some(MethodDescriptor { parameters, return_type })
+    }
+
+    /// The local variable slots the parameters take, not counting `this`.
+    pub fn parameter_slots(&self) -> usize {
+        self.parameters.iter().map(FieldType::slots).sum()
+    }
+}
+
+#[test]
+fn test_parses_descriptors() {
+    assert_eq!(Some(FieldType::Array(Box::new(FieldType::Object("java/lang/String".to_string())))), FieldType::parse("[Ljava/lang/String;"));
+    assert_eq!(None, FieldType::parse("L;"));
+    assert_eq!(None, FieldType::parse("II"));
+
+    let descriptor = MethodDescriptor::parse("(IJ[[DLjava/lang/Object;)V").unwrap();
+    assert_eq!(vec![FieldType::Int, FieldType::Long, FieldType::parse("[[D").unwrap(), FieldType::Object("java/lang/Object".to_string())], descriptor.parameters);
+    assert_eq!(None, descriptor.return_type);
+    assert_eq!(5, descriptor.parameter_slots());
+    assert_eq!("[[D", descriptor.parameters[2].to_string());
+    assert_eq!(None, MethodDescriptor::parse("(I"));
+    assert_eq!(None, MethodDescriptor::parse("()VV"));
+}
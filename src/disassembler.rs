@@ -0,0 +1,374 @@
+use std::fmt::Write;
+use crate::bytecode::Operands;
+use crate::instruction::{CpIndex, Instruction};
+use crate::{Attribute, AttributeCode, AttributeInfo, ConstantPoolInfo, JavaClass};
+
+/// Renders a `Code` attribute the way `javap -c` does, with constant pool references resolved in
+/// comments. Line numbers are interleaved as `.line` directives before the instructions they
+/// start at, and the exception table follows the code.
+pub fn disassemble(code: &AttributeCode, constant_pool_infos: &[ConstantPoolInfo]) -> String {
+    let mut output = String::new();
+    writeln!(output, "    Code:").unwrap();
+    writeln!(output, "      stack={}, locals={}", code.max_stack, code.max_locals).unwrap();
+
+    let instructions = match code.instructions() {
+        Ok(instructions) => instructions,
+        Err(error) => {
+            writeln!(output, "      <invalid code: {error}>").unwrap();
+            return output;
+        }
+    };
+    let line_numbers: Vec<(usize, usize)> = code.attributes.iter()
+        .filter_map(|attribute_info| match &attribute_info.attribute {
+            Attribute::LineNumberTable(line_number_table) => Some(&line_number_table.line_number_entries),
+            _ => None,
+        })
+        .flatten()
+        .map(|entry| (entry.start_pc, entry.line_number))
+        .collect();
+
+    for (pc, instruction) in &instructions {
+        for (_, line_number) in line_numbers.iter().filter(|(start_pc, _)| start_pc == pc) {
+            writeln!(output, "      .line {line_number}").unwrap();
+        }
+        writeln!(output, "{}", format_instruction(*pc, instruction, constant_pool_infos)).unwrap();
+    }
+
+    if !code.exceptions.is_empty() {
+        writeln!(output, "    Exception table:").unwrap();
+        writeln!(output, "       from    to  target type").unwrap();
+        for entry in &code.exceptions {
+            let catch_type = match entry.catch_pc {
+                0 => "any".to_string(),
+                catch_pc => capitalize(&describe_constant(constant_pool_infos, catch_pc)),
+            };
+            writeln!(output, "{:>12}{:>6}{:>6}   {catch_type}", entry.start_pc, entry.end_pc, entry.handler_pc).unwrap();
+        }
+    }
+    output
+}
+
+pub(crate) const CLASS_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "public"), (0x0010, "final"), (0x0020, "super"), (0x0200, "interface"), (0x0400, "abstract"),
+    (0x1000, "synthetic"), (0x2000, "annotation"), (0x4000, "enum"), (0x8000, "module"),
+];
+
+pub(crate) const FIELD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0008, "static"), (0x0010, "final"),
+    (0x0040, "volatile"), (0x0080, "transient"), (0x1000, "synthetic"), (0x4000, "enum"),
+];
+
+pub(crate) const METHOD_FLAGS: &[(u16, &str)] = &[
+    (0x0001, "public"), (0x0002, "private"), (0x0004, "protected"), (0x0008, "static"), (0x0010, "final"),
+    (0x0020, "synchronized"), (0x0040, "bridge"), (0x0080, "varargs"), (0x0100, "native"), (0x0400, "abstract"),
+    (0x0800, "strict"), (0x1000, "synthetic"),
+];
+
+/// Renders a whole class in the syntax [`assemble`](crate::assembler::assemble) reads, so that
+/// assembling the output gives back an identical class. The constant pool is listed entry by
+/// entry to keep its indexes, and the code is the same javap-style listing as [`disassemble`].
+pub fn disassemble_class(java_class: &JavaClass) -> String {
+    let constant_pool_infos = &java_class.constant_pool_infos;
+    let mut output = String::new();
+    writeln!(output, ".class {}{}", flag_words(java_class.flags.0, CLASS_FLAGS), quote_token(&java_class.this_class_name)).unwrap();
+    writeln!(output, ".version {} {}", java_class.major, java_class.minor).unwrap();
+    match &java_class.super_class_name {
+        Some(super_class_name) => writeln!(output, ".super {}", quote_token(super_class_name)).unwrap(),
+        None => writeln!(output, ".super #{}", java_class.super_class).unwrap(),
+    }
+    for interface_name in &java_class.interfaces_names {
+        writeln!(output, ".implements {}", quote_token(interface_name)).unwrap();
+    }
+
+    for (position, constant_pool_info) in constant_pool_infos.iter().enumerate() {
+        let index = position + 1;
+        let entry = match constant_pool_info {
+            ConstantPoolInfo::Utf8(utf8_info) => format!("Utf8 {}", escape(&utf8_info.string)),
+            ConstantPoolInfo::Integer(integer_info) => format!("Integer {}", integer_info.value),
+            ConstantPoolInfo::Float(float_info) => format!("Float 0x{:08x}", float_info.value.to_bits()),
+            ConstantPoolInfo::Long(long_info) => format!("Long {}", long_info.value),
+            ConstantPoolInfo::Double(double_info) => format!("Double 0x{:016x}", double_info.value.to_bits()),
+            ConstantPoolInfo::Class(class_info) => format!("Class #{}", class_info.name_index),
+            ConstantPoolInfo::String(string_info) => format!("String #{}", string_info.name_index),
+            ConstantPoolInfo::FieldRef(field_ref) => format!("Fieldref #{} #{}", field_ref.class_index, field_ref.name_and_type_index),
+            ConstantPoolInfo::MethodRef(method_ref) => format!("Methodref #{} #{}", method_ref.class_index, method_ref.name_and_type_index),
+            ConstantPoolInfo::InterfaceMethodRef(method_ref) => format!("InterfaceMethodref #{} #{}", method_ref.class_index, method_ref.name_and_type_index),
+            ConstantPoolInfo::NameAndType(name_and_type) => format!("NameAndType #{} #{}", name_and_type.name_index, name_and_type.descriptor_index),
+            ConstantPoolInfo::MethodKind(method_kind) => format!("MethodHandle {} #{}", method_kind.reference_kind, method_kind.reference_index),
+            ConstantPoolInfo::MethodType(method_type) => format!("MethodType #{}", method_type.descriptor_index),
+            ConstantPoolInfo::Dynamic(dynamic) => format!("Dynamic {} #{}", dynamic.bootstrap_method_attr_index, dynamic.name_and_type_index),
+            ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => format!("InvokeDynamic {} #{}", invoke_dynamic.bootstrap_method_attr_index, invoke_dynamic.name_and_type_index),
+            ConstantPoolInfo::Unusable => continue,
+        };
+        match constant_pool_info {
+            ConstantPoolInfo::Utf8(_) => writeln!(output, ".constant #{index} {entry}").unwrap(),
+            _ => writeln!(output, "{:<40}// {}", format!(".constant #{index} {entry}"), describe_constant(constant_pool_infos, index)).unwrap(),
+        }
+    }
+    for attribute_info in &java_class.attributes {
+        if let Attribute::BootstrapMethods(bootstrap_methods) = &attribute_info.attribute {
+            for bootstrap_method in &bootstrap_methods.bootstrap_methods {
+                let arguments: String = bootstrap_method.argument_indexes.iter().map(|index| format!(" #{index}")).collect();
+                let directive = format!(".bootstrap #{}{arguments}", bootstrap_method.method_handle_index);
+                writeln!(output, "{directive:<40}// {}", describe_constant(constant_pool_infos, bootstrap_method.method_handle_index)).unwrap();
+            }
+        } else {
+            write_attributes(&mut output, "", std::slice::from_ref(attribute_info));
+        }
+    }
+
+    for field in &java_class.fields {
+        writeln!(output, ".field {}{} {}", flag_words(field.access_flags, FIELD_FLAGS), quote_token(&field.name), quote_token(&field.descriptor)).unwrap();
+        for attribute_info in &field.attributes_info {
+            if let Attribute::ConstantValue(constant_value) = &attribute_info.attribute {
+                writeln!(output, "  .constantvalue #{:<20}// {}", constant_value.constant_value_index, describe_constant(constant_pool_infos, constant_value.constant_value_index)).unwrap();
+            } else {
+                write_attributes(&mut output, "  ", std::slice::from_ref(attribute_info));
+            }
+        }
+        writeln!(output, ".end field").unwrap();
+    }
+
+    for method in &java_class.methods {
+        writeln!(output, ".method {}{} {}", flag_words(method.access_flags, METHOD_FLAGS), quote_token(&method.name), quote_token(&method.descriptor)).unwrap();
+        for attribute_info in &method.attributes_info {
+            if let Attribute::Code(code) = &attribute_info.attribute {
+                output.push_str(&disassemble(code, constant_pool_infos));
+                write_attributes(&mut output, "      ", &code.attributes);
+                writeln!(output, "    .end code").unwrap();
+            } else {
+                write_attributes(&mut output, "  ", std::slice::from_ref(attribute_info));
+            }
+        }
+        writeln!(output, ".end method").unwrap();
+    }
+    output
+}
+
+fn write_attributes(output: &mut String, indent: &str, attributes: &[AttributeInfo]) {
+    for attribute_info in attributes {
+        if let Attribute::Unknown(unknown) = &attribute_info.attribute {
+            writeln!(output, "{indent}.attribute {} {}", quote_token(&unknown.attribute_name), hex(&unknown.info)).unwrap();
+        }
+    }
+}
+
+/// The words for the flags set in `flags`, each followed by a space. Bits without a word are
+/// written as a hex number.
+fn flag_words(flags: u16, words: &[(u16, &str)]) -> String {
+    let mut output = String::new();
+    let mut remaining = flags;
+    for (flag, word) in words {
+        if flags & flag != 0 {
+            output.push_str(word);
+            output.push(' ');
+            remaining &= !flag;
+        }
+    }
+    if remaining != 0 {
+        output.push_str(&format!("0x{remaining:04x} "));
+    }
+    output
+}
+
+fn hex(bytes: &[u8]) -> String {
+    if bytes.is_empty() {
+        return "\"\"".to_string();
+    }
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Quotes a name or descriptor when it would otherwise not read back as a single token.
+fn quote_token(token: &str) -> String {
+    if token.is_empty() || token.contains(|character: char| character.is_whitespace() || matches!(character, '"' | ',' | ';' | '#')) || token.contains("//") {
+        escape(token)
+    } else {
+        token.to_string()
+    }
+}
+
+/// A double-quoted string literal with Rust-style escapes.
+fn escape(string: &str) -> String {
+    let mut output = String::from("\"");
+    for character in string.chars() {
+        match character {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            character if character.is_control() => output.push_str(&format!("\\u{{{:x}}}", character as u32)),
+            character => output.push(character),
+        }
+    }
+    output.push('"');
+    output
+}
+
+/// One instruction as javap prints it, e.g. `       1: invokespecial #1   // Method ...`.
+/// Switches span several lines.
+pub fn format_instruction(pc: usize, instruction: &Instruction, constant_pool_infos: &[ConstantPoolInfo]) -> String {
+    let mnemonic = instruction.opcode().mnemonic();
+    let with_operands = |operands: String| format!("{mnemonic:<13} {operands}");
+    let with_constant = |operands: String, CpIndex(index): CpIndex| {
+        format!("{:<34}// {}", with_operands(operands), describe_constant(constant_pool_infos, index as usize))
+    };
+    let text = match instruction {
+        Instruction::Bipush(value) => with_operands(value.to_string()),
+        Instruction::Sipush(value) => with_operands(value.to_string()),
+        Instruction::Iinc { index, increment } => with_operands(format!("{index}, {increment}")),
+        Instruction::Newarray(array_type) => with_operands(array_type.name().to_string()),
+        Instruction::Invokeinterface { index, count } => with_constant(format!("#{},  {count}", index.0), *index),
+        Instruction::Invokedynamic(index) => with_constant(format!("#{},  0", index.0), *index),
+        Instruction::Multianewarray { index, dimensions } => with_constant(format!("#{},  {dimensions}", index.0), *index),
+        Instruction::Tableswitch { default, low, high, targets } => {
+            let mut text = format!("{mnemonic:<13} {{ // {low} to {high}\n");
+            for (key, target) in (*low..=*high).zip(targets) {
+                text.push_str(&format!("{key:>22}: {}\n", target.0));
+            }
+            text.push_str(&format!("{:>22}: {}\n          }}", "default", default.0));
+            text
+        }
+        Instruction::Lookupswitch { default, pairs } => {
+            let mut text = format!("{mnemonic:<13} {{ // {}\n", pairs.len());
+            for (key, target) in pairs {
+                text.push_str(&format!("{key:>22}: {}\n", target.0));
+            }
+            text.push_str(&format!("{:>22}: {}\n          }}", "default", default.0));
+            text
+        }
+        _ => match instruction.opcode().operands() {
+            Operands::ConstantPoolIndexByte | Operands::ConstantPoolIndex => {
+                let index = instruction.constant_pool_index().unwrap_or(CpIndex(0));
+                with_constant(format!("#{}", index.0), index)
+            }
+            Operands::LocalIndex => with_operands(instruction.local_index().unwrap_or_default().to_string()),
+            Operands::Branch | Operands::WideBranch => with_operands(instruction.branch_targets()[0].0.to_string()),
+            _ => mnemonic.to_string(),
+        },
+    };
+    format!("{pc:>8}: {}", text.trim_end())
+}
+
+/// Describes a constant the way javap comments do, e.g. `Method java/lang/Object."<init>":()V`.
+pub fn describe_constant(constant_pool_infos: &[ConstantPoolInfo], index: usize) -> String {
+    let member = |class_name: &str, name: &str, descriptor: &str| format!("{}.{}:{descriptor}", quote(class_name), quote(name));
+    match index.checked_sub(1).and_then(|index| constant_pool_infos.get(index)) {
+        Some(ConstantPoolInfo::Utf8(utf8_info)) => format!("Utf8 {}", utf8_info.string),
+        Some(ConstantPoolInfo::Integer(integer_info)) => format!("int {}", integer_info.value),
+        Some(ConstantPoolInfo::Float(float_info)) => format!("float {:?}f", float_info.value),
+        Some(ConstantPoolInfo::Long(long_info)) => format!("long {}l", long_info.value),
+        Some(ConstantPoolInfo::Double(double_info)) => format!("double {:?}d", double_info.value),
+        Some(ConstantPoolInfo::Class(class_info)) => format!("class {}", quote(&class_info.name)),
+        Some(ConstantPoolInfo::String(string_info)) => format!("String {}", string_info.string),
+        Some(ConstantPoolInfo::FieldRef(field_ref)) => format!("Field {}", member(&field_ref.class_name, &field_ref.name, &field_ref.descriptor)),
+        Some(ConstantPoolInfo::MethodRef(method_ref)) => format!("Method {}", member(&method_ref.class_name, &method_ref.name, &method_ref.descriptor)),
+        Some(ConstantPoolInfo::InterfaceMethodRef(method_ref)) => format!("InterfaceMethod {}", member(&method_ref.class_name, &method_ref.name, &method_ref.descriptor)),
+        Some(ConstantPoolInfo::NameAndType(name_and_type)) => format!("NameAndType {}:{}", quote(&name_and_type.name), name_and_type.descriptor),
+        Some(ConstantPoolInfo::MethodKind(method_kind)) => {
+            format!("MethodHandle {}:{}", reference_kind_name(method_kind.reference_kind), member(&method_kind.class_name, &method_kind.name, &method_kind.descriptor))
+        }
+        Some(ConstantPoolInfo::MethodType(method_type)) => format!("MethodType {}", method_type.descriptor),
+        Some(ConstantPoolInfo::Dynamic(dynamic)) => format!("Dynamic #{}:{}:{}", dynamic.bootstrap_method_attr_index, quote(&dynamic.name), dynamic.descriptor),
+        Some(ConstantPoolInfo::InvokeDynamic(invoke_dynamic)) => {
+            format!("InvokeDynamic #{}:{}:{}", invoke_dynamic.bootstrap_method_attr_index, quote(&invoke_dynamic.name), invoke_dynamic.descriptor)
+        }
+        Some(ConstantPoolInfo::Unusable) | None => format!("<invalid #{index}>"),
+    }
+}
+
+/// The `REF_` name of a method handle's reference kind.
+pub fn reference_kind_name(reference_kind: u8) -> &'static str {
+    match reference_kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_unknown",
+    }
+}
+
+/// javap quotes names like `<init>` and array class names.
+fn quote(name: &str) -> String {
+    if name.starts_with('<') || name.starts_with('[') {
+        format!("\"{name}\"")
+    } else {
+        name.to_string()
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut characters = text.chars();
+    match characters.next() {
+        Some(first) => first.to_uppercase().chain(characters).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+fn disassemble_method(class_file: &str, method_name: &str) -> String {
+    let java_class = crate::JavaClass::load_from_file(class_file).unwrap();
+    let method = java_class.methods.iter().find(|method| method.name == method_name).unwrap();
+    disassemble(method.code().unwrap(), &java_class.constant_pool_infos)
+}
+
+#[test]
+fn test_disassembles_like_javap() {
+    assert_eq!("    Code:
+      stack=2, locals=1
+      .line 1
+       0: aload_0
+       1: invokespecial #1                  // Method java/lang/Object.\"<init>\":()V
+      .line 2
+       4: aload_0
+       5: bipush        9
+       7: putfield      #7                  // Field MyClass.privateInt:I
+      .line 3
+      10: aload_0
+      11: iconst_2
+      12: putfield      #13                 // Field MyClass.publicInt:I
+      .line 4
+      15: aload_0
+      16: ldc           #16                 // String jjj
+      18: putfield      #18                 // Field MyClass.packageString:Ljava/lang/String;
+      21: return
+", disassemble_method("MyClass.class", "<init>"));
+
+    let main = disassemble_method("MyClass.class", "main");
+    assert!(main.contains("      14: invokedynamic #30,  0             // InvokeDynamic #0:makeConcatWithConstants:(I)Ljava/lang/String;\n"));
+    assert!(main.contains("      19: invokevirtual #34                 // Method java/io/PrintStream.println:(Ljava/lang/String;)V\n"));
+}
+
+#[test]
+fn test_disassembles_switches_and_exception_table() {
+    let classify = disassemble_method("Flow.class", "classify");
+    assert!(classify.contains("       1: tableswitch   { // 1 to 3
+                     1: 28
+                     2: 31
+                     3: 34
+               default: 37
+          }
+      .line 6
+      28: bipush        10
+"));
+    let sparse = disassemble_method("Flow.class", "sparse");
+    assert!(sparse.contains("       1: lookupswitch  { // 3
+                     1: 36
+                   100: 38
+                  1000: 40
+               default: 42
+          }
+"));
+    let parse = disassemble_method("Flow.class", "parse");
+    assert!(parse.ends_with("    Exception table:
+       from    to  target type
+           0     5    15   Class java/lang/NumberFormatException
+           0     5    28   any
+          15    18    28   any
+"));
+}
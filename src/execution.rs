@@ -1,23 +1,543 @@
-use crate::bytecode::Operation;
-use crate::frames::OperandStack;
-use crate::thread::Thread;
+use std::error::Error;
+use std::fmt;
+use crate::ConstantPoolInfo;
+use crate::class::{Field, Method, MethodRef, RuntimeConstantPool};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::frames::{is_assignable, mismatch, Frame, OperandStack, Value};
+use crate::heap::Reference;
+use crate::instruction::{BranchTarget, CpIndex, Instruction};
+use crate::jvm::Jvm;
+use crate::numeric::{self, Number, NumericError};
 
-pub struct Execution {
-    bytecode: Vec<u8>,
-    op_counter: usize,
-    thread: Thread
+/// Why a method couldn't run to completion, with the methods that were running when it happened,
+/// innermost first.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ExecutionError {
+    pub message: String,
+    /// Like `MyClass.main([Ljava/lang/String;)V pc 14`.
+    pub trace: Vec<String>,
 }
 
-impl Execution {
-    fn current_operand(self) -> Operation {
-        self.bytecode[self.op_counter]
+impl ExecutionError {
+    pub fn new(message: impl Into<String>) -> Self {
+        ExecutionError { message: message.into(), trace: Vec::new() }
     }
 
-    fn execute(self, operand_stack: OperandStack) {
-        match self.current_operand() {
-            AALOAD => {
-                self.thread.current_method()
+    /// Adds the method the error went through, and where in it.
+    pub(crate) fn at(mut self, method: &Method, pc: usize) -> Self {
+        self.trace.push(format!("{method} pc {pc}"));
+        self
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        for location in &self.trace {
+            write!(f, "\n    at {location}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ExecutionError {}
+
+/// Where execution goes after an instruction.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step {
+    Next,
+    /// Continue at the instruction at this pc.
+    Jump(usize),
+    /// Invoke a method with the arguments popped off the stack, `this` first.
+    Invoke { kind: InvokeKind, method_ref: MethodRef, arguments: Vec<Value> },
+    /// Run the call site of an `invokedynamic` with its `InvokeDynamic` constant and the arguments
+    /// popped off the stack.
+    InvokeDynamic { index: usize, arguments: Vec<Value> },
+    /// The method returned, with a value unless it is `void`.
+    Return(Option<Value>),
+}
+
+/// Which invoke instruction invokes a method, which decides how it is resolved and selected.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum InvokeKind {
+    Static,
+    Special,
+    Virtual,
+    Interface,
+}
+
+/// Executes the instruction of the frame's method at `pc`.
+pub(crate) fn execute(jvm: &mut Jvm, frame: &mut Frame, pc: usize, instruction: &Instruction) -> Result<Step, ExecutionError> {
+    let operand_stack = &mut frame.operand_stack;
+    match instruction {
+        Instruction::Nop => {}
+        Instruction::AconstNull => operand_stack.push(Value::Reference(None))?,
+        Instruction::IconstM1 => operand_stack.push(Value::Int(-1))?,
+        Instruction::Iconst0 => operand_stack.push(Value::Int(0))?,
+        Instruction::Iconst1 => operand_stack.push(Value::Int(1))?,
+        Instruction::Iconst2 => operand_stack.push(Value::Int(2))?,
+        Instruction::Iconst3 => operand_stack.push(Value::Int(3))?,
+        Instruction::Iconst4 => operand_stack.push(Value::Int(4))?,
+        Instruction::Iconst5 => operand_stack.push(Value::Int(5))?,
+        Instruction::Lconst0 => operand_stack.push(Value::Long(0))?,
+        Instruction::Lconst1 => operand_stack.push(Value::Long(1))?,
+        Instruction::Fconst0 => operand_stack.push(Value::Float(0.0))?,
+        Instruction::Fconst1 => operand_stack.push(Value::Float(1.0))?,
+        Instruction::Fconst2 => operand_stack.push(Value::Float(2.0))?,
+        Instruction::Dconst0 => operand_stack.push(Value::Double(0.0))?,
+        Instruction::Dconst1 => operand_stack.push(Value::Double(1.0))?,
+        Instruction::Bipush(value) => operand_stack.push(Value::Int(*value as i32))?,
+        Instruction::Sipush(value) => operand_stack.push(Value::Int(*value as i32))?,
+        Instruction::Ldc(CpIndex(index)) | Instruction::LdcW(CpIndex(index)) | Instruction::Ldc2W(CpIndex(index)) => {
+            let constant = constant(jvm, frame.constant_pool(), *index as usize)?;
+            frame.operand_stack.push(constant)?;
+        }
+        Instruction::Iload(_) | Instruction::Iload0 | Instruction::Iload1 | Instruction::Iload2 | Instruction::Iload3 => {
+            let value = frame.load_int(local_index(instruction))?;
+            frame.operand_stack.push(Value::Int(value))?;
+        }
+        Instruction::Lload(_) | Instruction::Lload0 | Instruction::Lload1 | Instruction::Lload2 | Instruction::Lload3 => {
+            let value = frame.load_long(local_index(instruction))?;
+            frame.operand_stack.push(Value::Long(value))?;
+        }
+        Instruction::Fload(_) | Instruction::Fload0 | Instruction::Fload1 | Instruction::Fload2 | Instruction::Fload3 => {
+            let value = frame.load_float(local_index(instruction))?;
+            frame.operand_stack.push(Value::Float(value))?;
+        }
+        Instruction::Dload(_) | Instruction::Dload0 | Instruction::Dload1 | Instruction::Dload2 | Instruction::Dload3 => {
+            let value = frame.load_double(local_index(instruction))?;
+            frame.operand_stack.push(Value::Double(value))?;
+        }
+        Instruction::Aload(_) | Instruction::Aload0 | Instruction::Aload1 | Instruction::Aload2 | Instruction::Aload3 => {
+            let value = frame.load_reference(local_index(instruction))?;
+            frame.operand_stack.push(Value::Reference(value))?;
+        }
+        Instruction::Istore(_) | Instruction::Istore0 | Instruction::Istore1 | Instruction::Istore2 | Instruction::Istore3 => {
+            let value = operand_stack.pop_int()?;
+            frame.store(local_index(instruction), Value::Int(value))?;
+        }
+        Instruction::Lstore(_) | Instruction::Lstore0 | Instruction::Lstore1 | Instruction::Lstore2 | Instruction::Lstore3 => {
+            let value = operand_stack.pop_long()?;
+            frame.store(local_index(instruction), Value::Long(value))?;
+        }
+        Instruction::Fstore(_) | Instruction::Fstore0 | Instruction::Fstore1 | Instruction::Fstore2 | Instruction::Fstore3 => {
+            let value = operand_stack.pop_float()?;
+            frame.store(local_index(instruction), Value::Float(value))?;
+        }
+        Instruction::Dstore(_) | Instruction::Dstore0 | Instruction::Dstore1 | Instruction::Dstore2 | Instruction::Dstore3 => {
+            let value = operand_stack.pop_double()?;
+            frame.store(local_index(instruction), Value::Double(value))?;
+        }
+        Instruction::Astore(_) | Instruction::Astore0 | Instruction::Astore1 | Instruction::Astore2 | Instruction::Astore3 => {
+            // astore also takes the return addresses jsr pushes.
+            let value = match operand_stack.pop()? {
+                value @ (Value::Reference(_) | Value::ReturnAddress(_)) => value,
+                value => return Err(mismatch("reference", value, "on the operand stack")),
+            };
+            frame.store(local_index(instruction), value)?;
+        }
+        // The stack manipulations work on slots, so what they do to a long or double is what they
+        // do to two ints.
+        Instruction::Pop => {
+            operand_stack.pop_slots(1)?;
+        }
+        Instruction::Pop2 => {
+            operand_stack.pop_slots(2)?;
+        }
+        Instruction::Dup => duplicate(operand_stack, 1, 0)?,
+        Instruction::DupX1 => duplicate(operand_stack, 1, 1)?,
+        Instruction::DupX2 => duplicate(operand_stack, 1, 2)?,
+        Instruction::Dup2 => duplicate(operand_stack, 2, 0)?,
+        Instruction::Dup2X1 => duplicate(operand_stack, 2, 1)?,
+        Instruction::Dup2X2 => duplicate(operand_stack, 2, 2)?,
+        Instruction::Swap => {
+            let first = operand_stack.pop_slots(1)?;
+            let second = operand_stack.pop_slots(1)?;
+            operand_stack.push_all(&first)?;
+            operand_stack.push_all(&second)?;
+        }
+        Instruction::Iadd | Instruction::Isub | Instruction::Imul | Instruction::Idiv | Instruction::Irem
+        | Instruction::Ishl | Instruction::Ishr | Instruction::Iushr | Instruction::Iand | Instruction::Ior | Instruction::Ixor => {
+            let value2 = operand_stack.pop_int()?;
+            let value1 = operand_stack.pop_int()?;
+            operand_stack.push(compute(instruction, &[Value::Int(value1), Value::Int(value2)])?)?;
+        }
+        Instruction::Ladd | Instruction::Lsub | Instruction::Lmul | Instruction::Ldiv | Instruction::Lrem
+        | Instruction::Land | Instruction::Lor | Instruction::Lxor | Instruction::Lcmp => {
+            let value2 = operand_stack.pop_long()?;
+            let value1 = operand_stack.pop_long()?;
+            operand_stack.push(compute(instruction, &[Value::Long(value1), Value::Long(value2)])?)?;
+        }
+        Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => {
+            let value2 = operand_stack.pop_int()?;
+            let value1 = operand_stack.pop_long()?;
+            operand_stack.push(compute(instruction, &[Value::Long(value1), Value::Int(value2)])?)?;
+        }
+        Instruction::Fadd | Instruction::Fsub | Instruction::Fmul | Instruction::Fdiv | Instruction::Frem
+        | Instruction::Fcmpl | Instruction::Fcmpg => {
+            let value2 = operand_stack.pop_float()?;
+            let value1 = operand_stack.pop_float()?;
+            operand_stack.push(compute(instruction, &[Value::Float(value1), Value::Float(value2)])?)?;
+        }
+        Instruction::Dadd | Instruction::Dsub | Instruction::Dmul | Instruction::Ddiv | Instruction::Drem
+        | Instruction::Dcmpl | Instruction::Dcmpg => {
+            let value2 = operand_stack.pop_double()?;
+            let value1 = operand_stack.pop_double()?;
+            operand_stack.push(compute(instruction, &[Value::Double(value1), Value::Double(value2)])?)?;
+        }
+        Instruction::Ineg | Instruction::I2l | Instruction::I2f | Instruction::I2d
+        | Instruction::I2b | Instruction::I2c | Instruction::I2s => {
+            let value = operand_stack.pop_int()?;
+            operand_stack.push(compute(instruction, &[Value::Int(value)])?)?;
+        }
+        Instruction::Lneg | Instruction::L2i | Instruction::L2f | Instruction::L2d => {
+            let value = operand_stack.pop_long()?;
+            operand_stack.push(compute(instruction, &[Value::Long(value)])?)?;
+        }
+        Instruction::Fneg | Instruction::F2i | Instruction::F2l | Instruction::F2d => {
+            let value = operand_stack.pop_float()?;
+            operand_stack.push(compute(instruction, &[Value::Float(value)])?)?;
+        }
+        Instruction::Dneg | Instruction::D2i | Instruction::D2l | Instruction::D2f => {
+            let value = operand_stack.pop_double()?;
+            operand_stack.push(compute(instruction, &[Value::Double(value)])?)?;
+        }
+        Instruction::Iinc { index, increment } => {
+            let value = frame.load_int(*index as usize)?;
+            frame.store(*index as usize, Value::Int(value.wrapping_add(*increment as i32)))?;
+        }
+        Instruction::Ifeq(target) | Instruction::Ifne(target) | Instruction::Iflt(target)
+        | Instruction::Ifge(target) | Instruction::Ifgt(target) | Instruction::Ifle(target) => {
+            let value = operand_stack.pop_int()?;
+            return Ok(branch(compare(instruction, value, 0), *target));
+        }
+        Instruction::IfIcmpeq(target) | Instruction::IfIcmpne(target) | Instruction::IfIcmplt(target)
+        | Instruction::IfIcmpge(target) | Instruction::IfIcmpgt(target) | Instruction::IfIcmple(target) => {
+            let value2 = operand_stack.pop_int()?;
+            let value1 = operand_stack.pop_int()?;
+            return Ok(branch(compare(instruction, value1, value2), *target));
+        }
+        Instruction::IfAcmpeq(target) | Instruction::IfAcmpne(target) => {
+            let value2 = operand_stack.pop_reference()?;
+            let value1 = operand_stack.pop_reference()?;
+            return Ok(branch((value1 == value2) == matches!(instruction, Instruction::IfAcmpeq(_)), *target));
+        }
+        Instruction::Ifnull(target) | Instruction::Ifnonnull(target) => {
+            let value = operand_stack.pop_reference()?;
+            return Ok(branch(value.is_none() == matches!(instruction, Instruction::Ifnull(_)), *target));
+        }
+        Instruction::Goto(BranchTarget(target)) | Instruction::GotoW(BranchTarget(target)) => return Ok(Step::Jump(*target)),
+        Instruction::Tableswitch { default, low, high, targets } => {
+            let index = operand_stack.pop_int()?;
+            let target = match index {
+                index if (*low..=*high).contains(&index) => targets.get((index as i64 - *low as i64) as usize).unwrap_or(default),
+                _ => default,
+            };
+            return Ok(Step::Jump(target.0));
+        }
+        Instruction::Lookupswitch { default, pairs } => {
+            let key = operand_stack.pop_int()?;
+            let target = pairs.iter().find(|(value, _)| *value == key).map_or(default, |(_, target)| target);
+            return Ok(Step::Jump(target.0));
+        }
+        // jsr and ret only appear in class files older than version 51.
+        Instruction::Jsr(BranchTarget(target)) | Instruction::JsrW(BranchTarget(target)) => {
+            operand_stack.push(Value::ReturnAddress(pc + instruction.length(pc)))?;
+            return Ok(Step::Jump(*target));
+        }
+        Instruction::Ret(index) => return Ok(Step::Jump(frame.load_return_address(*index as usize)?)),
+        Instruction::Getstatic(CpIndex(index)) | Instruction::Putstatic(CpIndex(index)) => {
+            let field_ref = frame.constant_pool().field_ref(*index as usize)?;
+            let (class, field) = jvm.method_area.resolve_field(&field_ref)?;
+            if !field.is_static() {
+                return Err(ExecutionError::new(format!("IncompatibleClassChangeError: {field} is not static")));
+            }
+            if let Instruction::Getstatic(_) = instruction {
+                let value = class.static_values.borrow()[field.index];
+                frame.operand_stack.push(value)?;
+            } else {
+                let value = frame.operand_stack.pop()?;
+                match FieldType::parse(&field.descriptor) {
+                    Some(field_type) if is_assignable(&value, &field_type) => class.static_values.borrow_mut()[field.index] = value,
+                    _ => return Err(ExecutionError::new(format!("{value:?} can't be stored in {field}"))),
+                }
             }
         }
+        Instruction::Getfield(CpIndex(index)) | Instruction::Putfield(CpIndex(index)) => {
+            let field_ref = frame.constant_pool().field_ref(*index as usize)?;
+            let (_, field) = jvm.method_area.resolve_field(&field_ref)?;
+            if field.is_static() {
+                return Err(ExecutionError::new(format!("IncompatibleClassChangeError: {field} is static")));
+            }
+            if let Instruction::Getfield(_) = instruction {
+                let object = object_with_field(jvm, frame.operand_stack.pop_reference()?, &field, "read")?;
+                let value = jvm.heap.get_field(object, &field)?;
+                frame.operand_stack.push(value)?;
+            } else {
+                let value = frame.operand_stack.pop()?;
+                let object = object_with_field(jvm, frame.operand_stack.pop_reference()?, &field, "written")?;
+                jvm.heap.put_field(object, &field, value)?;
+            }
+        }
+        Instruction::New(CpIndex(index)) => {
+            let object = jvm.allocate(frame.constant_pool().class_name(*index as usize)?)?;
+            frame.operand_stack.push(object)?;
+        }
+        Instruction::Invokestatic(CpIndex(index)) | Instruction::Invokespecial(CpIndex(index))
+        | Instruction::Invokevirtual(CpIndex(index)) | Instruction::Invokeinterface { index: CpIndex(index), .. } => {
+            let kind = match instruction {
+                Instruction::Invokestatic(_) => InvokeKind::Static,
+                Instruction::Invokespecial(_) => InvokeKind::Special,
+                Instruction::Invokevirtual(_) => InvokeKind::Virtual,
+                _ => InvokeKind::Interface,
+            };
+            let method_ref = frame.constant_pool().method_ref(*index as usize)?;
+            let Some(descriptor) = MethodDescriptor::parse(&method_ref.descriptor) else {
+                return Err(ExecutionError::new(format!("{} is not a method descriptor", method_ref.descriptor)));
+            };
+            let count = descriptor.parameters.len() + usize::from(kind != InvokeKind::Static);
+            let mut arguments = (0..count).map(|_| frame.operand_stack.pop()).collect::<Result<Vec<_>, _>>()?;
+            arguments.reverse();
+            return Ok(Step::Invoke { kind, method_ref, arguments });
+        }
+        Instruction::Invokedynamic(CpIndex(index)) => {
+            let Some(ConstantPoolInfo::InvokeDynamic(invoke_dynamic)) = frame.constant_pool().get(*index as usize) else {
+                return Err(ExecutionError::new(format!("#{index} is not an invokedynamic call site")));
+            };
+            let Some(descriptor) = MethodDescriptor::parse(&invoke_dynamic.descriptor) else {
+                return Err(ExecutionError::new(format!("{} is not a method descriptor", invoke_dynamic.descriptor)));
+            };
+            let mut arguments = (0..descriptor.parameters.len()).map(|_| frame.operand_stack.pop()).collect::<Result<Vec<_>, _>>()?;
+            arguments.reverse();
+            return Ok(Step::InvokeDynamic { index: *index as usize, arguments });
+        }
+        Instruction::Ireturn => return Ok(Step::Return(Some(Value::Int(operand_stack.pop_int()?)))),
+        Instruction::Lreturn => return Ok(Step::Return(Some(Value::Long(operand_stack.pop_long()?)))),
+        Instruction::Freturn => return Ok(Step::Return(Some(Value::Float(operand_stack.pop_float()?)))),
+        Instruction::Dreturn => return Ok(Step::Return(Some(Value::Double(operand_stack.pop_double()?)))),
+        Instruction::Areturn => return Ok(Step::Return(Some(Value::Reference(operand_stack.pop_reference()?)))),
+        Instruction::Return => return Ok(Step::Return(None)),
+        _ => return Err(ExecutionError::new(format!("{} is not supported", instruction.opcode().mnemonic()))),
+    }
+    Ok(Step::Next)
+}
+
+/// The result of an arithmetic, comparison or conversion instruction.
+fn compute(instruction: &Instruction, operands: &[Value]) -> Result<Value, ExecutionError> {
+    let numbers = operands.iter().map(|operand| match *operand {
+        Value::Int(value) => Ok(Number::Int(value)),
+        Value::Long(value) => Ok(Number::Long(value)),
+        Value::Float(value) => Ok(Number::Float(value)),
+        Value::Double(value) => Ok(Number::Double(value)),
+        Value::Reference(_) | Value::ReturnAddress(_) => Err(ExecutionError::new(format!("{} takes numbers, not {operand:?}", instruction.opcode().mnemonic()))),
+    }).collect::<Result<Vec<_>, _>>()?;
+    match numeric::compute(instruction, &numbers) {
+        Ok(Number::Int(value)) => Ok(Value::Int(value)),
+        Ok(Number::Long(value)) => Ok(Value::Long(value)),
+        Ok(Number::Float(value)) => Ok(Value::Float(value)),
+        Ok(Number::Double(value)) => Ok(Value::Double(value)),
+        Err(NumericError::DivisionByZero) => Err(ExecutionError::new("ArithmeticException: / by zero")),
+        Err(NumericError::Unsupported) => Err(ExecutionError::new(format!("{} can't be computed on {operands:?}", instruction.opcode().mnemonic()))),
+    }
+}
+
+/// How an `if<cond>` or `if_icmp<cond>` instruction compares its operands.
+fn compare(instruction: &Instruction, value1: i32, value2: i32) -> bool {
+    match instruction {
+        Instruction::Ifeq(_) | Instruction::IfIcmpeq(_) => value1 == value2,
+        Instruction::Ifne(_) | Instruction::IfIcmpne(_) => value1 != value2,
+        Instruction::Iflt(_) | Instruction::IfIcmplt(_) => value1 < value2,
+        Instruction::Ifge(_) | Instruction::IfIcmpge(_) => value1 >= value2,
+        Instruction::Ifgt(_) | Instruction::IfIcmpgt(_) => value1 > value2,
+        _ => value1 <= value2,
     }
 }
+
+fn branch(taken: bool, BranchTarget(target): BranchTarget) -> Step {
+    if taken { Step::Jump(target) } else { Step::Next }
+}
+
+/// The value of a loadable constant: a number, or the interned `java/lang/String` of a string.
+pub(crate) fn constant(jvm: &mut Jvm, constant_pool: &RuntimeConstantPool, index: usize) -> Result<Value, ExecutionError> {
+    match constant_pool.get(index) {
+        Some(ConstantPoolInfo::Integer(integer)) => Ok(Value::Int(integer.value)),
+        Some(ConstantPoolInfo::Float(float)) => Ok(Value::Float(float.value)),
+        Some(ConstantPoolInfo::Long(long)) => Ok(Value::Long(long.value)),
+        Some(ConstantPoolInfo::Double(double)) => Ok(Value::Double(double.value)),
+        Some(ConstantPoolInfo::String(string)) => jvm.intern(&string.string),
+        _ => Err(ExecutionError::new(format!("Loading constant #{index} is not supported"))),
+    }
+}
+
+/// The object `getfield` or `putfield` accesses a field of, which has to be of the field's class
+/// or a subclass.
+fn object_with_field(jvm: &Jvm, reference: Option<Reference>, field: &Field, access: &str) -> Result<Reference, ExecutionError> {
+    let Some(reference) = reference else {
+        return Err(ExecutionError::new(format!("NullPointerException: {field} {access} on null")));
+    };
+    let class = jvm.heap.class(reference)?;
+    if class.name != field.class_name && jvm.method_area.superclasses(class)?.iter().all(|super_class| super_class.name != field.class_name) {
+        return Err(ExecutionError::new(format!("{} objects have no field {field}", class.name)));
+    }
+    Ok(reference)
+}
+
+fn local_index(instruction: &Instruction) -> usize {
+    instruction.local_index().unwrap_or_default() as usize
+}
+
+/// Copies the top `slots` slots of the stack below the `skipped` slots under them.
+fn duplicate(operand_stack: &mut OperandStack, slots: usize, skipped: usize) -> Result<(), ExecutionError> {
+    let copied = operand_stack.pop_slots(slots)?;
+    let under = operand_stack.pop_slots(skipped)?;
+    operand_stack.push_all(&copied)?;
+    operand_stack.push_all(&under)?;
+    operand_stack.push_all(&copied)
+}
+
+/// Runs an instruction on two operands. Unary instructions only take the second one, leaving the
+/// first one on the stack.
+#[cfg(test)]
+fn run_operation(mnemonic: &str, operands: &[Value; 2]) -> Result<Value, String> {
+    let type_of = |value: &Value| match value {
+        Value::Int(_) => ("I", "i"),
+        Value::Long(_) => ("J", "l"),
+        Value::Float(_) => ("F", "f"),
+        _ => ("D", "d"),
+    };
+    let (type1, prefix1) = type_of(&operands[0]);
+    let (type2, prefix2) = type_of(&operands[1]);
+    let slot2 = operands[0].slots();
+    let result_prefix = match mnemonic.split_once('2') {
+        Some((_, "b" | "c" | "s")) => "i",
+        Some((_, to)) => to,
+        None if mnemonic.contains("cmp") => "i",
+        None => &mnemonic[..1],
+    };
+    let return_type = match result_prefix { "i" => "I", "l" => "J", "f" => "F", _ => "D" };
+    let descriptor = format!("({type1}{type2}){return_type}");
+    let mut jvm = crate::jvm::Jvm::new();
+    jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super Case
+        .method public static run {descriptor}
+            .limit stack 4; .limit locals 4
+            {prefix1}load 0; {prefix2}load {slot2}; {mnemonic}; {result_prefix}return
+        .end method
+    ")).unwrap()).unwrap();
+    let result = jvm.invoke_static("Case", "run", &descriptor, operands);
+    result.map(Option::unwrap).map_err(|error| error.message)
+}
+
+#[test]
+fn test_integer_arithmetic_matches_java() {
+    use Value::{Int, Long};
+    let division_by_zero = Err("ArithmeticException: / by zero");
+    let cases = [
+        ("iadd", [Int(i32::MAX), Int(1)], Ok(Int(i32::MIN))),
+        ("isub", [Int(i32::MIN), Int(1)], Ok(Int(i32::MAX))),
+        ("imul", [Int(123456789), Int(1000)], Ok(Int(-1097262584))),
+        ("imul", [Int(65536), Int(65536)], Ok(Int(0))),
+        ("idiv", [Int(-7), Int(2)], Ok(Int(-3))),
+        ("idiv", [Int(i32::MIN), Int(-1)], Ok(Int(i32::MIN))),
+        ("idiv", [Int(1), Int(0)], division_by_zero),
+        ("irem", [Int(-7), Int(2)], Ok(Int(-1))),
+        ("irem", [Int(7), Int(-2)], Ok(Int(1))),
+        ("irem", [Int(i32::MIN), Int(-1)], Ok(Int(0))),
+        ("irem", [Int(1), Int(0)], division_by_zero),
+        ("ishl", [Int(1), Int(33)], Ok(Int(2))),
+        ("ishl", [Int(1), Int(-1)], Ok(Int(i32::MIN))),
+        ("ishr", [Int(-16), Int(2)], Ok(Int(-4))),
+        ("iushr", [Int(-16), Int(28)], Ok(Int(15))),
+        ("iand", [Int(12), Int(10)], Ok(Int(8))),
+        ("ior", [Int(12), Int(10)], Ok(Int(14))),
+        ("ixor", [Int(12), Int(10)], Ok(Int(6))),
+        ("ladd", [Long(i64::MAX), Long(1)], Ok(Long(i64::MIN))),
+        ("lsub", [Long(0), Long(i64::MIN)], Ok(Long(i64::MIN))),
+        ("lmul", [Long(i64::MAX), Long(2)], Ok(Long(-2))),
+        ("ldiv", [Long(i64::MIN), Long(-1)], Ok(Long(i64::MIN))),
+        ("ldiv", [Long(1), Long(0)], division_by_zero),
+        ("lrem", [Long(-7), Long(3)], Ok(Long(-1))),
+        ("lrem", [Long(1), Long(0)], division_by_zero),
+        ("lshl", [Long(1), Int(65)], Ok(Long(2))),
+        ("lshr", [Long(-1), Int(63)], Ok(Long(-1))),
+        ("lushr", [Long(-1), Int(63)], Ok(Long(1))),
+        ("lushr", [Long(-1), Int(-1)], Ok(Long(1))),
+        ("land", [Long(12), Long(10)], Ok(Long(8))),
+        ("lor", [Long(12), Long(10)], Ok(Long(14))),
+        ("lxor", [Long(12), Long(10)], Ok(Long(6))),
+        ("ineg", [Int(0), Int(i32::MIN)], Ok(Int(i32::MIN))),
+        ("lneg", [Long(0), Long(i64::MIN)], Ok(Long(i64::MIN))),
+    ];
+    for (mnemonic, operands, expected) in cases {
+        assert_eq!(expected.map_err(str::to_string), run_operation(mnemonic, &operands), "{mnemonic} {operands:?}");
+    }
+}
+
+#[test]
+fn test_floating_point_arithmetic_comparisons_and_conversions_match_java() {
+    use Value::{Double, Float, Int, Long};
+    let cases = [
+        ("fadd", [Float(0.1), Float(0.2)], Float(0.3)),
+        ("fsub", [Float(1.0), Float(f32::INFINITY)], Float(f32::NEG_INFINITY)),
+        ("fmul", [Float(-0.0), Float(5.0)], Float(-0.0)),
+        ("fdiv", [Float(1.0), Float(0.0)], Float(f32::INFINITY)),
+        ("fdiv", [Float(0.0), Float(0.0)], Float(f32::NAN)),
+        ("frem", [Float(-5.5), Float(2.0)], Float(-1.5)),
+        ("frem", [Float(5.5), Float(-2.0)], Float(1.5)),
+        ("frem", [Float(1.0), Float(0.0)], Float(f32::NAN)),
+        ("fneg", [Int(0), Float(0.0)], Float(-0.0)),
+        ("dadd", [Double(0.1), Double(0.2)], Double(0.30000000000000004)),
+        ("dsub", [Double(0.0), Double(0.0)], Double(0.0)),
+        ("dmul", [Double(1e308), Double(10.0)], Double(f64::INFINITY)),
+        ("ddiv", [Double(-1.0), Double(0.0)], Double(f64::NEG_INFINITY)),
+        ("drem", [Double(-7.5), Double(2.0)], Double(-1.5)),
+        ("drem", [Double(3.0), Double(f64::INFINITY)], Double(3.0)),
+        ("drem", [Double(f64::INFINITY), Double(3.0)], Double(f64::NAN)),
+        ("dneg", [Int(0), Double(f64::NAN)], Double(f64::NAN)),
+        ("lcmp", [Long(-1), Long(1)], Int(-1)),
+        ("lcmp", [Long(i64::MAX), Long(i64::MAX)], Int(0)),
+        ("fcmpl", [Float(2.0), Float(1.0)], Int(1)),
+        ("fcmpl", [Float(f32::NAN), Float(1.0)], Int(-1)),
+        ("fcmpg", [Float(f32::NAN), Float(1.0)], Int(1)),
+        ("fcmpg", [Float(-0.0), Float(0.0)], Int(0)),
+        ("dcmpl", [Double(1.0), Double(f64::NAN)], Int(-1)),
+        ("dcmpg", [Double(1.0), Double(f64::NAN)], Int(1)),
+        ("dcmpg", [Double(1.0), Double(2.0)], Int(-1)),
+        ("i2l", [Int(0), Int(-1)], Long(-1)),
+        ("i2f", [Int(0), Int(16777217)], Float(16777216.0)),
+        ("i2d", [Int(0), Int(i32::MIN)], Double(-2147483648.0)),
+        ("l2i", [Int(0), Long(0x1_8000_0000)], Int(i32::MIN)),
+        ("l2f", [Int(0), Long(i64::MAX)], Float(9.223372e18)),
+        ("l2d", [Int(0), Long(-3)], Double(-3.0)),
+        ("f2i", [Int(0), Float(-2.9)], Int(-2)),
+        ("f2i", [Int(0), Float(1e10)], Int(i32::MAX)),
+        ("f2i", [Int(0), Float(f32::NAN)], Int(0)),
+        ("f2l", [Int(0), Float(f32::NEG_INFINITY)], Long(i64::MIN)),
+        ("f2d", [Int(0), Float(0.1)], Double(0.10000000149011612)),
+        ("d2i", [Int(0), Double(-1e100)], Int(i32::MIN)),
+        ("d2l", [Int(0), Double(f64::NAN)], Long(0)),
+        ("d2l", [Int(0), Double(1e19)], Long(i64::MAX)),
+        ("d2f", [Int(0), Double(1e39)], Float(f32::INFINITY)),
+        ("i2b", [Int(0), Int(200)], Int(-56)),
+        ("i2c", [Int(0), Int(-1)], Int(65535)),
+        ("i2s", [Int(0), Int(40000)], Int(-25536)),
+    ];
+    for (mnemonic, operands, expected) in cases {
+        // Debug output tells NaNs and zeros of either sign apart, unlike ==.
+        let result = run_operation(mnemonic, &operands).map(|value| format!("{value:?}"));
+        assert_eq!(Ok(format!("{expected:?}")), result, "{mnemonic} {operands:?}");
+    }
+}
+
+#[test]
+fn test_iinc_wraps_and_reaches_wide_locals() {
+    let mut jvm = crate::jvm::Jvm::new();
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Increments
+        .method public static run (I)I
+            .limit stack 1; .limit locals 301
+            iinc 0 -1; iload_0; istore 300; iinc 300 1000; iload 300; ireturn
+        .end method
+    ").unwrap()).unwrap();
+    assert_eq!(Some(Value::Int(i32::MAX.wrapping_add(1000))), jvm.invoke_static("Increments", "run", "(I)I", &[Value::Int(i32::MIN)]).unwrap());
+}
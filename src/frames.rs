@@ -1,92 +1,281 @@
-use crate::bytecode::*;
-use crate::MethodInfo;
-
-enum LocalVariable {
-    Boolean(bool),
-    Byte(i8),
-    Char(u32),
-    Short(i16),
+use std::rc::Rc;
+use crate::class::{Class, Code, Method, RuntimeConstantPool};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::execution::ExecutionError;
+use crate::heap::Reference;
+
+/// A value of one of the JVM's computational types, as held by local variables and the operand
+/// stack. `boolean`, `byte`, `char` and `short` values are ints.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Value {
     Int(i32),
+    Long(i64),
     Float(f32),
-    Reference(usize),
+    Double(f64),
+    /// `None` is `null`.
+    Reference(Option<Reference>),
+    /// The pc a `jsr` returns to.
     ReturnAddress(usize),
-    // use pair to hold long or double
 }
 
-struct Operand {}
+impl Value {
+    /// `long` and `double` values take two local variables and two operand stack slots.
+    pub fn is_category2(&self) -> bool {
+        matches!(self, Value::Long(_) | Value::Double(_))
+    }
+
+    pub fn slots(&self) -> usize {
+        if self.is_category2() { 2 } else { 1 }
+    }
+
+    /// The name of the value's type the way the JVMS spells it.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Long(_) => "long",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::Reference(_) => "reference",
+            Value::ReturnAddress(_) => "returnAddress",
+        }
+    }
+}
+
+/// Generates the accessors that take a value of one type out of a `Value`, and fail naming where
+/// the value was when it has another type.
+macro_rules! typed_accessors {
+    ($($pop:ident, $load:ident, $type:ty, $variant:ident, $name:literal;)*) => {
+        impl OperandStack {
+            $(
+                pub fn $pop(&mut self) -> Result<$type, ExecutionError> {
+                    match self.pop()? {
+                        Value::$variant(value) => Ok(value),
+                        value => Err(mismatch($name, value, "on the operand stack")),
+                    }
+                }
+            )*
+        }
+
+        impl Frame {
+            $(
+                pub fn $load(&self, index: usize) -> Result<$type, ExecutionError> {
+                    match self.load(index)? {
+                        Value::$variant(value) => Ok(value),
+                        value => Err(mismatch($name, value, format!("in local variable {index}"))),
+                    }
+                }
+            )*
+        }
+    };
+}
+
+typed_accessors! {
+    pop_int, load_int, i32, Int, "int";
+    pop_long, load_long, i64, Long, "long";
+    pop_float, load_float, f32, Float, "float";
+    pop_double, load_double, f64, Double, "double";
+    pop_reference, load_reference, Option<Reference>, Reference, "reference";
+    pop_return_address, load_return_address, usize, ReturnAddress, "returnAddress";
+}
+
+/// The error for a value of the wrong type, where `place` tells where it was.
+pub(crate) fn mismatch(expected: &str, found: Value, place: impl std::fmt::Display) -> ExecutionError {
+    ExecutionError::new(format!("Expected {} {expected} {place} but found {found:?}", article(expected)))
+}
+
+fn article(type_name: &str) -> &'static str {
+    if type_name.starts_with('i') { "an" } else { "a" }
+}
 
+/// The operand stack of a frame. Its depth counts `long` and `double` values twice, and can't
+/// go past the `max_stack` of the method's code.
+#[derive(Debug)]
 pub struct OperandStack {
-    // long and double contribute two units to the depth. the rest only one
-    values: Vec<LocalVariable>,
+    values: Vec<Value>,
+    depth: usize,
+    max_depth: usize,
 }
 
 impl OperandStack {
-    pub(crate) fn pop(&self) -> Operand {
-        todo!()
+    fn new(code: &Code) -> Self {
+        OperandStack { values: Vec::with_capacity(code.max_stack), depth: 0, max_depth: code.max_stack }
     }
-}
 
-impl OperandStack {
-    fn new(method: &Method) -> Self {
-        // the length of the operands stack is determined by the bytecode
-        todo!()
+    pub fn push(&mut self, value: Value) -> Result<(), ExecutionError> {
+        if self.depth + value.slots() > self.max_depth {
+            return Err(ExecutionError::new(format!("Pushing {} {} would overflow the operand stack of depth {}", article(value.type_name()), value.type_name(), self.max_depth)));
+        }
+        self.depth += value.slots();
+        self.values.push(value);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Result<Value, ExecutionError> {
+        let value = self.values.pop().ok_or_else(|| ExecutionError::new("The operand stack is empty"))?;
+        self.depth -= value.slots();
+        Ok(value)
     }
 
-    fn push(self, variable: LocalVariable) {
-        // loads value or constant into the stack. it can also push
-        // here we also prepare parameters for called methods and receive method results
+    /// Pops the values taking the top `slots` slots, bottom first, the way the `pop` and `dup`
+    /// forms see the stack. Fails rather than split a `long` or `double`.
+    pub fn pop_slots(&mut self, slots: usize) -> Result<Vec<Value>, ExecutionError> {
+        let mut values = Vec::new();
+        let mut popped = 0;
+        while popped < slots {
+            let value = self.pop()?;
+            popped += value.slots();
+            values.insert(0, value);
+        }
+        if popped != slots {
+            return Err(ExecutionError::new(format!("The top {slots} operand stack slots split {} {}", article(values[0].type_name()), values[0].type_name())));
+        }
+        Ok(values)
     }
 
-    fn pop_int(self) -> LocalVariable::Int {
-        unimplemented!()
+    pub fn push_all(&mut self, values: &[Value]) -> Result<(), ExecutionError> {
+        values.iter().try_for_each(|value| self.push(*value))
     }
 }
 
-type MethodCallParameters = ();
-type Method = MethodInfo;
-
+/// The activation of a method: its local variables, operand stack and the next instruction to run.
+#[derive(Debug)]
 pub struct Frame {
-    variables: Vec<LocalVariable>,
-    operand_stack: OperandStack,
-    run_time_constant_pool: (),
-    // run-time constant pool of the class of the current method
-    pub method: Method,
+    /// A `long` or `double` is held by the first of its two variables, the second one being `None`.
+    variables: Vec<Option<Value>>,
+    pub operand_stack: OperandStack,
+    /// The class declaring the method, whose run-time constant pool the instructions refer to.
+    pub class: Rc<Class>,
+    pub method: Rc<Method>,
+    /// The position in the method's instructions of the next one to execute.
+    pub position: usize,
 }
 
-
 impl Frame {
-    fn fill_variables(method: &Method, parameters: MethodCallParameters) -> Vec<LocalVariable> {
-        // on method, the variable 0 is reference to this
-        todo!()
+    /// A frame for invoking a method with code, its arguments in the first local variables:
+    /// `this` first for an instance method, then the parameters of its descriptor.
+    pub fn new(class: Rc<Class>, method: Rc<Method>, arguments: &[Value]) -> Result<Self, ExecutionError> {
+        let Some(code) = &method.code else {
+            return Err(ExecutionError::new(format!("{method} has no code")));
+        };
+        let Some(descriptor) = MethodDescriptor::parse(&method.descriptor) else {
+            return Err(ExecutionError::new(format!("{method} has an invalid descriptor")));
+        };
+        let this = (!method.is_static()).then(|| FieldType::Object(method.class_name.clone()));
+        let parameters: Vec<_> = this.iter().chain(&descriptor.parameters).collect();
+        if parameters.len() != arguments.len() {
+            let plural = if parameters.len() == 1 { "" } else { "s" };
+            return Err(ExecutionError::new(format!("{method} takes {} argument{plural} but was passed {}", parameters.len(), arguments.len())));
+        }
+        let mut variables = vec![None; code.max_locals];
+        let mut index = 0;
+        for (parameter, argument) in parameters.into_iter().zip(arguments) {
+            if !is_assignable(argument, parameter) {
+                return Err(ExecutionError::new(format!("{method} takes {parameter} for local variable {index} but was passed {argument:?}")));
+            }
+            let Some(variable) = variables.get_mut(index..index + argument.slots()) else {
+                return Err(ExecutionError::new(format!("{method} has {} local variables, too few for its arguments", code.max_locals)));
+            };
+            variable[0] = Some(*argument);
+            index += argument.slots();
+        }
+        let operand_stack = OperandStack::new(code);
+        Ok(Frame { variables, operand_stack, class, method, position: 0 })
     }
-}
 
-impl Frame {
-    fn new(method: Method, parameters: MethodCallParameters) -> Self {
-        let variables: Vec<LocalVariable> = Frame::fill_variables(&method, parameters);
-        let operand_stack = OperandStack::new(&method);
-        Self {
-            variables,
-            operand_stack,
-            run_time_constant_pool: (),
-            method,
-        }
+    pub fn constant_pool(&self) -> &RuntimeConstantPool {
+        &self.class.constant_pool
     }
 
-    fn execute(self, operation: Operation) {
-        match operation {
-            IADD => self.execute_iadd(),
-            _ => {}
+    pub fn load(&self, index: usize) -> Result<Value, ExecutionError> {
+        match self.variables.get(index) {
+            Some(Some(value)) => Ok(*value),
+            Some(None) => Err(ExecutionError::new(format!("Local variable {index} is read before it is written"))),
+            None => Err(ExecutionError::new(format!("Local variable {index} is out of range"))),
         }
     }
 
-    fn execute_iadd(self) {
-        let i1pop = self.operand_stack.pop_int();
-        let i2pop = self.operand_stack.pop_int();
-        if let LocalVariable::Int(i1) = i1pop {
-            if let LocalVariable::Int(i2) = i2pop {
-                self.operand_stack.push(LocalVariable::Int(i1 + i2));
-            }
+    /// Stores a value, in two variables for a `long` or `double`. A `long` or `double` partly
+    /// overwritten becomes unusable.
+    pub fn store(&mut self, index: usize, value: Value) -> Result<(), ExecutionError> {
+        if index + value.slots() > self.variables.len() {
+            return Err(ExecutionError::new(format!("Local variable {index} is out of range")));
+        }
+        if index > 0 && self.variables[index - 1].is_some_and(|previous| previous.is_category2()) {
+            self.variables[index - 1] = None;
         }
+        self.variables[index] = Some(value);
+        if value.is_category2() {
+            self.variables[index + 1] = None;
+        }
+        Ok(())
+    }
+}
+
+/// Whether a value has the computational type of a parameter type.
+pub(crate) fn is_assignable(value: &Value, field_type: &FieldType) -> bool {
+    match (value, field_type) {
+        (Value::Int(_), FieldType::Boolean | FieldType::Byte | FieldType::Char | FieldType::Short | FieldType::Int) => true,
+        (Value::Long(_), FieldType::Long) | (Value::Float(_), FieldType::Float) | (Value::Double(_), FieldType::Double) => true,
+        (Value::Reference(_), field_type) => field_type.is_reference(),
+        _ => false,
     }
 }
+
+#[test]
+fn test_category2_values_take_two_slots() {
+    let java_class = crate::assembler::assemble("
+        .class public super Slots
+        .method public static f ()V
+            .limit stack 3; .limit locals 3
+            return
+        .end method
+    ").unwrap();
+    let class = Rc::new(Class::new(java_class).unwrap());
+    let mut frame = Frame::new(class.clone(), class.methods[0].clone(), &[]).unwrap();
+
+    frame.operand_stack.push(Value::Long(1)).unwrap();
+    frame.operand_stack.push(Value::Int(2)).unwrap();
+    let error = frame.operand_stack.push(Value::Int(3)).unwrap_err();
+    assert_eq!("Pushing an int would overflow the operand stack of depth 3", error.message);
+    assert_eq!("Expected a long on the operand stack but found Int(2)", frame.operand_stack.pop_long().unwrap_err().message);
+    assert_eq!(Value::Long(1), frame.operand_stack.pop_slots(2).unwrap()[0]);
+
+    frame.store(0, Value::Double(0.5)).unwrap();
+    assert_eq!(0.5, frame.load_double(0).unwrap());
+    assert_eq!("Local variable 1 is read before it is written", frame.load(1).unwrap_err().message);
+    frame.store(1, Value::Int(7)).unwrap();
+    assert_eq!("Local variable 0 is read before it is written", frame.load(0).unwrap_err().message);
+    assert_eq!("Local variable 2 is out of range", frame.store(2, Value::Long(0)).unwrap_err().message);
+    assert_eq!("Expected a reference in local variable 1 but found Int(7)", frame.load_reference(1).unwrap_err().message);
+}
+
+#[test]
+fn test_arguments_fill_the_first_local_variables() {
+    let java_class = crate::assembler::assemble("
+        .class public super Arguments
+        .method public f (JLjava/lang/String;Z)V
+            .limit stack 0; .limit locals 6
+            return
+        .end method
+        .method public static g (D)V
+            .limit stack 0; .limit locals 1
+            return
+        .end method
+    ").unwrap();
+    let class = Rc::new(Class::new(java_class).unwrap());
+    let (f, g) = (class.methods[0].clone(), class.methods[1].clone());
+
+    let frame = Frame::new(class.clone(), f.clone(), &[Value::Reference(Reference::new(24)), Value::Long(-1), Value::Reference(None), Value::Int(1)]).unwrap();
+    assert_eq!(Reference::new(24), frame.load_reference(0).unwrap());
+    assert_eq!(-1, frame.load_long(1).unwrap());
+    assert_eq!(None, frame.load_reference(3).unwrap());
+    assert_eq!(1, frame.load_int(4).unwrap());
+    assert!(frame.load(5).is_err());
+
+    let error = Frame::new(class.clone(), f.clone(), &[Value::Reference(None)]).unwrap_err();
+    assert_eq!("Arguments.f(JLjava/lang/String;Z)V takes 4 arguments but was passed 1", error.message);
+    let error = Frame::new(class.clone(), f, &[Value::Reference(None), Value::Int(0), Value::Reference(None), Value::Int(1)]).unwrap_err();
+    assert_eq!("Arguments.f(JLjava/lang/String;Z)V takes J for local variable 1 but was passed Int(0)", error.message);
+    let error = Frame::new(class, g, &[Value::Double(0.0)]).unwrap_err();
+    assert_eq!("Arguments.g(D)V has 1 local variables, too few for its arguments", error.message);
+}
@@ -1,7 +1,308 @@
-pub struct Heap {}
+use std::collections::HashMap;
+use std::fmt;
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use crate::call_site::LambdaTarget;
+use crate::class::{Class, Field};
+use crate::descriptor::FieldType;
+use crate::execution::ExecutionError;
+use crate::frames::{is_assignable, Value};
+
+/// What every object starts with: the index of its class among the classes of the heap's objects,
+/// its identity hash, 0 until it is asked for, and its lock word.
+pub const OBJECT_HEADER_SIZE: usize = 16;
+const CLASS_OFFSET: usize = 0;
+const HASH_OFFSET: usize = 4;
+const LOCK_OFFSET: usize = 8;
+
+/// Objects start at multiples of this, and take a multiple of it.
+const OBJECT_ALIGNMENT: usize = 8;
+
+/// A reference to an object: where it starts on the heap. References take 4 bytes in fields,
+/// heaps being smaller than 4 GiB, and no object starts at 0, which leaves it for `null`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Reference(NonZeroU32);
+
+impl Reference {
+    pub(crate) fn new(address: usize) -> Option<Reference> {
+        NonZeroU32::new(u32::try_from(address).ok()?).map(Reference)
+    }
+
+    pub fn address(self) -> usize {
+        self.0.get() as usize
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#x}", self.0)
+    }
+}
+
+/// What the JVM itself keeps for objects of the classes it implements, besides their fields.
+#[derive(Debug)]
+pub enum ObjectData {
+    /// The characters of a `java/lang/String`.
+    String(String),
+    /// A lambda spun by `LambdaMetafactory`: the method its functional interface method runs, and
+    /// the values it captured, which are passed first.
+    Lambda { target: Rc<LambdaTarget>, captured: Vec<Value> },
+}
+
+/// The memory objects are allocated from.
+#[derive(Debug)]
+pub struct Heap {
+    size: usize,
+    /// The bytes allocated so far.
+    memory: Vec<u8>,
+    /// Where each object starts, in the order they were allocated and so sorted.
+    objects: Vec<usize>,
+    /// The classes of the objects, which the class words of their headers index.
+    classes: Vec<Rc<Class>>,
+    class_indexes: HashMap<*const Class, u32>,
+    data: HashMap<Reference, ObjectData>,
+    /// The string literals loaded so far, which `ldc` of equal strings gives the same object of.
+    interned: HashMap<String, Reference>,
+    /// The state identity hashes are generated from.
+    hash_seed: u32,
+}
 
 impl Heap {
-    fn allocate(self, size: usize) -> Result<usize, Err> {
-        // OutOfMemoryError
+    /// A heap of `size` bytes, at most 4 GiB.
+    pub fn new(size: usize) -> Self {
+        Heap {
+            size: size.min(u32::MAX as usize),
+            memory: vec![0; OBJECT_ALIGNMENT],
+            objects: Vec::new(),
+            classes: Vec::new(),
+            class_indexes: HashMap::new(),
+            data: HashMap::new(),
+            interned: HashMap::new(),
+            hash_seed: 0x9e37_79b9,
+        }
+    }
+
+    /// Reserves `size` zeroed bytes starting at a multiple of 8 and returns where they start.
+    /// Running out of heap is an `OutOfMemoryError`.
+    pub fn allocate(&mut self, size: usize) -> Result<usize, ExecutionError> {
+        let start = self.memory.len().next_multiple_of(OBJECT_ALIGNMENT);
+        match start.checked_add(size).filter(|end| *end <= self.size) {
+            Some(end) => self.memory.resize(end, 0),
+            None => return Err(ExecutionError::new("OutOfMemoryError: Java heap space")),
+        }
+        Ok(start)
+    }
+
+    /// Allocates an object of `class`, its fields holding their default values.
+    pub fn allocate_object(&mut self, class: Rc<Class>) -> Result<Reference, ExecutionError> {
+        let address = self.allocate(class.instance_size.max(OBJECT_HEADER_SIZE))?;
+        let class_index = match self.class_indexes.get(&Rc::as_ptr(&class)) {
+            Some(index) => *index,
+            None => {
+                let index = self.classes.len() as u32;
+                self.class_indexes.insert(Rc::as_ptr(&class), index);
+                self.classes.push(class);
+                index
+            }
+        };
+        self.write(address + CLASS_OFFSET, &class_index.to_le_bytes());
+        self.objects.push(address);
+        Ok(Reference::new(address).unwrap_or_else(|| unreachable!()))
+    }
+
+    /// Allocates an object of a class the JVM implements, which holds `data`. The data takes its
+    /// share of the heap too.
+    pub fn allocate_with(&mut self, class: Rc<Class>, data: ObjectData) -> Result<Reference, ExecutionError> {
+        let size = match &data {
+            ObjectData::String(string) => string.encode_utf16().count() * 2,
+            ObjectData::Lambda { captured, .. } => captured.iter().map(|value| value.slots() * 4).sum(),
+        };
+        let reference = self.allocate_object(class)?;
+        self.allocate(size)?;
+        self.data.insert(reference, data);
+        Ok(reference)
+    }
+
+    /// The `java/lang/String` object of a string literal, allocated the first time it is asked for.
+    pub fn intern(&mut self, string_class: Rc<Class>, string: &str) -> Result<Reference, ExecutionError> {
+        if let Some(reference) = self.interned.get(string) {
+            return Ok(*reference);
+        }
+        let reference = self.allocate_with(string_class, ObjectData::String(string.to_string()))?;
+        self.interned.insert(string.to_string(), reference);
+        Ok(reference)
+    }
+
+    /// The class of the object a reference is to.
+    pub fn class(&self, reference: Reference) -> Result<&Rc<Class>, ExecutionError> {
+        let address = self.object(reference)?;
+        let index = u32::from_le_bytes(self.read(address + CLASS_OFFSET));
+        Ok(&self.classes[index as usize])
+    }
+
+    pub fn data(&self, reference: Reference) -> Option<&ObjectData> {
+        self.data.get(&reference)
     }
+
+    /// The identity hash of an object, which `Object.hashCode` returns unless overridden. It is
+    /// generated the first time it is asked for and kept in the header.
+    pub fn identity_hash(&mut self, reference: Reference) -> Result<i32, ExecutionError> {
+        let address = self.object(reference)?;
+        let hash = u32::from_le_bytes(self.read(address + HASH_OFFSET));
+        if hash != 0 {
+            return Ok(hash as i32);
+        }
+        // Marsaglia's xorshift, keeping 31 bits so that hashes are positive like HotSpot's.
+        loop {
+            self.hash_seed ^= self.hash_seed << 13;
+            self.hash_seed ^= self.hash_seed >> 17;
+            self.hash_seed ^= self.hash_seed << 5;
+            let hash = self.hash_seed & 0x7fff_ffff;
+            if hash != 0 {
+                self.write(address + HASH_OFFSET, &hash.to_le_bytes());
+                return Ok(hash as i32);
+            }
+        }
+    }
+
+    /// The lock word of an object, 0 while no thread holds its monitor.
+    pub fn lock_word(&self, reference: Reference) -> Result<u64, ExecutionError> {
+        let address = self.object(reference)?;
+        Ok(u64::from_le_bytes(self.read(address + LOCK_OFFSET)))
+    }
+
+    /// The value of an instance field of an object of the field's class or a subclass.
+    pub fn get_field(&self, reference: Reference, field: &Field) -> Result<Value, ExecutionError> {
+        let (address, field_type) = self.field_address(reference, field)?;
+        Ok(match field_type {
+            FieldType::Boolean | FieldType::Byte => Value::Int(i8::from_le_bytes(self.read(address)) as i32),
+            FieldType::Char => Value::Int(u16::from_le_bytes(self.read(address)) as i32),
+            FieldType::Short => Value::Int(i16::from_le_bytes(self.read(address)) as i32),
+            FieldType::Int => Value::Int(i32::from_le_bytes(self.read(address))),
+            FieldType::Float => Value::Float(f32::from_le_bytes(self.read(address))),
+            FieldType::Long => Value::Long(i64::from_le_bytes(self.read(address))),
+            FieldType::Double => Value::Double(f64::from_le_bytes(self.read(address))),
+            FieldType::Object(_) | FieldType::Array(_) => Value::Reference(Reference::new(u32::from_le_bytes(self.read(address)) as usize)),
+        })
+    }
+
+    /// Stores a value in an instance field, narrowing an int to the field's type the way
+    /// `putfield` does.
+    pub fn put_field(&mut self, reference: Reference, field: &Field, value: Value) -> Result<(), ExecutionError> {
+        let (address, field_type) = self.field_address(reference, field)?;
+        if !is_assignable(&value, &field_type) {
+            return Err(ExecutionError::new(format!("{value:?} can't be stored in {field}")));
+        }
+        match (field_type, value) {
+            (FieldType::Boolean, Value::Int(value)) => self.write(address, &[value as u8 & 1]),
+            (FieldType::Byte, Value::Int(value)) => self.write(address, &(value as i8).to_le_bytes()),
+            (FieldType::Char | FieldType::Short, Value::Int(value)) => self.write(address, &(value as u16).to_le_bytes()),
+            (_, Value::Int(value)) => self.write(address, &value.to_le_bytes()),
+            (_, Value::Float(value)) => self.write(address, &value.to_le_bytes()),
+            (_, Value::Long(value)) => self.write(address, &value.to_le_bytes()),
+            (_, Value::Double(value)) => self.write(address, &value.to_le_bytes()),
+            (_, Value::Reference(value)) => self.write(address, &value.map_or(0, |reference| reference.0.get()).to_le_bytes()),
+            (_, Value::ReturnAddress(_)) => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Where an object starts, which has to be where one was allocated.
+    fn object(&self, reference: Reference) -> Result<usize, ExecutionError> {
+        match self.objects.binary_search(&reference.address()) {
+            Ok(_) => Ok(reference.address()),
+            Err(_) => Err(ExecutionError::new(format!("Reference {reference} is not to an object"))),
+        }
+    }
+
+    fn field_address(&self, reference: Reference, field: &Field) -> Result<(usize, FieldType), ExecutionError> {
+        let class = self.class(reference)?;
+        let field_type = FieldType::parse(&field.descriptor).filter(|_| !field.is_static());
+        match field_type {
+            Some(field_type) if field.offset + field_size(&field_type) <= class.instance_size => Ok((reference.address() + field.offset, field_type)),
+            _ => Err(ExecutionError::new(format!("{} objects have no field {field}", class.name))),
+        }
+    }
+
+    fn read<const N: usize>(&self, address: usize) -> [u8; N] {
+        self.memory[address..address + N].try_into().unwrap_or_else(|_| unreachable!())
+    }
+
+    fn write(&mut self, address: usize, bytes: &[u8]) {
+        self.memory[address..address + bytes.len()].copy_from_slice(bytes);
+    }
+}
+
+/// The bytes a field of a type takes in an object.
+fn field_size(field_type: &FieldType) -> usize {
+    match field_type {
+        FieldType::Boolean | FieldType::Byte => 1,
+        FieldType::Char | FieldType::Short => 2,
+        FieldType::Int | FieldType::Float | FieldType::Object(_) | FieldType::Array(_) => 4,
+        FieldType::Long | FieldType::Double => 8,
+    }
+}
+
+/// Lays out the instance fields of a class after those of its superclass, which end at `start`,
+/// and returns the size of its objects. Wider fields go first, so that each is aligned to its
+/// size without padding.
+pub(crate) fn lay_out_fields(fields: &mut [Field], start: usize) -> usize {
+    let mut offset = start;
+    for size in [8, 4, 2, 1] {
+        for field in fields.iter_mut().filter(|field| !field.is_static()) {
+            if FieldType::parse(&field.descriptor).map_or(4, |field_type| field_size(&field_type)) == size {
+                field.offset = offset;
+                offset += size;
+            }
+        }
+    }
+    offset.next_multiple_of(OBJECT_ALIGNMENT)
+}
+
+#[test]
+fn test_lays_out_fields_after_the_header_and_the_superclass() {
+    let mut jvm = crate::jvm::Jvm::new();
+    let base = jvm.load_class(crate::assembler::assemble("
+        .class public super Base
+        .field public b B
+        .field public l J
+        .field public static count I
+        .field public s Ljava/lang/String;
+        .field public c C
+    ").unwrap()).unwrap();
+    let derived = jvm.load_class(crate::assembler::assemble("
+        .class public super Derived
+        .super Base
+        .field public i I
+    ").unwrap()).unwrap();
+    let offsets = |class: &Class| class.fields.iter().filter(|field| !field.is_static()).map(|field| field.offset).collect::<Vec<_>>();
+    assert_eq!(vec![30, 16, 24, 28], offsets(&base));
+    assert_eq!(32, base.instance_size);
+    assert_eq!(vec![32], offsets(&derived));
+    assert_eq!(40, derived.instance_size);
+
+    let Value::Reference(Some(object)) = jvm.allocate("Derived").unwrap() else { unreachable!() };
+    let heap = &mut jvm.heap;
+    assert_eq!(0, object.address() % OBJECT_ALIGNMENT);
+    assert_eq!("Derived", heap.class(object).unwrap().name);
+    assert_eq!(0, heap.lock_word(object).unwrap());
+    let hash = heap.identity_hash(object).unwrap();
+    assert!(hash > 0);
+    assert_eq!(hash, heap.identity_hash(object).unwrap());
+    assert_eq!(Value::Reference(None), heap.get_field(object, &base.fields[3]).unwrap());
+    heap.put_field(object, &base.fields[0], Value::Int(300)).unwrap();
+    heap.put_field(object, &base.fields[1], Value::Long(-1)).unwrap();
+    heap.put_field(object, &base.fields[4], Value::Int(-1)).unwrap();
+    heap.put_field(object, &derived.fields[0], Value::Int(7)).unwrap();
+    assert_eq!(Value::Int(44), heap.get_field(object, &base.fields[0]).unwrap());
+    assert_eq!(Value::Long(-1), heap.get_field(object, &base.fields[1]).unwrap());
+    assert_eq!(Value::Int(0xffff), heap.get_field(object, &base.fields[4]).unwrap());
+    assert_eq!(Value::Int(7), heap.get_field(object, &derived.fields[0]).unwrap());
+    assert!(heap.put_field(object, &base.fields[3], Value::Int(1)).is_err());
+    let address = Reference::new(object.address() + 8).unwrap();
+    assert_eq!(format!("Reference {address} is not to an object"), heap.class(address).unwrap_err().message);
+
+    let mut heap = Heap::new(64);
+    assert!(heap.allocate(48).is_ok());
+    assert_eq!("OutOfMemoryError: Java heap space", heap.allocate(16).unwrap_err().message);
 }
@@ -0,0 +1,1339 @@
+use std::error::Error;
+use std::fmt;
+use crate::bytecode::{Opcode, Operands};
+use crate::descriptor::FieldType;
+
+/// An index into the constant pool.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct CpIndex(pub u16);
+
+/// The pc a branch jumps to.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct BranchTarget(pub usize);
+
+/// The element type `newarray` creates an array of.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum ArrayType {
+    Boolean = 4,
+    Char = 5,
+    Float = 6,
+    Double = 7,
+    Byte = 8,
+    Short = 9,
+    Int = 10,
+    Long = 11,
+}
+
+impl ArrayType {
+    pub fn from_u8(atype: u8) -> Option<ArrayType> {
+        match atype {
+            4 => Some(ArrayType::Boolean),
+            5 => Some(ArrayType::Char),
+            6 => Some(ArrayType::Float),
+            7 => Some(ArrayType::Double),
+            8 => Some(ArrayType::Byte),
+            9 => Some(ArrayType::Short),
+            10 => Some(ArrayType::Int),
+            11 => Some(ArrayType::Long),
+            _ => None,
+        }
+    }
+
+    /// The Java name of the element type, as javap prints it.
+    pub fn name(self) -> &'static str {
+        match self {
+            ArrayType::Boolean => "boolean",
+            ArrayType::Char => "char",
+            ArrayType::Float => "float",
+            ArrayType::Double => "double",
+            ArrayType::Byte => "byte",
+            ArrayType::Short => "short",
+            ArrayType::Int => "int",
+            ArrayType::Long => "long",
+        }
+    }
+}
+
+/// The computational type of a value. `boolean`, `byte`, `char` and `short` values are `int`s.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Type {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+}
+
+impl Type {
+    pub fn of(field_type: &FieldType) -> Type {
+        match field_type {
+            FieldType::Long => Type::Long,
+            FieldType::Float => Type::Float,
+            FieldType::Double => Type::Double,
+            FieldType::Object(_) | FieldType::Array(_) => Type::Reference,
+            _ => Type::Int,
+        }
+    }
+
+    pub fn is_wide(self) -> bool {
+        matches!(self, Type::Long | Type::Double)
+    }
+
+    pub(crate) fn slots(self) -> usize {
+        if self.is_wide() { 2 } else { 1 }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Long => write!(f, "long"),
+            Type::Float => write!(f, "float"),
+            Type::Double => write!(f, "double"),
+            Type::Reference => write!(f, "ref"),
+        }
+    }
+}
+
+/// A constant an instruction loads.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Literal {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    String(String),
+    Class(String),
+    /// A method type, method handle or dynamic constant, left in the constant pool of the class
+    /// it was loaded from.
+    Pooled { index: CpIndex, ty: Type },
+}
+
+impl Literal {
+    pub fn ty(&self) -> Type {
+        match self {
+            Literal::Int(_) => Type::Int,
+            Literal::Long(_) => Type::Long,
+            Literal::Float(_) => Type::Float,
+            Literal::Double(_) => Type::Double,
+            Literal::Null | Literal::String(_) | Literal::Class(_) => Type::Reference,
+            Literal::Pooled { ty, .. } => *ty,
+        }
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Int(value) => write!(f, "{value}"),
+            Literal::Long(value) => write!(f, "{value}L"),
+            Literal::Float(value) => write!(f, "{value:?}f"),
+            Literal::Double(value) => write!(f, "{value:?}d"),
+            Literal::Null => write!(f, "null"),
+            Literal::String(string) => write!(f, "{string:?}"),
+            Literal::Class(class_name) => write!(f, "class {class_name}"),
+            Literal::Pooled { index, .. } => write!(f, "#{}", index.0),
+        }
+    }
+}
+
+/// Matches the instructions with a single branch target, binding it to `$target`.
+macro_rules! single_branch {
+    ($target:ident) => {
+        Instruction::Ifeq($target) | Instruction::Ifne($target) | Instruction::Iflt($target) | Instruction::Ifge($target)
+        | Instruction::Ifgt($target) | Instruction::Ifle($target) | Instruction::IfIcmpeq($target) | Instruction::IfIcmpne($target)
+        | Instruction::IfIcmplt($target) | Instruction::IfIcmpge($target) | Instruction::IfIcmpgt($target) | Instruction::IfIcmple($target)
+        | Instruction::IfAcmpeq($target) | Instruction::IfAcmpne($target) | Instruction::Goto($target) | Instruction::Jsr($target)
+        | Instruction::Ifnull($target) | Instruction::Ifnonnull($target) | Instruction::GotoW($target) | Instruction::JsrW($target)
+    };
+}
+
+/// A decoded instruction. Branch targets are absolute pcs. The `wide` forms decode to the same
+/// variants as the narrow ones.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Instruction {
+    Nop,
+    AconstNull,
+    IconstM1,
+    Iconst0,
+    Iconst1,
+    Iconst2,
+    Iconst3,
+    Iconst4,
+    Iconst5,
+    Lconst0,
+    Lconst1,
+    Fconst0,
+    Fconst1,
+    Fconst2,
+    Dconst0,
+    Dconst1,
+    Bipush(i8),
+    Sipush(i16),
+    Ldc(CpIndex),
+    LdcW(CpIndex),
+    Ldc2W(CpIndex),
+    Iload(u16),
+    Lload(u16),
+    Fload(u16),
+    Dload(u16),
+    Aload(u16),
+    Iload0,
+    Iload1,
+    Iload2,
+    Iload3,
+    Lload0,
+    Lload1,
+    Lload2,
+    Lload3,
+    Fload0,
+    Fload1,
+    Fload2,
+    Fload3,
+    Dload0,
+    Dload1,
+    Dload2,
+    Dload3,
+    Aload0,
+    Aload1,
+    Aload2,
+    Aload3,
+    Iaload,
+    Laload,
+    Faload,
+    Daload,
+    Aaload,
+    Baload,
+    Caload,
+    Saload,
+    Istore(u16),
+    Lstore(u16),
+    Fstore(u16),
+    Dstore(u16),
+    Astore(u16),
+    Istore0,
+    Istore1,
+    Istore2,
+    Istore3,
+    Lstore0,
+    Lstore1,
+    Lstore2,
+    Lstore3,
+    Fstore0,
+    Fstore1,
+    Fstore2,
+    Fstore3,
+    Dstore0,
+    Dstore1,
+    Dstore2,
+    Dstore3,
+    Astore0,
+    Astore1,
+    Astore2,
+    Astore3,
+    Iastore,
+    Lastore,
+    Fastore,
+    Dastore,
+    Aastore,
+    Bastore,
+    Castore,
+    Sastore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    Iadd,
+    Ladd,
+    Fadd,
+    Dadd,
+    Isub,
+    Lsub,
+    Fsub,
+    Dsub,
+    Imul,
+    Lmul,
+    Fmul,
+    Dmul,
+    Idiv,
+    Ldiv,
+    Fdiv,
+    Ddiv,
+    Irem,
+    Lrem,
+    Frem,
+    Drem,
+    Ineg,
+    Lneg,
+    Fneg,
+    Dneg,
+    Ishl,
+    Lshl,
+    Ishr,
+    Lshr,
+    Iushr,
+    Lushr,
+    Iand,
+    Land,
+    Ior,
+    Lor,
+    Ixor,
+    Lxor,
+    Iinc { index: u16, increment: i16 },
+    I2l,
+    I2f,
+    I2d,
+    L2i,
+    L2f,
+    L2d,
+    F2i,
+    F2l,
+    F2d,
+    D2i,
+    D2l,
+    D2f,
+    I2b,
+    I2c,
+    I2s,
+    Lcmp,
+    Fcmpl,
+    Fcmpg,
+    Dcmpl,
+    Dcmpg,
+    Ifeq(BranchTarget),
+    Ifne(BranchTarget),
+    Iflt(BranchTarget),
+    Ifge(BranchTarget),
+    Ifgt(BranchTarget),
+    Ifle(BranchTarget),
+    IfIcmpeq(BranchTarget),
+    IfIcmpne(BranchTarget),
+    IfIcmplt(BranchTarget),
+    IfIcmpge(BranchTarget),
+    IfIcmpgt(BranchTarget),
+    IfIcmple(BranchTarget),
+    IfAcmpeq(BranchTarget),
+    IfAcmpne(BranchTarget),
+    Goto(BranchTarget),
+    Jsr(BranchTarget),
+    Ret(u16),
+    Tableswitch { default: BranchTarget, low: i32, high: i32, targets: Vec<BranchTarget> },
+    Lookupswitch { default: BranchTarget, pairs: Vec<(i32, BranchTarget)> },
+    Ireturn,
+    Lreturn,
+    Freturn,
+    Dreturn,
+    Areturn,
+    Return,
+    Getstatic(CpIndex),
+    Putstatic(CpIndex),
+    Getfield(CpIndex),
+    Putfield(CpIndex),
+    Invokevirtual(CpIndex),
+    Invokespecial(CpIndex),
+    Invokestatic(CpIndex),
+    Invokeinterface { index: CpIndex, count: u8 },
+    Invokedynamic(CpIndex),
+    New(CpIndex),
+    Newarray(ArrayType),
+    Anewarray(CpIndex),
+    Arraylength,
+    Athrow,
+    Checkcast(CpIndex),
+    Instanceof(CpIndex),
+    Monitorenter,
+    Monitorexit,
+    Multianewarray { index: CpIndex, dimensions: u8 },
+    Ifnull(BranchTarget),
+    Ifnonnull(BranchTarget),
+    GotoW(BranchTarget),
+    JsrW(BranchTarget),
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> Opcode {
+        match self {
+            Instruction::Nop => Opcode::Nop,
+            Instruction::AconstNull => Opcode::AconstNull,
+            Instruction::IconstM1 => Opcode::IconstM1,
+            Instruction::Iconst0 => Opcode::Iconst0,
+            Instruction::Iconst1 => Opcode::Iconst1,
+            Instruction::Iconst2 => Opcode::Iconst2,
+            Instruction::Iconst3 => Opcode::Iconst3,
+            Instruction::Iconst4 => Opcode::Iconst4,
+            Instruction::Iconst5 => Opcode::Iconst5,
+            Instruction::Lconst0 => Opcode::Lconst0,
+            Instruction::Lconst1 => Opcode::Lconst1,
+            Instruction::Fconst0 => Opcode::Fconst0,
+            Instruction::Fconst1 => Opcode::Fconst1,
+            Instruction::Fconst2 => Opcode::Fconst2,
+            Instruction::Dconst0 => Opcode::Dconst0,
+            Instruction::Dconst1 => Opcode::Dconst1,
+            Instruction::Bipush(_) => Opcode::Bipush,
+            Instruction::Sipush(_) => Opcode::Sipush,
+            Instruction::Ldc(_) => Opcode::Ldc,
+            Instruction::LdcW(_) => Opcode::LdcW,
+            Instruction::Ldc2W(_) => Opcode::Ldc2W,
+            Instruction::Iload(_) => Opcode::Iload,
+            Instruction::Lload(_) => Opcode::Lload,
+            Instruction::Fload(_) => Opcode::Fload,
+            Instruction::Dload(_) => Opcode::Dload,
+            Instruction::Aload(_) => Opcode::Aload,
+            Instruction::Iload0 => Opcode::Iload0,
+            Instruction::Iload1 => Opcode::Iload1,
+            Instruction::Iload2 => Opcode::Iload2,
+            Instruction::Iload3 => Opcode::Iload3,
+            Instruction::Lload0 => Opcode::Lload0,
+            Instruction::Lload1 => Opcode::Lload1,
+            Instruction::Lload2 => Opcode::Lload2,
+            Instruction::Lload3 => Opcode::Lload3,
+            Instruction::Fload0 => Opcode::Fload0,
+            Instruction::Fload1 => Opcode::Fload1,
+            Instruction::Fload2 => Opcode::Fload2,
+            Instruction::Fload3 => Opcode::Fload3,
+            Instruction::Dload0 => Opcode::Dload0,
+            Instruction::Dload1 => Opcode::Dload1,
+            Instruction::Dload2 => Opcode::Dload2,
+            Instruction::Dload3 => Opcode::Dload3,
+            Instruction::Aload0 => Opcode::Aload0,
+            Instruction::Aload1 => Opcode::Aload1,
+            Instruction::Aload2 => Opcode::Aload2,
+            Instruction::Aload3 => Opcode::Aload3,
+            Instruction::Iaload => Opcode::Iaload,
+            Instruction::Laload => Opcode::Laload,
+            Instruction::Faload => Opcode::Faload,
+            Instruction::Daload => Opcode::Daload,
+            Instruction::Aaload => Opcode::Aaload,
+            Instruction::Baload => Opcode::Baload,
+            Instruction::Caload => Opcode::Caload,
+            Instruction::Saload => Opcode::Saload,
+            Instruction::Istore(_) => Opcode::Istore,
+            Instruction::Lstore(_) => Opcode::Lstore,
+            Instruction::Fstore(_) => Opcode::Fstore,
+            Instruction::Dstore(_) => Opcode::Dstore,
+            Instruction::Astore(_) => Opcode::Astore,
+            Instruction::Istore0 => Opcode::Istore0,
+            Instruction::Istore1 => Opcode::Istore1,
+            Instruction::Istore2 => Opcode::Istore2,
+            Instruction::Istore3 => Opcode::Istore3,
+            Instruction::Lstore0 => Opcode::Lstore0,
+            Instruction::Lstore1 => Opcode::Lstore1,
+            Instruction::Lstore2 => Opcode::Lstore2,
+            Instruction::Lstore3 => Opcode::Lstore3,
+            Instruction::Fstore0 => Opcode::Fstore0,
+            Instruction::Fstore1 => Opcode::Fstore1,
+            Instruction::Fstore2 => Opcode::Fstore2,
+            Instruction::Fstore3 => Opcode::Fstore3,
+            Instruction::Dstore0 => Opcode::Dstore0,
+            Instruction::Dstore1 => Opcode::Dstore1,
+            Instruction::Dstore2 => Opcode::Dstore2,
+            Instruction::Dstore3 => Opcode::Dstore3,
+            Instruction::Astore0 => Opcode::Astore0,
+            Instruction::Astore1 => Opcode::Astore1,
+            Instruction::Astore2 => Opcode::Astore2,
+            Instruction::Astore3 => Opcode::Astore3,
+            Instruction::Iastore => Opcode::Iastore,
+            Instruction::Lastore => Opcode::Lastore,
+            Instruction::Fastore => Opcode::Fastore,
+            Instruction::Dastore => Opcode::Dastore,
+            Instruction::Aastore => Opcode::Aastore,
+            Instruction::Bastore => Opcode::Bastore,
+            Instruction::Castore => Opcode::Castore,
+            Instruction::Sastore => Opcode::Sastore,
+            Instruction::Pop => Opcode::Pop,
+            Instruction::Pop2 => Opcode::Pop2,
+            Instruction::Dup => Opcode::Dup,
+            Instruction::DupX1 => Opcode::DupX1,
+            Instruction::DupX2 => Opcode::DupX2,
+            Instruction::Dup2 => Opcode::Dup2,
+            Instruction::Dup2X1 => Opcode::Dup2X1,
+            Instruction::Dup2X2 => Opcode::Dup2X2,
+            Instruction::Swap => Opcode::Swap,
+            Instruction::Iadd => Opcode::Iadd,
+            Instruction::Ladd => Opcode::Ladd,
+            Instruction::Fadd => Opcode::Fadd,
+            Instruction::Dadd => Opcode::Dadd,
+            Instruction::Isub => Opcode::Isub,
+            Instruction::Lsub => Opcode::Lsub,
+            Instruction::Fsub => Opcode::Fsub,
+            Instruction::Dsub => Opcode::Dsub,
+            Instruction::Imul => Opcode::Imul,
+            Instruction::Lmul => Opcode::Lmul,
+            Instruction::Fmul => Opcode::Fmul,
+            Instruction::Dmul => Opcode::Dmul,
+            Instruction::Idiv => Opcode::Idiv,
+            Instruction::Ldiv => Opcode::Ldiv,
+            Instruction::Fdiv => Opcode::Fdiv,
+            Instruction::Ddiv => Opcode::Ddiv,
+            Instruction::Irem => Opcode::Irem,
+            Instruction::Lrem => Opcode::Lrem,
+            Instruction::Frem => Opcode::Frem,
+            Instruction::Drem => Opcode::Drem,
+            Instruction::Ineg => Opcode::Ineg,
+            Instruction::Lneg => Opcode::Lneg,
+            Instruction::Fneg => Opcode::Fneg,
+            Instruction::Dneg => Opcode::Dneg,
+            Instruction::Ishl => Opcode::Ishl,
+            Instruction::Lshl => Opcode::Lshl,
+            Instruction::Ishr => Opcode::Ishr,
+            Instruction::Lshr => Opcode::Lshr,
+            Instruction::Iushr => Opcode::Iushr,
+            Instruction::Lushr => Opcode::Lushr,
+            Instruction::Iand => Opcode::Iand,
+            Instruction::Land => Opcode::Land,
+            Instruction::Ior => Opcode::Ior,
+            Instruction::Lor => Opcode::Lor,
+            Instruction::Ixor => Opcode::Ixor,
+            Instruction::Lxor => Opcode::Lxor,
+            Instruction::Iinc { .. } => Opcode::Iinc,
+            Instruction::I2l => Opcode::I2l,
+            Instruction::I2f => Opcode::I2f,
+            Instruction::I2d => Opcode::I2d,
+            Instruction::L2i => Opcode::L2i,
+            Instruction::L2f => Opcode::L2f,
+            Instruction::L2d => Opcode::L2d,
+            Instruction::F2i => Opcode::F2i,
+            Instruction::F2l => Opcode::F2l,
+            Instruction::F2d => Opcode::F2d,
+            Instruction::D2i => Opcode::D2i,
+            Instruction::D2l => Opcode::D2l,
+            Instruction::D2f => Opcode::D2f,
+            Instruction::I2b => Opcode::I2b,
+            Instruction::I2c => Opcode::I2c,
+            Instruction::I2s => Opcode::I2s,
+            Instruction::Lcmp => Opcode::Lcmp,
+            Instruction::Fcmpl => Opcode::Fcmpl,
+            Instruction::Fcmpg => Opcode::Fcmpg,
+            Instruction::Dcmpl => Opcode::Dcmpl,
+            Instruction::Dcmpg => Opcode::Dcmpg,
+            Instruction::Ifeq(_) => Opcode::Ifeq,
+            Instruction::Ifne(_) => Opcode::Ifne,
+            Instruction::Iflt(_) => Opcode::Iflt,
+            Instruction::Ifge(_) => Opcode::Ifge,
+            Instruction::Ifgt(_) => Opcode::Ifgt,
+            Instruction::Ifle(_) => Opcode::Ifle,
+            Instruction::IfIcmpeq(_) => Opcode::IfIcmpeq,
+            Instruction::IfIcmpne(_) => Opcode::IfIcmpne,
+            Instruction::IfIcmplt(_) => Opcode::IfIcmplt,
+            Instruction::IfIcmpge(_) => Opcode::IfIcmpge,
+            Instruction::IfIcmpgt(_) => Opcode::IfIcmpgt,
+            Instruction::IfIcmple(_) => Opcode::IfIcmple,
+            Instruction::IfAcmpeq(_) => Opcode::IfAcmpeq,
+            Instruction::IfAcmpne(_) => Opcode::IfAcmpne,
+            Instruction::Goto(_) => Opcode::Goto,
+            Instruction::Jsr(_) => Opcode::Jsr,
+            Instruction::Ret(_) => Opcode::Ret,
+            Instruction::Tableswitch { .. } => Opcode::Tableswitch,
+            Instruction::Lookupswitch { .. } => Opcode::Lookupswitch,
+            Instruction::Ireturn => Opcode::Ireturn,
+            Instruction::Lreturn => Opcode::Lreturn,
+            Instruction::Freturn => Opcode::Freturn,
+            Instruction::Dreturn => Opcode::Dreturn,
+            Instruction::Areturn => Opcode::Areturn,
+            Instruction::Return => Opcode::Return,
+            Instruction::Getstatic(_) => Opcode::Getstatic,
+            Instruction::Putstatic(_) => Opcode::Putstatic,
+            Instruction::Getfield(_) => Opcode::Getfield,
+            Instruction::Putfield(_) => Opcode::Putfield,
+            Instruction::Invokevirtual(_) => Opcode::Invokevirtual,
+            Instruction::Invokespecial(_) => Opcode::Invokespecial,
+            Instruction::Invokestatic(_) => Opcode::Invokestatic,
+            Instruction::Invokeinterface { .. } => Opcode::Invokeinterface,
+            Instruction::Invokedynamic(_) => Opcode::Invokedynamic,
+            Instruction::New(_) => Opcode::New,
+            Instruction::Newarray(_) => Opcode::Newarray,
+            Instruction::Anewarray(_) => Opcode::Anewarray,
+            Instruction::Arraylength => Opcode::Arraylength,
+            Instruction::Athrow => Opcode::Athrow,
+            Instruction::Checkcast(_) => Opcode::Checkcast,
+            Instruction::Instanceof(_) => Opcode::Instanceof,
+            Instruction::Monitorenter => Opcode::Monitorenter,
+            Instruction::Monitorexit => Opcode::Monitorexit,
+            Instruction::Multianewarray { .. } => Opcode::Multianewarray,
+            Instruction::Ifnull(_) => Opcode::Ifnull,
+            Instruction::Ifnonnull(_) => Opcode::Ifnonnull,
+            Instruction::GotoW(_) => Opcode::GotoW,
+            Instruction::JsrW(_) => Opcode::JsrW,
+        }
+    }
+}
+
+pub(crate) fn without_operands(opcode: Opcode) -> Instruction {
+    match opcode {
+        Opcode::Nop => Instruction::Nop,
+        Opcode::AconstNull => Instruction::AconstNull,
+        Opcode::IconstM1 => Instruction::IconstM1,
+        Opcode::Iconst0 => Instruction::Iconst0,
+        Opcode::Iconst1 => Instruction::Iconst1,
+        Opcode::Iconst2 => Instruction::Iconst2,
+        Opcode::Iconst3 => Instruction::Iconst3,
+        Opcode::Iconst4 => Instruction::Iconst4,
+        Opcode::Iconst5 => Instruction::Iconst5,
+        Opcode::Lconst0 => Instruction::Lconst0,
+        Opcode::Lconst1 => Instruction::Lconst1,
+        Opcode::Fconst0 => Instruction::Fconst0,
+        Opcode::Fconst1 => Instruction::Fconst1,
+        Opcode::Fconst2 => Instruction::Fconst2,
+        Opcode::Dconst0 => Instruction::Dconst0,
+        Opcode::Dconst1 => Instruction::Dconst1,
+        Opcode::Iload0 => Instruction::Iload0,
+        Opcode::Iload1 => Instruction::Iload1,
+        Opcode::Iload2 => Instruction::Iload2,
+        Opcode::Iload3 => Instruction::Iload3,
+        Opcode::Lload0 => Instruction::Lload0,
+        Opcode::Lload1 => Instruction::Lload1,
+        Opcode::Lload2 => Instruction::Lload2,
+        Opcode::Lload3 => Instruction::Lload3,
+        Opcode::Fload0 => Instruction::Fload0,
+        Opcode::Fload1 => Instruction::Fload1,
+        Opcode::Fload2 => Instruction::Fload2,
+        Opcode::Fload3 => Instruction::Fload3,
+        Opcode::Dload0 => Instruction::Dload0,
+        Opcode::Dload1 => Instruction::Dload1,
+        Opcode::Dload2 => Instruction::Dload2,
+        Opcode::Dload3 => Instruction::Dload3,
+        Opcode::Aload0 => Instruction::Aload0,
+        Opcode::Aload1 => Instruction::Aload1,
+        Opcode::Aload2 => Instruction::Aload2,
+        Opcode::Aload3 => Instruction::Aload3,
+        Opcode::Iaload => Instruction::Iaload,
+        Opcode::Laload => Instruction::Laload,
+        Opcode::Faload => Instruction::Faload,
+        Opcode::Daload => Instruction::Daload,
+        Opcode::Aaload => Instruction::Aaload,
+        Opcode::Baload => Instruction::Baload,
+        Opcode::Caload => Instruction::Caload,
+        Opcode::Saload => Instruction::Saload,
+        Opcode::Istore0 => Instruction::Istore0,
+        Opcode::Istore1 => Instruction::Istore1,
+        Opcode::Istore2 => Instruction::Istore2,
+        Opcode::Istore3 => Instruction::Istore3,
+        Opcode::Lstore0 => Instruction::Lstore0,
+        Opcode::Lstore1 => Instruction::Lstore1,
+        Opcode::Lstore2 => Instruction::Lstore2,
+        Opcode::Lstore3 => Instruction::Lstore3,
+        Opcode::Fstore0 => Instruction::Fstore0,
+        Opcode::Fstore1 => Instruction::Fstore1,
+        Opcode::Fstore2 => Instruction::Fstore2,
+        Opcode::Fstore3 => Instruction::Fstore3,
+        Opcode::Dstore0 => Instruction::Dstore0,
+        Opcode::Dstore1 => Instruction::Dstore1,
+        Opcode::Dstore2 => Instruction::Dstore2,
+        Opcode::Dstore3 => Instruction::Dstore3,
+        Opcode::Astore0 => Instruction::Astore0,
+        Opcode::Astore1 => Instruction::Astore1,
+        Opcode::Astore2 => Instruction::Astore2,
+        Opcode::Astore3 => Instruction::Astore3,
+        Opcode::Iastore => Instruction::Iastore,
+        Opcode::Lastore => Instruction::Lastore,
+        Opcode::Fastore => Instruction::Fastore,
+        Opcode::Dastore => Instruction::Dastore,
+        Opcode::Aastore => Instruction::Aastore,
+        Opcode::Bastore => Instruction::Bastore,
+        Opcode::Castore => Instruction::Castore,
+        Opcode::Sastore => Instruction::Sastore,
+        Opcode::Pop => Instruction::Pop,
+        Opcode::Pop2 => Instruction::Pop2,
+        Opcode::Dup => Instruction::Dup,
+        Opcode::DupX1 => Instruction::DupX1,
+        Opcode::DupX2 => Instruction::DupX2,
+        Opcode::Dup2 => Instruction::Dup2,
+        Opcode::Dup2X1 => Instruction::Dup2X1,
+        Opcode::Dup2X2 => Instruction::Dup2X2,
+        Opcode::Swap => Instruction::Swap,
+        Opcode::Iadd => Instruction::Iadd,
+        Opcode::Ladd => Instruction::Ladd,
+        Opcode::Fadd => Instruction::Fadd,
+        Opcode::Dadd => Instruction::Dadd,
+        Opcode::Isub => Instruction::Isub,
+        Opcode::Lsub => Instruction::Lsub,
+        Opcode::Fsub => Instruction::Fsub,
+        Opcode::Dsub => Instruction::Dsub,
+        Opcode::Imul => Instruction::Imul,
+        Opcode::Lmul => Instruction::Lmul,
+        Opcode::Fmul => Instruction::Fmul,
+        Opcode::Dmul => Instruction::Dmul,
+        Opcode::Idiv => Instruction::Idiv,
+        Opcode::Ldiv => Instruction::Ldiv,
+        Opcode::Fdiv => Instruction::Fdiv,
+        Opcode::Ddiv => Instruction::Ddiv,
+        Opcode::Irem => Instruction::Irem,
+        Opcode::Lrem => Instruction::Lrem,
+        Opcode::Frem => Instruction::Frem,
+        Opcode::Drem => Instruction::Drem,
+        Opcode::Ineg => Instruction::Ineg,
+        Opcode::Lneg => Instruction::Lneg,
+        Opcode::Fneg => Instruction::Fneg,
+        Opcode::Dneg => Instruction::Dneg,
+        Opcode::Ishl => Instruction::Ishl,
+        Opcode::Lshl => Instruction::Lshl,
+        Opcode::Ishr => Instruction::Ishr,
+        Opcode::Lshr => Instruction::Lshr,
+        Opcode::Iushr => Instruction::Iushr,
+        Opcode::Lushr => Instruction::Lushr,
+        Opcode::Iand => Instruction::Iand,
+        Opcode::Land => Instruction::Land,
+        Opcode::Ior => Instruction::Ior,
+        Opcode::Lor => Instruction::Lor,
+        Opcode::Ixor => Instruction::Ixor,
+        Opcode::Lxor => Instruction::Lxor,
+        Opcode::I2l => Instruction::I2l,
+        Opcode::I2f => Instruction::I2f,
+        Opcode::I2d => Instruction::I2d,
+        Opcode::L2i => Instruction::L2i,
+        Opcode::L2f => Instruction::L2f,
+        Opcode::L2d => Instruction::L2d,
+        Opcode::F2i => Instruction::F2i,
+        Opcode::F2l => Instruction::F2l,
+        Opcode::F2d => Instruction::F2d,
+        Opcode::D2i => Instruction::D2i,
+        Opcode::D2l => Instruction::D2l,
+        Opcode::D2f => Instruction::D2f,
+        Opcode::I2b => Instruction::I2b,
+        Opcode::I2c => Instruction::I2c,
+        Opcode::I2s => Instruction::I2s,
+        Opcode::Lcmp => Instruction::Lcmp,
+        Opcode::Fcmpl => Instruction::Fcmpl,
+        Opcode::Fcmpg => Instruction::Fcmpg,
+        Opcode::Dcmpl => Instruction::Dcmpl,
+        Opcode::Dcmpg => Instruction::Dcmpg,
+        Opcode::Ireturn => Instruction::Ireturn,
+        Opcode::Lreturn => Instruction::Lreturn,
+        Opcode::Freturn => Instruction::Freturn,
+        Opcode::Dreturn => Instruction::Dreturn,
+        Opcode::Areturn => Instruction::Areturn,
+        Opcode::Return => Instruction::Return,
+        Opcode::Arraylength => Instruction::Arraylength,
+        Opcode::Athrow => Instruction::Athrow,
+        Opcode::Monitorenter => Instruction::Monitorenter,
+        Opcode::Monitorexit => Instruction::Monitorexit,
+        _ => unreachable!("{opcode:?} takes operands"),
+    }
+}
+
+pub(crate) fn with_local_index(opcode: Opcode, index: u16) -> Instruction {
+    match opcode {
+        Opcode::Iload => Instruction::Iload(index),
+        Opcode::Lload => Instruction::Lload(index),
+        Opcode::Fload => Instruction::Fload(index),
+        Opcode::Dload => Instruction::Dload(index),
+        Opcode::Aload => Instruction::Aload(index),
+        Opcode::Istore => Instruction::Istore(index),
+        Opcode::Lstore => Instruction::Lstore(index),
+        Opcode::Fstore => Instruction::Fstore(index),
+        Opcode::Dstore => Instruction::Dstore(index),
+        Opcode::Astore => Instruction::Astore(index),
+        Opcode::Ret => Instruction::Ret(index),
+        _ => unreachable!("{opcode:?} doesn't take these operands"),
+    }
+}
+
+pub(crate) fn with_constant_pool_index(opcode: Opcode, index: CpIndex) -> Instruction {
+    match opcode {
+        Opcode::Ldc => Instruction::Ldc(index),
+        Opcode::LdcW => Instruction::LdcW(index),
+        Opcode::Ldc2W => Instruction::Ldc2W(index),
+        Opcode::Getstatic => Instruction::Getstatic(index),
+        Opcode::Putstatic => Instruction::Putstatic(index),
+        Opcode::Getfield => Instruction::Getfield(index),
+        Opcode::Putfield => Instruction::Putfield(index),
+        Opcode::Invokevirtual => Instruction::Invokevirtual(index),
+        Opcode::Invokespecial => Instruction::Invokespecial(index),
+        Opcode::Invokestatic => Instruction::Invokestatic(index),
+        Opcode::New => Instruction::New(index),
+        Opcode::Anewarray => Instruction::Anewarray(index),
+        Opcode::Checkcast => Instruction::Checkcast(index),
+        Opcode::Instanceof => Instruction::Instanceof(index),
+        Opcode::Invokedynamic => Instruction::Invokedynamic(index),
+        _ => unreachable!("{opcode:?} doesn't take these operands"),
+    }
+}
+
+pub(crate) fn with_branch_target(opcode: Opcode, target: BranchTarget) -> Instruction {
+    match opcode {
+        Opcode::Ifeq => Instruction::Ifeq(target),
+        Opcode::Ifne => Instruction::Ifne(target),
+        Opcode::Iflt => Instruction::Iflt(target),
+        Opcode::Ifge => Instruction::Ifge(target),
+        Opcode::Ifgt => Instruction::Ifgt(target),
+        Opcode::Ifle => Instruction::Ifle(target),
+        Opcode::IfIcmpeq => Instruction::IfIcmpeq(target),
+        Opcode::IfIcmpne => Instruction::IfIcmpne(target),
+        Opcode::IfIcmplt => Instruction::IfIcmplt(target),
+        Opcode::IfIcmpge => Instruction::IfIcmpge(target),
+        Opcode::IfIcmpgt => Instruction::IfIcmpgt(target),
+        Opcode::IfIcmple => Instruction::IfIcmple(target),
+        Opcode::IfAcmpeq => Instruction::IfAcmpeq(target),
+        Opcode::IfAcmpne => Instruction::IfAcmpne(target),
+        Opcode::Goto => Instruction::Goto(target),
+        Opcode::Jsr => Instruction::Jsr(target),
+        Opcode::Ifnull => Instruction::Ifnull(target),
+        Opcode::Ifnonnull => Instruction::Ifnonnull(target),
+        Opcode::GotoW => Instruction::GotoW(target),
+        Opcode::JsrW => Instruction::JsrW(target),
+        _ => unreachable!("{opcode:?} doesn't take these operands"),
+    }
+}
+
+impl Instruction {
+    /// Where the instruction can jump to, not counting the next instruction it may fall through to.
+    pub fn branch_targets(&self) -> Vec<BranchTarget> {
+        match self {
+            Instruction::Tableswitch { default, targets, .. } => std::iter::once(*default).chain(targets.iter().copied()).collect(),
+            Instruction::Lookupswitch { default, pairs } => std::iter::once(*default).chain(pairs.iter().map(|(_, target)| *target)).collect(),
+            single_branch!(target) => vec![*target],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The same targets as [`branch_targets`](Instruction::branch_targets), for retargeting the instruction.
+    pub fn branch_targets_mut(&mut self) -> Vec<&mut BranchTarget> {
+        match self {
+            Instruction::Tableswitch { default, targets, .. } => std::iter::once(default).chain(targets.iter_mut()).collect(),
+            Instruction::Lookupswitch { default, pairs } => std::iter::once(default).chain(pairs.iter_mut().map(|(_, target)| target)).collect(),
+            single_branch!(target) => vec![target],
+            _ => Vec::new(),
+        }
+    }
+
+    /// The constant pool entry the instruction refers to.
+    pub fn constant_pool_index(&self) -> Option<CpIndex> {
+        match self {
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) | Instruction::Getstatic(index)
+            | Instruction::Putstatic(index) | Instruction::Getfield(index) | Instruction::Putfield(index)
+            | Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) | Instruction::Invokestatic(index)
+            | Instruction::Invokedynamic(index) | Instruction::New(index) | Instruction::Anewarray(index)
+            | Instruction::Checkcast(index) | Instruction::Instanceof(index)
+            | Instruction::Invokeinterface { index, .. } | Instruction::Multianewarray { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// The local variable the instruction loads, stores, increments or returns through, including
+    /// the one implied by forms like `iload_1`.
+    pub fn local_index(&self) -> Option<u16> {
+        match self {
+            Instruction::Iload(index) | Instruction::Lload(index) | Instruction::Fload(index) | Instruction::Dload(index)
+            | Instruction::Aload(index) | Instruction::Istore(index) | Instruction::Lstore(index)
+            | Instruction::Fstore(index) | Instruction::Dstore(index) | Instruction::Astore(index)
+            | Instruction::Ret(index)
+            | Instruction::Iinc { index, .. } => Some(*index),
+            Instruction::Iload0 | Instruction::Lload0 | Instruction::Fload0 | Instruction::Dload0
+            | Instruction::Aload0 | Instruction::Istore0 | Instruction::Lstore0 | Instruction::Fstore0
+            | Instruction::Dstore0 | Instruction::Astore0 => Some(0),
+            Instruction::Iload1 | Instruction::Lload1 | Instruction::Fload1 | Instruction::Dload1
+            | Instruction::Aload1 | Instruction::Istore1 | Instruction::Lstore1 | Instruction::Fstore1
+            | Instruction::Dstore1 | Instruction::Astore1 => Some(1),
+            Instruction::Iload2 | Instruction::Lload2 | Instruction::Fload2 | Instruction::Dload2
+            | Instruction::Aload2 | Instruction::Istore2 | Instruction::Lstore2 | Instruction::Fstore2
+            | Instruction::Dstore2 | Instruction::Astore2 => Some(2),
+            Instruction::Iload3 | Instruction::Lload3 | Instruction::Fload3 | Instruction::Dload3
+            | Instruction::Aload3 | Instruction::Istore3 | Instruction::Lstore3 | Instruction::Fstore3
+            | Instruction::Dstore3 | Instruction::Astore3 => Some(3),
+            _ => None,
+        }
+    }
+
+    /// The value the instruction pushes from its opcode and operands alone, like `iconst_2`,
+    /// `bipush 100` or `aconst_null`. `ldc` isn't one, its value is in the constant pool.
+    pub fn pushed_literal(&self) -> Option<Literal> {
+        Some(match self {
+            Instruction::AconstNull => Literal::Null,
+            Instruction::IconstM1 => Literal::Int(-1),
+            Instruction::Iconst0 => Literal::Int(0),
+            Instruction::Iconst1 => Literal::Int(1),
+            Instruction::Iconst2 => Literal::Int(2),
+            Instruction::Iconst3 => Literal::Int(3),
+            Instruction::Iconst4 => Literal::Int(4),
+            Instruction::Iconst5 => Literal::Int(5),
+            Instruction::Lconst0 => Literal::Long(0),
+            Instruction::Lconst1 => Literal::Long(1),
+            Instruction::Fconst0 => Literal::Float(0.0),
+            Instruction::Fconst1 => Literal::Float(1.0),
+            Instruction::Fconst2 => Literal::Float(2.0),
+            Instruction::Dconst0 => Literal::Double(0.0),
+            Instruction::Dconst1 => Literal::Double(1.0),
+            Instruction::Bipush(value) => Literal::Int(*value as i32),
+            Instruction::Sipush(value) => Literal::Int(*value as i32),
+            _ => return None,
+        })
+    }
+
+    /// The shortest instruction pushing `literal` without the constant pool, the inverse of
+    /// [`Instruction::pushed_literal`]. The rest take `ldc`.
+    pub fn push(literal: &Literal) -> Option<Instruction> {
+        Some(match *literal {
+            Literal::Null => Instruction::AconstNull,
+            Literal::Int(-1) => Instruction::IconstM1,
+            Literal::Int(0) => Instruction::Iconst0,
+            Literal::Int(1) => Instruction::Iconst1,
+            Literal::Int(2) => Instruction::Iconst2,
+            Literal::Int(3) => Instruction::Iconst3,
+            Literal::Int(4) => Instruction::Iconst4,
+            Literal::Int(5) => Instruction::Iconst5,
+            Literal::Int(value) => match (i8::try_from(value), i16::try_from(value)) {
+                (Ok(value), _) => Instruction::Bipush(value),
+                (_, Ok(value)) => Instruction::Sipush(value),
+                _ => return None,
+            },
+            Literal::Long(0) => Instruction::Lconst0,
+            Literal::Long(1) => Instruction::Lconst1,
+            // Only +0.0 has a constant, -0.0 compares equal but isn't the same value
+            Literal::Float(value) if value.to_bits() == 0f32.to_bits() => Instruction::Fconst0,
+            Literal::Float(1.0) => Instruction::Fconst1,
+            Literal::Float(2.0) => Instruction::Fconst2,
+            Literal::Double(value) if value.to_bits() == 0f64.to_bits() => Instruction::Dconst0,
+            Literal::Double(1.0) => Instruction::Dconst1,
+            _ => return None,
+        })
+    }
+
+    /// The type, whether it is a store and the local variable of a load or store, like
+    /// `(Type::Long, true, 1)` for `lstore_1`.
+    pub fn local_access(&self) -> Option<(Type, bool, u16)> {
+        let (ty, store) = match self {
+            Instruction::Iload(_) | Instruction::Iload0 | Instruction::Iload1 | Instruction::Iload2 | Instruction::Iload3 => (Type::Int, false),
+            Instruction::Lload(_) | Instruction::Lload0 | Instruction::Lload1 | Instruction::Lload2 | Instruction::Lload3 => (Type::Long, false),
+            Instruction::Fload(_) | Instruction::Fload0 | Instruction::Fload1 | Instruction::Fload2 | Instruction::Fload3 => (Type::Float, false),
+            Instruction::Dload(_) | Instruction::Dload0 | Instruction::Dload1 | Instruction::Dload2 | Instruction::Dload3 => (Type::Double, false),
+            Instruction::Aload(_) | Instruction::Aload0 | Instruction::Aload1 | Instruction::Aload2 | Instruction::Aload3 => (Type::Reference, false),
+            Instruction::Istore(_) | Instruction::Istore0 | Instruction::Istore1 | Instruction::Istore2 | Instruction::Istore3 => (Type::Int, true),
+            Instruction::Lstore(_) | Instruction::Lstore0 | Instruction::Lstore1 | Instruction::Lstore2 | Instruction::Lstore3 => (Type::Long, true),
+            Instruction::Fstore(_) | Instruction::Fstore0 | Instruction::Fstore1 | Instruction::Fstore2 | Instruction::Fstore3 => (Type::Float, true),
+            Instruction::Dstore(_) | Instruction::Dstore0 | Instruction::Dstore1 | Instruction::Dstore2 | Instruction::Dstore3 => (Type::Double, true),
+            Instruction::Astore(_) | Instruction::Astore0 | Instruction::Astore1 | Instruction::Astore2 | Instruction::Astore3 => (Type::Reference, true),
+            _ => return None,
+        };
+        Some((ty, store, self.local_index()?))
+    }
+
+    /// The number of bytes the instruction takes when encoded at `pc`. Local variable operands
+    /// that don't fit a byte take the `wide` form.
+    pub fn length(&self, pc: usize) -> usize {
+        match self {
+            Instruction::Tableswitch { targets, .. } => 1 + switch_padding(pc) + 12 + 4 * targets.len(),
+            Instruction::Lookupswitch { pairs, .. } => 1 + switch_padding(pc) + 8 + 8 * pairs.len(),
+            _ if self.is_wide() => 2 + 2 * self.opcode().operands().length().unwrap_or(0),
+            _ => 1 + self.opcode().operands().length().unwrap_or(0),
+        }
+    }
+
+    fn is_wide(&self) -> bool {
+        match self {
+            Instruction::Iinc { index, increment } => *index > u8::MAX as u16 || i8::try_from(*increment).is_err(),
+            _ => self.opcode().operands() == Operands::LocalIndex && self.local_index().is_some_and(|index| index > u8::MAX as u16),
+        }
+    }
+
+    fn encode_at(&self, code: &mut Vec<u8>) -> Result<(), EncodeErrorKind> {
+        let pc = code.len();
+        let offset = |target: BranchTarget| target.0 as i64 - pc as i64;
+        let short_offset = |target: BranchTarget| i16::try_from(offset(target)).map_err(|_| EncodeErrorKind::BranchTooFar(offset(target)));
+        let wide_offset = |target: BranchTarget| i32::try_from(offset(target)).map_err(|_| EncodeErrorKind::BranchTooFar(offset(target)));
+        if self.is_wide() {
+            code.push(Opcode::Wide as u8);
+        }
+        code.push(self.opcode() as u8);
+        match self {
+            Instruction::Bipush(value) => code.push(*value as u8),
+            Instruction::Sipush(value) => code.extend_from_slice(&value.to_be_bytes()),
+            Instruction::Iinc { index, increment } if self.is_wide() => {
+                code.extend_from_slice(&index.to_be_bytes());
+                code.extend_from_slice(&increment.to_be_bytes());
+            }
+            Instruction::Iinc { index, increment } => code.extend_from_slice(&[*index as u8, *increment as i8 as u8]),
+            Instruction::Ldc(CpIndex(index)) => code.push(u8::try_from(*index).map_err(|_| EncodeErrorKind::ConstantIndexTooLarge(*index))?),
+            Instruction::Invokeinterface { index, count } => {
+                code.extend_from_slice(&index.0.to_be_bytes());
+                code.extend_from_slice(&[*count, 0]);
+            }
+            Instruction::Invokedynamic(index) => {
+                code.extend_from_slice(&index.0.to_be_bytes());
+                code.extend_from_slice(&[0, 0]);
+            }
+            Instruction::Multianewarray { index, dimensions } => {
+                code.extend_from_slice(&index.0.to_be_bytes());
+                code.push(*dimensions);
+            }
+            Instruction::Newarray(array_type) => code.push(*array_type as u8),
+            Instruction::Tableswitch { default, low, high, targets } => {
+                code.resize(code.len() + switch_padding(pc), 0);
+                code.extend_from_slice(&wide_offset(*default)?.to_be_bytes());
+                code.extend_from_slice(&low.to_be_bytes());
+                code.extend_from_slice(&high.to_be_bytes());
+                for target in targets {
+                    code.extend_from_slice(&wide_offset(*target)?.to_be_bytes());
+                }
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                code.resize(code.len() + switch_padding(pc), 0);
+                code.extend_from_slice(&wide_offset(*default)?.to_be_bytes());
+                code.extend_from_slice(&(pairs.len() as i32).to_be_bytes());
+                for (key, target) in pairs {
+                    code.extend_from_slice(&key.to_be_bytes());
+                    code.extend_from_slice(&wide_offset(*target)?.to_be_bytes());
+                }
+            }
+            _ => match self.opcode().operands() {
+                Operands::LocalIndex if self.is_wide() => code.extend_from_slice(&self.local_index().unwrap_or_default().to_be_bytes()),
+                Operands::LocalIndex => code.push(self.local_index().unwrap_or_default() as u8),
+                Operands::ConstantPoolIndex => code.extend_from_slice(&self.constant_pool_index().unwrap_or(CpIndex(0)).0.to_be_bytes()),
+                Operands::Branch => code.extend_from_slice(&short_offset(self.branch_targets()[0])?.to_be_bytes()),
+                Operands::WideBranch => code.extend_from_slice(&wide_offset(self.branch_targets()[0])?.to_be_bytes()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Switch operands start at a multiple of four from the start of the code.
+fn switch_padding(pc: usize) -> usize {
+    (4 - (pc + 1) % 4) % 4
+}
+
+/// Encodes instructions laid out one after another from pc 0. Branch targets are taken as they
+/// are, so they have to be the pcs the instructions end up at. Operands that don't fit the
+/// instruction's form are an error rather than truncated.
+pub fn encode(instructions: &[Instruction]) -> Result<Vec<u8>, EncodeError> {
+    let mut code = Vec::new();
+    for instruction in instructions {
+        let pc = code.len();
+        instruction.encode_at(&mut code).map_err(|kind| EncodeError { kind, pc })?;
+    }
+    Ok(code)
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum EncodeErrorKind {
+    /// `ldc` only takes a one byte index, `ldc_w` takes any.
+    ConstantIndexTooLarge(u16),
+    /// The offset doesn't fit the branch instruction's form.
+    BranchTooFar(i64),
+}
+
+/// Why instructions could not be encoded, and the pc of the instruction at fault.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct EncodeError {
+    pub kind: EncodeErrorKind,
+    pub pc: usize,
+}
+
+impl fmt::Display for EncodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeErrorKind::ConstantIndexTooLarge(index) => write!(f, "Constant pool index {index} doesn't fit ldc, use ldc_w"),
+            EncodeErrorKind::BranchTooFar(offset) => write!(f, "The branch offset {offset} doesn't fit the instruction"),
+        }
+    }
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pc {}", self.kind, self.pc)
+    }
+}
+
+impl Error for EncodeError {}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DecodeErrorKind {
+    /// The instruction's operands run past the end of the code.
+    Truncated,
+    UnknownOpcode(u8),
+    /// `wide` is followed by an opcode it can't modify.
+    InvalidWide(u8),
+    InvalidArrayType(u8),
+    InvalidSwitch { low: i32, high: i32 },
+    InvalidPairCount(i32),
+    /// The target is outside the code or in the middle of an instruction.
+    InvalidBranchTarget(i64),
+}
+
+/// Why code could not be decoded, and the pc of the instruction at fault.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DecodeError {
+    pub kind: DecodeErrorKind,
+    pub pc: usize,
+}
+
+impl fmt::Display for DecodeErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeErrorKind::Truncated => write!(f, "The instruction runs past the end of the code"),
+            DecodeErrorKind::UnknownOpcode(opcode) => write!(f, "Opcode 0x{opcode:02x} is not defined"),
+            DecodeErrorKind::InvalidWide(opcode) => write!(f, "Opcode 0x{opcode:02x} can't be modified by wide"),
+            DecodeErrorKind::InvalidArrayType(atype) => write!(f, "Array type {atype} is not defined"),
+            DecodeErrorKind::InvalidSwitch { low, high } => write!(f, "The switch's low value {low} is above its high value {high}"),
+            DecodeErrorKind::InvalidPairCount(count) => write!(f, "The switch's pair count {count} is negative"),
+            DecodeErrorKind::InvalidBranchTarget(target) => write!(f, "Branch target {target} is not the start of an instruction"),
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at pc {}", self.kind, self.pc)
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Decodes the `code` of a `Code` attribute into its instructions, each with its pc.
+pub fn decode(code: &[u8]) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+    let mut code_reader = CodeReader { code, position: 0 };
+    let mut instructions = Vec::new();
+    while code_reader.position < code.len() {
+        let pc = code_reader.position;
+        let instruction = code_reader.read_instruction().map_err(|kind| DecodeError { kind, pc })?;
+        instructions.push((pc, instruction));
+    }
+
+    let mut starts = vec![false; code.len()];
+    for (pc, _) in &instructions {
+        starts[*pc] = true;
+    }
+    for (pc, instruction) in &instructions {
+        if let Some(BranchTarget(target)) = instruction.branch_targets().into_iter().find(|BranchTarget(target)| !starts[*target]) {
+            return Err(DecodeError { kind: DecodeErrorKind::InvalidBranchTarget(target as i64), pc: *pc });
+        }
+    }
+    Ok(instructions)
+}
+
+struct CodeReader<'a> {
+    code: &'a [u8],
+    position: usize,
+}
+
+impl<'a> CodeReader<'a> {
+    fn read_instruction(&mut self) -> Result<Instruction, DecodeErrorKind> {
+        let pc = self.position;
+        let byte = self.read_u8()?;
+        let opcode = Opcode::from_u8(byte).ok_or(DecodeErrorKind::UnknownOpcode(byte))?;
+        let instruction = match opcode.operands() {
+            Operands::None => without_operands(opcode),
+            Operands::Byte => Instruction::Bipush(self.read_u8()? as i8),
+            Operands::Short => Instruction::Sipush(self.read_u16()? as i16),
+            Operands::LocalIndex => with_local_index(opcode, self.read_u8()? as u16),
+            Operands::LocalIndexAndConst => Instruction::Iinc {
+                index: self.read_u8()? as u16,
+                increment: self.read_u8()? as i8 as i16,
+            },
+            Operands::ConstantPoolIndexByte => with_constant_pool_index(opcode, CpIndex(self.read_u8()? as u16)),
+            Operands::ConstantPoolIndex => with_constant_pool_index(opcode, CpIndex(self.read_u16()?)),
+            Operands::InvokeInterface => {
+                let index = CpIndex(self.read_u16()?);
+                let count = self.read_u8()?;
+                self.read_u8()?;
+                Instruction::Invokeinterface { index, count }
+            }
+            Operands::InvokeDynamic => {
+                let index = CpIndex(self.read_u16()?);
+                self.read_u16()?;
+                with_constant_pool_index(opcode, index)
+            }
+            Operands::MultiANewArray => Instruction::Multianewarray {
+                index: CpIndex(self.read_u16()?),
+                dimensions: self.read_u8()?,
+            },
+            Operands::ArrayType => {
+                let atype = self.read_u8()?;
+                Instruction::Newarray(ArrayType::from_u8(atype).ok_or(DecodeErrorKind::InvalidArrayType(atype))?)
+            }
+            Operands::Branch => {
+                let offset = self.read_u16()? as i16;
+                with_branch_target(opcode, self.branch_target(pc, offset as i32)?)
+            }
+            Operands::WideBranch => {
+                let offset = self.read_i32()?;
+                with_branch_target(opcode, self.branch_target(pc, offset)?)
+            }
+            Operands::TableSwitch => {
+                self.skip_padding()?;
+                let default = self.read_branch_target(pc)?;
+                let low = self.read_i32()?;
+                let high = self.read_i32()?;
+                if low > high {
+                    return Err(DecodeErrorKind::InvalidSwitch { low, high });
+                }
+                let count = (high as i64 - low as i64 + 1) as usize;
+                let mut targets = Vec::with_capacity(count.min(self.remaining() / 4));
+                for _ in 0..count {
+                    targets.push(self.read_branch_target(pc)?);
+                }
+                Instruction::Tableswitch { default, low, high, targets }
+            }
+            Operands::LookupSwitch => {
+                self.skip_padding()?;
+                let default = self.read_branch_target(pc)?;
+                let count = self.read_i32()?;
+                if count < 0 {
+                    return Err(DecodeErrorKind::InvalidPairCount(count));
+                }
+                let mut pairs = Vec::with_capacity((count as usize).min(self.remaining() / 8));
+                for _ in 0..count {
+                    let key = self.read_i32()?;
+                    pairs.push((key, self.read_branch_target(pc)?));
+                }
+                Instruction::Lookupswitch { default, pairs }
+            }
+            Operands::Wide => {
+                let byte = self.read_u8()?;
+                match Opcode::from_u8(byte) {
+                    Some(Opcode::Iinc) => Instruction::Iinc {
+                        index: self.read_u16()?,
+                        increment: self.read_u16()? as i16,
+                    },
+                    Some(opcode) if opcode.operands() == Operands::LocalIndex => with_local_index(opcode, self.read_u16()?),
+                    _ => return Err(DecodeErrorKind::InvalidWide(byte)),
+                }
+            }
+        };
+        Ok(instruction)
+    }
+
+    fn remaining(&self) -> usize {
+        self.code.len() - self.position
+    }
+
+    fn read_bytes<const N: usize>(&mut self) -> Result<[u8; N], DecodeErrorKind> {
+        let bytes = self.code.get(self.position..self.position + N).ok_or(DecodeErrorKind::Truncated)?;
+        self.position += N;
+        let mut buffer = [0; N];
+        buffer.copy_from_slice(bytes);
+        Ok(buffer)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodeErrorKind> {
+        Ok(u8::from_be_bytes(self.read_bytes()?))
+    }
+
+    fn read_u16(&mut self) -> Result<u16, DecodeErrorKind> {
+        Ok(u16::from_be_bytes(self.read_bytes()?))
+    }
+
+    fn read_i32(&mut self) -> Result<i32, DecodeErrorKind> {
+        Ok(i32::from_be_bytes(self.read_bytes()?))
+    }
+
+    /// Switch operands start at a multiple of four from the start of the code.
+    fn skip_padding(&mut self) -> Result<(), DecodeErrorKind> {
+        while !self.position.is_multiple_of(4) {
+            self.read_u8()?;
+        }
+        Ok(())
+    }
+
+    fn read_branch_target(&mut self, pc: usize) -> Result<BranchTarget, DecodeErrorKind> {
+        let offset = self.read_i32()?;
+        self.branch_target(pc, offset)
+    }
+
+    fn branch_target(&self, pc: usize, offset: i32) -> Result<BranchTarget, DecodeErrorKind> {
+        let target = pc as i64 + offset as i64;
+        if target < 0 || target >= self.code.len() as i64 {
+            return Err(DecodeErrorKind::InvalidBranchTarget(target));
+        }
+        Ok(BranchTarget(target as usize))
+    }
+}
+
+#[cfg(test)]
+proptest::proptest! {
+    #[test]
+    fn test_decoder_survives_random_code(code in proptest::collection::vec(proptest::prelude::any::<u8>(), 0..512)) {
+        let _ = decode(&code);
+    }
+}
+
+#[test]
+fn test_decodes_method_code() {
+    let java_class = crate::JavaClass::load_from_file("MyClass.class").unwrap();
+    let constructor = java_class.methods.iter().find(|method| method.name == "<init>").unwrap();
+    let instructions = constructor.code().unwrap().instructions().unwrap();
+    assert_eq!((0, Instruction::Aload0), instructions[0]);
+    assert_eq!((1, Instruction::Invokespecial(CpIndex(1))), instructions[1]);
+    assert_eq!((4, Instruction::Aload0), instructions[2]);
+    assert_eq!((5, Instruction::Bipush(9)), instructions[3]);
+    assert_eq!((7, Instruction::Putfield(CpIndex(7))), instructions[4]);
+}
+
+#[test]
+fn test_decodes_wide_switches_and_wide_branches() {
+    let code = [
+        0xc4, 0x15, 0x01, 0x00, // wide iload 256
+        0xc4, 0x84, 0x00, 0x01, 0xff, 0x00, // wide iinc 1 -256
+        0xaa, 0x00, // tableswitch, padded to pc 12
+        0x00, 0x00, 0x00, 0x1b, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x16, 0x00, 0x00, 0x00, 0x1b,
+        0xc8, 0xff, 0xff, 0xff, 0xe0, // goto_w 0
+        0xb1, // return
+    ];
+    let instructions = decode(&code).unwrap();
+    assert_eq!(vec![
+        (0, Instruction::Iload(256)),
+        (4, Instruction::Iinc { index: 1, increment: -256 }),
+        (10, Instruction::Tableswitch { default: BranchTarget(37), low: 1, high: 2, targets: vec![BranchTarget(32), BranchTarget(37)] }),
+        (32, Instruction::GotoW(BranchTarget(0))),
+        (37, Instruction::Return),
+    ], instructions);
+}
+
+#[test]
+fn test_invalid_code_is_an_error() {
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::Truncated, pc: 1 }), decode(&[0x00, 0x11, 0x01]));
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::UnknownOpcode(0xca), pc: 0 }), decode(&[0xca]));
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::InvalidWide(0x60), pc: 0 }), decode(&[0xc4, 0x60]));
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::InvalidBranchTarget(2), pc: 0 }), decode(&[0xa7, 0x00, 0x02, 0xb1]));
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::InvalidBranchTarget(-1), pc: 0 }), decode(&[0xa7, 0xff, 0xff]));
+    assert_eq!(Err(DecodeError { kind: DecodeErrorKind::Truncated, pc: 0 }), decode(&[0xaa, 0, 0, 0, 0, 0, 0, 0, 0x80, 0, 0, 0, 0x7f, 0xff, 0xff, 0xff]));
+}
+
+#[test]
+fn test_encoding_reproduces_decoded_code() {
+    for class_file in ["MyClass.class", "Flow.class"] {
+        let java_class = crate::JavaClass::load_from_file(class_file).unwrap();
+        for code in java_class.methods.iter().filter_map(|method| method.code()) {
+            let instructions: Vec<Instruction> = code.instructions().unwrap().into_iter().map(|(_, instruction)| instruction).collect();
+            assert_eq!(Ok(code.code.clone()), encode(&instructions));
+        }
+    }
+    let wide = [0xc4, 0x15, 0x01, 0x00, 0xc4, 0x84, 0x00, 0x01, 0xff, 0x00, 0xb1];
+    let instructions: Vec<Instruction> = decode(&wide).unwrap().into_iter().map(|(_, instruction)| instruction).collect();
+    assert_eq!(Ok(wide.to_vec()), encode(&instructions));
+}
+
+#[test]
+fn test_operands_that_dont_fit_are_an_error() {
+    let too_far = [Instruction::Nop, Instruction::Goto(BranchTarget(40_000))];
+    assert_eq!(Err(EncodeError { kind: EncodeErrorKind::BranchTooFar(39_999), pc: 1 }), encode(&too_far));
+    let too_large = [Instruction::Ldc(CpIndex(256))];
+    assert_eq!(Err(EncodeError { kind: EncodeErrorKind::ConstantIndexTooLarge(256), pc: 0 }), encode(&too_large));
+}
+
+#[test]
+fn test_local_access() {
+    assert_eq!(Some((Type::Long, true, 1)), Instruction::Lstore1.local_access());
+    assert_eq!(Some((Type::Reference, false, 300)), Instruction::Aload(300).local_access());
+    assert_eq!(None, Instruction::Iinc { index: 1, increment: 1 }.local_access());
+    assert_eq!(None, Instruction::Ret(1).local_access());
+}
+
+#[test]
+fn test_push_is_the_inverse_of_pushed_literal() {
+    for instruction in [Instruction::AconstNull, Instruction::IconstM1, Instruction::Iconst5, Instruction::Bipush(-100), Instruction::Sipush(1000),
+        Instruction::Lconst1, Instruction::Fconst0, Instruction::Fconst2, Instruction::Dconst0, Instruction::Dconst1] {
+        assert_eq!(Some(instruction.clone()), Instruction::push(&instruction.pushed_literal().unwrap()));
+    }
+    assert_eq!(None, Instruction::push(&Literal::Int(100_000)));
+    assert_eq!(None, Instruction::push(&Literal::Float(-0.0)));
+    assert_eq!(None, Instruction::push(&Literal::String("text".to_string())));
+}
@@ -1,11 +1,480 @@
-use crate::heap::Heap;
+use std::fmt;
+use std::io::{self, Write};
+use std::rc::Rc;
+use crate::JavaClass;
+use crate::class::Class;
+use crate::execution::{self, ExecutionError};
+use crate::frames::Value;
+use crate::heap::{Heap, ObjectData};
+use crate::method_area::MethodArea;
 use crate::thread::Thread;
 
+/// How many bytes objects can take.
+const HEAP_SIZE: usize = 64 * 1024 * 1024;
+
+/// A stand-in for `java/lang/Object` until the class library can be loaded, with the constructor
+/// every other constructor ends up invoking.
+const OBJECT: &str = "
+    .class public super java/lang/Object
+    .method public <init> ()V
+        .limit stack 0; .limit locals 1
+        return
+    .end method
+";
+
+/// The rest of the class library the JVM implements itself: strings, and `System.out` to print
+/// with. The methods of `PrintStream` are added for each type they print.
+const CLASS_LIBRARY: [&str; 3] = [
+    ".class public final super java/lang/String",
+    ".class public final super java/lang/System
+    .field public static final out Ljava/io/PrintStream;",
+    ".class public super java/io/PrintStream",
+];
+
+/// The parameter types `PrintStream.print` and `println` are overloaded for.
+const PRINTED_TYPES: [&str; 8] = ["Z", "C", "I", "J", "F", "D", "Ljava/lang/String;", "Ljava/lang/Object;"];
+
+/// A virtual machine that classes are loaded into and methods run on.
 pub struct Jvm {
-    heap: Heap,
-    threads: Vec<Thread>,
+    pub(crate) method_area: MethodArea,
+    pub(crate) heap: Heap,
+    /// Where `System.out` writes to.
+    pub(crate) output: Box<dyn Write>,
+    /// How many classes were spun for lambdas, which numbers their names.
+    pub(crate) lambda_classes: usize,
+}
+
+impl Default for Jvm {
+    fn default() -> Self {
+        let mut object = crate::assembler::assemble(OBJECT).expect("java/lang/Object assembles");
+        object.super_class_name = None;
+        let mut method_area = MethodArea::new();
+        method_area.add(Class::new(object).expect("java/lang/Object decodes")).expect("java/lang/Object links");
+        let mut jvm = Jvm { method_area, heap: Heap::new(HEAP_SIZE), output: Box::new(io::stdout()), lambda_classes: 0 };
+        for source in CLASS_LIBRARY {
+            let mut source = source.to_string();
+            if source.ends_with("PrintStream") {
+                source.push_str("\n.method public native println ()V\n.end method");
+                for printed_type in PRINTED_TYPES {
+                    for name in ["print", "println"] {
+                        source.push_str(&format!("\n.method public native {name} ({printed_type})V\n.end method"));
+                    }
+                }
+            }
+            let java_class = crate::assembler::assemble(&source).expect("the class library assembles");
+            jvm.load_class(java_class).expect("the class library loads");
+        }
+        let out = jvm.allocate("java/io/PrintStream").expect("System.out is allocated");
+        let system = jvm.method_area.class("java/lang/System").expect("java/lang/System is loaded");
+        system.static_values.borrow_mut()[0] = out;
+        jvm
+    }
 }
 
 impl Jvm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads and links a class, its static fields starting out with their `ConstantValue`.
+    pub fn load_class(&mut self, java_class: JavaClass) -> Result<Rc<Class>, ExecutionError> {
+        let class = Class::new(java_class).map_err(|error| ExecutionError::new(error.to_string()))?;
+        let class = self.method_area.add(class)?;
+        for field in class.fields.iter().filter(|field| field.is_static()) {
+            if let Some(index) = field.constant_value {
+                let value = execution::constant(self, &class.constant_pool, index)?;
+                class.static_values.borrow_mut()[field.index] = value;
+            }
+        }
+        Ok(class)
+    }
+
+    /// Sends what `System.out` prints to `output` rather than the standard output.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.output = Box::new(output);
+    }
+
+    /// Runs a static method on a new thread, and returns what it returned, `None` for `void`
+    /// methods.
+    pub fn invoke_static(&mut self, class_name: &str, name: &str, descriptor: &str, arguments: &[Value]) -> Result<Option<Value>, ExecutionError> {
+        let Some(class) = self.method_area.class(class_name) else {
+            return Err(ExecutionError::new(format!("NoClassDefFoundError: {class_name}")));
+        };
+        let Some(method) = class.method(name, descriptor).filter(|method| method.is_static()).cloned() else {
+            return Err(ExecutionError::new(format!("NoSuchMethodError: {class_name}.{name}{descriptor}")));
+        };
+        Thread::new().run_method(self, class, method, arguments)
+    }
+
+    /// Allocates an object of a loaded class without running a constructor, for passing to the
+    /// methods invoked.
+    pub fn allocate(&mut self, class_name: &str) -> Result<Value, ExecutionError> {
+        let class = self.method_area.loaded_class(class_name)?;
+        if class.is_interface() || class.flags.is_abstract() {
+            return Err(ExecutionError::new(format!("InstantiationError: {class_name}")));
+        }
+        let reference = self.heap.allocate_object(class)?;
+        Ok(Value::Reference(Some(reference)))
+    }
+
+    /// The `java/lang/String` of a string literal, the same object for equal literals.
+    pub fn intern(&mut self, string: &str) -> Result<Value, ExecutionError> {
+        let class = self.method_area.loaded_class("java/lang/String")?;
+        let reference = self.heap.intern(class, string)?;
+        Ok(Value::Reference(Some(reference)))
+    }
+
+    /// A new `java/lang/String`.
+    pub fn new_string(&mut self, string: String) -> Result<Value, ExecutionError> {
+        let class = self.method_area.loaded_class("java/lang/String")?;
+        let reference = self.heap.allocate_with(class, ObjectData::String(string))?;
+        Ok(Value::Reference(Some(reference)))
+    }
+
+    /// The characters of a `java/lang/String`, `None` for other values.
+    pub fn string(&self, value: Value) -> Option<&str> {
+        let Value::Reference(Some(reference)) = value else { return None };
+        match self.heap.data(reference)? {
+            ObjectData::String(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for Jvm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jvm").field("method_area", &self.method_area).field("heap", &self.heap).finish_non_exhaustive()
+    }
+}
+
+/// A JVM with `MyClass` and the interfaces it implements loaded.
+#[cfg(test)]
+fn jvm_with_my_class() -> Jvm {
+    let mut jvm = Jvm::new();
+    for file in ["A.class", "B.class", "MyClass.class"] {
+        jvm.load_class(JavaClass::load_from_file(file).unwrap()).unwrap();
+    }
+    jvm
+}
+
+#[test]
+fn test_runs_static_int_methods() {
+    let mut jvm = jvm_with_my_class();
+    assert_eq!(Some(Value::Int(54)), jvm.invoke_static("MyClass", "staticIntMethod", "()I", &[]).unwrap());
+
+    // The arithmetic main starts with
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Arithmetic
+        .method public static sum ()I
+            .limit stack 2; .limit locals 3
+            bipush 9; istore_1; bipush 11; istore_2
+            iload_1; iload_2; iadd; ireturn
+        .end method
+    ").unwrap()).unwrap();
+    assert_eq!(Some(Value::Int(20)), jvm.invoke_static("Arithmetic", "sum", "()I", &[]).unwrap());
+}
+
+#[test]
+fn test_moves_wide_values_through_locals_and_the_stack() {
+    let mut jvm = Jvm::new();
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Wide
+        .method public static long ()J
+            .limit stack 6; .limit locals 3
+            ldc2_w 5000000000l; lstore_1; iconst_1; lload_1
+            dup2_x1; pop2; pop; lreturn
+        .end method
+        .method public static double ()D
+            .limit stack 4; .limit locals 2
+            dconst_1; dup2; pop2; dstore_0; dload_0; dreturn
+        .end method
+        .method public static third (IJD)D
+            .limit stack 2; .limit locals 5
+            dload_3; dreturn
+        .end method
+        .method public static torn ()J
+            .limit stack 2; .limit locals 2
+            lconst_1; lstore_0; iconst_0; istore_1; lload_0; lreturn
+        .end method
+        .method public static split ()V
+            .limit stack 2; .limit locals 0
+            lconst_0; pop; return
+        .end method
+    ").unwrap()).unwrap();
+    assert_eq!(Some(Value::Long(5_000_000_000)), jvm.invoke_static("Wide", "long", "()J", &[]).unwrap());
+    assert_eq!(Some(Value::Double(1.0)), jvm.invoke_static("Wide", "double", "()D", &[]).unwrap());
+    assert_eq!(Some(Value::Double(0.5)), jvm.invoke_static("Wide", "third", "(IJD)D", &[Value::Int(1), Value::Long(2), Value::Double(0.5)]).unwrap());
+    let error = jvm.invoke_static("Wide", "torn", "()J", &[]).unwrap_err();
+    assert_eq!("Local variable 0 is read before it is written\n    at Wide.torn()J pc 4", error.to_string());
+    let error = jvm.invoke_static("Wide", "split", "()V", &[]).unwrap_err();
+    assert_eq!("The top 1 operand stack slots split a long", error.message);
+}
+
+#[test]
+fn test_runs_loops_and_switches() {
+    let mut jvm = Jvm::new();
+    jvm.load_class(JavaClass::load_from_file("Flow.class").unwrap()).unwrap();
+    let run = |jvm: &mut Jvm, name: &str, value: i32| jvm.invoke_static("Flow", name, "(I)I", &[Value::Int(value)]).unwrap();
+    assert_eq!(Some(Value::Int(4950)), run(&mut jvm, "loop", 100));
+    assert_eq!(Some(Value::Int(0)), run(&mut jvm, "loop", -5));
+    assert_eq!(Some(Value::Int(20)), run(&mut jvm, "classify", 2));
+    assert_eq!(Some(Value::Int(-1)), run(&mut jvm, "classify", i32::MIN));
+    assert_eq!(Some(Value::Int(3)), run(&mut jvm, "sparse", 1000));
+    assert_eq!(Some(Value::Int(0)), run(&mut jvm, "sparse", 99));
+    assert_eq!(Some(Value::Long(2000000000001)), jvm.invoke_static("Flow", "longs", "(J)J", &[Value::Long(2)]).unwrap());
+
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Legacy
+        .method public static run (Ljava/lang/Object;)I
+            .limit stack 2; .limit locals 3
+            iconst_0; istore_1
+            jsr Add; jsr Add
+            aload_0; ifnonnull NotNull
+            jsr Add
+        NotNull:
+            aload_0; aconst_null; if_acmpeq Done
+            iinc 1 10
+        Done:
+            iload_1; ireturn
+        Add:
+            astore_2; iinc 1 1; ret 2
+        .end method
+    ").unwrap()).unwrap();
+    let run = |jvm: &mut Jvm, value| jvm.invoke_static("Legacy", "run", "(Ljava/lang/Object;)I", &[value]).unwrap();
+    assert_eq!(Some(Value::Int(3)), run(&mut jvm, Value::Reference(None)));
+    let object = jvm.allocate("java/lang/Object").unwrap();
+    assert_eq!(Some(Value::Int(12)), run(&mut jvm, object));
+}
+
+#[test]
+fn test_invokes_methods_through_interfaces_and_classes() {
+    let mut jvm = jvm_with_my_class();
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Caller
+        .method public static viaA (LA;I)I
+            .limit stack 2; .limit locals 2
+            aload_0; iload_1; invokeinterface A.methodFromA(I)I 2; ireturn
+        .end method
+        .method public static sum (LMyClass;)I
+            .limit stack 3; .limit locals 1
+            aload_0; invokevirtual MyClass.intMethod()I
+            invokestatic MyClass.staticIntMethod()I; iadd
+            aload_0; invokespecial java/lang/Object.<init>()V
+            ireturn
+        .end method
+    ").unwrap()).unwrap();
+    let object = jvm.allocate("MyClass").unwrap();
+    assert_eq!(Some(Value::Int(42)), jvm.invoke_static("Caller", "viaA", "(LA;I)I", &[object, Value::Int(40)]).unwrap());
+    assert_eq!(Some(Value::Int(63)), jvm.invoke_static("Caller", "sum", "(LMyClass;)I", &[object]).unwrap());
+    let error = jvm.invoke_static("Caller", "viaA", "(LA;I)I", &[Value::Reference(None), Value::Int(40)]).unwrap_err();
+    assert_eq!("NullPointerException: A.methodFromA(I)I invoked on null\n    at Caller.viaA(LA;I)I pc 2", error.to_string());
+    assert!(jvm.allocate("A").is_err());
+}
+
+#[test]
+fn test_selects_overriding_super_and_default_methods() {
+    let mut jvm = Jvm::new();
+    let sources = [
+        ".class public super Base
+        .method public f ()I
+            .limit stack 1; .limit locals 1
+            iconst_1; ireturn
+        .end method",
+        ".class public super Middle
+        .super Base
+        .method public f ()I
+            .limit stack 1; .limit locals 1
+            iconst_2; ireturn
+        .end method",
+        // Without ACC_SUPER, invokespecial runs the method the reference names.
+        ".class public Old
+        .super Middle
+        .method public static g (LOld;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokespecial Base.f()I; ireturn
+        .end method",
+        ".class public super Derived
+        .super Middle
+        .method public f ()I
+            .limit stack 1; .limit locals 1
+            iconst_3; ireturn
+        .end method
+        .method public static g (LDerived;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokespecial Base.f()I; ireturn
+        .end method
+        .method public static virtual (LBase;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokevirtual Base.f()I; ireturn
+        .end method",
+        ".class public abstract interface I
+        .method public h ()I
+            .limit stack 1; .limit locals 1
+            iconst_4; ireturn
+        .end method",
+        ".class public abstract interface J
+        .implements I
+        .method public h ()I
+            .limit stack 1; .limit locals 1
+            iconst_5; ireturn
+        .end method",
+        ".class public abstract interface L
+        .method public h ()I
+            .limit stack 1; .limit locals 1
+            bipush 6; ireturn
+        .end method",
+        ".class public abstract interface Abstract
+        .method public abstract h ()I
+        .end method",
+        ".class public super Defaults
+        .implements I
+        .implements J
+        .implements Abstract
+        .method public static h (LI;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokeinterface I.h()I 1; ireturn
+        .end method",
+        ".class public super Conflict
+        .implements J
+        .implements L",
+        ".class public super Missing
+        .implements Abstract",
+    ];
+    for source in sources {
+        jvm.load_class(crate::assembler::assemble(source).unwrap()).unwrap();
+    }
+    let run = |jvm: &mut Jvm, class: &str, name: &str, descriptor: &str, receiver: &str| {
+        let object = jvm.allocate(receiver).unwrap();
+        jvm.invoke_static(class, name, descriptor, &[object]).map(Option::unwrap).map_err(|error| error.message)
+    };
+    assert_eq!(Ok(Value::Int(2)), run(&mut jvm, "Derived", "g", "(LDerived;)I", "Derived"));
+    assert_eq!(Ok(Value::Int(1)), run(&mut jvm, "Old", "g", "(LOld;)I", "Old"));
+    assert_eq!(Ok(Value::Int(3)), run(&mut jvm, "Derived", "virtual", "(LBase;)I", "Derived"));
+    assert_eq!(Ok(Value::Int(2)), run(&mut jvm, "Derived", "virtual", "(LBase;)I", "Old"));
+    assert_eq!(Ok(Value::Int(5)), run(&mut jvm, "Defaults", "h", "(LI;)I", "Defaults"));
+    assert_eq!(Err("IncompatibleClassChangeError: Conflict.h()I has conflicting defaults in J, L".to_string()), run(&mut jvm, "Defaults", "h", "(LI;)I", "Conflict"));
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Caller
+        .method public static h (LAbstract;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokeinterface Abstract.h()I 1; ireturn
+        .end method
+    ").unwrap()).unwrap();
+    assert_eq!(Err("AbstractMethodError: Missing.h()I".to_string()), run(&mut jvm, "Caller", "h", "(LAbstract;)I", "Missing"));
+}
+
+#[test]
+fn test_reports_where_execution_failed() {
+    let mut jvm = jvm_with_my_class();
+    let error = jvm.invoke_static("MyClass", "main", "([Ljava/lang/String;)V", &[]).unwrap_err();
+    assert_eq!("MyClass.main([Ljava/lang/String;)V takes 1 argument but was passed 0", error.to_string());
+
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Broken
+        .method public static broken ()I
+            .limit stack 1; .limit locals 1
+            iload_0; ireturn
+        .end method
+    ").unwrap()).unwrap();
+    let error = jvm.invoke_static("Broken", "broken", "()I", &[]).unwrap_err();
+    assert_eq!("Local variable 0 is read before it is written\n    at Broken.broken()I pc 0", error.to_string());
+    assert!(jvm.invoke_static("Missing", "broken", "()I", &[]).is_err());
+}
+
+/// What `System.out` printed, shared with the JVM printing it.
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct Printed(Rc<std::cell::RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for Printed {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(bytes)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_links_string_concatenation_and_lambdas() {
+    let mut jvm = jvm_with_my_class();
+    for file in ["Operation.class", "Lambdas.class"] {
+        jvm.load_class(JavaClass::load_from_file(file).unwrap()).unwrap();
+    }
+    let printed = Printed::default();
+    jvm.set_output(printed.clone());
+    let arguments = [Value::Reference(None)];
+    jvm.invoke_static("MyClass", "main", "([Ljava/lang/String;)V", &arguments).unwrap();
+    jvm.invoke_static("Lambdas", "main", "([Ljava/lang/String;)V", &arguments).unwrap();
+    assert_eq!("Value is 20\nAda has 3/10000000000 (b, true) 0.5 1.0E10 null\n29\n", String::from_utf8(printed.0.take()).unwrap());
+
+    assert_eq!(Some(Value::Int(8)), jvm.invoke_static("Lambdas", "reference", "()I", &[]).unwrap());
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Caller
+        .method public static bound (LLambdas;)I
+            .limit stack 1; .limit locals 1
+            aload_0; invokevirtual Lambdas.bound()I; ireturn
+        .end method
+    ").unwrap()).unwrap();
+    let lambdas = jvm.allocate("Lambdas").unwrap();
+    assert_eq!(Some(Value::Int(42)), jvm.invoke_static("Caller", "bound", "(LLambdas;)I", &[lambdas]).unwrap());
+
+    // Each call site is linked once, spinning one class per lambda.
+    assert_eq!(3, jvm.lambda_classes);
+    assert_eq!(Some(Value::Int(33)), jvm.invoke_static("Lambdas", "capturing", "(I)I", &[Value::Int(3)]).unwrap());
+    assert_eq!(3, jvm.lambda_classes);
+    let describe = |jvm: &mut Jvm, name| {
+        let arguments = [Value::Int(1), Value::Long(-2), Value::Int(0x263A), Value::Int(0), name, Value::Double(f64::NAN), Value::Float(1e-4)];
+        jvm.invoke_static("Lambdas", "describe", "(IJCZLjava/lang/String;DF)Ljava/lang/String;", &arguments).unwrap().unwrap()
+    };
+    let name = jvm.intern("Bo").unwrap();
+    let description = describe(&mut jvm, name);
+    assert_eq!(Some("Bo has 1/-2 (☺, false) NaN 1.0E-4 null"), jvm.string(description));
+    assert_ne!(description, describe(&mut jvm, name));
+    assert_eq!(name, jvm.intern("Bo").unwrap());
+}
+
+#[test]
+fn test_constructs_objects_and_accesses_their_fields() {
+    let mut jvm = jvm_with_my_class();
+    jvm.load_class(crate::assembler::assemble("
+        .class public super Maker
+        .method public static make ()LMyClass;
+            .limit stack 2; .limit locals 0
+            new MyClass; dup; invokespecial MyClass.<init>()V; areturn
+        .end method
+        .method public static privateInt (LMyClass;)I
+            .limit stack 1; .limit locals 1
+            aload_0; getfield MyClass.privateInt:I; ireturn
+        .end method
+        .method public static packageString (LMyClass;)Ljava/lang/String;
+            .limit stack 1; .limit locals 1
+            aload_0; getfield MyClass.packageString:Ljava/lang/String;
+            areturn
+        .end method
+        .method public static increment (LMyClass;)I
+            .limit stack 3; .limit locals 1
+            aload_0; dup; getfield MyClass.publicInt:I; iconst_1; iadd; putfield MyClass.publicInt:I
+            aload_0; getfield MyClass.publicInt:I; ireturn
+        .end method
+        .method public static interface ()V
+            .limit stack 1; .limit locals 0
+            new A; pop; return
+        .end method
+    ").unwrap()).unwrap();
+    let object = jvm.invoke_static("Maker", "make", "()LMyClass;", &[]).unwrap().unwrap();
+    assert_eq!(Some(Value::Int(9)), jvm.invoke_static("Maker", "privateInt", "(LMyClass;)I", &[object]).unwrap());
+    let string = jvm.invoke_static("Maker", "packageString", "(LMyClass;)Ljava/lang/String;", &[object]).unwrap().unwrap();
+    assert_eq!(Some("jjj"), jvm.string(string));
+    assert_eq!(Some(Value::Int(3)), jvm.invoke_static("Maker", "increment", "(LMyClass;)I", &[object]).unwrap());
+    assert_eq!(Some(Value::Int(4)), jvm.invoke_static("Maker", "increment", "(LMyClass;)I", &[object]).unwrap());
 
+    let error = jvm.invoke_static("Maker", "privateInt", "(LMyClass;)I", &[Value::Reference(None)]).unwrap_err();
+    assert_eq!("NullPointerException: MyClass.privateInt:I read on null\n    at Maker.privateInt(LMyClass;)I pc 1", error.to_string());
+    let object = jvm.allocate("java/lang/Object").unwrap();
+    let error = jvm.invoke_static("Maker", "privateInt", "(LMyClass;)I", &[object]).unwrap_err();
+    assert_eq!("java/lang/Object objects have no field MyClass.privateInt:I", error.message);
+    assert_eq!("InstantiationError: A", jvm.invoke_static("Maker", "interface", "()V", &[]).unwrap_err().message);
 }
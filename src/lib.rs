@@ -1,116 +1,179 @@
-mod bytecode;
+pub mod assembler;
+pub mod bytecode;
+pub mod call_site;
+pub mod class;
 pub mod class_printer;
-mod stack;
-mod frames;
-mod thread;
-mod execution;
-mod heap;
-mod jvm;
-mod method_area;
-mod class;
-
-use std::fs::File;
+pub mod class_reader;
+pub mod class_writer;
+pub mod constant_pool;
+pub mod control_flow;
+pub mod dataflow;
+pub mod decompiler;
+pub mod descriptor;
+pub mod disassembler;
+pub mod execution;
+pub mod frames;
+pub mod heap;
+pub mod instruction;
+pub mod jvm;
+pub mod method_area;
+pub mod natives;
+pub mod numeric;
+pub mod optimizer;
+pub mod parse_error;
+pub mod signature;
+pub mod ssa;
+pub mod stack;
+pub mod stack_map;
+pub mod thread;
+pub mod visitor;
+
 use std::io;
-use std::io::prelude::*;
-use std::io::{BufReader};
-use log::{info, debug, trace, warn};
+use crate::class_reader::{ClassReader, ParseMode};
+use crate::disassembler::disassemble;
+use crate::instruction::{decode, DecodeError, Instruction};
+use crate::parse_error::{Diagnostic, ParseError};
+use crate::visitor::JavaClassBuilder;
 
 static VERSIONS: [&str; 20] = ["1.1", "1.2", "1.3", "1.4", "5.0", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16", "17", "18", "19", "20"];
 const VERSION_SHIFT: usize = 45;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolUtf8Info {
     tag: u8,
     pub string: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolIntegerInfo {
     tag: u8,
     pub value: i32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolFloatInfo {
     tag: u8,
     pub value: f32,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolLongInfo {
     tag: u8,
     pub value: i64,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolDoubleInfo {
     tag: u8,
     pub value: f64,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolClassInfo {
     tag: u8,
     pub name_index: usize,
+    pub name: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolStringInfo {
     tag: u8,
     pub name_index: usize,
+    pub string: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolFieldRefInfo {
     tag: u8,
     pub class_index: usize,
     pub name_and_type_index: usize,
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolMethodRefInfo {
     tag: u8,
     pub class_index: usize,
     pub name_and_type_index: usize,
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolInterfaceMethodRefInfo {
     tag: u8,
     pub class_index: usize,
     pub name_and_type_index: usize,
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ConstantPoolNameAndTypeInfo {
     tag: u8,
     pub name_index: usize,
     pub descriptor_index: usize,
+    pub name: String,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DynamicInfo {
     tag: u8,
     pub bootstrap_method_attr_index: usize,
     pub name_and_type_index: usize,
+    pub name: String,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodKindInfo {
     tag: u8,
     pub reference_kind: u8,
     pub reference_index: usize,
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MethodTypeInfo {
+    tag: u8,
+    pub descriptor_index: usize,
+    pub descriptor: String,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct InvokeDynamicInfo {
     tag: u8,
     pub bootstrap_method_attr_index: usize,
     pub name_and_type_index: usize,
+    pub name: String,
+    pub descriptor: String,
 }
 
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstantPoolInfo {
     Utf8(ConstantPoolUtf8Info),
     Integer(ConstantPoolIntegerInfo),
@@ -124,16 +187,22 @@ pub enum ConstantPoolInfo {
     InterfaceMethodRef(ConstantPoolInterfaceMethodRefInfo),
     NameAndType(ConstantPoolNameAndTypeInfo),
     MethodKind(MethodKindInfo),
+    MethodType(MethodTypeInfo),
     Dynamic(DynamicInfo),
     InvokeDynamic(InvokeDynamicInfo),
+    /// The slot following a `Long` or `Double` entry, which the JVMS declares unusable, or one
+    /// lenient parsing couldn't read.
+    Unusable,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeConstantValue {
     pub constant_value_index: usize,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ExceptionTableEntry {
     start_pc: usize,
     end_pc: usize,
@@ -141,13 +210,15 @@ pub struct ExceptionTableEntry {
     catch_pc: usize,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct LineNumberTableEntry {
     start_pc: usize,
     line_number: usize,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeCode {
     attribute_name_index: usize,
     max_stack: usize,
@@ -159,24 +230,30 @@ pub struct AttributeCode {
 }
 
 impl AttributeCode {
-    pub fn print(&self) {
-        // for byte in self.code {
-        //     match byte { _ => {} }
-        // }
+    /// Decodes the bytecode into typed instructions, each with its pc.
+    pub fn instructions(&self) -> Result<Vec<(usize, Instruction)>, DecodeError> {
+        decode(&self.code)
+    }
+
+    pub fn print(&self, constant_pool_infos: &[ConstantPoolInfo]) {
+        print!("{}", disassemble(self, constant_pool_infos));
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeExceptions {
 // TODO
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeSourceFile {
 // TODO
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeLineNumberTable {
     pub attribute_name_index: usize,
     pub attribute_name: String,
@@ -184,27 +261,57 @@ pub struct AttributeLineNumberTable {
     pub line_number_entries: Vec<LineNumberTableEntry>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeLocalVariableTable {
 // TODO
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeInnerClasses {
     // TODO
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeSynthetic {
     // TODO
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeDeprecated {
     // TODO
 }
 
-#[derive(Debug, PartialEq)]
+/// The bootstrap methods of the class, which `invokedynamic` call sites and dynamic constants
+/// refer to by their position in the table.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttributeBootstrapMethods {
+    pub bootstrap_methods: Vec<BootstrapMethod>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BootstrapMethod {
+    /// The `MethodHandle` constant of the bootstrap method.
+    pub method_handle_index: usize,
+    /// The constants passed to it after the lookup, name and type.
+    pub argument_indexes: Vec<usize>,
+}
+
+/// An attribute we don't model, kept as raw bytes so it can be written back out.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AttributeUnknown {
+    pub attribute_name: String,
+    pub info: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Attribute {
     ConstantValue(AttributeConstantValue),
     Code(AttributeCode),
@@ -215,33 +322,51 @@ pub enum Attribute {
     InnerClasses(AttributeInnerClasses),
     Synthetic(AttributeSynthetic),
     Deprecated(AttributeDeprecated),
+    BootstrapMethods(AttributeBootstrapMethods),
+    Unknown(AttributeUnknown),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AttributeInfo {
     pub attribute_name_index: usize,
     pub attribute: Attribute,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FieldInfo {
     pub access_flags: u16,
     pub name_index: usize,
     pub name: String,
     pub descriptor_index: usize,
+    pub descriptor: String,
     pub attributes_info: Vec<AttributeInfo>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MethodInfo {
     pub access_flags: u16,
     pub name_index: usize,
     pub name: String,
     pub descriptor_index: usize,
+    pub descriptor: String,
     pub attributes_info: Vec<AttributeInfo>,
 }
 
-#[derive(PartialEq, Debug)]
+impl MethodInfo {
+    /// The method's `Code` attribute, which abstract and native methods don't have.
+    pub fn code(&self) -> Option<&AttributeCode> {
+        self.attributes_info.iter().find_map(|attribute_info| match &attribute_info.attribute {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+}
+
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ClassFlags(u16);
 
 impl ClassFlags {
@@ -290,7 +415,8 @@ impl ClassFlags {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct JavaClass {
     pub prelude: u32,
     pub major: u16,
@@ -299,124 +425,18 @@ pub struct JavaClass {
     pub constant_pool_infos: Vec<ConstantPoolInfo>,
     pub flags: ClassFlags,
     pub this_class: usize,
+    pub this_class_name: String,
     pub super_class: usize,
+    pub super_class_name: Option<String>,
     pub interfaces_count: usize,
     pub interfaces_indexes: Vec<usize>,
+    pub interfaces_names: Vec<String>,
     pub fields: Vec<FieldInfo>,
     pub methods: Vec<MethodInfo>,
-}
-
-struct BytesReader {
-    eight_bytes_buffer: [u8; 8],
-    four_bytes_buffer: [u8; 4],
-    two_bytes_buffer: [u8; 2],
-    one_byte_buffer: [u8; 1],
-    buf_reader: BufReader<File>,
-    read_bytes: Vec<usize>,
-}
-
-impl BytesReader {
-    fn new(buf_reader: BufReader<File>) -> Self {
-        BytesReader {
-            eight_bytes_buffer: [0; 8],
-            four_bytes_buffer: [0; 4],
-            two_bytes_buffer: [0; 2],
-            one_byte_buffer: [0; 1],
-            buf_reader,
-            read_bytes: Vec::new(),
-        }
-    }
-
-    fn read_i64(&mut self) -> i64 {
-        self.buf_reader.read(&mut self.eight_bytes_buffer).unwrap();
-        self.mark_read_bytes(8);
-        i64::from_be_bytes(self.eight_bytes_buffer)
-    }
-
-    fn read_u32(&mut self) -> u32 {
-        self.buf_reader.read(&mut self.four_bytes_buffer).unwrap();
-        self.mark_read_bytes(4);
-        u32::from_be_bytes(self.four_bytes_buffer)
-    }
-
-    fn read_i32(&mut self) -> i32 {
-        self.buf_reader.read(&mut self.four_bytes_buffer).unwrap();
-        self.mark_read_bytes(4);
-        i32::from_be_bytes(self.four_bytes_buffer)
-    }
-
-    fn read_u16(&mut self) -> u16 {
-        self.buf_reader.read(&mut self.two_bytes_buffer).unwrap();
-        self.mark_read_bytes(2);
-        u16::from_be_bytes(self.two_bytes_buffer)
-    }
-
-    fn read_i16(&mut self) -> i16 {
-        self.buf_reader.read(&mut self.two_bytes_buffer).unwrap();
-        self.mark_read_bytes(2);
-        i16::from_be_bytes(self.two_bytes_buffer)
-    }
-
-    fn read_u8(&mut self) -> u8 {
-        self.buf_reader.read(&mut self.one_byte_buffer).unwrap();
-        self.mark_read_bytes(1);
-        u8::from_be_bytes(self.one_byte_buffer)
-    }
-
-    fn read_f32(&mut self) -> f32 {
-        self.buf_reader.read(&mut self.four_bytes_buffer).unwrap();
-        self.mark_read_bytes(4);
-        f32::from_be_bytes(self.four_bytes_buffer)
-    }
-
-    fn read_f64(&mut self) -> f64 {
-        self.buf_reader.read(&mut self.eight_bytes_buffer).unwrap();
-        self.mark_read_bytes(8);
-        f64::from_be_bytes(self.eight_bytes_buffer)
-    }
-
-    fn read_bytes(&mut self, bytes_to_read: usize) -> Vec<u8> {
-        let mut output: Vec<u8> = vec![0; bytes_to_read];
-        self.buf_reader.read(&mut output).unwrap();
-        self.mark_read_bytes(bytes_to_read);
-        output
-    }
-
-    fn read_string(&mut self, bytes_of_utf8_string: usize) -> String {
-        let vec = self.read_bytes(bytes_of_utf8_string);
-        self.mark_read_bytes(bytes_of_utf8_string);
-        String::from_utf8(vec).unwrap()
-    }
-
-    fn consume(&mut self, amt: usize) -> () {
-        self.mark_read_bytes(amt);
-        self.buf_reader.consume(amt);
-    }
-
-    fn mark_read_bytes(&mut self, amt: usize) {
-        for num in self.read_bytes.iter_mut() {
-            *num += amt;
-        }
-    }
-
-    fn begin_read_block(&mut self) {
-        self.read_bytes.push(0);
-    }
-
-    fn assert_read(&mut self, amt: usize) {
-        let read_bytes = self.read_bytes.pop().unwrap();
-        assert_eq!(read_bytes, amt, "Expected to read {amt} bytes but read {read_bytes}");
-    }
+    pub attributes: Vec<AttributeInfo>,
 }
 
 impl JavaClass {
-    fn check_preludee(self) -> Result<(), String> {
-        Self::check_prelude(self.prelude)
-    }
-    fn java_version(self) -> Result<&'static str, String> {
-        Self::resolve_version(self.major)
-    }
-
     pub fn resolve_version(major: u16) -> Result<&'static str, String> {
         let major_index = major as usize - VERSION_SHIFT;
         if major_index > VERSIONS.len() - 1 {
@@ -436,306 +456,36 @@ impl JavaClass {
 
 
     pub fn load_from_file(path: &str) -> Result<Self, io::Error> {
-        let file = File::open(path)?;
-
-        let mut bytes_reader = BytesReader::new(BufReader::new(file));
-
-
-        let prelude = bytes_reader.read_u32();
-        let minor = bytes_reader.read_u16();
-        let major = bytes_reader.read_u16();
-        let constant_pool_count: usize = bytes_reader.read_u16() as usize;
-
-        let mut constant_pool_infos: Vec<ConstantPoolInfo> = Vec::with_capacity(constant_pool_count + 1);
-
-        for _ in 1..constant_pool_count {
-            let tag = bytes_reader.read_u8();
-            match tag {
-                1 => {
-                    let length = bytes_reader.read_u16() as usize;
-                    let string = bytes_reader.read_string(length);
-                    constant_pool_infos.push(ConstantPoolInfo::Utf8(ConstantPoolUtf8Info {
-                        tag,
-                        string,
-                    }));
-                }
-                3 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Integer(ConstantPoolIntegerInfo {
-                        tag,
-                        value: bytes_reader.read_i32(),
-                    }))
-                }
-                4 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Float(ConstantPoolFloatInfo {
-                        tag,
-                        value: bytes_reader.read_f32(),
-                    }))
-                }
-                5 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Long(ConstantPoolLongInfo {
-                        tag,
-                        value: bytes_reader.read_i64(),
-                    }))
-                }
-                6 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Double(ConstantPoolDoubleInfo {
-                        tag,
-                        value: bytes_reader.read_f64(),
-                    }))
-                }
-                7 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Class(ConstantPoolClassInfo {
-                        tag,
-                        name_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                8 => {
-                    constant_pool_infos.push(ConstantPoolInfo::String(ConstantPoolStringInfo {
-                        tag,
-                        name_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                9 => {
-                    constant_pool_infos.push(ConstantPoolInfo::FieldRef(ConstantPoolFieldRefInfo {
-                        tag,
-                        class_index: bytes_reader.read_u16() as usize,
-                        name_and_type_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                10 => {
-                    constant_pool_infos.push(ConstantPoolInfo::MethodRef(ConstantPoolMethodRefInfo {
-                        tag,
-                        class_index: bytes_reader.read_u16() as usize,
-                        name_and_type_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                11 => {
-                    constant_pool_infos.push(ConstantPoolInfo::InterfaceMethodRef(ConstantPoolInterfaceMethodRefInfo {
-                        tag,
-                        class_index: bytes_reader.read_u16() as usize,
-                        name_and_type_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                12 => {
-                    constant_pool_infos.push(ConstantPoolInfo::NameAndType(ConstantPoolNameAndTypeInfo {
-                        tag,
-                        name_index: bytes_reader.read_u16() as usize,
-                        descriptor_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                15 => {
-                    constant_pool_infos.push(ConstantPoolInfo::MethodKind(MethodKindInfo {
-                        tag,
-                        reference_kind: bytes_reader.read_u8(),
-                        reference_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                17 => {
-                    constant_pool_infos.push(ConstantPoolInfo::Dynamic(DynamicInfo {
-                        tag,
-                        bootstrap_method_attr_index: bytes_reader.read_u16() as usize,
-                        name_and_type_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-                18 => {
-                    constant_pool_infos.push(ConstantPoolInfo::InvokeDynamic(InvokeDynamicInfo {
-                        tag,
-                        bootstrap_method_attr_index: bytes_reader.read_u16() as usize,
-                        name_and_type_index: bytes_reader.read_u16() as usize,
-                    }))
-                }
-
-                _ => {
-                    unimplemented!("jjjj")
-                }
-            }
-        }
-
-        let flags = ClassFlags::new(bytes_reader.read_u16());
-        let this_class = bytes_reader.read_u16() as usize;
-        let super_class = bytes_reader.read_u16() as usize;
-
-        let interfaces_count: usize = bytes_reader.read_u16() as usize;
-        let mut interfaces_indexes: Vec<usize> = Vec::with_capacity(interfaces_count);
-
-        for _ in 0..interfaces_count {
-            interfaces_indexes.push(bytes_reader.read_u16() as usize);
-        }
-
-        let fields_count: usize = bytes_reader.read_u16() as usize;
-        let mut fields: Vec<FieldInfo> = Vec::with_capacity(fields_count);
-
-        for _n in 0..fields_count {
-            let access_flags = bytes_reader.read_u16();
-            let name_index = bytes_reader.read_u16() as usize;
-            let name = Self::get_name_info(&constant_pool_infos, &name_index);
-            let descriptor_index = bytes_reader.read_u16() as usize;
-
-            let attributes_info = Self::read_attributes(&mut bytes_reader, &constant_pool_infos);
-
-            fields.push(FieldInfo {
-                access_flags,
-                name_index,
-                name,
-                descriptor_index,
-                attributes_info,
-            });
-        }
-
-        let methods_count = bytes_reader.read_u16() as usize;
-        let mut methods: Vec<MethodInfo> = Vec::with_capacity(methods_count);
-
-        for _ in 0..methods_count {
-            let access_flags = bytes_reader.read_u16();
-            let name_index = bytes_reader.read_u16() as usize;
-            let name = Self::get_name_info(&constant_pool_infos, &name_index);
-            let descriptor_index = bytes_reader.read_u16() as usize;
-            let attributes_info = Self::read_attributes(&mut bytes_reader, &constant_pool_infos);
-
-            methods.push(MethodInfo {
-                access_flags,
-                name_index,
-                name,
-                descriptor_index,
-                attributes_info,
-            });
-        }
-
+        let class_reader = ClassReader::from_file(path)?;
+        Ok(Self::read(&class_reader)?)
+    }
 
-        Ok(JavaClass {
-            prelude,
-            major,
-            minor,
-            constant_pool_count,
-            constant_pool_infos,
-            flags,
-            this_class,
-            super_class,
-            interfaces_count,
-            interfaces_indexes,
-            fields,
-            methods,
-        })
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, ParseError> {
+        Self::read(&ClassReader::new(bytes))
     }
 
-    fn read_attributes(bytes_reader: &mut BytesReader, constant_pool_infos: &Vec<ConstantPoolInfo>) -> Vec<AttributeInfo> {
-        let attributes_count = bytes_reader.read_u16() as usize;
-        debug!("It has {} attributes", attributes_count);
-        let mut attributes_info: Vec<AttributeInfo> = Vec::with_capacity(attributes_count);
+    /// Loads a class in the given mode, along with what lenient parsing had to recover from.
+    pub fn load_from_file_with_mode(path: &str, mode: ParseMode) -> Result<(Self, Vec<Diagnostic>), io::Error> {
+        let class_reader = ClassReader::from_file(path)?.with_mode(mode);
+        Ok(Self::read_with_diagnostics(&class_reader)?)
+    }
 
-        for n in 0..attributes_count {
-            if let Some(attribute_info) = Self::read_attribute(bytes_reader, constant_pool_infos, n, attributes_count) {
-                attributes_info.push(attribute_info);
-            }
-        }
-        attributes_info
+    pub fn read(class_reader: &ClassReader) -> Result<Self, ParseError> {
+        Ok(Self::read_with_diagnostics(class_reader)?.0)
     }
 
-    fn read_attribute(bytes_reader: &mut BytesReader, constant_pool_infos: &Vec<ConstantPoolInfo>, n: usize, attributes_count: usize) -> Option<AttributeInfo> {
-        let attribute_name_index = bytes_reader.read_u16() as usize;
-        let attribute_name = Self::get_name_info(&constant_pool_infos, &attribute_name_index);
-        let attribute_length = bytes_reader.read_u32() as usize;
-        debug!("attribute {n}/{attributes_count} has name {attribute_name} and length {attribute_length}");
-        bytes_reader.begin_read_block();
-
-        match attribute_name.as_str() {
-            "ConstantValue" => {
-                let constant_value_index = bytes_reader.read_u16() as usize;
-                bytes_reader.assert_read(attribute_length);
-                Some(AttributeInfo {
-                    attribute_name_index,
-                    attribute: Attribute::ConstantValue(AttributeConstantValue { constant_value_index }),
-                })
-            }
-            "Code" => {
-                let max_stack = bytes_reader.read_u16() as usize;
-                let max_locals = bytes_reader.read_u16() as usize;
-                let code_length = bytes_reader.read_u32() as usize;
-                let code = bytes_reader.read_bytes(code_length);
-                let exception_table_length = bytes_reader.read_u16() as usize;
-                let mut exceptions: Vec<ExceptionTableEntry> = Vec::with_capacity(exception_table_length);
-
-                for _ in 0..exception_table_length {
-                    let start_pc = bytes_reader.read_u16() as usize;
-                    let end_pc = bytes_reader.read_u16() as usize;
-                    let handler_pc = bytes_reader.read_u16() as usize;
-                    let catch_pc = bytes_reader.read_u16() as usize;
-
-                    exceptions.push(ExceptionTableEntry {
-                        start_pc,
-                        end_pc,
-                        handler_pc,
-                        catch_pc,
-                    })
-                }
-                let attributes = Self::read_attributes(bytes_reader, constant_pool_infos);
-                bytes_reader.assert_read(attribute_length);
-                Some(AttributeInfo {
-                    attribute_name_index,
-                    attribute: Attribute::Code(AttributeCode {
-                        attribute_name_index,
-                        max_stack,
-                        max_locals,
-                        code_length,
-                        code,
-                        exceptions,
-                        attributes,
-                    }),
-                })
-            }
-            "StackMapTable" => {
-                unimplemented!("StackMapTable")
-            }
-            "BootstrapMethods" => {
-                unimplemented!("BootstrapMethods")
-            }
-            "NestHost" => {
-                unimplemented!("NestHost")
-            }
-            "NestMembers" => {
-                unimplemented!("NestMembers")
-            }
-            "PermittedSubclasses" => {
-                unimplemented!("PermittedSubclasses")
-            }
-            "LineNumberTable" => {
-                let line_number_table_length = bytes_reader.read_u16() as usize;
-                let mut line_number_entries: Vec<LineNumberTableEntry> = Vec::with_capacity(line_number_table_length);
-                for _ in 0..line_number_table_length {
-                    let start_pc = bytes_reader.read_u16() as usize;
-                    let line_number = bytes_reader.read_u16() as usize;
-                    line_number_entries.push(LineNumberTableEntry {
-                        start_pc,
-                        line_number,
-                    })
-                }
-                bytes_reader.assert_read(attribute_length);
-                Some(AttributeInfo {
-                    attribute_name_index,
-                    attribute: Attribute::LineNumberTable(AttributeLineNumberTable {
-                        attribute_name_index,
-                        attribute_name,
-                        attribute_length,
-                        line_number_entries,
-                    }),
-                })
-            }
-            _ => {
-                debug!("Skipping {attribute_length} bytes for {attribute_name}");
-                bytes_reader.consume(attribute_length);
-                bytes_reader.assert_read(attribute_length);
-                None
-            }
-        }
+    pub fn read_with_diagnostics(class_reader: &ClassReader) -> Result<(Self, Vec<Diagnostic>), ParseError> {
+        let mut builder = JavaClassBuilder::new();
+        let diagnostics = class_reader.accept(&mut builder)?;
+        Ok((builder.build(), diagnostics))
     }
 
-    fn get_name_info(constant_pool_infos: &Vec<ConstantPoolInfo>, attribute_name_index: &usize) -> String {
-        let info = &constant_pool_infos[attribute_name_index - 1];
-        if let ConstantPoolInfo::Utf8(i) = info {
-            (&i).string.clone()
-        } else {
-            panic!("Name is wrong")
-        }
+    /// The entries of the class's `BootstrapMethods` attribute, empty when it has none.
+    pub fn bootstrap_methods(&self) -> &[BootstrapMethod] {
+        self.attributes.iter().find_map(|attribute_info| match &attribute_info.attribute {
+            Attribute::BootstrapMethods(bootstrap_methods) => Some(bootstrap_methods.bootstrap_methods.as_slice()),
+            _ => None,
+        }).unwrap_or_default()
     }
 }
 
@@ -753,3 +503,14 @@ fn test_check_prelude() {
     assert!(JavaClass::check_prelude(0xCAFEBABE).is_ok());
     assert!(JavaClass::check_prelude(0xCAFEBABB).is_err_and(|err| err == "The prelude is 0xCAFEBABB instead of 0xCAFEBABE"));
 }
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_class_round_trips_through_json() {
+    let class = JavaClass::load_from_file("MyClass.class").unwrap();
+    let json = serde_json::to_value(&class).unwrap();
+    assert_eq!("MyClass", json["this_class_name"]);
+    assert_eq!("java/io/PrintStream", json["constant_pool_infos"][33]["MethodRef"]["class_name"]);
+    assert_eq!("println", json["constant_pool_infos"][33]["MethodRef"]["name"]);
+    assert_eq!(class, serde_json::from_value(json).unwrap());
+}
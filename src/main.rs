@@ -1,16 +1,167 @@
 use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::process;
 use rjvm::JavaClass;
+use rjvm::assembler::assemble;
+use rjvm::control_flow::ControlFlowGraph;
+use rjvm::decompiler::{decompile_class, decompile_method};
+use rjvm::optimizer::optimize_class;
+use rjvm::stack_map::ClassPath;
+use rjvm::class_reader::ParseMode;
 use rjvm::class_printer::ClassPrinter;
 use text_colorizer::Colorize;
 
+const USAGE: &str = "Usage: rjvm [--format text|json|asm] [--lenient] <class file>\n       rjvm asm <source> [-o <class file>]\n       rjvm cfg <class file> <method>\n       rjvm decompile <class file> [method]\n       rjvm optimize <class file> [-o <class file>] [-cp <directories>]";
 
-fn main() -> std::io::Result<()> {
+fn main() {
     pretty_env_logger::init();
-    let file_name = env::args().skip(1).next().expect("Java class should be passed as an argument");
+    match env::args().nth(1).as_deref() {
+        Some("asm") => assemble_file(env::args().skip(2)),
+        Some("cfg") => print_control_flow_graph(env::args().skip(2)),
+        Some("decompile") => print_decompiled(env::args().skip(2)),
+        Some("optimize") => optimize_file(env::args().skip(2)),
+        _ => print_class(env::args().skip(1)),
+    }
+}
+
+/// Prints the class as text, JSON or assembler source.
+fn print_class(mut args: impl Iterator<Item = String>) {
+    let mut format = String::from("text");
+    let mut mode = ParseMode::Strict;
+    let mut file_name = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().unwrap_or_else(|| exit_with_usage()),
+            "--lenient" => mode = ParseMode::Lenient,
+            _ => file_name = Some(arg),
+        }
+    }
+    let file_name = file_name.unwrap_or_else(|| exit_with_usage());
+
+    let class = match JavaClass::load_from_file_with_mode(file_name.as_str(), mode) {
+        Ok((class, diagnostics)) => {
+            for diagnostic in diagnostics {
+                eprintln!("{}: {file_name}: {diagnostic}", "Warning".bold().yellow());
+            }
+            class
+        }
+        Err(error) => exit_with_error(&file_name, error),
+    };
+    match format.as_str() {
+        "text" => {
+            println!("{}: {file_name}", "File".bold().blue());
+            ClassPrinter::new(class).print();
+        }
+        "json" => print_json(&class),
+        "asm" => print!("{}", rjvm::disassembler::disassemble_class(&class)),
+        _ => exit_with_usage(),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn print_json(class: &JavaClass) {
+    println!("{}", serde_json::to_string_pretty(class).expect("Class should serialize to JSON"));
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_class: &JavaClass) {
+    eprintln!("JSON output needs rjvm to be built with the `serde` feature");
+    process::exit(2);
+}
+
+fn assemble_file(mut args: impl Iterator<Item = String>) {
+    let mut source_name = None;
+    let mut output_name = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output_name = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            _ => source_name = Some(arg),
+        }
+    }
+    let source_name = source_name.unwrap_or_else(|| exit_with_usage());
+    let output_name = output_name.unwrap_or_else(|| Path::new(&source_name).with_extension("class").to_string_lossy().into_owned());
+
+    let source = fs::read_to_string(&source_name).unwrap_or_else(|error| exit_with_error(&source_name, error));
+    let class = assemble(&source).unwrap_or_else(|error| exit_with_error(&source_name, error));
+    if let Err(error) = fs::write(&output_name, class.to_bytes()) {
+        exit_with_error(&output_name, error);
+    }
+}
 
-    let class = JavaClass::load_from_file(file_name.as_str())?;
-    println!("{}: {file_name}", "File".bold().blue());
-    ClassPrinter::new(class).print();
+/// Prints the graph of a method as DOT. The method is given by name, or by name and descriptor like
+/// `parse(Ljava/lang/String;)I` when it is overloaded.
+fn print_control_flow_graph(mut args: impl Iterator<Item = String>) {
+    let (Some(file_name), Some(method_name)) = (args.next(), args.next()) else { exit_with_usage() };
+    let class = load_or_exit(&file_name);
+    let method = class.methods.iter()
+        .find(|method| method.name == method_name || format!("{}{}", method.name, method.descriptor) == method_name);
+    let Some(code) = method.and_then(|method| method.code()) else {
+        exit_with_error(&file_name, format!("no method {method_name} with code"));
+    };
+    match ControlFlowGraph::new(code) {
+        Ok(graph) => print!("{}", graph.to_dot(&method_name, &class.constant_pool_infos)),
+        Err(error) => exit_with_error(&file_name, error),
+    }
+}
+
+/// Prints the class as Java source, or just one method of it, given like for `rjvm cfg`.
+fn print_decompiled(mut args: impl Iterator<Item = String>) {
+    let Some(file_name) = args.next() else { exit_with_usage() };
+    let class = load_or_exit(&file_name);
+    let Some(method_name) = args.next() else {
+        print!("{}", decompile_class(&class));
+        return;
+    };
+    let Some(method) = class.methods.iter()
+        .find(|method| method.name == method_name || format!("{}{}", method.name, method.descriptor) == method_name) else {
+        exit_with_error(&file_name, format!("no method {method_name}"));
+    };
+    match decompile_method(&class, method) {
+        Ok(source) => print!("{source}"),
+        Err(error) => exit_with_error(&file_name, error),
+    }
+}
+
+/// Optimizes the class and writes it to the output, or next to the class file as
+/// `Foo.optimized.class` without one. The frames of changed methods need the super classes of
+/// the classes they merge, read from the class path directories.
+fn optimize_file(mut args: impl Iterator<Item = String>) {
+    let mut file_name = None;
+    let mut output_name = None;
+    let mut directories = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output_name = Some(args.next().unwrap_or_else(|| exit_with_usage())),
+            "-cp" => directories.extend(env::split_paths(&args.next().unwrap_or_else(|| exit_with_usage()))),
+            _ => file_name = Some(arg),
+        }
+    }
+    let file_name = file_name.unwrap_or_else(|| exit_with_usage());
+    let output_name = output_name.unwrap_or_else(|| Path::new(&file_name).with_extension("optimized.class").to_string_lossy().into_owned());
+
+    let mut class = load_or_exit(&file_name);
+    let mut hierarchy = ClassPath::new(directories);
+    hierarchy.add(&class);
+    if let Err(error) = optimize_class(&mut class, &hierarchy) {
+        exit_with_error(&file_name, error);
+    }
+    if let Err(error) = fs::write(&output_name, class.to_bytes()) {
+        exit_with_error(&output_name, error);
+    }
+}
+
+fn load_or_exit(file_name: &str) -> JavaClass {
+    JavaClass::load_from_file(file_name).unwrap_or_else(|error| exit_with_error(file_name, error))
+}
+
+fn exit_with_error(file_name: &str, error: impl Display) -> ! {
+    eprintln!("{}: {file_name}: {error}", "Error".bold().red());
+    process::exit(1);
+}
 
-    Ok(())
+fn exit_with_usage() -> ! {
+    eprintln!("{USAGE}");
+    process::exit(2);
 }
@@ -1,11 +1,372 @@
-use std::collections::HashMap;
-
-
+use std::collections::{BTreeMap, HashMap};
+use std::rc::Rc;
+use crate::class::{Class, Field, FieldRef, Method, MethodRef};
+use crate::execution::ExecutionError;
+use crate::heap::{lay_out_fields, OBJECT_HEADER_SIZE};
 
+/// The classes loaded into the JVM, by their internal names.
+#[derive(Debug, Default)]
 pub struct MethodArea {
-    class: HashMap<&str, Class>
+    classes: HashMap<String, Rc<Class>>,
 }
 
 impl MethodArea {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Links a class and adds it, replacing any loaded before under the same name. Its superclass
+    /// and superinterfaces have to be loaded first.
+    pub fn add(&mut self, class: Class) -> Result<Rc<Class>, ExecutionError> {
+        let class = Rc::new(self.link(class)?);
+        self.classes.insert(class.name.clone(), class.clone());
+        Ok(class)
+    }
+
+    pub fn class(&self, name: &str) -> Option<Rc<Class>> {
+        self.classes.get(name).cloned()
+    }
+
+    /// The class named `name`, which failing to find is a `NoClassDefFoundError`.
+    pub fn loaded_class(&self, name: &str) -> Result<Rc<Class>, ExecutionError> {
+        self.class(name).ok_or_else(|| ExecutionError::new(format!("NoClassDefFoundError: {name}")))
+    }
+
+    /// The superclasses of a class, up to `java/lang/Object`.
+    pub fn superclasses(&self, class: &Class) -> Result<Vec<Rc<Class>>, ExecutionError> {
+        let mut classes: Vec<Rc<Class>> = Vec::new();
+        let mut super_class_name = &class.super_class_name;
+        while let Some(name) = super_class_name {
+            if *name == class.name || classes.iter().any(|class| class.name == *name) {
+                return Err(ExecutionError::new(format!("ClassCircularityError: {name}")));
+            }
+            classes.push(self.loaded_class(name)?);
+            super_class_name = &classes[classes.len() - 1].super_class_name;
+        }
+        Ok(classes)
+    }
+
+    /// The interfaces a class or interface implements or extends, directly or through its
+    /// superclasses and superinterfaces.
+    pub fn superinterfaces(&self, class: &Class) -> Result<Vec<Rc<Class>>, ExecutionError> {
+        let superclasses = self.superclasses(class)?;
+        let mut names: Vec<String> = std::iter::once(class).chain(superclasses.iter().map(|class| &**class))
+            .flat_map(|class| class.interface_names.clone()).collect();
+        names.reverse();
+        let mut interfaces: Vec<Rc<Class>> = Vec::new();
+        while let Some(name) = names.pop() {
+            if interfaces.iter().all(|interface| interface.name != name) {
+                let interface = self.loaded_class(&name)?;
+                names.extend(interface.interface_names.iter().rev().cloned());
+                interfaces.push(interface);
+            }
+        }
+        Ok(interfaces)
+    }
+
+    /// Resolves a symbolic reference to a field, as in JVMS 5.4.3.2, to the class declaring it
+    /// and the field.
+    pub fn resolve_field(&self, field_ref: &FieldRef) -> Result<(Rc<Class>, Field), ExecutionError> {
+        let class = self.loaded_class(&field_ref.class_name)?;
+        self.look_up_field(&class, field_ref)?
+            .ok_or_else(|| ExecutionError::new(format!("NoSuchFieldError: {}.{}", field_ref.class_name, field_ref.name)))
+    }
+
+    /// Looks a field up in a class, then its superinterfaces, then its superclass.
+    fn look_up_field(&self, class: &Rc<Class>, field_ref: &FieldRef) -> Result<Option<(Rc<Class>, Field)>, ExecutionError> {
+        if let Some(field) = class.field(&field_ref.name, &field_ref.descriptor) {
+            return Ok(Some((class.clone(), field.clone())));
+        }
+        for interface_name in &class.interface_names {
+            if let Some(found) = self.look_up_field(&self.loaded_class(interface_name)?, field_ref)? {
+                return Ok(Some(found));
+            }
+        }
+        match &class.super_class_name {
+            Some(super_class_name) => self.look_up_field(&self.loaded_class(super_class_name)?, field_ref),
+            None => Ok(None),
+        }
+    }
+
+    /// Resolves a symbolic reference to a method, as in JVMS 5.4.3.3 for classes and 5.4.3.4 for
+    /// interfaces.
+    pub fn resolve_method(&self, method_ref: &MethodRef) -> Result<Rc<Method>, ExecutionError> {
+        let MethodRef { class_name, name, descriptor, interface } = method_ref;
+        let class = self.loaded_class(class_name)?;
+        if class.is_interface() != *interface {
+            let kind = if class.is_interface() { "an interface" } else { "a class" };
+            return Err(ExecutionError::new(format!("IncompatibleClassChangeError: {class_name} is {kind}")));
+        }
+        if let Some(method) = self.look_up(&class, name, descriptor)? {
+            return Ok(method);
+        }
+        if let [method] = &non_abstract(self.maximally_specific_methods(&class, name, descriptor)?)[..] {
+            return Ok(method.clone());
+        }
+        let inherited = self.superinterfaces(&class)?.iter()
+            .find_map(|interface| interface.method(name, descriptor).filter(|method| !method.is_private() && !method.is_static()).cloned());
+        inherited.ok_or_else(|| ExecutionError::new(format!("NoSuchMethodError: {class_name}.{name}{descriptor}")))
+    }
+
+    /// The method `invokevirtual` and `invokeinterface` run on an object of class `receiver` for
+    /// a resolved method, from the receiver's vtable or itable.
+    pub fn dispatch(&self, receiver: &Class, resolved: &Rc<Method>) -> Result<Rc<Method>, ExecutionError> {
+        if resolved.is_private() {
+            return Ok(resolved.clone());
+        }
+        let method = match resolved.vtable_index {
+            Some(index) => receiver.vtable.get(index),
+            None => receiver.itables.get(&resolved.class_name).and_then(|itable| itable.get(resolved.index)?.as_ref()),
+        };
+        match method.filter(|method| method.name == resolved.name && method.descriptor == resolved.descriptor && overrides(method, resolved)) {
+            Some(method) => concrete(method.clone()),
+            // What the tables don't hold, or only hold through other methods, takes selection.
+            None => self.select_method(receiver, resolved),
+        }
+    }
+
+    /// Selects the method to run on an object of class `receiver` for a resolved method, as in
+    /// JVMS 5.4.6, which the vtables and itables hold the results of.
+    pub fn select_method(&self, receiver: &Class, resolved: &Rc<Method>) -> Result<Rc<Method>, ExecutionError> {
+        if resolved.is_private() {
+            return Ok(resolved.clone());
+        }
+        let superclasses = self.superclasses(receiver)?;
+        let classes = std::iter::once(receiver).chain(superclasses.iter().map(|class| &**class));
+        let overriding = classes.enumerate().find_map(|(position, class)| {
+            let between = below(&superclasses[position.min(superclasses.len())..], &resolved.class_name);
+            class.method(&resolved.name, &resolved.descriptor).filter(|method| overrides_through(method, resolved, between)).cloned()
+        });
+        match overriding {
+            Some(method) => concrete(method),
+            None => self.only_default_method(receiver, &resolved.name, &resolved.descriptor),
+        }
+    }
+
+    /// The method `invokespecial` runs for a resolved method, from the class `current` the
+    /// instruction is in. With `ACC_SUPER`, methods of superclasses are looked up from the direct
+    /// superclass of the current class rather than from the class the reference names.
+    pub fn special_method(&self, current: &Class, method_ref: &MethodRef, resolved: &Rc<Method>) -> Result<Rc<Method>, ExecutionError> {
+        let referenced = self.loaded_class(&method_ref.class_name)?;
+        let is_superclass = self.superclasses(current)?.iter().any(|class| class.name == referenced.name);
+        let class = match &current.super_class_name {
+            Some(super_class_name) if resolved.name != "<init>" && !referenced.is_interface() && is_superclass && current.flags.is_super() => {
+                self.loaded_class(super_class_name)?
+            }
+            _ => referenced,
+        };
+        let (name, descriptor) = (&resolved.name, &resolved.descriptor);
+        match self.look_up(&class, name, descriptor)? {
+            Some(method) => concrete(method),
+            None => self.only_default_method(&class, name, descriptor),
+        }
+    }
+
+    /// Lays out the instance fields of a class after those of its superclass, its vtable, where
+    /// overriding methods take the indexes of the methods they override, and its itables, with
+    /// the method selected for each method of each superinterface.
+    fn link(&self, mut class: Class) -> Result<Class, ExecutionError> {
+        let superclasses = self.superclasses(&class)?;
+        let superinterfaces = self.superinterfaces(&class)?;
+        if class.is_interface() {
+            return Ok(class);
+        }
+        let start = superclasses.first().map_or(OBJECT_HEADER_SIZE, |super_class| super_class.instance_size);
+        class.instance_size = lay_out_fields(&mut class.fields, start);
+        let mut vtable = superclasses.first().map(|super_class| super_class.vtable.clone()).unwrap_or_default();
+        for method in &mut class.methods {
+            if method.is_static() || method.is_private() || method.name.starts_with('<') {
+                continue;
+            }
+            // Any method that held an entry may be the one overridden, as a package-private method
+            // is only overridden from its own package or through a method that is.
+            let mut overridden = Vec::new();
+            for index in 0..vtable.len() {
+                let held = superclasses.iter().filter_map(|class| class.vtable.get(index))
+                    .filter(|entry| entry.name == method.name && entry.descriptor == method.descriptor);
+                if held.clone().any(|entry| overrides_through(method, entry, below(&superclasses, &entry.class_name))) {
+                    if let Some(final_method) = held.clone().find(|entry| entry.is_final()) {
+                        return Err(ExecutionError::new(format!("VerifyError: {method} overrides final method {final_method}")));
+                    }
+                    overridden.push(index);
+                }
+            }
+            let index = overridden.first().copied().unwrap_or(vtable.len());
+            Rc::make_mut(method).vtable_index = Some(index);
+            if index == vtable.len() {
+                vtable.push(method.clone());
+            }
+            for index in overridden {
+                vtable[index] = method.clone();
+            }
+        }
+        class.vtable = vtable;
+        let mut itables = BTreeMap::new();
+        for interface in superinterfaces {
+            let itable = interface.methods.iter()
+                .map(|method| match method.is_static() || method.is_private() || method.name.starts_with('<') {
+                    true => None,
+                    false => self.select_method(&class, method).ok(),
+                })
+                .collect();
+            itables.insert(interface.name.clone(), itable);
+        }
+        class.itables = itables;
+        Ok(class)
+    }
+
+    /// The method a class or one of its superclasses declares, or for an interface, the method it
+    /// declares or a public instance method of `java/lang/Object`.
+    fn look_up(&self, class: &Class, name: &str, descriptor: &str) -> Result<Option<Rc<Method>>, ExecutionError> {
+        if let Some(method) = class.method(name, descriptor) {
+            return Ok(Some(method.clone()));
+        }
+        if !class.is_interface() {
+            return Ok(self.superclasses(class)?.iter().find_map(|class| class.method(name, descriptor).cloned()));
+        }
+        let object = self.loaded_class("java/lang/Object")?;
+        Ok(object.method(name, descriptor).filter(|method| method.is_public() && !method.is_static()).cloned())
+    }
+
+    /// The methods with the name and descriptor that superinterfaces of the class declare and no
+    /// more specific superinterface redeclares, as JVMS 5.4.3.3 defines them.
+    fn maximally_specific_methods(&self, class: &Class, name: &str, descriptor: &str) -> Result<Vec<Rc<Method>>, ExecutionError> {
+        let mut candidates = Vec::new();
+        for interface in self.superinterfaces(class)? {
+            if let Some(method) = interface.method(name, descriptor).filter(|method| !method.is_private() && !method.is_static()) {
+                let superinterfaces = self.superinterfaces(&interface)?;
+                candidates.push((method.clone(), superinterfaces));
+            }
+        }
+        let is_redeclared = |method: &Method| candidates.iter()
+            .any(|(_, superinterfaces)| superinterfaces.iter().any(|interface| interface.name == method.class_name));
+        Ok(candidates.iter().filter(|(method, _)| !is_redeclared(method)).map(|(method, _)| method.clone()).collect())
+    }
+
+    /// The one default method a class inherits with the name and descriptor.
+    fn only_default_method(&self, class: &Class, name: &str, descriptor: &str) -> Result<Rc<Method>, ExecutionError> {
+        match &non_abstract(self.maximally_specific_methods(class, name, descriptor)?)[..] {
+            [method] => Ok(method.clone()),
+            [] => Err(ExecutionError::new(format!("AbstractMethodError: {}.{name}{descriptor}", class.name))),
+            methods => {
+                let owners: Vec<&str> = methods.iter().map(|method| method.class_name.as_str()).collect();
+                Err(ExecutionError::new(format!("IncompatibleClassChangeError: {}.{name}{descriptor} has conflicting defaults in {}", class.name, owners.join(", "))))
+            }
+        }
+    }
+}
+
+/// Whether `method` overrides `overridden`, as in JVMS 5.4.5. A method overrides itself.
+fn overrides(method: &Rc<Method>, overridden: &Rc<Method>) -> bool {
+    Rc::ptr_eq(method, overridden)
+        || (!method.is_private() && !method.is_static()
+            && (overridden.is_public() || overridden.is_protected() || method.package() == overridden.package()))
+}
+
+/// Whether `method` overrides `overridden`, directly or through a method of one of the classes
+/// `between` them, nearest first, which JVMS 5.4.5 allows.
+fn overrides_through(method: &Rc<Method>, overridden: &Rc<Method>, between: &[Rc<Class>]) -> bool {
+    overrides(method, overridden) || between.iter().enumerate().any(|(position, class)| {
+        class.method(&method.name, &method.descriptor).is_some_and(|middle| !middle.is_private() && !middle.is_static()
+            && overrides(method, middle) && overrides_through(middle, overridden, &between[position + 1..]))
+    })
+}
+
+/// The superclasses, nearest first, below the class named `class_name`.
+fn below<'a>(superclasses: &'a [Rc<Class>], class_name: &str) -> &'a [Rc<Class>] {
+    let end = superclasses.iter().position(|class| class.name == class_name).unwrap_or(superclasses.len());
+    &superclasses[..end]
+}
+
+fn non_abstract(methods: Vec<Rc<Method>>) -> Vec<Rc<Method>> {
+    methods.into_iter().filter(|method| !method.is_abstract()).collect()
+}
+
+fn concrete(method: Rc<Method>) -> Result<Rc<Method>, ExecutionError> {
+    if method.is_abstract() {
+        return Err(ExecutionError::new(format!("AbstractMethodError: {method}")));
+    }
+    Ok(method)
+}
+
+#[test]
+fn test_links_vtables_and_itables() {
+    let mut jvm = crate::jvm::Jvm::new();
+    for file in ["A.class", "B.class"] {
+        jvm.load_class(crate::JavaClass::load_from_file(file).unwrap()).unwrap();
+    }
+    let my_class = jvm.load_class(crate::JavaClass::load_from_file("MyClass.class").unwrap()).unwrap();
+    assert_eq!("\
+vtable of MyClass:
+  0: MyClass.intMethod()I
+  1: MyClass.methodFromA(I)I
+  2: MyClass.methodFromB()Ljava/lang/String;
+itable of MyClass for A:
+  methodFromA(I)I: MyClass.methodFromA(I)I
+itable of MyClass for B:
+  methodFromB()Ljava/lang/String;: MyClass.methodFromB()Ljava/lang/String;
+", my_class.dispatch_tables());
+
+    let method = |name: &str, value: i32, flags: &str| format!("
+        .method {flags} {name} ()I
+            .limit stack 1; .limit locals 1
+            bipush {value}; ireturn
+        .end method");
+    let base = jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super p/Base {} {}", method("m", 1, ""), method("n", 1, "public final"))).unwrap()).unwrap();
+    let same = jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super p/Same
+        .super p/Base {}", method("m", 2, "public"))).unwrap()).unwrap();
+    // A package-private method isn't overridden from another package.
+    let other = jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super q/Other
+        .super p/Base {}", method("m", 3, "public"))).unwrap()).unwrap();
+    assert_eq!("p/Same.m()I", same.vtable[0].to_string());
+    assert_eq!(["p/Base.m()I", "p/Base.n()I", "q/Other.m()I"], other.vtable.iter().map(|method| method.to_string()).collect::<Vec<_>>()[..]);
+    let base_m = base.method("m", "()I").unwrap();
+    let area = &jvm.method_area;
+    assert_eq!("p/Same.m()I", area.dispatch(&same, base_m).unwrap().to_string());
+    assert_eq!("p/Base.m()I", area.dispatch(&other, base_m).unwrap().to_string());
+    assert_eq!("q/Other.m()I", area.dispatch(&other, other.method("m", "()I").unwrap()).unwrap().to_string());
+
+    let error = jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super p/Final
+        .super p/Base {}", method("n", 4, "public"))).unwrap()).unwrap_err();
+    assert_eq!("VerifyError: p/Final.n()I overrides final method p/Base.n()I", error.message);
+    let error = jvm.load_class(crate::assembler::assemble(".class public super Orphan\n.super Missing").unwrap()).unwrap_err();
+    assert_eq!("NoClassDefFoundError: Missing", error.message);
+}
+
+#[test]
+fn test_follows_overrides_through_other_packages() {
+    let mut jvm = crate::jvm::Jvm::new();
+    let mut load = |name: &str, super_class: &str, value: i32, flags: &str| jvm.load_class(crate::assembler::assemble(&format!("
+        .class public super {name}
+        .super {super_class}
+        .method {flags} m ()I
+            .limit stack 1; .limit locals 1
+            bipush {value}; ireturn
+        .end method")).unwrap()).unwrap();
+    let base = load("p/Base", "java/lang/Object", 1, "");
+    // q/Leaf doesn't override p/Base.m itself, only the p/Same.m that does.
+    let same = load("p/Same", "p/Base", 2, "public");
+    let leaf = load("q/Leaf", "p/Same", 3, "public");
+    // p/Again overrides both p/Base.m and the q/Other.m that doesn't.
+    let other = load("q/Other", "p/Base", 4, "public");
+    let again = load("p/Again", "q/Other", 5, "public");
+    let tables = |class: &Class| class.vtable.iter().map(|method| method.to_string()).collect::<Vec<_>>();
+    assert_eq!(["q/Leaf.m()I"], tables(&leaf)[..]);
+    assert_eq!(["p/Again.m()I", "p/Again.m()I"], tables(&again)[..]);
 
+    let area = &jvm.method_area;
+    let base_m = base.method("m", "()I").unwrap();
+    for selected in [area.dispatch(&leaf, base_m), area.select_method(&leaf, base_m)] {
+        assert_eq!("q/Leaf.m()I", selected.unwrap().to_string());
+    }
+    assert_eq!("q/Leaf.m()I", area.select_method(&leaf, same.method("m", "()I").unwrap()).unwrap().to_string());
+    for resolved in [base_m, other.method("m", "()I").unwrap()] {
+        for selected in [area.dispatch(&again, resolved), area.select_method(&again, resolved)] {
+            assert_eq!("p/Again.m()I", selected.unwrap().to_string());
+        }
+    }
 }
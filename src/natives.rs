@@ -0,0 +1,99 @@
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+use crate::class::{Class, Method};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::execution::ExecutionError;
+use crate::frames::Value;
+use crate::heap::ObjectData;
+use crate::jvm::Jvm;
+use crate::thread::invoked_method;
+
+/// What running a method without code comes to.
+#[derive(Debug)]
+pub(crate) enum Native {
+    /// It returned, with a value unless it is `void`.
+    Return(Option<Value>),
+    /// The method of the class to invoke in its place with these arguments, for lambdas.
+    Invoke(Rc<Class>, Rc<Method>, Vec<Value>),
+}
+
+/// Runs a method without code the JVM implements itself: the methods of the classes spun for
+/// lambdas and those of the built-in class library.
+pub(crate) fn run(jvm: &mut Jvm, method: &Method, arguments: &[Value]) -> Result<Native, ExecutionError> {
+    let data = match arguments.first() {
+        Some(Value::Reference(Some(reference))) if !method.is_static() => jvm.heap.data(*reference),
+        _ => None,
+    };
+    if let Some(ObjectData::Lambda { target, captured }) = data {
+        let arguments: Vec<Value> = captured.iter().chain(&arguments[1..]).copied().collect();
+        let (class, method) = invoked_method(&jvm.method_area, &jvm.heap, &target.caller, target.kind, &target.method_ref, &arguments)?;
+        return Ok(Native::Invoke(class, method, arguments));
+    }
+    match (method.class_name.as_str(), method.name.as_str()) {
+        ("java/io/PrintStream", name @ ("print" | "println")) => {
+            let parameter = MethodDescriptor::parse(&method.descriptor).and_then(|descriptor| descriptor.parameters.into_iter().next());
+            let mut text = match (parameter, arguments.get(1)) {
+                (Some(parameter), Some(argument)) => java_string(jvm, *argument, &parameter)?,
+                _ => String::new(),
+            };
+            if name == "println" {
+                text.push('\n');
+            }
+            jvm.output.write_all(text.as_bytes()).map_err(|error| ExecutionError::new(format!("IOException: {error}")))?;
+            Ok(Native::Return(None))
+        }
+        _ => Err(ExecutionError::new(format!("UnsatisfiedLinkError: {method}"))),
+    }
+}
+
+/// A value of the given type the way `String.valueOf` and string concatenation write it.
+pub(crate) fn java_string(jvm: &Jvm, value: Value, field_type: &FieldType) -> Result<String, ExecutionError> {
+    match (value, field_type) {
+        (Value::Int(value), FieldType::Boolean) => Ok((value != 0).to_string()),
+        (Value::Int(value), FieldType::Char) => Ok(char::from_u32(value as u16 as u32).unwrap_or(char::REPLACEMENT_CHARACTER).to_string()),
+        (Value::Int(value), _) => Ok(value.to_string()),
+        (Value::Long(value), _) => Ok(value.to_string()),
+        (Value::Float(value), _) => Ok(java_float(value)),
+        (Value::Double(value), _) => Ok(java_float(value)),
+        (Value::Reference(None), _) => Ok("null".to_string()),
+        (Value::Reference(Some(reference)), _) => match jvm.heap.data(reference) {
+            Some(ObjectData::String(string)) => Ok(string.clone()),
+            _ => Err(ExecutionError::new(format!("Converting a {} to a string needs toString, which is not supported yet", jvm.heap.class(reference)?.name))),
+        },
+        (Value::ReturnAddress(_), _) => Err(ExecutionError::new(format!("{value:?} can't be converted to a string"))),
+    }
+}
+
+/// A `float` or `double` the way `Float.toString` and `Double.toString` write it: the shortest
+/// digits that tell it apart, as a decimal between 10^-3 and 10^7 and in scientific notation
+/// like `1.0E10` outside.
+pub(crate) fn java_float<T: Into<f64> + Copy + fmt::Display + fmt::LowerExp>(value: T) -> String {
+    let magnitude = value.into().abs();
+    if magnitude.is_nan() {
+        "NaN".to_string()
+    } else if magnitude.is_infinite() {
+        if value.into() > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if magnitude == 0.0 || (1e-3..1e7).contains(&magnitude) {
+        let decimal = value.to_string();
+        if decimal.contains('.') { decimal } else { format!("{decimal}.0") }
+    } else {
+        let scientific = format!("{value:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap_or_else(|| unreachable!());
+        if mantissa.contains('.') { format!("{mantissa}E{exponent}") } else { format!("{mantissa}.0E{exponent}") }
+    }
+}
+
+#[test]
+fn test_writes_floating_point_values_like_java() {
+    assert_eq!("1.0E10", java_float(1e10f32));
+    assert_eq!("0.5", java_float(0.5f64));
+    assert_eq!("-0.0", java_float(-0.0f64));
+    assert_eq!("100.0", java_float(100.0f32));
+    assert_eq!("1.234E-5", java_float(1.234e-5f64));
+    assert_eq!("9999999.0", java_float(9999999.0f64));
+    assert_eq!("1.0E7", java_float(1e7f64));
+    assert_eq!("0.1", java_float(0.1f32));
+    assert_eq!("-Infinity", java_float(f64::NEG_INFINITY));
+    assert_eq!("NaN", java_float(f32::NAN));
+}
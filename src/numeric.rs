@@ -0,0 +1,107 @@
+use crate::instruction::Instruction;
+
+/// A value of one of the numeric computational types.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Number {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+}
+
+/// Why an instruction has no result.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NumericError {
+    /// `idiv`, `irem`, `ldiv` or `lrem` by zero, which throws `ArithmeticException`.
+    DivisionByZero,
+    /// The instruction isn't arithmetic, a conversion or a comparison, or the operands aren't of
+    /// the types it takes.
+    Unsupported,
+}
+
+/// The result of an arithmetic, conversion or comparison instruction, with Java's
+/// semantics: integers wrap around, shift distances are masked, and comparisons of `NaN` give
+/// -1 for the `l` forms and 1 for the `g` ones.
+pub fn compute(instruction: &Instruction, operands: &[Number]) -> Result<Number, NumericError> {
+    use Number::{Double, Float, Int, Long};
+    let compare = |ordering: Option<std::cmp::Ordering>, nan: i32| match ordering {
+        Some(ordering) => ordering as i32,
+        None => nan,
+    };
+    Ok(match (instruction, operands) {
+        (Instruction::Idiv | Instruction::Irem, [Int(_), Int(0)]) | (Instruction::Ldiv | Instruction::Lrem, [Long(_), Long(0)]) => {
+            return Err(NumericError::DivisionByZero);
+        }
+        (Instruction::Iadd, [Int(a), Int(b)]) => Int(a.wrapping_add(*b)),
+        (Instruction::Isub, [Int(a), Int(b)]) => Int(a.wrapping_sub(*b)),
+        (Instruction::Imul, [Int(a), Int(b)]) => Int(a.wrapping_mul(*b)),
+        (Instruction::Idiv, [Int(a), Int(b)]) => Int(a.wrapping_div(*b)),
+        (Instruction::Irem, [Int(a), Int(b)]) => Int(a.wrapping_rem(*b)),
+        (Instruction::Ineg, [Int(a)]) => Int(a.wrapping_neg()),
+        (Instruction::Ishl, [Int(a), Int(b)]) => Int(a.wrapping_shl(*b as u32 & 0x1f)),
+        (Instruction::Ishr, [Int(a), Int(b)]) => Int(a.wrapping_shr(*b as u32 & 0x1f)),
+        (Instruction::Iushr, [Int(a), Int(b)]) => Int((*a as u32).wrapping_shr(*b as u32 & 0x1f) as i32),
+        (Instruction::Iand, [Int(a), Int(b)]) => Int(a & b),
+        (Instruction::Ior, [Int(a), Int(b)]) => Int(a | b),
+        (Instruction::Ixor, [Int(a), Int(b)]) => Int(a ^ b),
+        (Instruction::Ladd, [Long(a), Long(b)]) => Long(a.wrapping_add(*b)),
+        (Instruction::Lsub, [Long(a), Long(b)]) => Long(a.wrapping_sub(*b)),
+        (Instruction::Lmul, [Long(a), Long(b)]) => Long(a.wrapping_mul(*b)),
+        (Instruction::Ldiv, [Long(a), Long(b)]) => Long(a.wrapping_div(*b)),
+        (Instruction::Lrem, [Long(a), Long(b)]) => Long(a.wrapping_rem(*b)),
+        (Instruction::Lneg, [Long(a)]) => Long(a.wrapping_neg()),
+        (Instruction::Lshl, [Long(a), Int(b)]) => Long(a.wrapping_shl(*b as u32 & 0x3f)),
+        (Instruction::Lshr, [Long(a), Int(b)]) => Long(a.wrapping_shr(*b as u32 & 0x3f)),
+        (Instruction::Lushr, [Long(a), Int(b)]) => Long((*a as u64).wrapping_shr(*b as u32 & 0x3f) as i64),
+        (Instruction::Land, [Long(a), Long(b)]) => Long(a & b),
+        (Instruction::Lor, [Long(a), Long(b)]) => Long(a | b),
+        (Instruction::Lxor, [Long(a), Long(b)]) => Long(a ^ b),
+        (Instruction::Fadd, [Float(a), Float(b)]) => Float(*a + *b),
+        (Instruction::Fsub, [Float(a), Float(b)]) => Float(*a - *b),
+        (Instruction::Fmul, [Float(a), Float(b)]) => Float(*a * *b),
+        (Instruction::Fdiv, [Float(a), Float(b)]) => Float(*a / *b),
+        (Instruction::Frem, [Float(a), Float(b)]) => Float(*a % *b),
+        (Instruction::Fneg, [Float(a)]) => Float(-*a),
+        (Instruction::Dadd, [Double(a), Double(b)]) => Double(*a + *b),
+        (Instruction::Dsub, [Double(a), Double(b)]) => Double(*a - *b),
+        (Instruction::Dmul, [Double(a), Double(b)]) => Double(*a * *b),
+        (Instruction::Ddiv, [Double(a), Double(b)]) => Double(*a / *b),
+        (Instruction::Drem, [Double(a), Double(b)]) => Double(*a % *b),
+        (Instruction::Dneg, [Double(a)]) => Double(-*a),
+        // Rust's casts saturate and turn NaN into 0, just like Java's
+        (Instruction::I2l, [Int(a)]) => Long(*a as i64),
+        (Instruction::I2f, [Int(a)]) => Float(*a as f32),
+        (Instruction::I2d, [Int(a)]) => Double(*a as f64),
+        (Instruction::L2i, [Long(a)]) => Int(*a as i32),
+        (Instruction::L2f, [Long(a)]) => Float(*a as f32),
+        (Instruction::L2d, [Long(a)]) => Double(*a as f64),
+        (Instruction::F2i, [Float(a)]) => Int(*a as i32),
+        (Instruction::F2l, [Float(a)]) => Long(*a as i64),
+        (Instruction::F2d, [Float(a)]) => Double(*a as f64),
+        (Instruction::D2i, [Double(a)]) => Int(*a as i32),
+        (Instruction::D2l, [Double(a)]) => Long(*a as i64),
+        (Instruction::D2f, [Double(a)]) => Float(*a as f32),
+        (Instruction::I2b, [Int(a)]) => Int(*a as i8 as i32),
+        (Instruction::I2c, [Int(a)]) => Int(*a as u16 as i32),
+        (Instruction::I2s, [Int(a)]) => Int(*a as i16 as i32),
+        (Instruction::Lcmp, [Long(a), Long(b)]) => Int(a.cmp(b) as i32),
+        (Instruction::Fcmpl, [Float(a), Float(b)]) => Int(compare(a.partial_cmp(b), -1)),
+        (Instruction::Fcmpg, [Float(a), Float(b)]) => Int(compare(a.partial_cmp(b), 1)),
+        (Instruction::Dcmpl, [Double(a), Double(b)]) => Int(compare(a.partial_cmp(b), -1)),
+        (Instruction::Dcmpg, [Double(a), Double(b)]) => Int(compare(a.partial_cmp(b), 1)),
+        _ => return Err(NumericError::Unsupported),
+    })
+}
+
+#[test]
+fn test_computes_like_java() {
+    assert_eq!(Ok(Number::Int(i32::MIN)), compute(&Instruction::Idiv, &[Number::Int(i32::MIN), Number::Int(-1)]));
+    assert_eq!(Ok(Number::Int(-1)), compute(&Instruction::Irem, &[Number::Int(-7), Number::Int(2)]));
+    assert_eq!(Ok(Number::Int(2)), compute(&Instruction::Ishl, &[Number::Int(1), Number::Int(33)]));
+    assert_eq!(Ok(Number::Int(-1)), compute(&Instruction::Fcmpl, &[Number::Float(f32::NAN), Number::Float(0.0)]));
+    assert_eq!(Ok(Number::Int(1)), compute(&Instruction::Dcmpg, &[Number::Double(f64::NAN), Number::Double(0.0)]));
+    assert_eq!(Ok(Number::Long(i64::MAX)), compute(&Instruction::D2l, &[Number::Double(1e300)]));
+    assert_eq!(Ok(Number::Double(f64::INFINITY)), compute(&Instruction::Ddiv, &[Number::Double(1.0), Number::Double(0.0)]));
+    assert_eq!(Err(NumericError::DivisionByZero), compute(&Instruction::Lrem, &[Number::Long(1), Number::Long(0)]));
+    assert_eq!(Err(NumericError::Unsupported), compute(&Instruction::Iadd, &[Number::Int(1), Number::Long(1)]));
+}
@@ -0,0 +1,691 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use crate::bytecode::{Opcode, Operands, StackEffect};
+use crate::constant_pool::ConstantPoolBuilder;
+use crate::dataflow::{Constant, ConstantPropagation};
+use crate::instruction::{self, BranchTarget, CpIndex, DecodeError, Instruction, Literal};
+use crate::stack_map::{self, ClassHierarchy, FrameError};
+use crate::{Attribute, AttributeCode, ConstantPoolInfo, ExceptionTableEntry, JavaClass, LineNumberTableEntry};
+
+/// Why a method couldn't be optimized, and at which pc.
+#[derive(Debug, PartialEq, Clone)]
+pub struct OptimizeError {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}: {}", self.pc, self.message)
+    }
+}
+
+impl Error for OptimizeError {}
+
+impl From<DecodeError> for OptimizeError {
+    fn from(error: DecodeError) -> Self {
+        OptimizeError { pc: error.pc, message: error.to_string() }
+    }
+}
+
+impl From<FrameError> for OptimizeError {
+    fn from(error: FrameError) -> Self {
+        OptimizeError { pc: error.pc, message: error.message }
+    }
+}
+
+fn error<T>(pc: usize, message: impl Into<String>) -> Result<T, OptimizeError> {
+    Err(OptimizeError { pc, message: message.into() })
+}
+
+/// An exception table entry, with positions in [`Body::instructions`] instead of pcs.
+struct Handler {
+    start: usize,
+    end: usize,
+    handler: usize,
+    catch_pc: usize,
+}
+
+/// The code of a method being optimized. Branch targets and everything else pointing into the
+/// code are positions in `instructions` rather than pcs, `instructions.len()` being the end.
+struct Body {
+    instructions: Vec<Instruction>,
+    handlers: Vec<Handler>,
+    /// The entries of each `LineNumberTable`, by the position of the table among the code's attributes.
+    lines: Vec<(usize, Vec<(usize, usize)>)>,
+    /// The entries of each `LocalVariableTable` and `LocalVariableTypeTable`, the same way.
+    locals: Vec<(usize, Vec<LocalRange>)>,
+}
+
+/// Where a local variable table entry applies, and the rest of it as it was read.
+struct LocalRange {
+    start: usize,
+    end: usize,
+    rest: [u8; 6],
+}
+
+const LOCAL_TABLES: [&str; 2] = ["LocalVariableTable", "LocalVariableTypeTable"];
+
+impl Body {
+    fn new(code: &AttributeCode) -> Result<Body, OptimizeError> {
+        let decoded = instruction::decode(&code.code)?;
+        let mut positions: HashMap<usize, usize> = decoded.iter().enumerate().map(|(position, (pc, _))| (*pc, position)).collect();
+        positions.insert(code.code.len(), decoded.len());
+        let position = |pc: usize| match positions.get(&pc) {
+            Some(position) => Ok(*position),
+            None => error(pc, "The pc is not the start of an instruction"),
+        };
+
+        let mut instructions = Vec::with_capacity(decoded.len());
+        for (_, mut instruction) in decoded {
+            for target in instruction.branch_targets_mut() {
+                *target = BranchTarget(position(target.0)?);
+            }
+            instructions.push(instruction);
+        }
+        let mut handlers = Vec::with_capacity(code.exceptions.len());
+        for entry in &code.exceptions {
+            handlers.push(Handler {
+                start: position(entry.start_pc)?,
+                end: position(entry.end_pc)?,
+                handler: position(entry.handler_pc)?,
+                catch_pc: entry.catch_pc,
+            });
+        }
+        let mut lines = Vec::new();
+        let mut locals = Vec::new();
+        for (index, attribute_info) in code.attributes.iter().enumerate() {
+            match &attribute_info.attribute {
+                Attribute::LineNumberTable(table) => {
+                    let mut entries = Vec::with_capacity(table.line_number_entries.len());
+                    for entry in &table.line_number_entries {
+                        entries.push((position(entry.start_pc)?, entry.line_number));
+                    }
+                    lines.push((index, entries));
+                }
+                Attribute::Unknown(unknown) if LOCAL_TABLES.contains(&unknown.attribute_name.as_str()) => {
+                    let info = &unknown.info;
+                    let count = info.get(..2).map_or(0, |count| u16::from_be_bytes([count[0], count[1]]) as usize);
+                    let mut entries = Vec::with_capacity(count);
+                    for entry in info[2.min(info.len())..].chunks_exact(10).take(count) {
+                        let start_pc = u16::from_be_bytes([entry[0], entry[1]]) as usize;
+                        let length = u16::from_be_bytes([entry[2], entry[3]]) as usize;
+                        entries.push(LocalRange {
+                            start: position(start_pc)?,
+                            end: position(start_pc + length)?,
+                            rest: entry[4..].try_into().unwrap(),
+                        });
+                    }
+                    locals.push((index, entries));
+                }
+                _ => {}
+            }
+        }
+        Ok(Body { instructions, handlers, lines, locals })
+    }
+
+    /// Whether each instruction is jumped to or starts an exception handler.
+    fn labels(&self) -> Vec<bool> {
+        let mut labels = vec![false; self.instructions.len() + 1];
+        for instruction in &self.instructions {
+            for target in instruction.branch_targets() {
+                labels[target.0] = true;
+            }
+        }
+        for handler in &self.handlers {
+            labels[handler.handler] = true;
+        }
+        labels
+    }
+
+    /// Drops the `removed` instructions. Whatever pointed at one of them points at the next
+    /// instruction kept, and exception ranges and local variables left empty go away.
+    fn remove(&mut self, removed: &[bool]) {
+        let mut positions = Vec::with_capacity(removed.len() + 1);
+        let mut next = 0;
+        for removed in removed {
+            positions.push(next);
+            next += usize::from(!removed);
+        }
+        positions.push(next);
+
+        let mut instructions = Vec::with_capacity(next);
+        for (mut instruction, removed) in std::mem::take(&mut self.instructions).into_iter().zip(removed) {
+            if *removed {
+                continue;
+            }
+            for target in instruction.branch_targets_mut() {
+                *target = BranchTarget(positions[target.0]);
+            }
+            instructions.push(instruction);
+        }
+        self.instructions = instructions;
+        for handler in &mut self.handlers {
+            handler.start = positions[handler.start];
+            handler.end = positions[handler.end];
+            handler.handler = positions[handler.handler];
+        }
+        self.handlers.retain(|handler| handler.start < handler.end);
+        for (_, entries) in &mut self.lines {
+            for entry in entries.iter_mut() {
+                entry.0 = positions[entry.0];
+            }
+            // When lines collapse onto one instruction, the last one is the line of that instruction
+            let mut by_position: Vec<(usize, usize)> = Vec::with_capacity(entries.len());
+            for entry in entries.drain(..).filter(|entry| entry.0 < next) {
+                match by_position.iter_mut().find(|existing| existing.0 == entry.0) {
+                    Some(existing) => *existing = entry,
+                    None => by_position.push(entry),
+                }
+            }
+            *entries = by_position;
+        }
+        for (_, entries) in &mut self.locals {
+            for entry in entries.iter_mut() {
+                entry.start = positions[entry.start];
+                entry.end = positions[entry.end];
+            }
+            entries.retain(|entry| entry.start < entry.end);
+        }
+    }
+
+    /// Points jumps at the end of the `goto` chains they start, and replaces jumps to a return with
+    /// the return.
+    fn thread_jumps(&mut self) -> bool {
+        let mut changed = false;
+        for position in 0..self.instructions.len() {
+            let mut instruction = self.instructions[position].clone();
+            for target in instruction.branch_targets_mut() {
+                let destination = self.destination(target.0);
+                if destination != target.0 {
+                    *target = BranchTarget(destination);
+                    changed = true;
+                }
+            }
+            if let Some(target) = goto_target(&instruction) {
+                if is_return(&self.instructions[target]) {
+                    instruction = self.instructions[target].clone();
+                    changed = true;
+                }
+            }
+            self.instructions[position] = instruction;
+        }
+        changed
+    }
+
+    /// Where control ends up going from `target` through `goto`s.
+    fn destination(&self, mut target: usize) -> usize {
+        let mut seen = HashSet::new();
+        while let Some(next) = goto_target(&self.instructions[target]) {
+            if !seen.insert(target) {
+                break;
+            }
+            target = next;
+        }
+        target
+    }
+
+    /// Applies the peephole rules and folds constants, each rewrite taking a few consecutive
+    /// instructions that nothing jumps into the middle of.
+    fn rewrite(&mut self, constant_pool: &mut ConstantPoolBuilder) -> bool {
+        let labels = self.labels();
+        let mut removed = vec![false; self.instructions.len()];
+        let mut changed = false;
+        let mut position = 0;
+        while position < self.instructions.len() {
+            let unlabeled = |length: usize| (1..length).all(|offset| !labels[position + offset]);
+            let Some((length, replacement)) = self.peephole(position, constant_pool)
+                .or_else(|| self.fold(position, constant_pool))
+                .filter(|(length, _)| unlabeled(*length)) else {
+                position += 1;
+                continue;
+            };
+            removed[position..position + length].fill(true);
+            if let Some(replacement) = replacement {
+                self.instructions[position] = replacement;
+                removed[position] = false;
+            }
+            position += length;
+            changed = true;
+        }
+        self.remove(&removed);
+        changed
+    }
+
+    /// The number of instructions from `position` a peephole rule applies to, and what replaces them.
+    fn peephole(&self, position: usize, constant_pool: &ConstantPoolBuilder) -> Option<(usize, Option<Instruction>)> {
+        let window = &self.instructions[position..];
+        let next = position + 1;
+        match window {
+            [Instruction::Nop, ..] | [Instruction::Iinc { increment: 0, .. }, ..] => Some((1, None)),
+            [Instruction::Swap, Instruction::Swap, ..] => Some((2, None)),
+            [jump, ..] if goto_target(jump) == Some(next) => Some((1, None)),
+            [branch, ..] if is_conditional(branch) && branch.branch_targets()[0].0 == next => {
+                // The two operand forms, like `if_icmpeq`, pop two ints or references
+                let pop = match branch.opcode().stack_effect() {
+                    StackEffect::Fixed { pops: [_, _], .. } => Instruction::Pop2,
+                    _ => Instruction::Pop,
+                };
+                Some((1, Some(pop)))
+            }
+            // `ifeq skip; goto elsewhere; skip:` is `ifne elsewhere`
+            [branch, jump, ..] if is_conditional(branch) && branch.branch_targets()[0].0 == next + 1 => {
+                let target = goto_target(jump)?;
+                Some((2, Some(instruction::with_branch_target(negated(branch.opcode()), BranchTarget(target)))))
+            }
+            [load, store, ..] if matches!((load.local_access(), store.local_access()),
+                (Some((load_kind, false, load_index)), Some((store_kind, true, store_index))) if load_kind == store_kind && load_index == store_index) => Some((2, None)),
+            [push, Instruction::Pop, ..] if pushed_slots(push, constant_pool) == Some(1) => Some((2, None)),
+            [push, Instruction::Pop2, ..] if pushed_slots(push, constant_pool) == Some(2) => Some((2, None)),
+            _ => None,
+        }
+    }
+
+    /// Folds an operation or a branch on constants just pushed.
+    fn fold(&self, position: usize, constant_pool: &mut ConstantPoolBuilder) -> Option<(usize, Option<Instruction>)> {
+        let first = pushed_constant(&self.instructions[position], constant_pool)?;
+        let second = self.instructions.get(position + 1).and_then(|instruction| pushed_constant(instruction, constant_pool));
+        let (length, operands) = match second {
+            Some(second) => (3, vec![first, second]),
+            None => (2, vec![first]),
+        };
+        let operation = self.instructions.get(position + length - 1)?;
+        // Like `iinc`, an instruction that doesn't pop the constants leaves them on the stack
+        if !matches!(operation.opcode().stack_effect(), StackEffect::Fixed { pops, .. } if pops.len() == operands.len()) {
+            return None;
+        }
+        if is_conditional(operation) {
+            let target = operation.branch_targets()[0];
+            return Some((length, taken(operation.opcode(), &operands)?.then_some(Instruction::Goto(target))));
+        }
+        let folded = ConstantPropagation::fold(operation, &operands)?;
+        Some((length, Some(push(&folded, constant_pool)?)))
+    }
+
+    /// Drops the instructions control can't reach, following branches and exception handlers.
+    fn remove_unreachable(&mut self) -> bool {
+        let mut reachable = vec![false; self.instructions.len()];
+        let mut worklist = vec![0];
+        while !worklist.is_empty() {
+            while let Some(position) = worklist.pop() {
+                if position >= reachable.len() || reachable[position] {
+                    continue;
+                }
+                reachable[position] = true;
+                let instruction = &self.instructions[position];
+                worklist.extend(instruction.branch_targets().iter().map(|target| target.0));
+                if instruction.opcode().can_fall_through() {
+                    worklist.push(position + 1);
+                }
+            }
+            worklist.extend(self.handlers.iter()
+                .filter(|handler| !reachable[handler.handler] && reachable[handler.start..handler.end].contains(&true))
+                .map(|handler| handler.handler));
+        }
+        if !reachable.contains(&false) {
+            return false;
+        }
+        let removed: Vec<bool> = reachable.iter().map(|reachable| !reachable).collect();
+        self.remove(&removed);
+        true
+    }
+
+    /// The pc of each instruction and of the end of the code, turning `goto`s too far for a
+    /// 16-bit offset into `goto_w`s.
+    fn layout(&mut self) -> Result<Vec<usize>, OptimizeError> {
+        loop {
+            let mut pcs = Vec::with_capacity(self.instructions.len() + 1);
+            let mut pc = 0;
+            for instruction in &self.instructions {
+                pcs.push(pc);
+                pc += instruction.length(pc);
+            }
+            pcs.push(pc);
+            let mut widened = false;
+            for (position, instruction) in self.instructions.iter_mut().enumerate() {
+                if instruction.opcode().operands() != Operands::Branch {
+                    continue;
+                }
+                let target = instruction.branch_targets()[0];
+                if i16::try_from(pcs[target.0] as i64 - pcs[position] as i64).is_ok() {
+                    continue;
+                }
+                match instruction {
+                    Instruction::Goto(_) => *instruction = Instruction::GotoW(target),
+                    _ => return error(pcs[position], "The branch is too far"),
+                }
+                widened = true;
+            }
+            if !widened {
+                return Ok(pcs);
+            }
+        }
+    }
+
+    /// Writes the instructions back into `code`, with its exception table, line numbers and local
+    /// variables. Other attributes of the code, the `StackMapTable` among them, are dropped.
+    fn write(mut self, code: &mut AttributeCode) -> Result<(), OptimizeError> {
+        let pcs = self.layout()?;
+        let instructions: Vec<Instruction> = self.instructions.into_iter().map(|mut instruction| {
+            for target in instruction.branch_targets_mut() {
+                *target = BranchTarget(pcs[target.0]);
+            }
+            instruction
+        }).collect();
+        code.code = instruction::encode(&instructions).or_else(|encode_error| error(encode_error.pc, encode_error.kind.to_string()))?;
+        code.code_length = code.code.len();
+        code.exceptions = self.handlers.iter().map(|handler| ExceptionTableEntry {
+            start_pc: pcs[handler.start],
+            end_pc: pcs[handler.end],
+            handler_pc: pcs[handler.handler],
+            catch_pc: handler.catch_pc,
+        }).collect();
+        for (index, entries) in self.lines {
+            if let Attribute::LineNumberTable(table) = &mut code.attributes[index].attribute {
+                table.line_number_entries = entries.into_iter()
+                    .map(|(position, line_number)| LineNumberTableEntry { start_pc: pcs[position], line_number })
+                    .collect();
+                table.attribute_length = 2 + 4 * table.line_number_entries.len();
+            }
+        }
+        for (index, entries) in self.locals {
+            if let Attribute::Unknown(unknown) = &mut code.attributes[index].attribute {
+                let mut info = (entries.len() as u16).to_be_bytes().to_vec();
+                for entry in entries {
+                    info.extend_from_slice(&(pcs[entry.start] as u16).to_be_bytes());
+                    info.extend_from_slice(&((pcs[entry.end] - pcs[entry.start]) as u16).to_be_bytes());
+                    info.extend_from_slice(&entry.rest);
+                }
+                unknown.info = info;
+            }
+        }
+        code.attributes.retain(|attribute_info| match &attribute_info.attribute {
+            Attribute::LineNumberTable(_) => true,
+            Attribute::Unknown(unknown) => LOCAL_TABLES.contains(&unknown.attribute_name.as_str()),
+            _ => false,
+        });
+        Ok(())
+    }
+}
+
+fn goto_target(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Goto(target) | Instruction::GotoW(target) => Some(target.0),
+        _ => None,
+    }
+}
+
+fn is_return(instruction: &Instruction) -> bool {
+    matches!(instruction, Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn
+        | Instruction::Dreturn | Instruction::Areturn | Instruction::Return)
+}
+
+/// Whether the instruction is an `if` of some kind, jumping or falling through.
+fn is_conditional(instruction: &Instruction) -> bool {
+    let opcode = instruction.opcode();
+    opcode.can_branch() && opcode.can_fall_through() && !matches!(opcode, Opcode::Jsr | Opcode::JsrW)
+}
+
+fn negated(opcode: Opcode) -> Opcode {
+    match opcode {
+        Opcode::Ifeq => Opcode::Ifne,
+        Opcode::Ifne => Opcode::Ifeq,
+        Opcode::Iflt => Opcode::Ifge,
+        Opcode::Ifge => Opcode::Iflt,
+        Opcode::Ifgt => Opcode::Ifle,
+        Opcode::Ifle => Opcode::Ifgt,
+        Opcode::IfIcmpeq => Opcode::IfIcmpne,
+        Opcode::IfIcmpne => Opcode::IfIcmpeq,
+        Opcode::IfIcmplt => Opcode::IfIcmpge,
+        Opcode::IfIcmpge => Opcode::IfIcmplt,
+        Opcode::IfIcmpgt => Opcode::IfIcmple,
+        Opcode::IfIcmple => Opcode::IfIcmpgt,
+        Opcode::IfAcmpeq => Opcode::IfAcmpne,
+        Opcode::IfAcmpne => Opcode::IfAcmpeq,
+        Opcode::Ifnull => Opcode::Ifnonnull,
+        Opcode::Ifnonnull => Opcode::Ifnull,
+        _ => unreachable!("{opcode:?} is not a conditional branch"),
+    }
+}
+
+/// Whether an `if` jumps given the constants it compares, the single operand ones comparing with 0.
+fn taken(opcode: Opcode, operands: &[Constant]) -> Option<bool> {
+    let (a, b) = match operands {
+        [Constant::Null] => return match opcode {
+            Opcode::Ifnull => Some(true),
+            Opcode::Ifnonnull => Some(false),
+            _ => None,
+        },
+        [Constant::Int(a)] => (*a, 0),
+        [Constant::Int(a), Constant::Int(b)] => (*a, *b),
+        _ => return None,
+    };
+    Some(match (opcode, operands.len()) {
+        (Opcode::Ifeq, 1) | (Opcode::IfIcmpeq, 2) => a == b,
+        (Opcode::Ifne, 1) | (Opcode::IfIcmpne, 2) => a != b,
+        (Opcode::Iflt, 1) | (Opcode::IfIcmplt, 2) => a < b,
+        (Opcode::Ifge, 1) | (Opcode::IfIcmpge, 2) => a >= b,
+        (Opcode::Ifgt, 1) | (Opcode::IfIcmpgt, 2) => a > b,
+        (Opcode::Ifle, 1) | (Opcode::IfIcmple, 2) => a <= b,
+        _ => return None,
+    })
+}
+
+/// The constant the instruction pushes, if it pushes one without doing anything else.
+fn pushed_constant(instruction: &Instruction, constant_pool: &ConstantPoolBuilder) -> Option<Constant> {
+    match instruction {
+        Instruction::Ldc(CpIndex(index)) | Instruction::LdcW(CpIndex(index)) | Instruction::Ldc2W(CpIndex(index)) => {
+            match constant_pool.get(*index as usize)? {
+                ConstantPoolInfo::Integer(integer) => Some(Constant::Int(integer.value)),
+                ConstantPoolInfo::Float(float) => Some(Constant::Float(float.value.to_bits())),
+                ConstantPoolInfo::Long(long) => Some(Constant::Long(long.value)),
+                ConstantPoolInfo::Double(double) => Some(Constant::Double(double.value.to_bits())),
+                ConstantPoolInfo::String(string) => Some(Constant::String(string.string.clone())),
+                _ => None,
+            }
+        }
+        _ => instruction.pushed_literal().map(|literal| Constant::from_literal(&literal)),
+    }
+}
+
+/// How many slots the instruction pushes, if that is all it does.
+fn pushed_slots(instruction: &Instruction, constant_pool: &ConstantPoolBuilder) -> Option<usize> {
+    match instruction {
+        Instruction::Dup => return Some(1),
+        Instruction::Dup2 => return Some(2),
+        _ => {}
+    }
+    if let Some((ty, false, _)) = instruction.local_access() {
+        return Some(if ty.is_wide() { 2 } else { 1 });
+    }
+    match pushed_constant(instruction, constant_pool)? {
+        Constant::Long(_) | Constant::Double(_) => Some(2),
+        _ => Some(1),
+    }
+}
+
+/// The shortest instruction pushing `constant`, adding it to the constant pool when it needs `ldc`.
+fn push(constant: &Constant, constant_pool: &mut ConstantPoolBuilder) -> Option<Instruction> {
+    let ldc = |index: usize| match u8::try_from(index) {
+        Ok(_) => Instruction::Ldc(CpIndex(index as u16)),
+        Err(_) => Instruction::LdcW(CpIndex(index as u16)),
+    };
+    let literal = constant.literal()?;
+    if let Some(instruction) = Instruction::push(&literal) {
+        return Some(instruction);
+    }
+    Some(match literal {
+        Literal::Int(value) => ldc(constant_pool.integer(value)),
+        Literal::Long(value) => Instruction::Ldc2W(CpIndex(constant_pool.long(value) as u16)),
+        Literal::Float(value) => ldc(constant_pool.float(value)),
+        Literal::Double(value) => Instruction::Ldc2W(CpIndex(constant_pool.double(value) as u16)),
+        _ => return None,
+    })
+}
+
+/// Optimizes a method body until no more rules apply: jumps are threaded through `goto`s,
+/// peephole rules drop redundant instructions like a load stored straight back or a `goto` to
+/// the next instruction, operations and branches on constants are folded, and unreachable code
+/// is removed. Exception tables, line numbers and local variable tables follow the instructions;
+/// the `StackMapTable` is dropped, [`optimize_class`] recomputes it. Returns whether the code
+/// changed, leaving it untouched when it didn't.
+pub fn optimize_code(code: &mut AttributeCode, constant_pool: &mut ConstantPoolBuilder) -> Result<bool, OptimizeError> {
+    let mut body = Body::new(code)?;
+    let mut changed = false;
+    loop {
+        let changed_now = body.thread_jumps() | body.rewrite(constant_pool) | body.remove_unreachable();
+        if !changed_now {
+            break;
+        }
+        changed = true;
+    }
+    if changed {
+        body.write(code)?;
+    }
+    Ok(changed)
+}
+
+/// Optimizes every method of the class with [`optimize_code`] and recomputes the frames of the
+/// methods that changed, the others keeping theirs. Returns whether any method changed.
+pub fn optimize_class(java_class: &mut JavaClass, hierarchy: &dyn ClassHierarchy) -> Result<bool, OptimizeError> {
+    let mut constant_pool = ConstantPoolBuilder::from_constants(java_class.constant_pool_infos.clone());
+    let mut changed = Vec::new();
+    for (position, method) in java_class.methods.iter_mut().enumerate() {
+        let signature = format!("{}{}", method.name, method.descriptor);
+        let in_method = |error: OptimizeError| OptimizeError { pc: error.pc, message: format!("{signature}: {}", error.message) };
+        for attribute_info in &mut method.attributes_info {
+            if let Attribute::Code(code) = &mut attribute_info.attribute {
+                if optimize_code(code, &mut constant_pool).map_err(in_method)? {
+                    changed.push(position);
+                }
+            }
+        }
+    }
+    if changed.is_empty() {
+        return Ok(false);
+    }
+    // The constant pool only grew, so the other methods still refer to the right constants
+    java_class.constant_pool_infos = constant_pool.build();
+    java_class.constant_pool_count = java_class.constant_pool_infos.len() + 1;
+    stack_map::recompute_method_frames(java_class, hierarchy, &changed)?;
+    Ok(true)
+}
+
+#[test]
+fn test_optimizes_redundant_code() {
+    let mut java_class = crate::assembler::assemble("
+        .class public super Redundant
+        .version 52 0
+        .method public static f (I)I
+            .limit stack 2; .limit locals 2
+        Start:
+            .line 1
+            bipush 9; bipush 11; iadd; istore_1
+            .line 2
+            iload_1; istore_1
+            iload_0; ifeq Zero
+            goto Next
+        Next:
+            .line 3
+            iconst_1; ifeq Dead
+            goto Jump
+        Jump:
+            goto Done
+        Zero:
+            iconst_0; ireturn
+        Dead:
+            aconst_null; athrow
+            iconst_2; ireturn
+        Done:
+            .line 4
+            iload_1; ireturn
+        Handler:
+            pop; iconst_m1; ireturn
+            .catch java/lang/RuntimeException from Start to Zero using Handler
+            .catch any from Dead to Done using Handler
+        .end method
+    ").unwrap();
+    assert!(optimize_class(&mut java_class, &stack_map::KnownClasses::new()).unwrap());
+    let java_class = JavaClass::from_bytes(java_class.to_bytes()).unwrap();
+    let code = java_class.methods[0].code().unwrap();
+    assert_eq!("    Code:
+      stack=1, locals=2
+      .line 1
+       0: bipush        20
+       2: istore_1
+      .line 2
+       3: iload_0
+       4: ifne          9
+      .line 3
+       7: iconst_0
+       8: ireturn
+      .line 4
+       9: iload_1
+      10: ireturn
+      11: pop
+      12: iconst_m1
+      13: ireturn
+    Exception table:
+       from    to  target type
+           0     7    11   Class java/lang/RuntimeException
+", crate::disassembler::disassemble(code, &java_class.constant_pool_infos));
+    assert!(code.attributes.iter().any(|attribute_info| matches!(&attribute_info.attribute, Attribute::Unknown(unknown) if unknown.attribute_name == "StackMapTable")));
+}
+
+#[test]
+fn test_optimized_classes_keep_their_frames_and_debug_information() {
+    let hierarchy = stack_map::jdk_classes();
+    for class_file in ["Flow.class", "Decompile.class"] {
+        let original = JavaClass::load_from_file(class_file).unwrap();
+        let mut java_class = original.clone();
+        optimize_class(&mut java_class, &hierarchy).unwrap();
+        let java_class = JavaClass::from_bytes(java_class.to_bytes()).unwrap();
+        for (method, original_method) in java_class.methods.iter().zip(&original.methods) {
+            let Some(code) = method.code() else { continue };
+            stack_map::analyze(&java_class, method, &hierarchy).unwrap();
+            assert!(code.code.len() <= original_method.code().unwrap().code.len(), "{}", method.name);
+            let attribute_names = |code: &AttributeCode| -> Vec<String> {
+                code.attributes.iter().filter_map(|attribute_info| match &attribute_info.attribute {
+                    Attribute::LineNumberTable(_) => Some("LineNumberTable".to_string()),
+                    Attribute::Unknown(unknown) if unknown.attribute_name != "StackMapTable" => Some(unknown.attribute_name.clone()),
+                    _ => None,
+                }).collect()
+            };
+            assert_eq!(attribute_names(original_method.code().unwrap()), attribute_names(code), "{}", method.name);
+        }
+    }
+
+    let mut java_class = JavaClass::load_from_file("MyClass.class").unwrap();
+    assert!(!optimize_class(&mut java_class, &hierarchy).unwrap());
+    assert_eq!(JavaClass::load_from_file("MyClass.class").unwrap(), java_class);
+}
+
+#[test]
+fn test_constants_are_only_folded_into_instructions_that_pop_them() {
+    let mut java_class = crate::assembler::assemble("
+        .class public super Increment
+        .version 52 0
+        .method public static f (I)I
+            .limit stack 2; .limit locals 1
+            iconst_1; iinc 0 1; iload_0; iadd; ireturn
+        .end method
+    ").unwrap();
+    let original = java_class.clone();
+    assert!(!optimize_class(&mut java_class, &stack_map::KnownClasses::new()).unwrap());
+    assert_eq!(original, java_class);
+}
+
+#[test]
+fn test_optimized_frames_merge_through_the_class_hierarchy() {
+    let original = JavaClass::load_from_file("Lists.class").unwrap();
+    let error = optimize_class(&mut original.clone(), &stack_map::KnownClasses::new()).unwrap_err();
+    assert!(error.message.contains("needs the super classes of java/util/"), "{error}");
+
+    let mut java_class = original.clone();
+    assert!(optimize_class(&mut java_class, &stack_map::jdk_classes()).unwrap());
+    match stack_map::run_verified(&java_class) {
+        Some(result) => assert_eq!(Ok("[size 1] [size 1]\n".to_string()), result),
+        None => eprintln!("java is not installed, skipping verification"),
+    }
+}
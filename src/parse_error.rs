@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedEnd { expected: usize, remaining: usize },
+    LimitExceeded { limit: &'static str, value: usize, max: usize },
+    UnknownConstantTag(u8),
+    InvalidConstantIndex { index: usize, expected: &'static str },
+    InvalidUtf8,
+    AttributeLengthMismatch { expected: usize, read: usize },
+    /// The file doesn't start with 0xCAFEBABE.
+    InvalidPrelude(String),
+    /// A Long or Double at this index takes the last slot, leaving its second slot out of the pool.
+    WideConstantInLastSlot(usize),
+}
+
+const EXCERPT_ROW: usize = 16;
+
+/// Why a class file could not be parsed, and where.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    /// Absolute offset in the file of the value the error is about.
+    pub offset: usize,
+    /// The structures being read when parsing failed, outermost first, e.g.
+    /// `["methods[3] \"main\"", "attributes[0] Code", "attributes[1] LineNumberTable"]`.
+    pub path: Vec<String>,
+    excerpt_start: usize,
+    excerpt: Vec<u8>,
+}
+
+impl ParseError {
+    /// Keeps the rows of the file around `offset`, so the error can be rendered without the file.
+    pub fn new(kind: ParseErrorKind, offset: usize, path: Vec<String>, bytes: &[u8]) -> Self {
+        let excerpt_start = (offset / EXCERPT_ROW).saturating_sub(1) * EXCERPT_ROW;
+        let excerpt_end = ((offset / EXCERPT_ROW + 2) * EXCERPT_ROW).min(bytes.len());
+        let excerpt = bytes.get(excerpt_start..excerpt_end).unwrap_or_default().to_vec();
+        ParseError {
+            kind,
+            offset,
+            path,
+            excerpt_start,
+            excerpt,
+        }
+    }
+
+    /// A hex dump of the rows around the failing offset, with the failing byte marked.
+    pub fn hex_excerpt(&self) -> String {
+        let mut output = String::new();
+        let marked_row = self.offset / EXCERPT_ROW * EXCERPT_ROW;
+        let excerpt_end = (self.excerpt_start + self.excerpt.len()).max(marked_row + 1);
+        for row_start in (self.excerpt_start..excerpt_end).step_by(EXCERPT_ROW) {
+            let row = self.excerpt.get(row_start - self.excerpt_start..).unwrap_or_default();
+            let row = &row[..row.len().min(EXCERPT_ROW)];
+            let hex: Vec<String> = row.iter().map(|byte| format!("{byte:02x}")).collect();
+            let ascii: String = row.iter().map(|byte| if byte.is_ascii_graphic() { *byte as char } else { '.' }).collect();
+            output.push_str(&format!("  {row_start:08x}: {:<47}  {ascii}\n", hex.join(" ")));
+            if row_start == marked_row {
+                let column = self.offset - marked_row;
+                output.push_str(&format!("  {}^^\n", " ".repeat(10 + column * 3)));
+            }
+        }
+        output
+    }
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedEnd { expected, remaining } => {
+                write!(f, "Expected {expected} more bytes but only {remaining} are left")
+            }
+            ParseErrorKind::LimitExceeded { limit, value, max } => {
+                write!(f, "The {limit} of {value} exceeds the limit of {max}")
+            }
+            ParseErrorKind::UnknownConstantTag(tag) => write!(f, "Constant pool tag {tag} is not supported"),
+            ParseErrorKind::InvalidConstantIndex { index, expected } => {
+                write!(f, "Constant pool index {index} doesn't point to a {expected} constant")
+            }
+            ParseErrorKind::InvalidUtf8 => write!(f, "The string is not valid modified UTF-8"),
+            ParseErrorKind::AttributeLengthMismatch { expected, read } => {
+                write!(f, "Expected to read {expected} bytes but read {read}")
+            }
+            ParseErrorKind::InvalidPrelude(message) => write!(f, "{message}"),
+            ParseErrorKind::WideConstantInLastSlot(index) => {
+                write!(f, "The long or double constant {index} takes the last constant pool slot but needs two")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at offset 0x{:x}", self.kind, self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path.join(" / "))?;
+        }
+        write!(f, "\n{}", self.hex_excerpt())
+    }
+}
+
+impl Error for ParseError {}
+
+/// What lenient parsing did to get past a problem.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Recovery {
+    /// The attribute was dropped and reading resumed after its declared length.
+    SkippedAttribute,
+    /// The constant was read but its slot was left `Unusable`.
+    SkippedConstant,
+    /// A name, string or class that couldn't be read was replaced by a placeholder.
+    Placeholder,
+    /// Nothing more could be read; the class holds what came before the problem.
+    StoppedReading,
+}
+
+/// A problem lenient parsing recovered from instead of failing.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub error: ParseError,
+    pub recovery: Recovery,
+}
+
+impl fmt::Display for Recovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recovery::SkippedAttribute => write!(f, "Skipped the attribute"),
+            Recovery::SkippedConstant => write!(f, "Skipped the constant"),
+            Recovery::Placeholder => write!(f, "Used a placeholder"),
+            Recovery::StoppedReading => write!(f, "Stopped reading"),
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.recovery, self.error)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(error: ParseError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
@@ -0,0 +1,332 @@
+use std::fmt;
+use crate::descriptor::FieldType;
+
+/// A type in a `Signature` attribute (JVMS §4.7.9.1), which unlike a descriptor keeps type
+/// arguments and type variables.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum TypeSignature {
+    /// A primitive type.
+    Base(FieldType),
+    /// A class with its type arguments, then its inner classes with theirs. The first name is the
+    /// internal name of the outermost class, like `java/util/Map`.
+    Class(Vec<SimpleClassType>),
+    Variable(String),
+    Array(Box<TypeSignature>),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct SimpleClassType {
+    pub name: String,
+    pub arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    Exact(TypeSignature),
+    Extends(TypeSignature),
+    Super(TypeSignature),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct TypeParameter {
+    pub name: String,
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+/// The `Signature` of a generic class.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub super_class: TypeSignature,
+    pub interfaces: Vec<TypeSignature>,
+}
+
+/// The `Signature` of a generic method.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    /// `None` for `void`.
+    pub return_type: Option<TypeSignature>,
+    pub throws: Vec<TypeSignature>,
+}
+
+impl TypeSignature {
+    /// Parses the signature of a field, which is a reference type.
+    pub fn parse(signature: &str) -> Option<TypeSignature> {
+        let mut parser = Parser { rest: signature };
+        let type_signature = parser.reference_type()?;
+        parser.rest.is_empty().then_some(type_signature)
+    }
+
+    pub fn from_field_type(field_type: &FieldType) -> TypeSignature {
+        match field_type {
+            FieldType::Object(class_name) => TypeSignature::Class(vec![SimpleClassType { name: class_name.clone(), arguments: Vec::new() }]),
+            FieldType::Array(element) => TypeSignature::Array(Box::new(TypeSignature::from_field_type(element))),
+            _ => TypeSignature::Base(field_type.clone()),
+        }
+    }
+
+    fn is_object(&self) -> bool {
+        matches!(self, TypeSignature::Class(classes) if classes.len() == 1 && classes[0].name == "java/lang/Object" && classes[0].arguments.is_empty())
+    }
+}
+
+impl ClassSignature {
+    pub fn parse(signature: &str) -> Option<ClassSignature> {
+        let mut parser = Parser { rest: signature };
+        let type_parameters = parser.type_parameters()?;
+        let super_class = parser.class_type()?;
+        let mut interfaces = Vec::new();
+        while !parser.rest.is_empty() {
+            interfaces.push(parser.class_type()?);
+        }
+        Some(ClassSignature { type_parameters, super_class, interfaces })
+    }
+}
+
+impl MethodSignature {
+    pub fn parse(signature: &str) -> Option<MethodSignature> {
+        let mut parser = Parser { rest: signature };
+        let type_parameters = parser.type_parameters()?;
+        parser.expect('(')?;
+        let mut parameters = Vec::new();
+        while !parser.eat(')') {
+            parameters.push(parser.java_type()?);
+        }
+        let return_type = if parser.eat('V') { None } else { Some(parser.java_type()?) };
+        let mut throws = Vec::new();
+        while parser.eat('^') {
+            throws.push(parser.reference_type()?);
+        }
+        parser.rest.is_empty().then_some(MethodSignature { type_parameters, parameters, return_type, throws })
+    }
+}
+
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl Parser<'_> {
+    fn eat(&mut self, character: char) -> bool {
+        match self.rest.strip_prefix(character) {
+            Some(rest) => {
+                self.rest = rest;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn expect(&mut self, character: char) -> Option<()> {
+        self.eat(character).then_some(())
+    }
+
+    /// Reads up to the first of `terminators`, which must follow a non-empty identifier.
+    fn identifier(&mut self, terminators: &[char]) -> Option<String> {
+        let end = self.rest.find(terminators)?;
+        if end == 0 {
+            return None;
+        }
+        let identifier = self.rest[..end].to_string();
+        self.rest = &self.rest[end..];
+        Some(identifier)
+    }
+
+    fn type_parameters(&mut self) -> Option<Vec<TypeParameter>> {
+        let mut type_parameters = Vec::new();
+        if !self.eat('<') {
+            return Some(type_parameters);
+        }
+        while !self.eat('>') {
+            let name = self.identifier(&[':'])?;
+            self.expect(':')?;
+            let class_bound = if self.rest.starts_with(':') { None } else { Some(self.reference_type()?) };
+            let mut interface_bounds = Vec::new();
+            while self.eat(':') {
+                interface_bounds.push(self.reference_type()?);
+            }
+            type_parameters.push(TypeParameter { name, class_bound, interface_bounds });
+        }
+        (!type_parameters.is_empty()).then_some(type_parameters)
+    }
+
+    fn java_type(&mut self) -> Option<TypeSignature> {
+        let base = match self.rest.chars().next()? {
+            'B' => FieldType::Byte,
+            'C' => FieldType::Char,
+            'D' => FieldType::Double,
+            'F' => FieldType::Float,
+            'I' => FieldType::Int,
+            'J' => FieldType::Long,
+            'S' => FieldType::Short,
+            'Z' => FieldType::Boolean,
+            _ => return self.reference_type(),
+        };
+        self.rest = &self.rest[1..];
+        Some(TypeSignature::Base(base))
+    }
+
+    fn reference_type(&mut self) -> Option<TypeSignature> {
+        match self.rest.chars().next()? {
+            'L' => self.class_type(),
+            'T' => {
+                self.rest = &self.rest[1..];
+                let name = self.identifier(&[';'])?;
+                self.expect(';')?;
+                Some(TypeSignature::Variable(name))
+            }
+            '[' => {
+                self.rest = &self.rest[1..];
+                Some(TypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            _ => None,
+        }
+    }
+
+    fn class_type(&mut self) -> Option<TypeSignature> {
+        self.expect('L')?;
+        let mut classes = Vec::new();
+        loop {
+            let name = self.identifier(&['<', '.', ';'])?;
+            let mut arguments = Vec::new();
+            if self.eat('<') {
+                while !self.eat('>') {
+                    arguments.push(match self.rest.chars().next()? {
+                        '*' => {
+                            self.rest = &self.rest[1..];
+                            TypeArgument::Any
+                        }
+                        '+' => {
+                            self.rest = &self.rest[1..];
+                            TypeArgument::Extends(self.reference_type()?)
+                        }
+                        '-' => {
+                            self.rest = &self.rest[1..];
+                            TypeArgument::Super(self.reference_type()?)
+                        }
+                        _ => TypeArgument::Exact(self.reference_type()?),
+                    });
+                }
+                if arguments.is_empty() {
+                    return None;
+                }
+            }
+            classes.push(SimpleClassType { name, arguments });
+            if self.eat(';') {
+                return Some(TypeSignature::Class(classes));
+            }
+            self.expect('.')?;
+        }
+    }
+}
+
+/// The name a class goes by in Java source: `java/util/List` becomes `java.util.List`, and
+/// classes of `java.lang` lose their package.
+pub fn java_class_name(internal_name: &str) -> String {
+    match internal_name.strip_prefix("java/lang/") {
+        Some(simple_name) if !simple_name.contains('/') => simple_name.to_string(),
+        _ => internal_name.replace('/', "."),
+    }
+}
+
+/// The name of a type in Java source, like `int[]` or `String`.
+pub fn java_type_name(field_type: &FieldType) -> String {
+    TypeSignature::from_field_type(field_type).to_string()
+}
+
+impl fmt::Display for TypeSignature {
+    /// Writes the type the way Java source does, like `Map<String, ? extends T>[]`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeSignature::Base(base) => {
+                let name = match base {
+                    FieldType::Byte => "byte",
+                    FieldType::Char => "char",
+                    FieldType::Double => "double",
+                    FieldType::Float => "float",
+                    FieldType::Int => "int",
+                    FieldType::Long => "long",
+                    FieldType::Short => "short",
+                    _ => "boolean",
+                };
+                write!(f, "{name}")
+            }
+            TypeSignature::Class(classes) => {
+                for (position, class) in classes.iter().enumerate() {
+                    if position == 0 {
+                        write!(f, "{}", java_class_name(&class.name))?;
+                    } else {
+                        write!(f, ".{}", class.name)?;
+                    }
+                    if !class.arguments.is_empty() {
+                        let arguments: Vec<String> = class.arguments.iter().map(ToString::to_string).collect();
+                        write!(f, "<{}>", arguments.join(", "))?;
+                    }
+                }
+                Ok(())
+            }
+            TypeSignature::Variable(name) => write!(f, "{name}"),
+            TypeSignature::Array(element) => write!(f, "{element}[]"),
+        }
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeArgument::Any => write!(f, "?"),
+            TypeArgument::Exact(type_signature) => write!(f, "{type_signature}"),
+            TypeArgument::Extends(type_signature) => write!(f, "? extends {type_signature}"),
+            TypeArgument::Super(type_signature) => write!(f, "? super {type_signature}"),
+        }
+    }
+}
+
+impl fmt::Display for TypeParameter {
+    /// Writes the parameter with its bounds, leaving out a lone `Object` bound.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let bounds: Vec<String> = self.class_bound.iter()
+            .filter(|bound| !bound.is_object())
+            .chain(&self.interface_bounds)
+            .map(ToString::to_string)
+            .collect();
+        if !bounds.is_empty() {
+            write!(f, " extends {}", bounds.join(" & "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Writes type parameters as Java source declares them, like `<K, V extends Comparable<V>>`, or
+/// nothing when there are none.
+pub fn type_parameters_to_string(type_parameters: &[TypeParameter]) -> String {
+    if type_parameters.is_empty() {
+        return String::new();
+    }
+    let type_parameters: Vec<String> = type_parameters.iter().map(ToString::to_string).collect();
+    format!("<{}>", type_parameters.join(", "))
+}
+
+#[test]
+fn test_parses_signatures() {
+    let class_signature = ClassSignature::parse("<K:Ljava/lang/Object;V::Ljava/lang/Comparable<TV;>;>Ljava/util/AbstractMap<TK;TV;>;Ljava/io/Serializable;").unwrap();
+    assert_eq!("<K, V extends Comparable<V>>", type_parameters_to_string(&class_signature.type_parameters));
+    assert_eq!("java.util.AbstractMap<K, V>", class_signature.super_class.to_string());
+    assert_eq!("java.io.Serializable", class_signature.interfaces[0].to_string());
+
+    let method_signature = MethodSignature::parse("<T:Ljava/lang/Number;>(Ljava/util/List<+TT;>;[I)TT;^Ljava/io/IOException;").unwrap();
+    assert_eq!("<T extends Number>", type_parameters_to_string(&method_signature.type_parameters));
+    let parameters: Vec<String> = method_signature.parameters.iter().map(ToString::to_string).collect();
+    assert_eq!(vec!["java.util.List<? extends T>", "int[]"], parameters);
+    assert_eq!(Some(TypeSignature::Variable("T".to_string())), method_signature.return_type);
+    assert_eq!("java.io.IOException", method_signature.throws[0].to_string());
+
+    assert_eq!("java.util.Map<K>.Entry<String, ?>[]", TypeSignature::parse("[Ljava/util/Map<TK;>.Entry<Ljava/lang/String;*>;").unwrap().to_string());
+    assert_eq!(None, TypeSignature::parse("Ljava/util/List<>;"));
+    assert_eq!(None, MethodSignature::parse("(I"));
+}
@@ -0,0 +1,1750 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use crate::bytecode::Opcode;
+use crate::constant_pool::ConstantPoolBuilder;
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::instruction::{self, ArrayType, BranchTarget, CpIndex, DecodeError, Instruction, Literal, Type};
+use crate::stack_map::{self, ClassHierarchy, FrameError};
+use crate::{Attribute, AttributeCode, AttributeInfo, ConstantPoolInfo, ExceptionTableEntry, JavaClass, MethodInfo};
+
+/// A virtual register, assigned exactly once.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct Register(pub usize);
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// A block of a [`Function`], by its position in [`Function::blocks`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    Ushr,
+    And,
+    Or,
+    Xor,
+    /// `lcmp`
+    Compare,
+    /// `fcmpl` and `dcmpl`, giving -1 for NaN.
+    CompareL,
+    /// `fcmpg` and `dcmpg`, giving 1 for NaN.
+    CompareG,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum UnaryOp {
+    Neg,
+    ToInt,
+    ToLong,
+    ToFloat,
+    ToDouble,
+    ToByte,
+    ToChar,
+    ToShort,
+}
+
+/// The element type an array load or store works on. `baload` and `bastore` serve `boolean`
+/// arrays as well.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum ArrayKind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+    Byte,
+    Char,
+    Short,
+}
+
+impl ArrayKind {
+    fn value_type(self) -> Type {
+        match self {
+            ArrayKind::Long => Type::Long,
+            ArrayKind::Float => Type::Float,
+            ArrayKind::Double => Type::Double,
+            ArrayKind::Reference => Type::Reference,
+            _ => Type::Int,
+        }
+    }
+}
+
+/// The opcode of each binary operation and the type of its left operand. Shifts take an `int`
+/// distance, everything else two operands of the same type.
+const BINARY_OPERATIONS: &[(Opcode, BinaryOp, Type)] = &[
+    (Opcode::Iadd, BinaryOp::Add, Type::Int), (Opcode::Ladd, BinaryOp::Add, Type::Long),
+    (Opcode::Fadd, BinaryOp::Add, Type::Float), (Opcode::Dadd, BinaryOp::Add, Type::Double),
+    (Opcode::Isub, BinaryOp::Sub, Type::Int), (Opcode::Lsub, BinaryOp::Sub, Type::Long),
+    (Opcode::Fsub, BinaryOp::Sub, Type::Float), (Opcode::Dsub, BinaryOp::Sub, Type::Double),
+    (Opcode::Imul, BinaryOp::Mul, Type::Int), (Opcode::Lmul, BinaryOp::Mul, Type::Long),
+    (Opcode::Fmul, BinaryOp::Mul, Type::Float), (Opcode::Dmul, BinaryOp::Mul, Type::Double),
+    (Opcode::Idiv, BinaryOp::Div, Type::Int), (Opcode::Ldiv, BinaryOp::Div, Type::Long),
+    (Opcode::Fdiv, BinaryOp::Div, Type::Float), (Opcode::Ddiv, BinaryOp::Div, Type::Double),
+    (Opcode::Irem, BinaryOp::Rem, Type::Int), (Opcode::Lrem, BinaryOp::Rem, Type::Long),
+    (Opcode::Frem, BinaryOp::Rem, Type::Float), (Opcode::Drem, BinaryOp::Rem, Type::Double),
+    (Opcode::Ishl, BinaryOp::Shl, Type::Int), (Opcode::Lshl, BinaryOp::Shl, Type::Long),
+    (Opcode::Ishr, BinaryOp::Shr, Type::Int), (Opcode::Lshr, BinaryOp::Shr, Type::Long),
+    (Opcode::Iushr, BinaryOp::Ushr, Type::Int), (Opcode::Lushr, BinaryOp::Ushr, Type::Long),
+    (Opcode::Iand, BinaryOp::And, Type::Int), (Opcode::Land, BinaryOp::And, Type::Long),
+    (Opcode::Ior, BinaryOp::Or, Type::Int), (Opcode::Lor, BinaryOp::Or, Type::Long),
+    (Opcode::Ixor, BinaryOp::Xor, Type::Int), (Opcode::Lxor, BinaryOp::Xor, Type::Long),
+    (Opcode::Lcmp, BinaryOp::Compare, Type::Long),
+    (Opcode::Fcmpl, BinaryOp::CompareL, Type::Float), (Opcode::Fcmpg, BinaryOp::CompareG, Type::Float),
+    (Opcode::Dcmpl, BinaryOp::CompareL, Type::Double), (Opcode::Dcmpg, BinaryOp::CompareG, Type::Double),
+];
+
+/// The opcode of each unary operation and the type of its operand.
+const UNARY_OPERATIONS: &[(Opcode, UnaryOp, Type)] = &[
+    (Opcode::Ineg, UnaryOp::Neg, Type::Int), (Opcode::Lneg, UnaryOp::Neg, Type::Long),
+    (Opcode::Fneg, UnaryOp::Neg, Type::Float), (Opcode::Dneg, UnaryOp::Neg, Type::Double),
+    (Opcode::I2l, UnaryOp::ToLong, Type::Int), (Opcode::I2f, UnaryOp::ToFloat, Type::Int),
+    (Opcode::I2d, UnaryOp::ToDouble, Type::Int), (Opcode::L2i, UnaryOp::ToInt, Type::Long),
+    (Opcode::L2f, UnaryOp::ToFloat, Type::Long), (Opcode::L2d, UnaryOp::ToDouble, Type::Long),
+    (Opcode::F2i, UnaryOp::ToInt, Type::Float), (Opcode::F2l, UnaryOp::ToLong, Type::Float),
+    (Opcode::F2d, UnaryOp::ToDouble, Type::Float), (Opcode::D2i, UnaryOp::ToInt, Type::Double),
+    (Opcode::D2l, UnaryOp::ToLong, Type::Double), (Opcode::D2f, UnaryOp::ToFloat, Type::Double),
+    (Opcode::I2b, UnaryOp::ToByte, Type::Int), (Opcode::I2c, UnaryOp::ToChar, Type::Int),
+    (Opcode::I2s, UnaryOp::ToShort, Type::Int),
+];
+
+/// The load and store opcodes of each kind of array.
+const ARRAY_OPERATIONS: &[(ArrayKind, Opcode, Opcode)] = &[
+    (ArrayKind::Int, Opcode::Iaload, Opcode::Iastore), (ArrayKind::Long, Opcode::Laload, Opcode::Lastore),
+    (ArrayKind::Float, Opcode::Faload, Opcode::Fastore), (ArrayKind::Double, Opcode::Daload, Opcode::Dastore),
+    (ArrayKind::Reference, Opcode::Aaload, Opcode::Aastore), (ArrayKind::Byte, Opcode::Baload, Opcode::Bastore),
+    (ArrayKind::Char, Opcode::Caload, Opcode::Castore), (ArrayKind::Short, Opcode::Saload, Opcode::Sastore),
+];
+
+/// The opcodes loading, storing and returning each type.
+const LOCAL_OPERATIONS: &[(Type, Opcode, Opcode, Opcode)] = &[
+    (Type::Int, Opcode::Iload, Opcode::Istore, Opcode::Ireturn), (Type::Long, Opcode::Lload, Opcode::Lstore, Opcode::Lreturn),
+    (Type::Float, Opcode::Fload, Opcode::Fstore, Opcode::Freturn), (Type::Double, Opcode::Dload, Opcode::Dstore, Opcode::Dreturn),
+    (Type::Reference, Opcode::Aload, Opcode::Astore, Opcode::Areturn),
+];
+
+/// The short forms loading and storing locals 0 to 3 of each type.
+const SHORT_LOCAL_OPERATIONS: &[(Type, [Opcode; 4], [Opcode; 4])] = &[
+    (Type::Int, [Opcode::Iload0, Opcode::Iload1, Opcode::Iload2, Opcode::Iload3], [Opcode::Istore0, Opcode::Istore1, Opcode::Istore2, Opcode::Istore3]),
+    (Type::Long, [Opcode::Lload0, Opcode::Lload1, Opcode::Lload2, Opcode::Lload3], [Opcode::Lstore0, Opcode::Lstore1, Opcode::Lstore2, Opcode::Lstore3]),
+    (Type::Float, [Opcode::Fload0, Opcode::Fload1, Opcode::Fload2, Opcode::Fload3], [Opcode::Fstore0, Opcode::Fstore1, Opcode::Fstore2, Opcode::Fstore3]),
+    (Type::Double, [Opcode::Dload0, Opcode::Dload1, Opcode::Dload2, Opcode::Dload3], [Opcode::Dstore0, Opcode::Dstore1, Opcode::Dstore2, Opcode::Dstore3]),
+    (Type::Reference, [Opcode::Aload0, Opcode::Aload1, Opcode::Aload2, Opcode::Aload3], [Opcode::Astore0, Opcode::Astore1, Opcode::Astore2, Opcode::Astore3]),
+];
+
+/// The load, store and return opcodes of a type.
+fn local_opcodes(ty: Type) -> (Opcode, Opcode, Opcode) {
+    LOCAL_OPERATIONS.iter().find(|(entry_ty, _, _, _)| *entry_ty == ty).map(|(_, load, store, ret)| (*load, *store, *ret)).unwrap()
+}
+
+/// The short load and store opcodes of a type, for locals 0 to 3.
+fn short_local_opcodes(ty: Type) -> ([Opcode; 4], [Opcode; 4]) {
+    SHORT_LOCAL_OPERATIONS.iter().find(|(entry_ty, _, _)| *entry_ty == ty).map(|(_, loads, stores)| (*loads, *stores)).unwrap()
+}
+
+fn binary_opcode(op: BinaryOp, ty: Type) -> Option<Opcode> {
+    BINARY_OPERATIONS.iter().find(|(_, entry_op, entry_ty)| *entry_op == op && *entry_ty == ty).map(|(opcode, _, _)| *opcode)
+}
+
+fn unary_opcode(op: UnaryOp, ty: Type) -> Option<Opcode> {
+    UNARY_OPERATIONS.iter().find(|(_, entry_op, entry_ty)| *entry_op == op && *entry_ty == ty).map(|(opcode, _, _)| *opcode)
+}
+
+fn array_opcodes(kind: ArrayKind) -> (Opcode, Opcode) {
+    ARRAY_OPERATIONS.iter().find(|(entry_kind, _, _)| *entry_kind == kind).map(|(_, load, store)| (*load, *store)).unwrap()
+}
+
+/// A field a `getfield`, `putfield`, `getstatic` or `putstatic` refers to.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct FieldRef {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+}
+
+impl fmt::Display for FieldRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+/// A method an invoke instruction refers to. `interface` tells a `CONSTANT_InterfaceMethodref`
+/// from a `CONSTANT_Methodref`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct MethodRef {
+    pub class_name: String,
+    pub name: String,
+    pub descriptor: String,
+    pub interface: bool,
+}
+
+impl fmt::Display for MethodRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class_name, self.name, self.descriptor)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum InvokeKind {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+}
+
+impl InvokeKind {
+    fn opcode(self) -> Opcode {
+        match self {
+            InvokeKind::Virtual => Opcode::Invokevirtual,
+            InvokeKind::Special => Opcode::Invokespecial,
+            InvokeKind::Static => Opcode::Invokestatic,
+            InvokeKind::Interface => Opcode::Invokeinterface,
+        }
+    }
+}
+
+/// What a [`Statement`] computes or does.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Operation {
+    Literal(Literal),
+    /// `ty` is the type of `left`.
+    Binary { op: BinaryOp, ty: Type, left: Register, right: Register },
+    /// `ty` is the type of `value`.
+    Unary { op: UnaryOp, ty: Type, value: Register },
+    ArrayLoad { kind: ArrayKind, array: Register, index: Register },
+    ArrayStore { kind: ArrayKind, array: Register, index: Register, value: Register },
+    ArrayLength(Register),
+    /// Reads a static field when there is no `object`.
+    GetField { field: FieldRef, object: Option<Register> },
+    PutField { field: FieldRef, object: Option<Register>, value: Register },
+    /// The receiver, if any, comes first in `arguments`.
+    Invoke { kind: InvokeKind, method: MethodRef, arguments: Vec<Register> },
+    /// `index` is the `CONSTANT_InvokeDynamic` in the constant pool of the class the function was
+    /// lifted from.
+    InvokeDynamic { index: CpIndex, name: String, descriptor: String, arguments: Vec<Register> },
+    New(String),
+    /// Creates an array of `array_type`, with one length per dimension to create.
+    NewArray { array_type: FieldType, lengths: Vec<Register> },
+    CheckCast { class_name: String, value: Register },
+    InstanceOf { class_name: String, value: Register },
+    MonitorEnter(Register),
+    MonitorExit(Register),
+    /// The exception an exception handler block starts with. Only ever its first statement.
+    CaughtException,
+}
+
+impl Operation {
+    /// The registers the operation reads, in order.
+    pub fn operands(&self) -> Vec<Register> {
+        let mut operation = self.clone();
+        operation.operands_mut().into_iter().map(|register| *register).collect()
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Register> {
+        match self {
+            Operation::Literal(_) | Operation::New(_) | Operation::CaughtException => Vec::new(),
+            Operation::Binary { left, right, .. } => vec![left, right],
+            Operation::Unary { value, .. } | Operation::CheckCast { value, .. } | Operation::InstanceOf { value, .. }
+            | Operation::ArrayLength(value) | Operation::MonitorEnter(value) | Operation::MonitorExit(value) => vec![value],
+            Operation::ArrayLoad { array, index, .. } => vec![array, index],
+            Operation::ArrayStore { array, index, value, .. } => vec![array, index, value],
+            Operation::GetField { object, .. } => object.iter_mut().collect(),
+            Operation::PutField { object, value, .. } => object.iter_mut().chain(std::iter::once(value)).collect(),
+            Operation::Invoke { arguments, .. } | Operation::InvokeDynamic { arguments, .. } => arguments.iter_mut().collect(),
+            Operation::NewArray { lengths, .. } => lengths.iter_mut().collect(),
+        }
+    }
+}
+
+/// One operation, assigning its result to `target` if it has one. `pc` is where the instruction
+/// it was lifted from was.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    pub target: Option<Register>,
+    pub operation: Operation,
+    pub pc: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Condition {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+}
+
+impl Condition {
+    fn negate(self) -> Condition {
+        match self {
+            Condition::Eq => Condition::Ne,
+            Condition::Ne => Condition::Eq,
+            Condition::Lt => Condition::Ge,
+            Condition::Ge => Condition::Lt,
+            Condition::Gt => Condition::Le,
+            Condition::Le => Condition::Gt,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = match self {
+            Condition::Eq => "==",
+            Condition::Ne => "!=",
+            Condition::Lt => "<",
+            Condition::Ge => ">=",
+            Condition::Gt => ">",
+            Condition::Le => "<=",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+/// The opcodes of the conditional branches: comparing with zero or `null`, and comparing two values.
+const BRANCHES: &[(Opcode, Condition, Type, bool)] = &[
+    (Opcode::Ifeq, Condition::Eq, Type::Int, false), (Opcode::Ifne, Condition::Ne, Type::Int, false),
+    (Opcode::Iflt, Condition::Lt, Type::Int, false), (Opcode::Ifge, Condition::Ge, Type::Int, false),
+    (Opcode::Ifgt, Condition::Gt, Type::Int, false), (Opcode::Ifle, Condition::Le, Type::Int, false),
+    (Opcode::IfIcmpeq, Condition::Eq, Type::Int, true), (Opcode::IfIcmpne, Condition::Ne, Type::Int, true),
+    (Opcode::IfIcmplt, Condition::Lt, Type::Int, true), (Opcode::IfIcmpge, Condition::Ge, Type::Int, true),
+    (Opcode::IfIcmpgt, Condition::Gt, Type::Int, true), (Opcode::IfIcmple, Condition::Le, Type::Int, true),
+    (Opcode::IfAcmpeq, Condition::Eq, Type::Reference, true), (Opcode::IfAcmpne, Condition::Ne, Type::Reference, true),
+    (Opcode::Ifnull, Condition::Eq, Type::Reference, false), (Opcode::Ifnonnull, Condition::Ne, Type::Reference, false),
+];
+
+/// How a block ends.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Terminator {
+    Goto(BlockId),
+    /// Compares `left` with `right`, or with 0 or `null` when there is no `right`.
+    If { condition: Condition, left: Register, right: Option<Register>, then: BlockId, otherwise: BlockId },
+    Switch { value: Register, cases: Vec<(i32, BlockId)>, default: BlockId },
+    Return(Option<Register>),
+    Throw(Register),
+}
+
+impl Terminator {
+    /// The blocks execution continues with, not counting exception handlers.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Goto(target) => vec![*target],
+            Terminator::If { then, otherwise, .. } => vec![*then, *otherwise],
+            Terminator::Switch { cases, default, .. } => cases.iter().map(|(_, target)| *target).chain(std::iter::once(*default)).collect(),
+            Terminator::Return(_) | Terminator::Throw(_) => Vec::new(),
+        }
+    }
+
+    pub fn successors_mut(&mut self) -> Vec<&mut BlockId> {
+        match self {
+            Terminator::Goto(target) => vec![target],
+            Terminator::If { then, otherwise, .. } => vec![then, otherwise],
+            Terminator::Switch { cases, default, .. } => cases.iter_mut().map(|(_, target)| target).chain(std::iter::once(default)).collect(),
+            Terminator::Return(_) | Terminator::Throw(_) => Vec::new(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Register> {
+        match self {
+            Terminator::Goto(_) | Terminator::Return(None) => Vec::new(),
+            Terminator::If { left, right, .. } => std::iter::once(left).chain(right.iter_mut()).collect(),
+            Terminator::Switch { value, .. } | Terminator::Return(Some(value)) | Terminator::Throw(value) => vec![value],
+        }
+    }
+}
+
+/// Picks the value of `target` by the block control came from.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Phi {
+    pub target: Register,
+    pub incoming: Vec<(BlockId, Register)>,
+}
+
+/// Where an exception goes, `catch_type` being `None` for handlers catching anything.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Handler {
+    pub catch_type: Option<String>,
+    pub block: BlockId,
+}
+
+/// A basic block. Its `handlers` cover its last statement, or its terminator when that is a
+/// return or a throw: blocks are split after every instruction that can throw into a handler, so
+/// the phis of the handler see the registers as they were at the throw.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Block {
+    /// The pc of the first instruction the block was lifted from.
+    pub pc: usize,
+    pub phis: Vec<Phi>,
+    pub statements: Vec<Statement>,
+    pub terminator: Terminator,
+    pub handlers: Vec<Handler>,
+}
+
+/// A method in SSA form. The first block is the entry.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Function {
+    pub name: String,
+    pub descriptor: String,
+    pub is_static: bool,
+    /// `this` first for instance methods, then the declared parameters.
+    pub parameters: Vec<Register>,
+    /// The type of every register, by its number.
+    pub types: Vec<Type>,
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    pub fn new_register(&mut self, ty: Type) -> Register {
+        self.types.push(ty);
+        Register(self.types.len() - 1)
+    }
+
+    pub fn register_type(&self, register: Register) -> Type {
+        self.types[register.0]
+    }
+
+    /// The blocks each block is entered from, normally or by an exception, without duplicates.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (block, basic_block) in self.blocks.iter().enumerate() {
+            let successors = basic_block.terminator.successors().into_iter().chain(basic_block.handlers.iter().map(|handler| handler.block));
+            for successor in successors {
+                if !predecessors[successor.0].contains(&BlockId(block)) {
+                    predecessors[successor.0].push(BlockId(block));
+                }
+            }
+        }
+        predecessors
+    }
+}
+
+fn list(registers: &[Register]) -> String {
+    registers.iter().map(ToString::to_string).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Literal(literal) => write!(f, "const {literal}"),
+            Operation::Binary { op, ty, left, right } => write!(f, "{} {left}, {right}", binary_opcode(*op, *ty).map_or("?", Opcode::mnemonic)),
+            Operation::Unary { op, ty, value } => write!(f, "{} {value}", unary_opcode(*op, *ty).map_or("?", Opcode::mnemonic)),
+            Operation::ArrayLoad { kind, array, index } => write!(f, "{} {array}[{index}]", array_opcodes(*kind).0.mnemonic()),
+            Operation::ArrayStore { kind, array, index, value } => write!(f, "{} {array}[{index}] = {value}", array_opcodes(*kind).1.mnemonic()),
+            Operation::ArrayLength(array) => write!(f, "arraylength {array}"),
+            Operation::GetField { field, object: Some(object) } => write!(f, "getfield {object}.{field}"),
+            Operation::GetField { field, object: None } => write!(f, "getstatic {field}"),
+            Operation::PutField { field, object: Some(object), value } => write!(f, "putfield {object}.{field} = {value}"),
+            Operation::PutField { field, object: None, value } => write!(f, "putstatic {field} = {value}"),
+            Operation::Invoke { kind, method, arguments } => write!(f, "{} {method}({})", kind.opcode().mnemonic(), list(arguments)),
+            Operation::InvokeDynamic { index, name, descriptor, arguments } => write!(f, "invokedynamic #{} {name}:{descriptor}({})", index.0, list(arguments)),
+            Operation::New(class_name) => write!(f, "new {class_name}"),
+            Operation::NewArray { array_type, lengths } => write!(f, "newarray {array_type}({})", list(lengths)),
+            Operation::CheckCast { class_name, value } => write!(f, "checkcast {class_name} {value}"),
+            Operation::InstanceOf { class_name, value } => write!(f, "instanceof {class_name} {value}"),
+            Operation::MonitorEnter(value) => write!(f, "monitorenter {value}"),
+            Operation::MonitorExit(value) => write!(f, "monitorexit {value}"),
+            Operation::CaughtException => write!(f, "caught"),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Goto(target) => write!(f, "goto {target}"),
+            Terminator::If { condition, left, right, then, otherwise } => {
+                let right = right.map_or_else(|| "0".to_string(), |right| right.to_string());
+                write!(f, "if {left} {condition} {right} goto {then} else {otherwise}")
+            }
+            Terminator::Switch { value, cases, default } => {
+                let cases: Vec<String> = cases.iter().map(|(key, target)| format!("{key}: {target}")).collect();
+                write!(f, "switch {value} [{}] default {default}", cases.join(", "))
+            }
+            Terminator::Return(Some(value)) => write!(f, "return {value}"),
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Throw(value) => write!(f, "throw {value}"),
+        }
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parameters: Vec<String> = self.parameters.iter().map(|parameter| format!("{} {parameter}", self.register_type(*parameter))).collect();
+        let modifier = if self.is_static { "static " } else { "" };
+        writeln!(f, "{modifier}{}{} ({}) {{", self.name, self.descriptor, parameters.join(", "))?;
+        for (block, basic_block) in self.blocks.iter().enumerate() {
+            writeln!(f, "b{block}: // pc {}", basic_block.pc)?;
+            for phi in &basic_block.phis {
+                let incoming: Vec<String> = phi.incoming.iter().map(|(from, value)| format!("{from}: {value}")).collect();
+                writeln!(f, "    {} {} = phi [{}]", self.register_type(phi.target), phi.target, incoming.join(", "))?;
+            }
+            for statement in &basic_block.statements {
+                match statement.target {
+                    Some(target) => writeln!(f, "    {} {target} = {}", self.register_type(target), statement.operation)?,
+                    None => writeln!(f, "    {}", statement.operation)?,
+                }
+            }
+            match &basic_block.terminator {
+                Terminator::If { condition, left, right: None, then, otherwise } if self.register_type(*left) == Type::Reference => {
+                    writeln!(f, "    if {left} {condition} null goto {then} else {otherwise}")?;
+                }
+                terminator => writeln!(f, "    {terminator}")?,
+            }
+            for handler in &basic_block.handlers {
+                writeln!(f, "    catch {} -> {}", handler.catch_type.as_deref().unwrap_or("any"), handler.block)?;
+            }
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// Why a method couldn't be lifted or lowered, and at which pc.
+#[derive(Debug, PartialEq, Clone)]
+pub struct IrError {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl fmt::Display for IrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}: {}", self.pc, self.message)
+    }
+}
+
+impl Error for IrError {}
+
+impl From<DecodeError> for IrError {
+    fn from(error: DecodeError) -> Self {
+        IrError { pc: error.pc, message: error.to_string() }
+    }
+}
+
+impl From<FrameError> for IrError {
+    fn from(error: FrameError) -> Self {
+        IrError { pc: error.pc, message: error.message }
+    }
+}
+
+fn error<T>(pc: usize, message: impl Into<String>) -> Result<T, IrError> {
+    Err(IrError { pc, message: message.into() })
+}
+
+/// What the lifter tracks per block: locals and operand stack slots.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+enum Variable {
+    Local(usize),
+    Stack(usize),
+}
+
+/// The operand stack while lifting a block, one entry per slot. The second slot of a `long` or
+/// `double` is `None`.
+type SymbolicStack = Vec<Option<Register>>;
+
+/// Builds SSA form while walking the blocks, the way Braun et al. describe in "Simple and
+/// Efficient Construction of Static Single Assignment Form": reading a variable a block doesn't
+/// define looks it up in the predecessors, placing phis where they meet, and blocks whose
+/// predecessors aren't all lifted yet get incomplete phis that are filled in once they are.
+struct Lifter<'a> {
+    constant_pool_infos: &'a [ConstantPoolInfo],
+    blocks: Vec<Block>,
+    predecessors: Vec<Vec<usize>>,
+    types: Vec<Option<Type>>,
+    definitions: HashMap<(Variable, usize), Register>,
+    sealed: Vec<bool>,
+    filled: Vec<bool>,
+    incomplete: Vec<Vec<(Variable, Register)>>,
+    exit_stacks: Vec<Option<SymbolicStack>>,
+}
+
+impl Lifter<'_> {
+    fn new_register(&mut self, ty: Option<Type>) -> Register {
+        self.types.push(ty);
+        Register(self.types.len() - 1)
+    }
+
+    fn new_phi(&mut self, block: usize) -> Register {
+        let target = self.new_register(None);
+        self.blocks[block].phis.push(Phi { target, incoming: Vec::new() });
+        target
+    }
+
+    fn read(&mut self, variable: Variable, block: usize) -> Result<Register, IrError> {
+        if let Some(register) = self.definitions.get(&(variable, block)) {
+            return Ok(*register);
+        }
+        let register = if !self.sealed[block] {
+            let phi = self.new_phi(block);
+            self.incomplete[block].push((variable, phi));
+            phi
+        } else if self.predecessors[block].len() == 1 {
+            self.read(variable, self.predecessors[block][0])?
+        } else if self.predecessors[block].is_empty() {
+            return error(self.blocks[block].pc, format!("{variable:?} is read before it is set"));
+        } else {
+            let phi = self.new_phi(block);
+            self.definitions.insert((variable, block), phi);
+            self.add_phi_operands(variable, block, phi)?;
+            phi
+        };
+        self.definitions.insert((variable, block), register);
+        Ok(register)
+    }
+
+    fn add_phi_operands(&mut self, variable: Variable, block: usize, phi: Register) -> Result<(), IrError> {
+        for predecessor in self.predecessors[block].clone() {
+            let value = self.read(variable, predecessor)?;
+            if self.types[phi.0].is_none() {
+                self.types[phi.0] = self.types[value.0];
+            }
+            let phi = self.blocks[block].phis.iter_mut().find(|candidate| candidate.target == phi).unwrap();
+            phi.incoming.push((BlockId(predecessor), value));
+        }
+        Ok(())
+    }
+
+    fn seal(&mut self, block: usize) -> Result<(), IrError> {
+        self.sealed[block] = true;
+        for (variable, phi) in std::mem::take(&mut self.incomplete[block]) {
+            self.add_phi_operands(variable, block, phi)?;
+        }
+        Ok(())
+    }
+
+    fn constant(&self, pc: usize, CpIndex(index): CpIndex) -> Result<&ConstantPoolInfo, IrError> {
+        match (index as usize).checked_sub(1).and_then(|position| self.constant_pool_infos.get(position)) {
+            Some(constant) => Ok(constant),
+            None => error(pc, format!("#{index} is not in the constant pool")),
+        }
+    }
+
+    fn class_name(&self, pc: usize, index: CpIndex) -> Result<String, IrError> {
+        match self.constant(pc, index)? {
+            ConstantPoolInfo::Class(class) => Ok(class.name.clone()),
+            _ => error(pc, format!("#{} is not a class", index.0)),
+        }
+    }
+
+    fn emit(&mut self, block: usize, pc: usize, operation: Operation, ty: Option<Type>) -> Option<Register> {
+        let target = ty.map(|ty| self.new_register(Some(ty)));
+        self.blocks[block].statements.push(Statement { target, operation, pc });
+        target
+    }
+
+    /// Lifts one instruction, giving the terminator if it ends the block. Branch targets are
+    /// given as pcs and turned into blocks by the caller.
+    fn instruction(&mut self, block: usize, pc: usize, instruction: &Instruction, stack: &mut SymbolicStack) -> Result<Option<Terminator>, IrError> {
+        fn pop(stack: &mut SymbolicStack, pc: usize) -> Result<Register, IrError> {
+            loop {
+                match stack.pop() {
+                    Some(Some(register)) => return Ok(register),
+                    Some(None) => continue,
+                    None => return error(pc, "The operand stack underflows"),
+                }
+            }
+        }
+        fn pop_many(stack: &mut SymbolicStack, pc: usize, count: usize) -> Result<Vec<Register>, IrError> {
+            let mut values = (0..count).map(|_| pop(stack, pc)).collect::<Result<Vec<Register>, IrError>>()?;
+            values.reverse();
+            Ok(values)
+        }
+        let push = |stack: &mut SymbolicStack, register: Register, ty: Type| {
+            stack.push(Some(register));
+            if ty.is_wide() {
+                stack.push(None);
+            }
+        };
+
+        let opcode = instruction.opcode();
+        let mnemonic = opcode.mnemonic();
+        let literal = match instruction {
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => Some(match self.constant(pc, *index)? {
+                ConstantPoolInfo::Integer(integer) => Literal::Int(integer.value),
+                ConstantPoolInfo::Float(float) => Literal::Float(float.value),
+                ConstantPoolInfo::Long(long) => Literal::Long(long.value),
+                ConstantPoolInfo::Double(double) => Literal::Double(double.value),
+                ConstantPoolInfo::String(string) => Literal::String(string.string.clone()),
+                ConstantPoolInfo::Class(class) => Literal::Class(class.name.clone()),
+                ConstantPoolInfo::Dynamic(dynamic) => {
+                    let ty = FieldType::parse(&dynamic.descriptor).map_or(Type::Reference, |field_type| Type::of(&field_type));
+                    Literal::Pooled { index: *index, ty }
+                }
+                _ => Literal::Pooled { index: *index, ty: Type::Reference },
+            }),
+            _ => instruction.pushed_literal(),
+        };
+        if let Some(literal) = literal {
+            let ty = literal.ty();
+            let register = self.emit(block, pc, Operation::Literal(literal), Some(ty)).unwrap();
+            push(stack, register, ty);
+            return Ok(None);
+        }
+
+        if let Some((_, store, index)) = instruction.local_access() {
+            let index = index as usize;
+            if store {
+                let register = pop(stack, pc)?;
+                self.definitions.insert((Variable::Local(index), block), register);
+            } else {
+                let register = self.read(Variable::Local(index), block)?;
+                let ty = self.types[register.0].unwrap_or(Type::Int);
+                push(stack, register, ty);
+            }
+            return Ok(None);
+        }
+        if let Some((_, op, ty)) = BINARY_OPERATIONS.iter().find(|(entry, _, _)| *entry == opcode) {
+            let [left, right] = pop_many(stack, pc, 2)?[..] else { unreachable!() };
+            let result_type = if matches!(op, BinaryOp::Compare | BinaryOp::CompareL | BinaryOp::CompareG) { Type::Int } else { *ty };
+            let register = self.emit(block, pc, Operation::Binary { op: *op, ty: *ty, left, right }, Some(result_type)).unwrap();
+            push(stack, register, result_type);
+            return Ok(None);
+        }
+        if let Some((_, op, ty)) = UNARY_OPERATIONS.iter().find(|(entry, _, _)| *entry == opcode) {
+            let value = pop(stack, pc)?;
+            let result_type = match op {
+                UnaryOp::Neg => *ty,
+                UnaryOp::ToLong => Type::Long,
+                UnaryOp::ToFloat => Type::Float,
+                UnaryOp::ToDouble => Type::Double,
+                _ => Type::Int,
+            };
+            let register = self.emit(block, pc, Operation::Unary { op: *op, ty: *ty, value }, Some(result_type)).unwrap();
+            push(stack, register, result_type);
+            return Ok(None);
+        }
+        if let Some((kind, load, _)) = ARRAY_OPERATIONS.iter().find(|(_, load, store)| *load == opcode || *store == opcode) {
+            if *load == opcode {
+                let [array, index] = pop_many(stack, pc, 2)?[..] else { unreachable!() };
+                let register = self.emit(block, pc, Operation::ArrayLoad { kind: *kind, array, index }, Some(kind.value_type())).unwrap();
+                push(stack, register, kind.value_type());
+            } else {
+                let [array, index, value] = pop_many(stack, pc, 3)?[..] else { unreachable!() };
+                self.emit(block, pc, Operation::ArrayStore { kind: *kind, array, index, value }, None);
+            }
+            return Ok(None);
+        }
+        if let Some((_, condition, _, two_operands)) = BRANCHES.iter().find(|(entry, _, _, _)| *entry == opcode) {
+            let (left, right) = if *two_operands {
+                let [left, right] = pop_many(stack, pc, 2)?[..] else { unreachable!() };
+                (left, Some(right))
+            } else {
+                (pop(stack, pc)?, None)
+            };
+            let then = BlockId(instruction.branch_targets()[0].0);
+            return Ok(Some(Terminator::If { condition: *condition, left, right, then, otherwise: BlockId(usize::MAX) }));
+        }
+
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::Iinc { index, increment } => {
+                let value = self.read(Variable::Local(*index as usize), block)?;
+                let increment = self.emit(block, pc, Operation::Literal(Literal::Int(*increment as i32)), Some(Type::Int)).unwrap();
+                let register = self.emit(block, pc, Operation::Binary { op: BinaryOp::Add, ty: Type::Int, left: value, right: increment }, Some(Type::Int)).unwrap();
+                self.definitions.insert((Variable::Local(*index as usize), block), register);
+            }
+            Instruction::Pop | Instruction::Pop2 | Instruction::Dup | Instruction::DupX1 | Instruction::DupX2
+            | Instruction::Dup2 | Instruction::Dup2X1 | Instruction::Dup2X2 | Instruction::Swap => {
+                let slots = match instruction {
+                    Instruction::Pop | Instruction::Dup => 1,
+                    Instruction::Pop2 | Instruction::DupX1 | Instruction::Dup2 | Instruction::Swap => 2,
+                    Instruction::DupX2 | Instruction::Dup2X1 => 3,
+                    _ => 4,
+                };
+                if stack.len() < slots {
+                    return error(pc, "The operand stack underflows");
+                }
+                let values = stack.split_off(stack.len() - slots);
+                let order: &[usize] = match instruction {
+                    Instruction::Pop | Instruction::Pop2 => &[],
+                    Instruction::Dup => &[0, 0],
+                    Instruction::DupX1 => &[1, 0, 1],
+                    Instruction::DupX2 => &[2, 0, 1, 2],
+                    Instruction::Dup2 => &[0, 1, 0, 1],
+                    Instruction::Dup2X1 => &[1, 2, 0, 1, 2],
+                    Instruction::Dup2X2 => &[2, 3, 0, 1, 2, 3],
+                    _ => &[1, 0],
+                };
+                stack.extend(order.iter().map(|position| values[*position]));
+            }
+            Instruction::Goto(target) | Instruction::GotoW(target) => return Ok(Some(Terminator::Goto(BlockId(target.0)))),
+            Instruction::Tableswitch { default, low, targets, .. } => {
+                let value = pop(stack, pc)?;
+                let cases = targets.iter().enumerate().map(|(position, target)| (low + position as i32, BlockId(target.0))).collect();
+                return Ok(Some(Terminator::Switch { value, cases, default: BlockId(default.0) }));
+            }
+            Instruction::Lookupswitch { default, pairs } => {
+                let value = pop(stack, pc)?;
+                let cases = pairs.iter().map(|(key, target)| (*key, BlockId(target.0))).collect();
+                return Ok(Some(Terminator::Switch { value, cases, default: BlockId(default.0) }));
+            }
+            Instruction::Ireturn | Instruction::Lreturn | Instruction::Freturn | Instruction::Dreturn | Instruction::Areturn => {
+                return Ok(Some(Terminator::Return(Some(pop(stack, pc)?))));
+            }
+            Instruction::Return => return Ok(Some(Terminator::Return(None))),
+            Instruction::Athrow => return Ok(Some(Terminator::Throw(pop(stack, pc)?))),
+            Instruction::Getstatic(index) | Instruction::Putstatic(index) | Instruction::Getfield(index) | Instruction::Putfield(index) => {
+                let ConstantPoolInfo::FieldRef(field_ref) = self.constant(pc, *index)? else {
+                    return error(pc, format!("#{} is not a field reference", index.0));
+                };
+                let field = FieldRef { class_name: field_ref.class_name.clone(), name: field_ref.name.clone(), descriptor: field_ref.descriptor.clone() };
+                let Some(field_type) = FieldType::parse(&field.descriptor) else {
+                    return error(pc, format!("{} is not a field descriptor", field.descriptor));
+                };
+                let ty = Type::of(&field_type);
+                match instruction {
+                    Instruction::Getstatic(_) | Instruction::Getfield(_) => {
+                        let object = if matches!(instruction, Instruction::Getfield(_)) { Some(pop(stack, pc)?) } else { None };
+                        let register = self.emit(block, pc, Operation::GetField { field, object }, Some(ty)).unwrap();
+                        push(stack, register, ty);
+                    }
+                    _ => {
+                        let value = pop(stack, pc)?;
+                        let object = if matches!(instruction, Instruction::Putfield(_)) { Some(pop(stack, pc)?) } else { None };
+                        self.emit(block, pc, Operation::PutField { field, object, value }, None);
+                    }
+                }
+            }
+            Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) | Instruction::Invokestatic(index)
+            | Instruction::Invokeinterface { index, .. } | Instruction::Invokedynamic(index) => {
+                let (method, name, descriptor) = match self.constant(pc, *index)? {
+                    ConstantPoolInfo::MethodRef(method_ref) => (Some((&method_ref.class_name, false)), &method_ref.name, &method_ref.descriptor),
+                    ConstantPoolInfo::InterfaceMethodRef(method_ref) => (Some((&method_ref.class_name, true)), &method_ref.name, &method_ref.descriptor),
+                    ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => (None, &invoke_dynamic.name, &invoke_dynamic.descriptor),
+                    _ => return error(pc, format!("#{} is not a method reference", index.0)),
+                };
+                let (name, descriptor) = (name.clone(), descriptor.clone());
+                let method = method.map(|(class_name, interface)| MethodRef { class_name: class_name.clone(), name: name.clone(), descriptor: descriptor.clone(), interface });
+                let Some(method_descriptor) = MethodDescriptor::parse(&descriptor) else {
+                    return error(pc, format!("{descriptor} is not a method descriptor"));
+                };
+                let has_receiver = !matches!(instruction, Instruction::Invokestatic(_) | Instruction::Invokedynamic(_));
+                let arguments = pop_many(stack, pc, method_descriptor.parameters.len() + has_receiver as usize)?;
+                let ty = method_descriptor.return_type.as_ref().map(Type::of);
+                let operation = match (method, instruction) {
+                    (Some(method), _) => {
+                        let kind = match instruction {
+                            Instruction::Invokevirtual(_) => InvokeKind::Virtual,
+                            Instruction::Invokespecial(_) => InvokeKind::Special,
+                            Instruction::Invokestatic(_) => InvokeKind::Static,
+                            _ => InvokeKind::Interface,
+                        };
+                        Operation::Invoke { kind, method, arguments }
+                    }
+                    (None, Instruction::Invokedynamic(_)) => Operation::InvokeDynamic { index: *index, name, descriptor, arguments },
+                    _ => return error(pc, format!("#{} is not a method reference", index.0)),
+                };
+                if let (Some(register), Some(ty)) = (self.emit(block, pc, operation, ty), ty) {
+                    push(stack, register, ty);
+                }
+            }
+            Instruction::New(index) => {
+                let class_name = self.class_name(pc, *index)?;
+                let register = self.emit(block, pc, Operation::New(class_name), Some(Type::Reference)).unwrap();
+                push(stack, register, Type::Reference);
+            }
+            Instruction::Newarray(_) | Instruction::Anewarray(_) | Instruction::Multianewarray { .. } => {
+                let (array_type, dimensions) = match instruction {
+                    Instruction::Newarray(array_type) => (FieldType::Array(Box::new(primitive_type(*array_type))), 1),
+                    Instruction::Anewarray(index) => {
+                        let class_name = self.class_name(pc, *index)?;
+                        let Some(element) = FieldType::from_class_name(&class_name) else {
+                            return error(pc, format!("{class_name} is not a class name"));
+                        };
+                        (FieldType::Array(Box::new(element)), 1)
+                    }
+                    Instruction::Multianewarray { index, dimensions } => {
+                        let class_name = self.class_name(pc, *index)?;
+                        let Some(array_type) = FieldType::parse(&class_name) else {
+                            return error(pc, format!("{class_name} is not an array type"));
+                        };
+                        (array_type, *dimensions as usize)
+                    }
+                    _ => unreachable!(),
+                };
+                let lengths = pop_many(stack, pc, dimensions)?;
+                let register = self.emit(block, pc, Operation::NewArray { array_type, lengths }, Some(Type::Reference)).unwrap();
+                push(stack, register, Type::Reference);
+            }
+            Instruction::Arraylength | Instruction::Checkcast(_) | Instruction::Instanceof(_) => {
+                let value = pop(stack, pc)?;
+                let (operation, ty) = match instruction {
+                    Instruction::Arraylength => (Operation::ArrayLength(value), Type::Int),
+                    Instruction::Checkcast(index) => (Operation::CheckCast { class_name: self.class_name(pc, *index)?, value }, Type::Reference),
+                    Instruction::Instanceof(index) => (Operation::InstanceOf { class_name: self.class_name(pc, *index)?, value }, Type::Int),
+                    _ => unreachable!(),
+                };
+                let register = self.emit(block, pc, operation, Some(ty)).unwrap();
+                push(stack, register, ty);
+            }
+            Instruction::Monitorenter | Instruction::Monitorexit => {
+                let value = pop(stack, pc)?;
+                let operation = if *instruction == Instruction::Monitorenter { Operation::MonitorEnter(value) } else { Operation::MonitorExit(value) };
+                self.emit(block, pc, operation, None);
+            }
+            _ => return error(pc, format!("{mnemonic} is not supported")),
+        }
+        Ok(None)
+    }
+}
+
+fn primitive_type(array_type: ArrayType) -> FieldType {
+    match array_type {
+        ArrayType::Boolean => FieldType::Boolean,
+        ArrayType::Char => FieldType::Char,
+        ArrayType::Float => FieldType::Float,
+        ArrayType::Double => FieldType::Double,
+        ArrayType::Byte => FieldType::Byte,
+        ArrayType::Short => FieldType::Short,
+        ArrayType::Int => FieldType::Int,
+        ArrayType::Long => FieldType::Long,
+    }
+}
+
+fn array_type_of(field_type: &FieldType) -> Option<ArrayType> {
+    Some(match field_type {
+        FieldType::Boolean => ArrayType::Boolean,
+        FieldType::Char => ArrayType::Char,
+        FieldType::Float => ArrayType::Float,
+        FieldType::Double => ArrayType::Double,
+        FieldType::Byte => ArrayType::Byte,
+        FieldType::Short => ArrayType::Short,
+        FieldType::Int => ArrayType::Int,
+        FieldType::Long => ArrayType::Long,
+        FieldType::Object(_) | FieldType::Array(_) => return None,
+    })
+}
+
+/// Lifts the code of a method into SSA form.
+///
+/// Locals and operand stack slots become registers, with phis where control flow merges.
+/// Unreachable code is left out, and phis that turn out to pick the same value on every edge or
+/// whose value is never used are removed. Subroutines (`jsr` and `ret`) are not supported.
+pub fn lift(java_class: &JavaClass, method: &MethodInfo) -> Result<Function, IrError> {
+    let Some(code) = method.code() else {
+        return error(0, format!("{} has no code", method.name));
+    };
+    let Some(descriptor) = MethodDescriptor::parse(&method.descriptor) else {
+        return error(0, format!("{} is not a method descriptor", method.descriptor));
+    };
+    let instructions = &instruction::decode(&code.code)?;
+    if instructions.is_empty() {
+        return error(0, "The code is empty");
+    }
+    if let Some((pc, _)) = instructions.iter().find(|(_, instruction)| matches!(instruction.opcode(), Opcode::Jsr | Opcode::JsrW | Opcode::Ret)) {
+        return error(*pc, "Subroutines are not supported");
+    }
+    let handlers_at = |pc: usize| -> Vec<(Option<String>, usize)> {
+        code.exceptions.iter()
+            .filter(|entry| entry.start_pc <= pc && pc < entry.end_pc)
+            .map(|entry| {
+                let catch_type = match java_class.constant_pool_infos.get(entry.catch_pc.wrapping_sub(1)) {
+                    Some(ConstantPoolInfo::Class(class)) if entry.catch_pc != 0 => Some(class.name.clone()),
+                    _ => None,
+                };
+                (catch_type, entry.handler_pc)
+            })
+            .collect()
+    };
+
+    // Blocks start at branch targets, handlers, after branches and after every instruction that can throw into a handler
+    let position_of = |pc: usize| instructions.binary_search_by_key(&pc, |(instruction_pc, _)| *instruction_pc).ok();
+    let mut leaders = vec![0];
+    leaders.extend(code.exceptions.iter().filter_map(|entry| position_of(entry.handler_pc)));
+    for (position, (pc, instruction)) in instructions.iter().enumerate() {
+        let opcode = instruction.opcode();
+        leaders.extend(instruction.branch_targets().iter().filter_map(|target| position_of(target.0)));
+        let ends_block = opcode.can_branch() || !opcode.can_fall_through() || opcode.can_throw() && !handlers_at(*pc).is_empty();
+        if ends_block && position + 1 < instructions.len() {
+            leaders.push(position + 1);
+        }
+    }
+    leaders.sort_unstable();
+    leaders.dedup();
+    let regions: Vec<std::ops::Range<usize>> = leaders.iter().enumerate()
+        .map(|(index, start)| *start..leaders.get(index + 1).copied().unwrap_or(instructions.len()))
+        .collect();
+    // Block 0 is an empty entry block, so that parameters are defined outside any loop
+    let block_at = |pc: usize| -> Option<usize> {
+        let position = instructions.binary_search_by_key(&pc, |(instruction_pc, _)| *instruction_pc).ok()?;
+        Some(regions.partition_point(|region| region.end <= position) + 1)
+    };
+
+    let count = regions.len() + 1;
+    let mut successors: Vec<Vec<usize>> = vec![vec![1]];
+    let mut handler_blocks: Vec<Vec<Handler>> = vec![Vec::new()];
+    for region in &regions {
+        let (pc, last) = &instructions[region.end - 1];
+        let mut normal = Vec::new();
+        for target in last.branch_targets() {
+            normal.push(block_at(target.0).ok_or_else(|| IrError { pc: *pc, message: format!("Branch target {} is not an instruction", target.0) })?);
+        }
+        if last.opcode().can_fall_through() {
+            match instructions.get(region.end) {
+                Some((next_pc, _)) => normal.push(block_at(*next_pc).unwrap()),
+                None => return error(*pc, "Execution falls off the end of the code"),
+            }
+        }
+        let mut handlers = Vec::new();
+        if last.opcode().can_throw() {
+            for (catch_type, handler_pc) in handlers_at(*pc) {
+                let block = block_at(handler_pc).ok_or_else(|| IrError { pc: *pc, message: format!("Handler {handler_pc} is not an instruction") })?;
+                handlers.push(Handler { catch_type, block: BlockId(block) });
+            }
+        }
+        normal.extend(handlers.iter().map(|handler| handler.block.0));
+        successors.push(normal);
+        handler_blocks.push(handlers);
+    }
+
+    // Reverse postorder, so that every block but loop headers and handlers has a lifted predecessor when its turn comes
+    let mut reachable = vec![false; count];
+    let mut postorder = Vec::with_capacity(count);
+    let mut stack = vec![(0, 0)];
+    reachable[0] = true;
+    while let Some((block, next)) = stack.pop() {
+        if let Some(successor) = successors[block].get(next).copied() {
+            stack.push((block, next + 1));
+            if !reachable[successor] {
+                reachable[successor] = true;
+                stack.push((successor, 0));
+            }
+        } else {
+            postorder.push(block);
+        }
+    }
+    let mut predecessors = vec![Vec::new(); count];
+    let mut exceptional_predecessors = vec![false; count];
+    let mut normal_predecessors = vec![false; count];
+    for block in (0..count).filter(|block| reachable[*block]) {
+        for successor in &successors[block] {
+            if !predecessors[*successor].contains(&block) {
+                predecessors[*successor].push(block);
+            }
+        }
+        for handler in &handler_blocks[block] {
+            exceptional_predecessors[handler.block.0] = true;
+        }
+        let handler_count = handler_blocks[block].len();
+        for successor in &successors[block][..successors[block].len() - handler_count] {
+            normal_predecessors[*successor] = true;
+        }
+    }
+
+    let mut lifter = Lifter {
+        constant_pool_infos: &java_class.constant_pool_infos,
+        blocks: (0..count).map(|block| Block {
+            pc: if block == 0 { 0 } else { instructions[regions[block - 1].start].0 },
+            phis: Vec::new(),
+            statements: Vec::new(),
+            terminator: Terminator::Goto(BlockId(1)),
+            handlers: std::mem::take(&mut handler_blocks[block]),
+        }).collect(),
+        predecessors,
+        types: Vec::new(),
+        definitions: HashMap::new(),
+        sealed: vec![false; count],
+        filled: vec![false; count],
+        incomplete: vec![Vec::new(); count],
+        exit_stacks: vec![None; count],
+    };
+    let mut parameters = Vec::new();
+    let mut slot = 0;
+    let is_static = method.access_flags & 0x0008 != 0;
+    let parameter_types = (!is_static).then_some(Type::Reference).into_iter().chain(descriptor.parameters.iter().map(Type::of));
+    for ty in parameter_types {
+        let register = lifter.new_register(Some(ty));
+        lifter.definitions.insert((Variable::Local(slot), 0), register);
+        parameters.push(register);
+        slot += ty.slots();
+    }
+
+    lifter.sealed[0] = true;
+    for block in postorder.into_iter().rev() {
+        if block > 0 {
+            let region = regions[block - 1].clone();
+            let mut stack: SymbolicStack = Vec::new();
+            if exceptional_predecessors[block] {
+                if normal_predecessors[block] {
+                    return error(lifter.blocks[block].pc, "An exception handler is also reached without an exception");
+                }
+                let pc = lifter.blocks[block].pc;
+                stack.push(lifter.emit(block, pc, Operation::CaughtException, Some(Type::Reference)));
+            } else {
+                let layout = lifter.predecessors[block].iter().find_map(|predecessor| lifter.exit_stacks[*predecessor].clone()).unwrap_or_default();
+                for (position, slot) in layout.iter().enumerate() {
+                    stack.push(match slot {
+                        Some(_) => Some(lifter.read(Variable::Stack(position), block)?),
+                        None => None,
+                    });
+                }
+            }
+            let mut terminator = None;
+            for (pc, instruction) in &instructions[region.clone()] {
+                terminator = lifter.instruction(block, *pc, instruction, &mut stack)?;
+            }
+            let next = instructions.get(region.end).map(|(pc, _)| block_at(*pc).unwrap());
+            let mut terminator = terminator.unwrap_or(Terminator::Goto(BlockId(usize::MAX)));
+            // Targets are pcs until here, and usize::MAX stands for the next instruction
+            for target in terminator.successors_mut() {
+                *target = BlockId(if target.0 == usize::MAX { next.unwrap() } else { block_at(target.0).unwrap() });
+            }
+            lifter.blocks[block].terminator = terminator;
+            for (position, slot) in stack.iter().enumerate() {
+                if let Some(register) = slot {
+                    lifter.definitions.insert((Variable::Stack(position), block), *register);
+                }
+            }
+            lifter.exit_stacks[block] = Some(stack);
+        }
+        lifter.filled[block] = true;
+        for successor in successors[block].clone() {
+            if !lifter.sealed[successor] && lifter.predecessors[successor].iter().all(|predecessor| lifter.filled[*predecessor]) {
+                lifter.seal(successor)?;
+            }
+        }
+    }
+
+    let Lifter { mut blocks, types, .. } = lifter;
+    for block in (0..count).filter(|block| !reachable[*block]) {
+        blocks[block].phis.clear();
+    }
+    let mut function = Function {
+        name: method.name.clone(),
+        descriptor: method.descriptor.clone(),
+        is_static,
+        parameters: Vec::new(),
+        types: Vec::new(),
+        blocks: Vec::new(),
+    };
+    simplify(&mut blocks, &parameters, types, reachable, &mut function)?;
+    Ok(function)
+}
+
+/// Removes trivial and dead phis, drops unreachable blocks and the entry block when it isn't
+/// needed, and numbers the blocks in pc order and the registers in order of definition.
+fn simplify(blocks: &mut [Block], parameters: &[Register], mut types: Vec<Option<Type>>, reachable: Vec<bool>, function: &mut Function) -> Result<(), IrError> {
+    let mut replacements: HashMap<Register, Register> = HashMap::new();
+    let resolve = |replacements: &HashMap<Register, Register>, mut register: Register| {
+        while let Some(replacement) = replacements.get(&register) {
+            register = *replacement;
+        }
+        register
+    };
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in blocks.iter_mut() {
+            let mut kept = Vec::new();
+            for mut phi in std::mem::take(&mut block.phis) {
+                for (_, value) in &mut phi.incoming {
+                    *value = resolve(&replacements, *value);
+                }
+                let mut values: Vec<Register> = phi.incoming.iter().map(|(_, value)| *value).filter(|value| *value != phi.target).collect();
+                values.sort_unstable();
+                values.dedup();
+                if values.len() == 1 {
+                    replacements.insert(phi.target, values[0]);
+                    changed = true;
+                } else {
+                    kept.push(phi);
+                }
+            }
+            block.phis = kept;
+        }
+    }
+    for block in blocks.iter_mut() {
+        for phi in &mut block.phis {
+            for (_, value) in &mut phi.incoming {
+                *value = resolve(&replacements, *value);
+            }
+        }
+        for statement in &mut block.statements {
+            for operand in statement.operation.operands_mut() {
+                *operand = resolve(&replacements, *operand);
+            }
+        }
+        for operand in block.terminator.operands_mut() {
+            *operand = resolve(&replacements, *operand);
+        }
+    }
+
+    // Phis are live when a statement or terminator uses them, or a live phi does
+    let mut live: HashSet<Register> = HashSet::new();
+    let mut worklist: Vec<Register> = Vec::new();
+    for block in blocks.iter_mut().enumerate().filter(|(block, _)| reachable[*block]).map(|(_, block)| block) {
+        for statement in &mut block.statements {
+            worklist.extend(statement.operation.operands());
+        }
+        worklist.extend(block.terminator.operands_mut().into_iter().map(|register| *register));
+    }
+    let phi_operands: HashMap<Register, Vec<Register>> = blocks.iter()
+        .flat_map(|block| block.phis.iter().map(|phi| (phi.target, phi.incoming.iter().map(|(_, value)| *value).collect())))
+        .collect();
+    while let Some(register) = worklist.pop() {
+        if live.insert(register) {
+            worklist.extend(phi_operands.get(&register).into_iter().flatten());
+        }
+    }
+    for block in blocks.iter_mut() {
+        block.phis.retain(|phi| live.contains(&phi.target));
+    }
+
+    // Phis only merging other phis of unknown type take the type of what they merge
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for phi in blocks.iter().flat_map(|block| &block.phis) {
+            if types[phi.target.0].is_none() {
+                if let Some(ty) = phi.incoming.iter().find_map(|(_, value)| types[value.0]) {
+                    types[phi.target.0] = Some(ty);
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    // The entry block is only kept when the first block of code is also entered from elsewhere
+    let first_predecessors = blocks.iter().enumerate()
+        .filter(|(block, _)| reachable[*block])
+        .filter(|(_, block)| block.terminator.successors().contains(&BlockId(1)) || block.handlers.iter().any(|handler| handler.block == BlockId(1)))
+        .count();
+    let keep_entry = first_predecessors > 1;
+    let mut block_numbers: Vec<Option<usize>> = vec![None; blocks.len()];
+    let mut order: Vec<usize> = (0..blocks.len()).filter(|block| reachable[*block] && (*block > 0 || keep_entry)).collect();
+    order.sort_by_key(|block| (*block > 0, blocks[*block].pc));
+    for (number, block) in order.iter().enumerate() {
+        block_numbers[*block] = Some(number);
+    }
+    let renumber_block = |block: BlockId| BlockId(block_numbers[block.0].unwrap());
+
+    let mut register_numbers: HashMap<Register, Register> = HashMap::new();
+    let mut new_types = Vec::new();
+    let mut number = |register: Register, new_types: &mut Vec<Type>| {
+        register_numbers.insert(register, Register(new_types.len()));
+        new_types.push(types[register.0].unwrap_or(Type::Int));
+    };
+    for parameter in parameters {
+        number(*parameter, &mut new_types);
+    }
+    for block in &order {
+        for phi in &blocks[*block].phis {
+            number(phi.target, &mut new_types);
+        }
+        for statement in &blocks[*block].statements {
+            if let Some(target) = statement.target {
+                number(target, &mut new_types);
+            }
+        }
+    }
+    let renumber = |register: Register| -> Result<Register, IrError> {
+        register_numbers.get(&register).copied().ok_or_else(|| IrError { pc: 0, message: format!("{register} is used but never set") })
+    };
+
+    function.parameters = parameters.iter().map(|parameter| renumber(*parameter)).collect::<Result<_, _>>()?;
+    for block in order {
+        let mut basic_block = blocks[block].clone();
+        for phi in &mut basic_block.phis {
+            phi.target = renumber(phi.target)?;
+            for (from, value) in &mut phi.incoming {
+                *from = renumber_block(*from);
+                *value = renumber(*value)?;
+            }
+        }
+        for statement in &mut basic_block.statements {
+            if let Some(target) = &mut statement.target {
+                *target = renumber(*target)?;
+            }
+            for operand in statement.operation.operands_mut() {
+                *operand = renumber(*operand)?;
+            }
+        }
+        for operand in basic_block.terminator.operands_mut() {
+            *operand = renumber(*operand)?;
+        }
+        for successor in basic_block.terminator.successors_mut() {
+            *successor = renumber_block(*successor);
+        }
+        for handler in &mut basic_block.handlers {
+            handler.block = renumber_block(handler.block);
+        }
+        function.blocks.push(basic_block);
+    }
+    function.types = new_types;
+    Ok(())
+}
+
+enum Item {
+    Label(usize),
+    /// An instruction with the labels of its branch targets.
+    Instruction(Instruction, Vec<usize>),
+}
+
+struct Lowerer<'a> {
+    function: &'a Function,
+    constant_pool: &'a mut ConstantPoolBuilder,
+    slots: Vec<u16>,
+    items: Vec<Item>,
+    labels: usize,
+    max_stack: usize,
+    /// Covered ranges as start and end labels, with the handler's block and catch type.
+    exceptions: Vec<(usize, usize, BlockId, usize)>,
+    /// Edges that need phi copies but can't have them at the end of their block.
+    trampolines: Vec<(usize, BlockId, BlockId)>,
+}
+
+impl Lowerer<'_> {
+    fn new_label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn push(&mut self, instruction: Instruction) {
+        self.items.push(Item::Instruction(instruction, Vec::new()));
+    }
+
+    fn branch(&mut self, opcode: Opcode, label: usize) {
+        self.items.push(Item::Instruction(instruction::with_branch_target(opcode, BranchTarget(0)), vec![label]));
+    }
+
+    fn local(&mut self, register: Register, store: bool) {
+        let slot = self.slots[register.0];
+        let ty = self.function.register_type(register);
+        let ((load, store_opcode, _), (short_loads, short_stores)) = (local_opcodes(ty), short_local_opcodes(ty));
+        let (opcode, short_forms) = if store { (store_opcode, short_stores) } else { (load, short_loads) };
+        let instruction = match short_forms.get(slot as usize) {
+            Some(short_form) => instruction::without_operands(*short_form),
+            None => instruction::with_local_index(opcode, slot),
+        };
+        self.push(instruction);
+    }
+
+    fn load(&mut self, registers: &[Register]) {
+        let slots: usize = registers.iter().map(|register| self.function.register_type(*register).slots()).sum();
+        self.max_stack = self.max_stack.max(slots);
+        for register in registers {
+            self.local(*register, false);
+        }
+    }
+
+    fn ldc(&mut self, index: usize, wide: bool) {
+        let index = CpIndex(index as u16);
+        self.push(match (wide, index.0 <= u8::MAX as u16) {
+            (true, _) => Instruction::Ldc2W(index),
+            (false, true) => Instruction::Ldc(index),
+            (false, false) => Instruction::LdcW(index),
+        });
+    }
+
+    /// Copies the values phis take when entering `to` from `from` into their registers, all at
+    /// once so that phis reading each other's registers see the old values.
+    fn phi_copies(&mut self, from: BlockId, to: BlockId) {
+        let copies: Vec<(Register, Register)> = self.function.blocks[to.0].phis.iter()
+            .filter_map(|phi| phi.incoming.iter().find(|(block, _)| *block == from).map(|(_, value)| (phi.target, *value)))
+            .collect();
+        let sources: Vec<Register> = copies.iter().map(|(_, source)| *source).collect();
+        self.load(&sources);
+        for (target, _) in copies.iter().rev() {
+            self.local(*target, true);
+        }
+    }
+
+    fn needs_copies(&self, from: BlockId, to: BlockId) -> bool {
+        self.function.blocks[to.0].phis.iter().any(|phi| phi.incoming.iter().any(|(block, _)| *block == from))
+    }
+
+    /// The label to jump to for an edge, going through a trampoline doing the phi copies if needed.
+    fn edge(&mut self, from: BlockId, to: BlockId) -> usize {
+        if !self.needs_copies(from, to) {
+            return to.0;
+        }
+        let label = self.new_label();
+        self.trampolines.push((label, from, to));
+        label
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<(), IrError> {
+        let operands = statement.operation.operands();
+        self.load(&operands);
+        let ty = statement.target.map(|target| self.function.register_type(target));
+        self.max_stack = self.max_stack.max(ty.map_or(0, Type::slots));
+        let missing = |what: &str| IrError { pc: statement.pc, message: format!("There is no instruction for {what}") };
+        match &statement.operation {
+            Operation::Literal(literal) => match (Instruction::push(literal), literal) {
+                (Some(instruction), _) => self.push(instruction),
+                (None, Literal::Int(value)) => {
+                    let index = self.constant_pool.integer(*value);
+                    self.ldc(index, false);
+                }
+                (None, Literal::Long(value)) => {
+                    let index = self.constant_pool.long(*value);
+                    self.ldc(index, true);
+                }
+                (None, Literal::Float(value)) => {
+                    let index = self.constant_pool.float(*value);
+                    self.ldc(index, false);
+                }
+                (None, Literal::Double(value)) => {
+                    let index = self.constant_pool.double(*value);
+                    self.ldc(index, true);
+                }
+                (None, Literal::String(string)) => {
+                    let index = self.constant_pool.string(string);
+                    self.ldc(index, false);
+                }
+                (None, Literal::Class(class_name)) => {
+                    let index = self.constant_pool.class(class_name);
+                    self.ldc(index, false);
+                }
+                (None, Literal::Pooled { index, ty }) => self.ldc(index.0 as usize, ty.is_wide()),
+                (None, Literal::Null) => unreachable!("aconst_null pushes null"),
+            },
+            Operation::Binary { op, ty, .. } => self.push(instruction::without_operands(binary_opcode(*op, *ty).ok_or_else(|| missing(&format!("{op:?} on {ty}")))?)),
+            Operation::Unary { op, ty, .. } => self.push(instruction::without_operands(unary_opcode(*op, *ty).ok_or_else(|| missing(&format!("{op:?} on {ty}")))?)),
+            Operation::ArrayLoad { kind, .. } => self.push(instruction::without_operands(array_opcodes(*kind).0)),
+            Operation::ArrayStore { kind, .. } => self.push(instruction::without_operands(array_opcodes(*kind).1)),
+            Operation::ArrayLength(_) => self.push(Instruction::Arraylength),
+            Operation::GetField { field, object } | Operation::PutField { field, object, .. } => {
+                let index = CpIndex(self.constant_pool.field_ref(&field.class_name, &field.name, &field.descriptor) as u16);
+                self.push(match (&statement.operation, object) {
+                    (Operation::GetField { .. }, Some(_)) => Instruction::Getfield(index),
+                    (Operation::GetField { .. }, None) => Instruction::Getstatic(index),
+                    (_, Some(_)) => Instruction::Putfield(index),
+                    (_, None) => Instruction::Putstatic(index),
+                });
+            }
+            Operation::Invoke { kind, method, .. } => {
+                let index = if method.interface {
+                    self.constant_pool.interface_method_ref(&method.class_name, &method.name, &method.descriptor)
+                } else {
+                    self.constant_pool.method_ref(&method.class_name, &method.name, &method.descriptor)
+                };
+                let index = CpIndex(index as u16);
+                self.push(match kind {
+                    InvokeKind::Interface => {
+                        let count = 1 + MethodDescriptor::parse(&method.descriptor).map_or(0, |descriptor| descriptor.parameter_slots());
+                        Instruction::Invokeinterface { index, count: count as u8 }
+                    }
+                    _ => instruction::with_constant_pool_index(kind.opcode(), index),
+                });
+            }
+            Operation::InvokeDynamic { index, .. } => self.push(Instruction::Invokedynamic(*index)),
+            Operation::New(class_name) => {
+                let index = self.constant_pool.class(class_name);
+                self.push(Instruction::New(CpIndex(index as u16)));
+            }
+            Operation::NewArray { array_type, lengths } => match (array_type, lengths.len()) {
+                (FieldType::Array(element), 1) => match array_type_of(element) {
+                    Some(primitive) => self.push(Instruction::Newarray(primitive)),
+                    None => {
+                        let index = self.constant_pool.class(&element.class_name().unwrap());
+                        self.push(Instruction::Anewarray(CpIndex(index as u16)));
+                    }
+                },
+                _ => {
+                    let Some(class_name) = array_type.class_name() else { return Err(missing(&format!("an array of {array_type}"))) };
+                    let index = self.constant_pool.class(&class_name);
+                    self.push(Instruction::Multianewarray { index: CpIndex(index as u16), dimensions: lengths.len() as u8 });
+                }
+            },
+            Operation::CheckCast { class_name, .. } | Operation::InstanceOf { class_name, .. } => {
+                let index = CpIndex(self.constant_pool.class(class_name) as u16);
+                let is_cast = matches!(statement.operation, Operation::CheckCast { .. });
+                self.push(if is_cast { Instruction::Checkcast(index) } else { Instruction::Instanceof(index) });
+            }
+            Operation::MonitorEnter(_) => self.push(Instruction::Monitorenter),
+            Operation::MonitorExit(_) => self.push(Instruction::Monitorexit),
+            Operation::CaughtException => {}
+        }
+        if let Some(target) = statement.target {
+            self.local(target, true);
+        }
+        Ok(())
+    }
+
+    fn terminator(&mut self, block: BlockId, next: Option<BlockId>) -> Result<(), IrError> {
+        let terminator = &self.function.blocks[block.0].terminator;
+        match terminator {
+            Terminator::Goto(target) => {
+                self.phi_copies(block, *target);
+                if Some(*target) != next {
+                    self.branch(Opcode::Goto, target.0);
+                }
+            }
+            Terminator::If { condition, left, right, then, otherwise } => {
+                let ty = self.function.register_type(*left);
+                let operands: Vec<Register> = std::iter::once(*left).chain(*right).collect();
+                self.load(&operands);
+                let then_label = self.edge(block, *then);
+                let otherwise_label = self.edge(block, *otherwise);
+                let opcode = |condition: Condition| {
+                    BRANCHES.iter()
+                        .find(|(_, entry_condition, entry_ty, two_operands)| *entry_condition == condition && *entry_ty == ty && *two_operands == right.is_some())
+                        .map(|(opcode, _, _, _)| *opcode)
+                        .ok_or_else(|| IrError { pc: self.function.blocks[block.0].pc, message: format!("There is no branch for {condition} on {ty}") })
+                };
+                if otherwise_label == next.map_or(usize::MAX, |next| next.0) {
+                    self.branch(opcode(*condition)?, then_label);
+                } else if then_label == next.map_or(usize::MAX, |next| next.0) {
+                    self.branch(opcode(condition.negate())?, otherwise_label);
+                } else {
+                    self.branch(opcode(*condition)?, then_label);
+                    self.branch(Opcode::Goto, otherwise_label);
+                }
+            }
+            Terminator::Switch { value, cases, default } => {
+                self.load(&[*value]);
+                let mut cases = cases.clone();
+                cases.sort_by_key(|(key, _)| *key);
+                let default_label = self.edge(block, *default);
+                let labels: Vec<(i32, usize)> = cases.iter().map(|(key, target)| (*key, self.edge(block, *target))).collect();
+                // The same choice javac makes between the two switches
+                let (low, high) = (labels.first().map_or(0, |(key, _)| *key as i64), labels.last().map_or(0, |(key, _)| *key as i64));
+                let count = labels.len() as i64;
+                let table = count > 0 && 4 + (high - low + 1) + 3 * 3 <= 3 + 2 * count + 3 * count;
+                let placeholder = BranchTarget(0);
+                if table {
+                    let mut targets = vec![default_label; (high - low + 1) as usize];
+                    for (key, label) in &labels {
+                        targets[(*key as i64 - low) as usize] = *label;
+                    }
+                    let instruction = Instruction::Tableswitch { default: placeholder, low: low as i32, high: high as i32, targets: vec![placeholder; targets.len()] };
+                    self.items.push(Item::Instruction(instruction, std::iter::once(default_label).chain(targets).collect()));
+                } else {
+                    let instruction = Instruction::Lookupswitch { default: placeholder, pairs: labels.iter().map(|(key, _)| (*key, placeholder)).collect() };
+                    self.items.push(Item::Instruction(instruction, std::iter::once(default_label).chain(labels.iter().map(|(_, label)| *label)).collect()));
+                }
+            }
+            Terminator::Return(Some(value)) => {
+                self.load(&[*value]);
+                let (_, _, return_opcode) = local_opcodes(self.function.register_type(*value));
+                self.push(instruction::without_operands(return_opcode));
+            }
+            Terminator::Return(None) => self.push(Instruction::Return),
+            Terminator::Throw(value) => {
+                self.load(&[*value]);
+                self.push(Instruction::Athrow);
+            }
+        }
+        Ok(())
+    }
+
+    /// Lowers a block. When it has handlers, the phi copies for them go just before the covered
+    /// statement or terminator, which is all the exception table covers.
+    fn block(&mut self, block: BlockId, next: Option<BlockId>) -> Result<(), IrError> {
+        let basic_block = &self.function.blocks[block.0];
+        self.items.push(Item::Label(block.0));
+        let covers_terminator = matches!(basic_block.terminator, Terminator::Return(_) | Terminator::Throw(_)) || basic_block.statements.is_empty();
+        let covered = if basic_block.handlers.is_empty() { None } else if covers_terminator { Some(basic_block.statements.len()) } else { Some(basic_block.statements.len() - 1) };
+        for position in 0..=basic_block.statements.len() {
+            let range = if covered == Some(position) {
+                for handler in &basic_block.handlers {
+                    self.phi_copies(block, handler.block);
+                }
+                let start = self.new_label();
+                self.items.push(Item::Label(start));
+                Some(start)
+            } else {
+                None
+            };
+            match basic_block.statements.get(position) {
+                Some(statement) => {
+                    self.statement(statement)?;
+                    if let Some(start) = range {
+                        // The store of the result can't throw, so it stays out of the range
+                        let store = if statement.target.is_some() { self.items.pop() } else { None };
+                        self.cover(start, block);
+                        self.items.extend(store);
+                    }
+                }
+                None => {
+                    self.terminator(block, next)?;
+                    if let Some(start) = range {
+                        self.cover(start, block);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn cover(&mut self, start: usize, block: BlockId) {
+        let end = self.new_label();
+        self.items.push(Item::Label(end));
+        for handler in &self.function.blocks[block.0].handlers {
+            let catch_type = handler.catch_type.as_ref().map_or(0, |class_name| self.constant_pool.class(class_name));
+            self.exceptions.push((start, end, handler.block, catch_type));
+        }
+    }
+}
+
+/// Lowers a function back to bytecode, adding the constants it refers to to `constant_pool`.
+///
+/// Every register gets a local variable of its own, parameters keeping theirs, and phis become
+/// copies at the end of the blocks they are entered from. Edges that can't take the copies, like
+/// the branches of an `if` whose target has phis, go through a block doing them. The code gets
+/// no `StackMapTable` and no debug attributes; [`lower_method`] adds the frames.
+pub fn lower(function: &Function, constant_pool: &mut ConstantPoolBuilder) -> Result<AttributeCode, IrError> {
+    let mut slots = vec![0; function.types.len()];
+    let mut next_slot = 0;
+    let ordered = function.parameters.iter().copied().chain((0..function.types.len()).map(Register).filter(|register| !function.parameters.contains(register)));
+    for register in ordered {
+        slots[register.0] = u16::try_from(next_slot).map_err(|_| IrError { pc: 0, message: "Too many registers for the locals".to_string() })?;
+        next_slot += function.register_type(register).slots();
+    }
+    let mut lowerer = Lowerer {
+        function,
+        constant_pool,
+        slots,
+        items: Vec::new(),
+        labels: function.blocks.len(),
+        max_stack: 0,
+        exceptions: Vec::new(),
+        trampolines: Vec::new(),
+    };
+    for block in 0..function.blocks.len() {
+        let next = (block + 1 < function.blocks.len()).then_some(BlockId(block + 1));
+        lowerer.block(BlockId(block), next)?;
+    }
+    for (label, from, to) in std::mem::take(&mut lowerer.trampolines) {
+        lowerer.items.push(Item::Label(label));
+        lowerer.phi_copies(from, to);
+        lowerer.branch(Opcode::Goto, to.0);
+    }
+
+    let mut label_pcs = vec![0; lowerer.labels];
+    let mut pc = 0;
+    for item in &lowerer.items {
+        match item {
+            Item::Label(label) => label_pcs[*label] = pc,
+            Item::Instruction(instruction, _) => pc += instruction.length(pc),
+        }
+    }
+    let mut instructions = Vec::new();
+    for item in std::mem::take(&mut lowerer.items) {
+        let Item::Instruction(mut instruction, labels) = item else { continue };
+        for (target, label) in instruction.branch_targets_mut().into_iter().zip(&labels) {
+            *target = BranchTarget(label_pcs[*label]);
+        }
+        instructions.push(instruction);
+    }
+    let code = instruction::encode(&instructions).or_else(|encode_error| error(encode_error.pc, encode_error.kind.to_string()))?;
+    let exceptions = lowerer.exceptions.iter().map(|(start, end, handler, catch_type)| ExceptionTableEntry {
+        start_pc: label_pcs[*start],
+        end_pc: label_pcs[*end],
+        handler_pc: label_pcs[handler.0],
+        catch_pc: *catch_type,
+    }).collect();
+    Ok(AttributeCode {
+        attribute_name_index: lowerer.constant_pool.utf8("Code"),
+        max_stack: lowerer.max_stack,
+        max_locals: next_slot,
+        code_length: code.len(),
+        code,
+        exceptions,
+        attributes: Vec::new(),
+    })
+}
+
+/// Replaces the code of the method `function` was lifted from with the lowered function, and
+/// recomputes the frames of the class.
+pub fn lower_method(java_class: &mut JavaClass, function: &Function, hierarchy: &dyn ClassHierarchy) -> Result<(), IrError> {
+    let Some(position) = java_class.methods.iter().position(|method| method.name == function.name && method.descriptor == function.descriptor) else {
+        return error(0, format!("There is no method {}{}", function.name, function.descriptor));
+    };
+    let mut constant_pool = ConstantPoolBuilder::from_constants(java_class.constant_pool_infos.clone());
+    let code = lower(function, &mut constant_pool)?;
+    let attribute_info = AttributeInfo { attribute_name_index: code.attribute_name_index, attribute: Attribute::Code(code) };
+    let method = &mut java_class.methods[position];
+    match method.attributes_info.iter().position(|attribute_info| matches!(attribute_info.attribute, Attribute::Code(_))) {
+        Some(existing) => method.attributes_info[existing] = attribute_info,
+        None => method.attributes_info.push(attribute_info),
+    }
+    java_class.constant_pool_infos = constant_pool.build();
+    java_class.constant_pool_count = java_class.constant_pool_infos.len() + 1;
+    stack_map::recompute_frames(java_class, hierarchy)?;
+    Ok(())
+}
+
+#[cfg(test)]
+fn lift_flow(name: &str) -> Function {
+    let java_class = JavaClass::load_from_file("Flow.class").unwrap();
+    let method = java_class.methods.iter().find(|method| method.name == name).unwrap();
+    lift(&java_class, method).unwrap()
+}
+
+#[test]
+fn test_lifts_loops_into_phis() {
+    assert_eq!("\
+static loop(I)I (int v0) {
+b0: // pc 0
+    int v1 = const 0
+    int v2 = const 0
+    goto b1
+b1: // pc 4
+    int v3 = phi [b0: v2, b2: v7]
+    int v4 = phi [b0: v1, b2: v5]
+    if v3 >= v0 goto b3 else b2
+b2: // pc 9
+    int v5 = iadd v4, v3
+    int v6 = const 1
+    int v7 = iadd v3, v6
+    goto b1
+b3: // pc 19
+    return v4
+}
+", lift_flow("loop").to_string());
+
+    let nullable = lift_flow("nullable");
+    assert_eq!("    ref v2 = phi [b1: v1, b2: v0]", nullable.to_string().lines().find(|line| line.contains("phi")).unwrap());
+}
+
+#[test]
+fn test_lifts_exception_handlers() {
+    let parse = lift_flow("parse");
+    let text = parse.to_string();
+    assert!(text.contains("    int v1 = invokestatic java/lang/Integer.parseInt:(Ljava/lang/String;)I(v0)\n    goto b1\n    catch java/lang/NumberFormatException -> b2\n    catch any -> b3\n"), "{text}");
+    assert!(text.contains("b2: // pc 15\n    ref v5 = caught\n"), "{text}");
+    assert_eq!(Vec::<Handler>::new(), parse.blocks[2].handlers);
+    assert_eq!(vec![vec![], vec![BlockId(0)], vec![BlockId(0)], vec![BlockId(0)]], parse.predecessors());
+
+    let java_class = JavaClass::load_from_file("MyClass.class").unwrap();
+    let main = java_class.methods.iter().find(|method| method.name == "main").unwrap();
+    let text = lift(&java_class, main).unwrap().to_string();
+    assert!(text.contains("invokevirtual java/io/PrintStream.println:(Ljava/lang/String;)V(v4, v5)"), "{text}");
+}
+
+#[test]
+fn test_lowering_round_trips() {
+    let hierarchy = stack_map::KnownClasses::new();
+    for class_file in ["Flow.class", "MyClass.class"] {
+        let mut java_class = JavaClass::load_from_file(class_file).unwrap();
+        let functions: Vec<Function> = java_class.methods.iter().map(|method| lift(&java_class, method).unwrap()).collect();
+        for function in &functions {
+            lower_method(&mut java_class, function, &hierarchy).unwrap();
+        }
+        let java_class = JavaClass::from_bytes(java_class.to_bytes()).unwrap();
+        for (method, function) in java_class.methods.iter().zip(&functions) {
+            stack_map::analyze(&java_class, method, &hierarchy).unwrap();
+            let relifted = lift(&java_class, method).unwrap();
+            let without_pcs = |function: &Function| function.to_string().lines().map(|line| line.split(" // pc").next().unwrap().to_string()).collect::<Vec<_>>();
+            assert_eq!(without_pcs(function), without_pcs(&relifted), "{}", method.name);
+        }
+    }
+}
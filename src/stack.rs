@@ -1,21 +1,32 @@
+use crate::execution::ExecutionError;
 use crate::frames::Frame;
-use crate::MethodInfo;
 
+/// The frames of a thread's method invocations, the current one last.
+#[derive(Debug)]
 pub struct Stack {
     pub frames: Vec<Frame>,
+    max_depth: usize,
 }
 
 impl Stack {
-    fn new_frame(self) {
-        unimplemented!("new frame is created")
+    pub fn new(max_depth: usize) -> Self {
+        Stack { frames: Vec::new(), max_depth }
     }
 
-    pub fn current_frame(self) -> Frame {
-        // self.frames.last().unwrap()
-        unimplemented!("missing lifetime")
+    /// Pushes a frame for an invoked method, or fails with a `StackOverflowError` when the stack is full.
+    pub fn push(&mut self, frame: Frame) -> Result<(), ExecutionError> {
+        if self.frames.len() >= self.max_depth {
+            return Err(ExecutionError::new(format!("StackOverflowError: more than {} frames", self.max_depth)));
+        }
+        self.frames.push(frame);
+        Ok(())
     }
 
-    fn current_method(self) -> MethodInfo {
-        self.current_frame().method
+    pub fn pop(&mut self) -> Option<Frame> {
+        self.frames.pop()
+    }
+
+    pub fn current_frame(&mut self) -> Option<&mut Frame> {
+        self.frames.last_mut()
     }
 }
@@ -0,0 +1,1034 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use crate::bytecode::Opcode;
+use crate::constant_pool::ConstantPoolBuilder;
+use crate::control_flow::{ControlFlowGraph, EdgeKind};
+use crate::dataflow::{self, LiveLocals, Liveness};
+use crate::descriptor::{FieldType, MethodDescriptor};
+use crate::instruction::{CpIndex, DecodeError, Instruction};
+use crate::{Attribute, AttributeCode, AttributeInfo, AttributeUnknown, ConstantPoolInfo, JavaClass, MethodInfo};
+
+/// Answers questions about classes the analysis only knows by name, to merge reference types
+/// flowing into the same pc.
+pub trait ClassHierarchy {
+    /// The super class of a class, `None` for `java/lang/Object` and for classes it doesn't know.
+    fn super_class(&self, class_name: &str) -> Option<String>;
+
+    fn is_interface(&self, class_name: &str) -> bool;
+}
+
+/// A [`ClassHierarchy`] of the classes added to it.
+#[derive(Debug, Default, Clone)]
+pub struct KnownClasses {
+    super_classes: HashMap<String, String>,
+    interfaces: HashSet<String>,
+}
+
+impl KnownClasses {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, java_class: &JavaClass) {
+        if let Some(super_class_name) = &java_class.super_class_name {
+            self.super_classes.insert(java_class.this_class_name.clone(), super_class_name.clone());
+        }
+        if java_class.flags.0 & 0x0200 != 0 {
+            self.interfaces.insert(java_class.this_class_name.clone());
+        }
+    }
+}
+
+impl ClassHierarchy for KnownClasses {
+    fn super_class(&self, class_name: &str) -> Option<String> {
+        self.super_classes.get(class_name).cloned()
+    }
+
+    fn is_interface(&self, class_name: &str) -> bool {
+        self.interfaces.contains(class_name)
+    }
+}
+
+/// A [`ClassHierarchy`] reading the classes it is asked about from directories of class files,
+/// like a JDK image extracted with `jimage extract` has one per module, and knowing the classes
+/// added to it.
+#[derive(Debug, Default)]
+pub struct ClassPath {
+    directories: Vec<PathBuf>,
+    classes: RefCell<KnownClasses>,
+    searched: RefCell<HashSet<String>>,
+}
+
+impl ClassPath {
+    pub fn new(directories: Vec<PathBuf>) -> Self {
+        ClassPath { directories, ..Self::default() }
+    }
+
+    pub fn add(&mut self, java_class: &JavaClass) {
+        self.classes.get_mut().add(java_class);
+        self.searched.get_mut().insert(java_class.this_class_name.clone());
+    }
+
+    /// Adds the class from the first directory that has it, once.
+    fn load(&self, class_name: &str) {
+        if !self.searched.borrow_mut().insert(class_name.to_string()) {
+            return;
+        }
+        let path = format!("{class_name}.class");
+        let java_class = self.directories.iter()
+            .find_map(|directory| JavaClass::from_bytes(fs::read(directory.join(&path)).ok()?).ok());
+        if let Some(java_class) = java_class {
+            self.classes.borrow_mut().add(&java_class);
+        }
+    }
+}
+
+impl ClassHierarchy for ClassPath {
+    fn super_class(&self, class_name: &str) -> Option<String> {
+        self.load(class_name);
+        self.classes.borrow().super_class(class_name)
+    }
+
+    fn is_interface(&self, class_name: &str) -> bool {
+        self.load(class_name);
+        self.classes.borrow().is_interface(class_name)
+    }
+}
+
+/// The closest class both classes extend. Interfaces are treated as `java/lang/Object`, like the
+/// verifier does. Fails with the name of a class whose super classes the hierarchy doesn't know.
+pub fn common_super_class(hierarchy: &dyn ClassHierarchy, first: &str, second: &str) -> Result<String, String> {
+    if first == second {
+        return Ok(first.to_string());
+    }
+    if [first, second].contains(&"java/lang/Object") || hierarchy.is_interface(first) || hierarchy.is_interface(second) {
+        return Ok("java/lang/Object".to_string());
+    }
+    let super_classes = |class_name: &str| -> Result<Vec<String>, String> {
+        let mut super_classes = vec![class_name.to_string()];
+        while super_classes.last().unwrap() != "java/lang/Object" {
+            let last = super_classes.last().unwrap();
+            super_classes.push(hierarchy.super_class(last).ok_or_else(|| last.clone())?);
+        }
+        Ok(super_classes)
+    };
+    let first_super_classes = super_classes(first)?;
+    let common = super_classes(second)?.into_iter().find(|class_name| first_super_classes.contains(class_name));
+    Ok(common.unwrap())
+}
+
+/// The types the verifier tracks for locals and operand stack entries (JVMS §4.10.1.2).
+///
+/// `long` and `double` values take two slots, the second of which is [`VerificationType::Top`].
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` in a constructor before the super constructor is called.
+    UninitializedThis,
+    /// The result of the `new` at the given pc before its constructor is called.
+    Uninitialized(usize),
+    /// A class by its internal name, or an array by its descriptor, like `CONSTANT_Class` entries.
+    Object(String),
+}
+
+impl VerificationType {
+    pub fn from_field_type(field_type: &FieldType) -> VerificationType {
+        match field_type {
+            FieldType::Byte | FieldType::Char | FieldType::Short | FieldType::Boolean | FieldType::Int => VerificationType::Integer,
+            FieldType::Float => VerificationType::Float,
+            FieldType::Long => VerificationType::Long,
+            FieldType::Double => VerificationType::Double,
+            FieldType::Object(_) | FieldType::Array(_) => VerificationType::Object(field_type.class_name().unwrap()),
+        }
+    }
+
+    pub fn is_wide(&self) -> bool {
+        matches!(self, VerificationType::Long | VerificationType::Double)
+    }
+
+    /// The least type both types can be used as, `Top` when they have nothing in common. Fails
+    /// like [`common_super_class`].
+    fn merge(&self, other: &VerificationType, hierarchy: &dyn ClassHierarchy) -> Result<VerificationType, String> {
+        Ok(match (self, other) {
+            _ if self == other => self.clone(),
+            (VerificationType::Null, VerificationType::Object(_)) => other.clone(),
+            (VerificationType::Object(_), VerificationType::Null) => self.clone(),
+            (VerificationType::Object(first), VerificationType::Object(second)) => {
+                VerificationType::Object(merge_class_names(hierarchy, first, second)?)
+            }
+            _ => VerificationType::Top,
+        })
+    }
+}
+
+fn merge_class_names(hierarchy: &dyn ClassHierarchy, first: &str, second: &str) -> Result<String, String> {
+    match (first.strip_prefix('['), second.strip_prefix('[')) {
+        (None, None) => common_super_class(hierarchy, first, second),
+        (Some(first_element), Some(second_element)) => {
+            let element_class = |element: &str| match FieldType::parse(element) {
+                Some(field_type) if field_type.is_reference() => field_type.class_name(),
+                _ => None,
+            };
+            Ok(match (element_class(first_element), element_class(second_element)) {
+                (Some(first_class), Some(second_class)) => {
+                    match FieldType::from_class_name(&merge_class_names(hierarchy, &first_class, &second_class)?) {
+                        Some(element) => FieldType::Array(Box::new(element)).to_string(),
+                        None => "java/lang/Object".to_string(),
+                    }
+                }
+                _ => "java/lang/Object".to_string(),
+            })
+        }
+        _ => Ok("java/lang/Object".to_string()),
+    }
+}
+
+/// The types of the locals and of the operand stack before an instruction, one entry per slot.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Frame {
+    pub locals: Vec<VerificationType>,
+    pub stack: Vec<VerificationType>,
+}
+
+impl Frame {
+    /// The frame a method starts with: `this` unless it is static, then its parameters.
+    pub fn initial(class_name: &str, method: &MethodInfo) -> Option<Frame> {
+        let descriptor = MethodDescriptor::parse(&method.descriptor)?;
+        let mut locals = Vec::new();
+        if method.access_flags & 0x0008 == 0 {
+            locals.push(if method.name == "<init>" && class_name != "java/lang/Object" {
+                VerificationType::UninitializedThis
+            } else {
+                VerificationType::Object(class_name.to_string())
+            });
+        }
+        for parameter in &descriptor.parameters {
+            push_slots(&mut locals, VerificationType::from_field_type(parameter));
+        }
+        Some(Frame { locals, stack: Vec::new() })
+    }
+
+    /// The frame both frames can be used as, or why there is none.
+    fn merge(&self, other: &Frame, hierarchy: &dyn ClassHierarchy) -> Result<Frame, String> {
+        if self.stack.len() != other.stack.len() {
+            return Err("The stack heights differ".to_string());
+        }
+        let merge_slots = |first: &[VerificationType], second: &[VerificationType]| -> Result<Vec<VerificationType>, String> {
+            let mut merged = first.iter().zip(second).map(|(first, second)| first.merge(second, hierarchy))
+                .collect::<Result<Vec<VerificationType>, String>>()
+                .map_err(|class_name| format!("Merging the types needs the super classes of {class_name}, which the class hierarchy doesn't know"))?;
+            // The second half of a long or double is only usable together with the first
+            for position in 1..merged.len() {
+                if merged[position - 1] == VerificationType::Top && first[position - 1].is_wide() {
+                    merged[position] = VerificationType::Top;
+                }
+            }
+            Ok(merged)
+        };
+        let mut locals = merge_slots(&self.locals, &other.locals)?;
+        while locals.last() == Some(&VerificationType::Top) {
+            locals.pop();
+        }
+        Ok(Frame { locals, stack: merge_slots(&self.stack, &other.stack)? })
+    }
+
+    /// The locals and stack as a `StackMapTable` lists them, with one entry for a `long` or `double`.
+    fn entries(slots: &[VerificationType]) -> Vec<VerificationType> {
+        let mut entries = Vec::with_capacity(slots.len());
+        let mut position = 0;
+        while position < slots.len() {
+            entries.push(slots[position].clone());
+            position += if slots[position].is_wide() { 2 } else { 1 };
+        }
+        entries
+    }
+}
+
+fn push_slots(slots: &mut Vec<VerificationType>, verification_type: VerificationType) {
+    let wide = verification_type.is_wide();
+    slots.push(verification_type);
+    if wide {
+        slots.push(VerificationType::Top);
+    }
+}
+
+/// Why frames couldn't be computed for a method, and at which pc.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameError {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}: {}", self.pc, self.message)
+    }
+}
+
+impl Error for FrameError {}
+
+impl From<DecodeError> for FrameError {
+    fn from(error: DecodeError) -> Self {
+        FrameError { pc: error.pc, message: error.to_string() }
+    }
+}
+
+fn error<T>(pc: usize, message: impl Into<String>) -> Result<T, FrameError> {
+    Err(FrameError { pc, message: message.into() })
+}
+
+/// The types at every reachable pc of a method, found by data-flow analysis over its control-flow
+/// graph, and the stack and locals it needs.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FrameAnalysis {
+    /// The frame before every reachable instruction, by pc.
+    pub frames: BTreeMap<usize, Frame>,
+    pub max_stack: usize,
+    pub max_locals: usize,
+}
+
+/// Infers the frames of a method. `jsr` and `ret` aren't supported, as class files that can have
+/// a `StackMapTable` can't use them.
+pub fn analyze(java_class: &JavaClass, method: &MethodInfo, hierarchy: &dyn ClassHierarchy) -> Result<FrameAnalysis, FrameError> {
+    let code = method.code().ok_or_else(|| FrameError { pc: 0, message: format!("{} has no code", method.name) })?;
+    let initial = Frame::initial(&java_class.this_class_name, method)
+        .ok_or_else(|| FrameError { pc: 0, message: format!("{} is not a method descriptor", method.descriptor) })?;
+    let graph = ControlFlowGraph::new(code)?;
+    let interpreter = TypeInterpreter { class_name: &java_class.this_class_name, constant_pool_infos: &java_class.constant_pool_infos };
+
+    let mut block_frames: Vec<Option<Frame>> = vec![None; graph.blocks.len()];
+    let mut worklist = Vec::new();
+    if !graph.blocks.is_empty() {
+        block_frames[0] = Some(initial.clone());
+        worklist.push(0);
+    }
+    let merge_into = |block_frames: &mut Vec<Option<Frame>>, worklist: &mut Vec<usize>, pc: usize, block: usize, frame: Frame| -> Result<(), FrameError> {
+        let merged = match &block_frames[block] {
+            None => frame,
+            Some(existing) => match existing.merge(&frame, hierarchy) {
+                Ok(merged) if merged == *existing => return Ok(()),
+                Ok(merged) => merged,
+                Err(message) => return error(pc, format!("{message} at pc {}", graph.blocks[block].start)),
+            },
+        };
+        block_frames[block] = Some(merged);
+        if !worklist.contains(&block) {
+            worklist.push(block);
+        }
+        Ok(())
+    };
+
+    while let Some(block) = worklist.pop() {
+        let mut frame = block_frames[block].clone().unwrap();
+        let mut last_pc = 0;
+        for (pc, instruction) in graph.block_instructions(block) {
+            last_pc = *pc;
+            for entry in code.exceptions.iter().filter(|entry| (entry.start_pc..entry.end_pc).contains(pc)) {
+                let catch_type = match entry.catch_pc {
+                    0 => "java/lang/Throwable".to_string(),
+                    index => interpreter.class_name_at(*pc, CpIndex(index as u16))?,
+                };
+                let handler_frame = Frame { locals: frame.locals.clone(), stack: vec![VerificationType::Object(catch_type)] };
+                if let Some(handler) = graph.block_at(entry.handler_pc) {
+                    merge_into(&mut block_frames, &mut worklist, *pc, handler, handler_frame)?;
+                }
+            }
+            interpreter.execute(*pc, instruction, &mut frame)?;
+        }
+        for edge in graph.successors(block) {
+            if edge.kind == EdgeKind::Normal {
+                merge_into(&mut block_frames, &mut worklist, last_pc, edge.to, frame.clone())?;
+            }
+        }
+    }
+
+    // One more pass over the settled block frames gives the frame at every pc and the peaks
+    let mut frames = BTreeMap::new();
+    let mut max_stack = 0;
+    let mut max_locals = initial.locals.len();
+    for (block, block_frame) in block_frames.iter().enumerate() {
+        let Some(mut frame) = block_frame.clone() else { continue };
+        for (pc, instruction) in graph.block_instructions(block) {
+            frames.insert(*pc, frame.clone());
+            max_stack = max_stack.max(frame.stack.len());
+            interpreter.execute(*pc, instruction, &mut frame)?;
+            max_stack = max_stack.max(frame.stack.len());
+            max_locals = max_locals.max(frame.locals.len());
+        }
+    }
+    if !code.exceptions.is_empty() {
+        max_stack = max_stack.max(1);
+    }
+    Ok(FrameAnalysis { frames, max_stack, max_locals })
+}
+
+/// Applies instructions to frames at the level of verification types.
+struct TypeInterpreter<'a> {
+    class_name: &'a str,
+    constant_pool_infos: &'a [ConstantPoolInfo],
+}
+
+impl TypeInterpreter<'_> {
+    fn constant(&self, pc: usize, CpIndex(index): CpIndex) -> Result<&ConstantPoolInfo, FrameError> {
+        (index as usize).checked_sub(1).and_then(|position| self.constant_pool_infos.get(position))
+            .ok_or_else(|| FrameError { pc, message: format!("#{index} is not in the constant pool") })
+    }
+
+    fn class_name_at(&self, pc: usize, index: CpIndex) -> Result<String, FrameError> {
+        match self.constant(pc, index)? {
+            ConstantPoolInfo::Class(class_info) => Ok(class_info.name.clone()),
+            _ => error(pc, format!("#{} is not a class", index.0)),
+        }
+    }
+
+    fn field_type(&self, pc: usize, index: CpIndex) -> Result<FieldType, FrameError> {
+        let descriptor = match self.constant(pc, index)? {
+            ConstantPoolInfo::FieldRef(field_ref) => &field_ref.descriptor,
+            _ => return error(pc, format!("#{} is not a field", index.0)),
+        };
+        FieldType::parse(descriptor).ok_or_else(|| FrameError { pc, message: format!("{descriptor} is not a field descriptor") })
+    }
+
+    /// The owner, name and descriptor of the method an invoke refers to.
+    fn method(&self, pc: usize, index: CpIndex) -> Result<(String, String, MethodDescriptor), FrameError> {
+        let (class_name, name, descriptor) = match self.constant(pc, index)? {
+            ConstantPoolInfo::MethodRef(method_ref) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor),
+            ConstantPoolInfo::InterfaceMethodRef(method_ref) => (&method_ref.class_name, &method_ref.name, &method_ref.descriptor),
+            ConstantPoolInfo::InvokeDynamic(invoke_dynamic) => (&String::new(), &invoke_dynamic.name, &invoke_dynamic.descriptor),
+            _ => return error(pc, format!("#{} is not a method", index.0)),
+        };
+        let method_descriptor = MethodDescriptor::parse(descriptor)
+            .ok_or_else(|| FrameError { pc, message: format!("{descriptor} is not a method descriptor") })?;
+        Ok((class_name.clone(), name.clone(), method_descriptor))
+    }
+
+    fn pop(&self, pc: usize, frame: &mut Frame, slots: usize) -> Result<Vec<VerificationType>, FrameError> {
+        if frame.stack.len() < slots {
+            return error(pc, "The operand stack underflows");
+        }
+        Ok(frame.stack.split_off(frame.stack.len() - slots))
+    }
+
+    fn load(&self, pc: usize, frame: &mut Frame, index: usize) -> Result<(), FrameError> {
+        let Some(verification_type) = frame.locals.get(index).cloned() else {
+            return error(pc, format!("Local {index} is read before it is set"));
+        };
+        push_slots(&mut frame.stack, verification_type);
+        Ok(())
+    }
+
+    fn store(&self, pc: usize, frame: &mut Frame, index: usize, slots: usize) -> Result<(), FrameError> {
+        let value = self.pop(pc, frame, slots)?;
+        if frame.locals.len() < index + slots {
+            frame.locals.resize(index + slots, VerificationType::Top);
+        }
+        // Overwriting half of a long or double invalidates it
+        if index > 0 && frame.locals[index - 1].is_wide() {
+            frame.locals[index - 1] = VerificationType::Top;
+        }
+        if frame.locals.get(index + slots - 1).is_some_and(VerificationType::is_wide) && frame.locals.len() > index + slots {
+            frame.locals[index + slots] = VerificationType::Top;
+        }
+        frame.locals.splice(index..index + slots, value);
+        Ok(())
+    }
+
+    fn execute(&self, pc: usize, instruction: &Instruction, frame: &mut Frame) -> Result<(), FrameError> {
+        use VerificationType::{Double, Float, Integer, Long, Null};
+        let opcode = instruction.opcode();
+        let push = |frame: &mut Frame, verification_type: VerificationType| push_slots(&mut frame.stack, verification_type);
+        match instruction {
+            Instruction::Nop | Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::Return => {}
+            Instruction::AconstNull => push(frame, Null),
+            Instruction::IconstM1 | Instruction::Iconst0 | Instruction::Iconst1 | Instruction::Iconst2 | Instruction::Iconst3
+            | Instruction::Iconst4 | Instruction::Iconst5 | Instruction::Bipush(_) | Instruction::Sipush(_) => push(frame, Integer),
+            Instruction::Lconst0 | Instruction::Lconst1 => push(frame, Long),
+            Instruction::Fconst0 | Instruction::Fconst1 | Instruction::Fconst2 => push(frame, Float),
+            Instruction::Dconst0 | Instruction::Dconst1 => push(frame, Double),
+            Instruction::Ldc(index) | Instruction::LdcW(index) | Instruction::Ldc2W(index) => {
+                let verification_type = match self.constant(pc, *index)? {
+                    ConstantPoolInfo::Integer(_) => Integer,
+                    ConstantPoolInfo::Float(_) => Float,
+                    ConstantPoolInfo::Long(_) => Long,
+                    ConstantPoolInfo::Double(_) => Double,
+                    ConstantPoolInfo::String(_) => VerificationType::Object("java/lang/String".to_string()),
+                    ConstantPoolInfo::Class(_) => VerificationType::Object("java/lang/Class".to_string()),
+                    ConstantPoolInfo::MethodKind(_) => VerificationType::Object("java/lang/invoke/MethodHandle".to_string()),
+                    ConstantPoolInfo::MethodType(_) => VerificationType::Object("java/lang/invoke/MethodType".to_string()),
+                    ConstantPoolInfo::Dynamic(dynamic) => match FieldType::parse(&dynamic.descriptor) {
+                        Some(field_type) => VerificationType::from_field_type(&field_type),
+                        None => return error(pc, format!("{} is not a field descriptor", dynamic.descriptor)),
+                    },
+                    _ => return error(pc, format!("#{} can't be loaded", index.0)),
+                };
+                push(frame, verification_type);
+            }
+            Instruction::Iaload | Instruction::Baload | Instruction::Caload | Instruction::Saload => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Integer);
+            }
+            Instruction::Laload => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Long);
+            }
+            Instruction::Faload => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Float);
+            }
+            Instruction::Daload => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Double);
+            }
+            Instruction::Aaload => {
+                let array = self.pop(pc, frame, 2)?.remove(0);
+                let element = match &array {
+                    VerificationType::Object(class_name) => match FieldType::parse(class_name) {
+                        Some(FieldType::Array(element)) => VerificationType::from_field_type(&element),
+                        _ => VerificationType::Object("java/lang/Object".to_string()),
+                    },
+                    _ => Null,
+                };
+                push(frame, element);
+            }
+            Instruction::Iastore | Instruction::Fastore | Instruction::Aastore | Instruction::Bastore | Instruction::Castore | Instruction::Sastore => {
+                self.pop(pc, frame, 3)?;
+            }
+            Instruction::Lastore | Instruction::Dastore => {
+                self.pop(pc, frame, 4)?;
+            }
+            Instruction::Pop | Instruction::Monitorenter | Instruction::Monitorexit | Instruction::Ifeq(_) | Instruction::Ifne(_)
+            | Instruction::Iflt(_) | Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) | Instruction::Ifnull(_)
+            | Instruction::Ifnonnull(_) | Instruction::Tableswitch { .. } | Instruction::Lookupswitch { .. } | Instruction::Ireturn
+            | Instruction::Freturn | Instruction::Areturn | Instruction::Athrow => {
+                self.pop(pc, frame, 1)?;
+            }
+            Instruction::Pop2 | Instruction::IfIcmpeq(_) | Instruction::IfIcmpne(_) | Instruction::IfIcmplt(_) | Instruction::IfIcmpge(_)
+            | Instruction::IfIcmpgt(_) | Instruction::IfIcmple(_) | Instruction::IfAcmpeq(_) | Instruction::IfAcmpne(_)
+            | Instruction::Lreturn | Instruction::Dreturn => {
+                self.pop(pc, frame, 2)?;
+            }
+            Instruction::Dup => {
+                let value = self.pop(pc, frame, 1)?;
+                frame.stack.extend(value.iter().cloned().chain(value.iter().cloned()));
+            }
+            Instruction::DupX1 | Instruction::DupX2 => {
+                let depth = if opcode == Opcode::DupX1 { 2 } else { 3 };
+                let mut values = self.pop(pc, frame, depth)?;
+                values.insert(0, values.last().unwrap().clone());
+                frame.stack.extend(values);
+            }
+            Instruction::Dup2 => {
+                let values = self.pop(pc, frame, 2)?;
+                frame.stack.extend(values.iter().cloned().chain(values.iter().cloned()));
+            }
+            Instruction::Dup2X1 | Instruction::Dup2X2 => {
+                let depth = if opcode == Opcode::Dup2X1 { 3 } else { 4 };
+                let values = self.pop(pc, frame, depth)?;
+                let top = values[depth - 2..].to_vec();
+                frame.stack.extend(top.into_iter().chain(values));
+            }
+            Instruction::Swap => {
+                let mut values = self.pop(pc, frame, 2)?;
+                values.swap(0, 1);
+                frame.stack.extend(values);
+            }
+            Instruction::Iadd | Instruction::Isub | Instruction::Imul | Instruction::Idiv | Instruction::Irem | Instruction::Ishl
+            | Instruction::Ishr | Instruction::Iushr | Instruction::Iand | Instruction::Ior | Instruction::Ixor | Instruction::Fcmpl
+            | Instruction::Fcmpg => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Integer);
+            }
+            Instruction::Ladd | Instruction::Lsub | Instruction::Lmul | Instruction::Ldiv | Instruction::Lrem | Instruction::Land
+            | Instruction::Lor | Instruction::Lxor => {
+                self.pop(pc, frame, 4)?;
+                push(frame, Long);
+            }
+            Instruction::Lshl | Instruction::Lshr | Instruction::Lushr => {
+                self.pop(pc, frame, 3)?;
+                push(frame, Long);
+            }
+            Instruction::Fadd | Instruction::Fsub | Instruction::Fmul | Instruction::Fdiv | Instruction::Frem => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Float);
+            }
+            Instruction::Dadd | Instruction::Dsub | Instruction::Dmul | Instruction::Ddiv | Instruction::Drem => {
+                self.pop(pc, frame, 4)?;
+                push(frame, Double);
+            }
+            Instruction::Lcmp | Instruction::Dcmpl | Instruction::Dcmpg => {
+                self.pop(pc, frame, 4)?;
+                push(frame, Integer);
+            }
+            Instruction::Ineg | Instruction::I2b | Instruction::I2c | Instruction::I2s => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Integer);
+            }
+            Instruction::Lneg | Instruction::D2l => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Long);
+            }
+            Instruction::Fneg | Instruction::I2f => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Float);
+            }
+            Instruction::Dneg | Instruction::L2d => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Double);
+            }
+            Instruction::I2l | Instruction::F2l => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Long);
+            }
+            Instruction::I2d | Instruction::F2d => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Double);
+            }
+            Instruction::L2i | Instruction::D2i => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Integer);
+            }
+            Instruction::L2f | Instruction::D2f => {
+                self.pop(pc, frame, 2)?;
+                push(frame, Float);
+            }
+            Instruction::F2i => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Integer);
+            }
+            Instruction::Iinc { index, .. } => {
+                if frame.locals.get(*index as usize) != Some(&Integer) {
+                    return error(pc, format!("Local {index} is not an int"));
+                }
+            }
+            Instruction::Jsr(_) | Instruction::JsrW(_) | Instruction::Ret(_) => {
+                return error(pc, "Subroutines can't be described by stack map frames");
+            }
+            Instruction::Getstatic(index) => {
+                let field_type = self.field_type(pc, *index)?;
+                push(frame, VerificationType::from_field_type(&field_type));
+            }
+            Instruction::Putstatic(index) => {
+                let field_type = self.field_type(pc, *index)?;
+                self.pop(pc, frame, field_type.slots())?;
+            }
+            Instruction::Getfield(index) => {
+                let field_type = self.field_type(pc, *index)?;
+                self.pop(pc, frame, 1)?;
+                push(frame, VerificationType::from_field_type(&field_type));
+            }
+            Instruction::Putfield(index) => {
+                let field_type = self.field_type(pc, *index)?;
+                self.pop(pc, frame, field_type.slots() + 1)?;
+            }
+            Instruction::Invokevirtual(index) | Instruction::Invokespecial(index) | Instruction::Invokestatic(index)
+            | Instruction::Invokeinterface { index, .. } | Instruction::Invokedynamic(index) => {
+                let (class_name, name, descriptor) = self.method(pc, *index)?;
+                self.pop(pc, frame, descriptor.parameter_slots())?;
+                if !matches!(opcode, Opcode::Invokestatic | Opcode::Invokedynamic) {
+                    let receiver = self.pop(pc, frame, 1)?.remove(0);
+                    if name == "<init>" {
+                        let initialized = match receiver {
+                            VerificationType::UninitializedThis => VerificationType::Object(self.class_name.to_string()),
+                            VerificationType::Uninitialized(_) => VerificationType::Object(class_name),
+                            _ => return error(pc, "A constructor is called on an initialized object"),
+                        };
+                        for slot in frame.locals.iter_mut().chain(frame.stack.iter_mut()) {
+                            if *slot == receiver {
+                                *slot = initialized.clone();
+                            }
+                        }
+                    }
+                }
+                if let Some(return_type) = &descriptor.return_type {
+                    push(frame, VerificationType::from_field_type(return_type));
+                }
+            }
+            Instruction::New(_) => push(frame, VerificationType::Uninitialized(pc)),
+            Instruction::Newarray(array_type) => {
+                self.pop(pc, frame, 1)?;
+                let element = match array_type.name() {
+                    "boolean" => "Z",
+                    "char" => "C",
+                    "float" => "F",
+                    "double" => "D",
+                    "byte" => "B",
+                    "short" => "S",
+                    "int" => "I",
+                    _ => "J",
+                };
+                push(frame, VerificationType::Object(format!("[{element}")));
+            }
+            Instruction::Anewarray(index) => {
+                self.pop(pc, frame, 1)?;
+                let element = self.class_name_at(pc, *index)?;
+                let element = FieldType::from_class_name(&element).ok_or_else(|| FrameError { pc, message: format!("{element} is not a class name") })?;
+                push(frame, VerificationType::Object(FieldType::Array(Box::new(element)).to_string()));
+            }
+            Instruction::Arraylength | Instruction::Instanceof(_) => {
+                self.pop(pc, frame, 1)?;
+                push(frame, Integer);
+            }
+            Instruction::Checkcast(index) => {
+                self.pop(pc, frame, 1)?;
+                push(frame, VerificationType::Object(self.class_name_at(pc, *index)?));
+            }
+            Instruction::Multianewarray { index, dimensions } => {
+                self.pop(pc, frame, *dimensions as usize)?;
+                push(frame, VerificationType::Object(self.class_name_at(pc, *index)?));
+            }
+            _ => match instruction.local_access() {
+                Some((_, false, index)) => self.load(pc, frame, index as usize)?,
+                Some((ty, true, index)) => self.store(pc, frame, index as usize, if ty.is_wide() { 2 } else { 1 })?,
+                None => return Err(FrameError { pc, message: format!("{} is not supported", opcode.mnemonic()) }),
+            },
+        }
+        Ok(())
+    }
+}
+
+/// One entry of a `StackMapTable`, describing the frame at a pc relative to the previous entry
+/// (JVMS §4.7.4). Locals and stack list one entry for a `long` or `double`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum StackMapFrame {
+    Same { offset_delta: u16 },
+    SameLocals1StackItem { offset_delta: u16, stack: VerificationType },
+    /// The previous locals without the last `count` ones.
+    Chop { offset_delta: u16, count: u8 },
+    /// The previous locals and `locals`.
+    Append { offset_delta: u16, locals: Vec<VerificationType> },
+    Full { offset_delta: u16, locals: Vec<VerificationType>, stack: Vec<VerificationType> },
+}
+
+impl StackMapFrame {
+    /// The most compact entry going from the `previous` frame to `frame`.
+    fn between(previous: &Frame, frame: &Frame, offset_delta: u16) -> StackMapFrame {
+        let previous_locals = Frame::entries(&previous.locals);
+        let locals = Frame::entries(&frame.locals);
+        let stack = Frame::entries(&frame.stack);
+        let common = previous_locals.iter().zip(&locals).take_while(|(previous, local)| previous == local).count();
+        match stack.len() {
+            0 if locals == previous_locals => StackMapFrame::Same { offset_delta },
+            1 if locals == previous_locals => StackMapFrame::SameLocals1StackItem { offset_delta, stack: stack[0].clone() },
+            0 if common == locals.len() && previous_locals.len() - common <= 3 => {
+                StackMapFrame::Chop { offset_delta, count: (previous_locals.len() - common) as u8 }
+            }
+            0 if common == previous_locals.len() && locals.len() - common <= 3 => {
+                StackMapFrame::Append { offset_delta, locals: locals[common..].to_vec() }
+            }
+            _ => StackMapFrame::Full { offset_delta, locals, stack },
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>, constant_pool: &mut ConstantPoolBuilder) {
+        let write_types = |bytes: &mut Vec<u8>, constant_pool: &mut ConstantPoolBuilder, verification_types: &[VerificationType]| {
+            for verification_type in verification_types {
+                match verification_type {
+                    VerificationType::Top => bytes.push(0),
+                    VerificationType::Integer => bytes.push(1),
+                    VerificationType::Float => bytes.push(2),
+                    VerificationType::Double => bytes.push(3),
+                    VerificationType::Long => bytes.push(4),
+                    VerificationType::Null => bytes.push(5),
+                    VerificationType::UninitializedThis => bytes.push(6),
+                    VerificationType::Object(class_name) => {
+                        bytes.push(7);
+                        bytes.extend((constant_pool.class(class_name) as u16).to_be_bytes());
+                    }
+                    VerificationType::Uninitialized(pc) => {
+                        bytes.push(8);
+                        bytes.extend((*pc as u16).to_be_bytes());
+                    }
+                }
+            }
+        };
+        match self {
+            StackMapFrame::Same { offset_delta } if *offset_delta < 64 => bytes.push(*offset_delta as u8),
+            StackMapFrame::Same { offset_delta } => {
+                bytes.push(251);
+                bytes.extend(offset_delta.to_be_bytes());
+            }
+            StackMapFrame::SameLocals1StackItem { offset_delta, stack } => {
+                if *offset_delta < 64 {
+                    bytes.push(64 + *offset_delta as u8);
+                } else {
+                    bytes.push(247);
+                    bytes.extend(offset_delta.to_be_bytes());
+                }
+                write_types(bytes, constant_pool, std::slice::from_ref(stack));
+            }
+            StackMapFrame::Chop { offset_delta, count } => {
+                bytes.push(251 - count);
+                bytes.extend(offset_delta.to_be_bytes());
+            }
+            StackMapFrame::Append { offset_delta, locals } => {
+                bytes.push(251 + locals.len() as u8);
+                bytes.extend(offset_delta.to_be_bytes());
+                write_types(bytes, constant_pool, locals);
+            }
+            StackMapFrame::Full { offset_delta, locals, stack } => {
+                bytes.push(255);
+                bytes.extend(offset_delta.to_be_bytes());
+                bytes.extend((locals.len() as u16).to_be_bytes());
+                write_types(bytes, constant_pool, locals);
+                bytes.extend((stack.len() as u16).to_be_bytes());
+                write_types(bytes, constant_pool, stack);
+            }
+        }
+    }
+}
+
+/// The `StackMapTable` entries of an analyzed method: a frame at every branch target and
+/// exception handler, in pc order.
+///
+/// Like javac, which only lists the locals in scope, frames leave out the locals at the end that
+/// aren't parameters and aren't read again. Unreachable code gets no frames.
+pub fn stack_map_frames(java_class: &JavaClass, method: &MethodInfo, analysis: &FrameAnalysis) -> Result<Vec<StackMapFrame>, FrameError> {
+    let code = method.code().ok_or_else(|| FrameError { pc: 0, message: format!("{} has no code", method.name) })?;
+    let initial = Frame::initial(&java_class.this_class_name, method)
+        .ok_or_else(|| FrameError { pc: 0, message: format!("{} is not a method descriptor", method.descriptor) })?;
+    let graph = ControlFlowGraph::new(code)?;
+    let live = dataflow::solve(&graph, &Liveness { max_locals: analysis.max_locals });
+    let dead = LiveLocals(vec![false; analysis.max_locals]);
+
+    let mut targets: Vec<usize> = graph.instructions.iter()
+        .filter(|(pc, _)| analysis.frames.contains_key(pc))
+        .flat_map(|(_, instruction)| instruction.branch_targets())
+        .map(|target| target.0)
+        .chain(code.exceptions.iter().map(|entry| entry.handler_pc))
+        .filter(|pc| analysis.frames.contains_key(pc))
+        .collect();
+    targets.sort_unstable();
+    targets.dedup();
+
+    let mut stack_map_frames = Vec::with_capacity(targets.len());
+    let mut previous = (None, initial.clone());
+    for pc in targets {
+        let mut frame = analysis.frames[&pc].clone();
+        let live_at_pc = &live.before.get(&pc).unwrap_or(&dead).0;
+        // Dead locals are forgotten, so no frame claims a local that a path to it doesn't set
+        for (index, local) in frame.locals.iter_mut().enumerate().skip(initial.locals.len()) {
+            if !live_at_pc[index] {
+                *local = VerificationType::Top;
+            }
+        }
+        loop {
+            let length = frame.locals.len();
+            let start = if length >= 2 && frame.locals[length - 2].is_wide() { length - 2 } else { length.saturating_sub(1) };
+            if length <= initial.locals.len() || start < initial.locals.len() {
+                break;
+            }
+            if frame.locals[start] != VerificationType::Top && live_at_pc[start] {
+                break;
+            }
+            frame.locals.truncate(start);
+        }
+        let offset_delta = match previous.0 {
+            None => pc,
+            Some(previous_pc) => pc - previous_pc - 1,
+        };
+        stack_map_frames.push(StackMapFrame::between(&previous.1, &frame, offset_delta as u16));
+        previous = (Some(pc), frame);
+    }
+    Ok(stack_map_frames)
+}
+
+/// Recomputes `max_stack`, `max_locals` and the `StackMapTable` of every method with code, adding
+/// the classes the frames refer to to the constant pool. Class files older than version 50 don't
+/// get a `StackMapTable`, and methods that need no frames have theirs removed.
+pub fn recompute_frames(java_class: &mut JavaClass, hierarchy: &dyn ClassHierarchy) -> Result<(), FrameError> {
+    let positions: Vec<usize> = (0..java_class.methods.len()).collect();
+    recompute_method_frames(java_class, hierarchy, &positions)
+}
+
+/// Like [`recompute_frames`], for the methods at `positions` only.
+pub fn recompute_method_frames(java_class: &mut JavaClass, hierarchy: &dyn ClassHierarchy, positions: &[usize]) -> Result<(), FrameError> {
+    let mut constant_pool = ConstantPoolBuilder::from_constants(java_class.constant_pool_infos.clone());
+    let mut results = Vec::with_capacity(java_class.methods.len());
+    for (position, method) in java_class.methods.iter().enumerate() {
+        if method.code().is_none() || !positions.contains(&position) {
+            results.push(None);
+            continue;
+        }
+        let in_method = |error: FrameError| FrameError { pc: error.pc, message: format!("{}{}: {}", method.name, method.descriptor, error.message) };
+        let analysis = analyze(java_class, method, hierarchy).map_err(in_method)?;
+        let stack_map = if java_class.major >= 50 {
+            stack_map_frames(java_class, method, &analysis).map_err(in_method)?
+        } else {
+            Vec::new()
+        };
+        let info = (!stack_map.is_empty()).then(|| {
+            let mut info = (stack_map.len() as u16).to_be_bytes().to_vec();
+            for frame in &stack_map {
+                frame.write(&mut info, &mut constant_pool);
+            }
+            info
+        });
+        results.push(Some((analysis.max_stack, analysis.max_locals, info)));
+    }
+
+    let stack_map_table_name_index = results.iter().flatten().any(|(_, _, info)| info.is_some()).then(|| constant_pool.utf8("StackMapTable"));
+    for (method, result) in java_class.methods.iter_mut().zip(results) {
+        let Some((max_stack, max_locals, info)) = result else { continue };
+        let code = method.attributes_info.iter_mut().find_map(|attribute_info| match &mut attribute_info.attribute {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        }).unwrap();
+        set_stack_map_table(code, max_stack, max_locals, stack_map_table_name_index.zip(info));
+    }
+    java_class.constant_pool_infos = constant_pool.build();
+    java_class.constant_pool_count = java_class.constant_pool_infos.len() + 1;
+    Ok(())
+}
+
+fn set_stack_map_table(code: &mut AttributeCode, max_stack: usize, max_locals: usize, stack_map_table: Option<(usize, Vec<u8>)>) {
+    code.max_stack = max_stack;
+    code.max_locals = max_locals;
+    let existing = code.attributes.iter().position(|attribute_info| {
+        matches!(&attribute_info.attribute, Attribute::Unknown(unknown) if unknown.attribute_name == "StackMapTable")
+    });
+    match (existing, stack_map_table) {
+        (Some(position), None) => {
+            code.attributes.remove(position);
+        }
+        (existing, Some((attribute_name_index, info))) => {
+            let attribute_info = AttributeInfo {
+                attribute_name_index,
+                attribute: Attribute::Unknown(AttributeUnknown { attribute_name: "StackMapTable".to_string(), info }),
+            };
+            match existing {
+                Some(position) => code.attributes[position] = attribute_info,
+                None => code.attributes.push(attribute_info),
+            }
+        }
+        (None, None) => {}
+    }
+}
+
+#[test]
+fn test_limits_match_javac() {
+    let java_class = JavaClass::load_from_file("MyClass.class").unwrap();
+    let hierarchy = KnownClasses::new();
+    for method in &java_class.methods {
+        let analysis = analyze(&java_class, method, &hierarchy).unwrap();
+        let code = method.code().unwrap();
+        assert_eq!((code.max_stack, code.max_locals), (analysis.max_stack, analysis.max_locals), "{}", method.name);
+        assert_eq!(Vec::<StackMapFrame>::new(), stack_map_frames(&java_class, method, &analysis).unwrap());
+    }
+
+    let main = java_class.methods.iter().find(|method| method.name == "main").unwrap();
+    let analysis = analyze(&java_class, main, &hierarchy).unwrap();
+    let string_array = VerificationType::Object("[Ljava/lang/String;".to_string());
+    assert_eq!(Frame {
+        locals: vec![string_array, VerificationType::Integer, VerificationType::Integer, VerificationType::Integer],
+        stack: vec![VerificationType::Object("java/io/PrintStream".to_string()), VerificationType::Integer],
+    }, analysis.frames[&14]);
+
+    let mut recomputed = java_class.clone();
+    recompute_frames(&mut recomputed, &hierarchy).unwrap();
+    assert_eq!(java_class, recomputed);
+}
+
+#[test]
+fn test_stack_map_tables_match_javac() {
+    let java_class = JavaClass::load_from_file("Flow.class").unwrap();
+    let mut stripped = java_class.clone();
+    for method in &mut stripped.methods {
+        for attribute_info in &mut method.attributes_info {
+            if let Attribute::Code(code) = &mut attribute_info.attribute {
+                set_stack_map_table(code, 0, 0, None);
+            }
+        }
+    }
+    assert_ne!(java_class, stripped);
+
+    recompute_frames(&mut stripped, &KnownClasses::new()).unwrap();
+    assert_eq!(java_class, stripped);
+
+    let analysis = analyze(&java_class, &java_class.methods[3], &KnownClasses::new()).unwrap();
+    assert_eq!(vec![
+        StackMapFrame::Append { offset_delta: 4, locals: vec![VerificationType::Integer, VerificationType::Integer] },
+        StackMapFrame::Chop { offset_delta: 14, count: 1 },
+    ], stack_map_frames(&java_class, &java_class.methods[3], &analysis).unwrap());
+}
+
+#[test]
+fn test_merges_references_to_their_common_super_class() {
+    let mut hierarchy = KnownClasses::new();
+    for class_file in ["MyClass.class", "A.class"] {
+        hierarchy.add(&JavaClass::load_from_file(class_file).unwrap());
+    }
+    assert_eq!(Ok("java/lang/Object".to_string()), common_super_class(&hierarchy, "MyClass", "A"));
+    assert_eq!(Err("java/lang/String".to_string()), common_super_class(&hierarchy, "MyClass", "java/lang/String"));
+    hierarchy.super_classes.extend(jdk_classes().super_classes);
+    assert_eq!(Ok("java/lang/Object".to_string()), common_super_class(&hierarchy, "MyClass", "java/lang/String"));
+    assert_eq!(Ok("java/util/AbstractList".to_string()), common_super_class(&hierarchy, "java/util/LinkedList", "java/util/ArrayList"));
+
+    let string = VerificationType::Object("java/lang/String".to_string());
+    let strings = VerificationType::Object("[Ljava/lang/String;".to_string());
+    let classes = VerificationType::Object("[LMyClass;".to_string());
+    assert_eq!(Ok(string.clone()), VerificationType::Null.merge(&string, &hierarchy));
+    assert_eq!(Ok(VerificationType::Object("[Ljava/lang/Object;".to_string())), strings.merge(&classes, &hierarchy));
+    assert_eq!(Ok(VerificationType::Object("java/lang/Object".to_string())), strings.merge(&VerificationType::Object("[I".to_string()), &hierarchy));
+    assert_eq!(Ok(VerificationType::Top), string.merge(&VerificationType::Integer, &hierarchy));
+}
+
+/// The JDK classes the fixtures use, with their super classes.
+#[cfg(test)]
+pub(crate) fn jdk_classes() -> KnownClasses {
+    let mut hierarchy = KnownClasses::new();
+    for (class_name, super_class_name) in [
+        ("java/lang/String", "java/lang/Object"),
+        ("java/lang/Throwable", "java/lang/Object"),
+        ("java/lang/Exception", "java/lang/Throwable"),
+        ("java/lang/RuntimeException", "java/lang/Exception"),
+        ("java/lang/ArithmeticException", "java/lang/RuntimeException"),
+        ("java/lang/IllegalArgumentException", "java/lang/RuntimeException"),
+        ("java/lang/NullPointerException", "java/lang/RuntimeException"),
+        ("java/lang/NumberFormatException", "java/lang/IllegalArgumentException"),
+        ("java/util/ArrayList", "java/util/AbstractList"),
+        ("java/util/LinkedList", "java/util/AbstractSequentialList"),
+        ("java/util/AbstractSequentialList", "java/util/AbstractList"),
+        ("java/util/AbstractList", "java/util/AbstractCollection"),
+        ("java/util/AbstractCollection", "java/lang/Object"),
+    ] {
+        hierarchy.super_classes.insert(class_name.to_string(), super_class_name.to_string());
+    }
+    hierarchy
+}
+
+/// Runs `main` of the class with `java`, whose verifier checks every frame, and returns its output.
+/// `None` when there is no `java` to run.
+#[cfg(test)]
+pub(crate) fn run_verified(java_class: &JavaClass) -> Option<Result<String, String>> {
+    let directory = std::env::temp_dir().join(format!("rjvm-verify-{}-{}", java_class.this_class_name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(directory.join(format!("{}.class", java_class.this_class_name)), java_class.to_bytes()).unwrap();
+    let output = std::process::Command::new("java").arg("-Xverify:all").arg("-cp").arg(&directory).arg(&java_class.this_class_name).output();
+    std::fs::remove_dir_all(&directory).unwrap();
+    let output = output.ok()?;
+    Some(match output.status.success() {
+        true => Ok(String::from_utf8_lossy(&output.stdout).into_owned()),
+        false => Err(String::from_utf8_lossy(&output.stderr).into_owned()),
+    })
+}
+
+#[test]
+fn test_frames_inside_try_ranges_keep_what_the_handler_reads() {
+    let original = JavaClass::load_from_file("Handlers.class").unwrap();
+    let mut java_class = original.clone();
+    recompute_frames(&mut java_class, &KnownClasses::new()).unwrap();
+    assert_eq!(original, java_class);
+    match run_verified(&java_class) {
+        Some(result) => assert_eq!(Ok("true\n".to_string()), result),
+        None => eprintln!("java is not installed, skipping verification"),
+    }
+}
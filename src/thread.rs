@@ -1,25 +1,140 @@
-use crate::frames::Frame;
-use crate::MethodInfo;
+use std::rc::Rc;
+use crate::call_site::CallSite;
+use crate::class::{Class, Method, MethodRef};
+use crate::execution::{self, ExecutionError, InvokeKind, Step};
+use crate::frames::{Frame, Value};
+use crate::heap::Heap;
+use crate::jvm::Jvm;
+use crate::method_area::MethodArea;
+use crate::natives::{self, Native};
 use crate::stack::Stack;
 
-pub type Method = MethodInfo;
+/// How deep invocations can nest before a `StackOverflowError`.
+const MAX_STACK_DEPTH: usize = 1024;
 
+/// A thread of execution with its own stack.
+#[derive(Debug)]
 pub struct Thread {
-    current_method: Method,
-    pc: usize,
-    stack: Stack
+    stack: Stack,
+}
+
+impl Default for Thread {
+    fn default() -> Self {
+        Thread { stack: Stack::new(MAX_STACK_DEPTH) }
+    }
 }
 
 impl Thread {
-    fn current_frame(self) -> Frame {
-        self.stack.current_frame()
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `method` of `class` with the given arguments until it returns, and hands back what it
+    /// returned. The methods it invokes are looked up in the JVM's method area.
+    pub fn run_method(&mut self, jvm: &mut Jvm, class: Rc<Class>, method: Rc<Method>, arguments: &[Value]) -> Result<Option<Value>, ExecutionError> {
+        self.stack.push(Frame::new(class, method, arguments)?)?;
+        let depth = self.stack.frames.len();
+        loop {
+            match self.step(jvm, depth) {
+                Ok(None) => {}
+                Ok(Some(value)) => return Ok(value),
+                Err(mut error) => {
+                    // The frames the error went through are done with.
+                    for frame in self.stack.frames.drain(depth - 1..).rev() {
+                        let code = frame.method.code.as_ref();
+                        let instructions = code.map(|code| &code.instructions[..]).unwrap_or_default();
+                        let pc = instructions.get(frame.position).or(instructions.last()).map_or(0, |(pc, _)| *pc);
+                        error = error.at(&frame.method, pc);
+                    }
+                    return Err(error);
+                }
+            }
+        }
+    }
+
+    /// Executes the current frame's next instruction, and hands back what the method run at
+    /// `depth` returned once it returns. A frame stays at its invoke until the invoked method
+    /// returns.
+    fn step(&mut self, jvm: &mut Jvm, depth: usize) -> Result<Option<Option<Value>>, ExecutionError> {
+        let Some(frame) = self.stack.current_frame() else {
+            return Err(ExecutionError::new("The stack is empty"));
+        };
+        let method = frame.method.clone();
+        // Frames are only created for methods with code.
+        let Some(code) = &method.code else { unreachable!() };
+        let Some((pc, instruction)) = code.instructions.get(frame.position) else {
+            return Err(ExecutionError::new("Execution ran past the end of the code"));
+        };
+        match execution::execute(jvm, frame, *pc, instruction)? {
+            Step::Next => frame.position += 1,
+            Step::Jump(target) => {
+                frame.position = code.position(target)
+                    .ok_or_else(|| ExecutionError::new(format!("Jumping to pc {target}, which doesn't start an instruction")))?;
+            }
+            Step::Invoke { kind, method_ref, arguments } => {
+                let (class, invoked) = invoked_method(&jvm.method_area, &jvm.heap, &frame.class, kind, &method_ref, &arguments)?;
+                self.invoke(jvm, class, invoked, arguments)?;
+            }
+            Step::InvokeDynamic { index, arguments } => {
+                let call_site = CallSite::linked(jvm, &frame.class.clone(), &method, *pc, index)?;
+                let value = call_site.invoke(jvm, arguments)?;
+                frame.operand_stack.push(value)?;
+                frame.position += 1;
+            }
+            Step::Return(value) => {
+                self.stack.pop();
+                if self.stack.frames.len() < depth {
+                    return Ok(Some(value));
+                }
+                self.return_to_caller(value)?;
+            }
+        }
+        Ok(None)
     }
 
-    fn run_method(self, method: Method) {
-        if self.stack.has_space_for(method) {
+    /// Pushes a frame for a method with code. The JVM runs methods without code itself, in
+    /// place of the current frame's invoke.
+    fn invoke(&mut self, jvm: &mut Jvm, class: Rc<Class>, method: Rc<Method>, arguments: Vec<Value>) -> Result<(), ExecutionError> {
+        if method.code.is_some() {
+            return self.stack.push(Frame::new(class, method, &arguments)?);
+        }
+        match natives::run(jvm, &method, &arguments)? {
+            Native::Return(value) => self.return_to_caller(value),
+            Native::Invoke(class, method, arguments) => self.invoke(jvm, class, method, arguments),
+        }
+    }
 
-        } else {
-            // throw Stack Overflow Error
+    /// Hands what an invoked method returned to the current frame, which moves past its invoke.
+    fn return_to_caller(&mut self, value: Option<Value>) -> Result<(), ExecutionError> {
+        let Some(caller) = self.stack.current_frame() else { unreachable!() };
+        if let Some(value) = value {
+            caller.operand_stack.push(value)?;
         }
+        caller.position += 1;
+        Ok(())
     }
 }
+
+/// The class and method an invoke instruction in `current` runs: the method the reference
+/// resolves to, or for an instance method, the one selected for the receiver.
+pub(crate) fn invoked_method(method_area: &MethodArea, heap: &Heap, current: &Rc<Class>, kind: InvokeKind, method_ref: &MethodRef, arguments: &[Value]) -> Result<(Rc<Class>, Rc<Method>), ExecutionError> {
+    let resolved = method_area.resolve_method(method_ref)?;
+    if resolved.is_static() != (kind == InvokeKind::Static) {
+        let kind = if resolved.is_static() { "static" } else { "not static" };
+        return Err(ExecutionError::new(format!("IncompatibleClassChangeError: {resolved} is {kind}")));
+    }
+    let receiver = match arguments.first() {
+        _ if kind == InvokeKind::Static => None,
+        Some(Value::Reference(Some(reference))) => {
+            Some(heap.class(*reference)?.clone())
+        }
+        Some(Value::Reference(None)) => return Err(ExecutionError::new(format!("NullPointerException: {resolved} invoked on null"))),
+        receiver => return Err(ExecutionError::new(format!("{resolved} invoked on {receiver:?} rather than an object"))),
+    };
+    let method = match (kind, receiver) {
+        (InvokeKind::Special, _) => method_area.special_method(current, method_ref, &resolved)?,
+        (InvokeKind::Virtual | InvokeKind::Interface, Some(receiver)) => method_area.dispatch(&receiver, &resolved)?,
+        _ => resolved,
+    };
+    Ok((method_area.loaded_class(&method.class_name)?, method))
+}
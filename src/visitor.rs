@@ -0,0 +1,427 @@
+use crate::{Attribute, AttributeBootstrapMethods, AttributeCode, AttributeConstantValue, AttributeInfo, AttributeLineNumberTable, AttributeUnknown, BootstrapMethod, ClassFlags, ConstantPoolInfo, ExceptionTableEntry, FieldInfo, JavaClass, LineNumberTableEntry, MethodInfo};
+
+/// Receives the parts of a class file in the order they appear in the file.
+///
+/// Every method has an empty default, so a visitor only implements the events it cares about.
+/// Returning `None` from [`visit_field`](ClassVisitor::visit_field) or
+/// [`visit_method`](ClassVisitor::visit_method) skips the member's contents.
+pub trait ClassVisitor {
+    fn visit_header(&mut self, _prelude: u32, _minor: u16, _major: u16) {}
+
+    fn visit_constant_pool(&mut self, _constant_pool_infos: &[ConstantPoolInfo]) {}
+
+    fn visit_class(&mut self, _access_flags: u16, _this_class: usize, _super_class: usize, _interfaces_indexes: &[usize]) {}
+
+    fn visit_field(&mut self, _access_flags: u16, _name_index: usize, _name: &str, _descriptor_index: usize, _descriptor: &str) -> Option<Box<dyn FieldVisitor + '_>> {
+        None
+    }
+
+    fn visit_method(&mut self, _access_flags: u16, _name_index: usize, _name: &str, _descriptor_index: usize, _descriptor: &str) -> Option<Box<dyn MethodVisitor + '_>> {
+        None
+    }
+
+    fn visit_bootstrap_methods(&mut self, _attribute_name_index: usize, _bootstrap_methods: &[BootstrapMethod]) {}
+
+    /// A class attribute, as the raw bytes following `attribute_length`.
+    fn visit_attribute(&mut self, _attribute_name_index: usize, _attribute_name: &str, _info: &[u8]) {}
+
+    fn visit_end(&mut self) {}
+}
+
+pub trait FieldVisitor {
+    fn visit_constant_value(&mut self, _attribute_name_index: usize, _constant_value_index: usize) {}
+
+    fn visit_attribute(&mut self, _attribute_name_index: usize, _attribute_name: &str, _info: &[u8]) {}
+
+    fn visit_end(&mut self) {}
+}
+
+pub trait MethodVisitor {
+    fn visit_code(&mut self, _attribute_name_index: usize, _max_stack: usize, _max_locals: usize) -> Option<Box<dyn CodeVisitor + '_>> {
+        None
+    }
+
+    fn visit_attribute(&mut self, _attribute_name_index: usize, _attribute_name: &str, _info: &[u8]) {}
+
+    fn visit_end(&mut self) {}
+}
+
+/// Receives the contents of a `Code` attribute: the bytecode, then the exception table, then the nested attributes.
+pub trait CodeVisitor {
+    fn visit_bytecode(&mut self, _code: &[u8]) {}
+
+    fn visit_exception_table_entry(&mut self, _entry: &ExceptionTableEntry) {}
+
+    fn visit_line_number_table(&mut self, _attribute_name_index: usize, _line_number_entries: &[LineNumberTableEntry]) {}
+
+    fn visit_attribute(&mut self, _attribute_name_index: usize, _attribute_name: &str, _info: &[u8]) {}
+
+    fn visit_end(&mut self) {}
+}
+
+/// Builds a [`JavaClass`] out of visitor events.
+pub struct JavaClassBuilder {
+    prelude: u32,
+    major: u16,
+    minor: u16,
+    constant_pool_infos: Vec<ConstantPoolInfo>,
+    flags: u16,
+    this_class: usize,
+    super_class: usize,
+    interfaces_indexes: Vec<usize>,
+    fields: Vec<FieldInfo>,
+    methods: Vec<MethodInfo>,
+    attributes: Vec<AttributeInfo>,
+}
+
+impl JavaClassBuilder {
+    pub fn new() -> Self {
+        JavaClassBuilder {
+            prelude: 0,
+            major: 0,
+            minor: 0,
+            constant_pool_infos: Vec::new(),
+            flags: 0,
+            this_class: 0,
+            super_class: 0,
+            interfaces_indexes: Vec::new(),
+            fields: Vec::new(),
+            methods: Vec::new(),
+            attributes: Vec::new(),
+        }
+    }
+
+    pub fn build(self) -> JavaClass {
+        let class_name = |index: usize| match index.checked_sub(1).and_then(|index| self.constant_pool_infos.get(index)) {
+            Some(ConstantPoolInfo::Class(class_info)) => Some(class_info.name.clone()),
+            _ => None,
+        };
+        let this_class_name = class_name(self.this_class).unwrap_or_default();
+        let super_class_name = class_name(self.super_class);
+        let interfaces_names = self.interfaces_indexes.iter().map(|index| class_name(*index).unwrap_or_default()).collect();
+        JavaClass {
+            prelude: self.prelude,
+            major: self.major,
+            minor: self.minor,
+            constant_pool_count: self.constant_pool_infos.len() + 1,
+            constant_pool_infos: self.constant_pool_infos,
+            flags: ClassFlags::new(self.flags),
+            this_class: self.this_class,
+            this_class_name,
+            super_class: self.super_class,
+            super_class_name,
+            interfaces_count: self.interfaces_indexes.len(),
+            interfaces_indexes: self.interfaces_indexes,
+            interfaces_names,
+            fields: self.fields,
+            methods: self.methods,
+            attributes: self.attributes,
+        }
+    }
+}
+
+impl Default for JavaClassBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClassVisitor for JavaClassBuilder {
+    fn visit_header(&mut self, prelude: u32, minor: u16, major: u16) {
+        self.prelude = prelude;
+        self.minor = minor;
+        self.major = major;
+    }
+
+    fn visit_constant_pool(&mut self, constant_pool_infos: &[ConstantPoolInfo]) {
+        self.constant_pool_infos = constant_pool_infos.to_vec();
+    }
+
+    fn visit_class(&mut self, access_flags: u16, this_class: usize, super_class: usize, interfaces_indexes: &[usize]) {
+        self.flags = access_flags;
+        self.this_class = this_class;
+        self.super_class = super_class;
+        self.interfaces_indexes = interfaces_indexes.to_vec();
+    }
+
+    fn visit_field(&mut self, access_flags: u16, name_index: usize, name: &str, descriptor_index: usize, descriptor: &str) -> Option<Box<dyn FieldVisitor + '_>> {
+        self.fields.push(FieldInfo {
+            access_flags,
+            name_index,
+            name: name.to_string(),
+            descriptor_index,
+            descriptor: descriptor.to_string(),
+            attributes_info: Vec::new(),
+        });
+        let field = self.fields.last_mut().unwrap();
+        Some(Box::new(AttributesBuilder(&mut field.attributes_info)))
+    }
+
+    fn visit_method(&mut self, access_flags: u16, name_index: usize, name: &str, descriptor_index: usize, descriptor: &str) -> Option<Box<dyn MethodVisitor + '_>> {
+        self.methods.push(MethodInfo {
+            access_flags,
+            name_index,
+            name: name.to_string(),
+            descriptor_index,
+            descriptor: descriptor.to_string(),
+            attributes_info: Vec::new(),
+        });
+        let method = self.methods.last_mut().unwrap();
+        Some(Box::new(AttributesBuilder(&mut method.attributes_info)))
+    }
+
+    fn visit_bootstrap_methods(&mut self, attribute_name_index: usize, bootstrap_methods: &[BootstrapMethod]) {
+        self.attributes.push(AttributeInfo {
+            attribute_name_index,
+            attribute: Attribute::BootstrapMethods(AttributeBootstrapMethods { bootstrap_methods: bootstrap_methods.to_vec() }),
+        });
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        AttributesBuilder(&mut self.attributes).push_unknown(attribute_name_index, attribute_name, info);
+    }
+}
+
+struct AttributesBuilder<'a>(&'a mut Vec<AttributeInfo>);
+
+impl AttributesBuilder<'_> {
+    fn push_unknown(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        self.0.push(AttributeInfo {
+            attribute_name_index,
+            attribute: Attribute::Unknown(AttributeUnknown {
+                attribute_name: attribute_name.to_string(),
+                info: info.to_vec(),
+            }),
+        });
+    }
+}
+
+impl FieldVisitor for AttributesBuilder<'_> {
+    fn visit_constant_value(&mut self, attribute_name_index: usize, constant_value_index: usize) {
+        self.0.push(AttributeInfo {
+            attribute_name_index,
+            attribute: Attribute::ConstantValue(AttributeConstantValue { constant_value_index }),
+        });
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        self.push_unknown(attribute_name_index, attribute_name, info);
+    }
+}
+
+impl MethodVisitor for AttributesBuilder<'_> {
+    fn visit_code(&mut self, attribute_name_index: usize, max_stack: usize, max_locals: usize) -> Option<Box<dyn CodeVisitor + '_>> {
+        self.0.push(AttributeInfo {
+            attribute_name_index,
+            attribute: Attribute::Code(AttributeCode {
+                attribute_name_index,
+                max_stack,
+                max_locals,
+                code_length: 0,
+                code: Vec::new(),
+                exceptions: Vec::new(),
+                attributes: Vec::new(),
+            }),
+        });
+        match &mut self.0.last_mut().unwrap().attribute {
+            Attribute::Code(code) => Some(Box::new(CodeBuilder(code))),
+            _ => unreachable!(),
+        }
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        self.push_unknown(attribute_name_index, attribute_name, info);
+    }
+}
+
+struct CodeBuilder<'a>(&'a mut AttributeCode);
+
+impl CodeVisitor for CodeBuilder<'_> {
+    fn visit_bytecode(&mut self, code: &[u8]) {
+        self.0.code_length = code.len();
+        self.0.code = code.to_vec();
+    }
+
+    fn visit_exception_table_entry(&mut self, entry: &ExceptionTableEntry) {
+        self.0.exceptions.push(entry.clone());
+    }
+
+    fn visit_line_number_table(&mut self, attribute_name_index: usize, line_number_entries: &[LineNumberTableEntry]) {
+        self.0.attributes.push(AttributeInfo {
+            attribute_name_index,
+            attribute: Attribute::LineNumberTable(AttributeLineNumberTable {
+                attribute_name_index,
+                attribute_name: "LineNumberTable".to_string(),
+                attribute_length: 2 + 4 * line_number_entries.len(),
+                line_number_entries: line_number_entries.to_vec(),
+            }),
+        });
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        AttributesBuilder(&mut self.0.attributes).push_unknown(attribute_name_index, attribute_name, info);
+    }
+}
+
+impl JavaClass {
+    /// Replays this class to a visitor, producing the same events as reading its class file would.
+    pub fn accept(&self, visitor: &mut dyn ClassVisitor) {
+        visitor.visit_header(self.prelude, self.minor, self.major);
+        visitor.visit_constant_pool(&self.constant_pool_infos);
+        visitor.visit_class(self.flags.0, self.this_class, self.super_class, &self.interfaces_indexes);
+
+        for field in &self.fields {
+            if let Some(mut field_visitor) = visitor.visit_field(field.access_flags, field.name_index, &field.name, field.descriptor_index, &field.descriptor) {
+                for attribute_info in &field.attributes_info {
+                    match &attribute_info.attribute {
+                        Attribute::ConstantValue(constant_value) => {
+                            field_visitor.visit_constant_value(attribute_info.attribute_name_index, constant_value.constant_value_index)
+                        }
+                        Attribute::Unknown(unknown) => {
+                            field_visitor.visit_attribute(attribute_info.attribute_name_index, &unknown.attribute_name, &unknown.info)
+                        }
+                        _ => {}
+                    }
+                }
+                field_visitor.visit_end();
+            }
+        }
+
+        for method in &self.methods {
+            if let Some(mut method_visitor) = visitor.visit_method(method.access_flags, method.name_index, &method.name, method.descriptor_index, &method.descriptor) {
+                for attribute_info in &method.attributes_info {
+                    match &attribute_info.attribute {
+                        Attribute::Code(code) => {
+                            if let Some(mut code_visitor) = method_visitor.visit_code(attribute_info.attribute_name_index, code.max_stack, code.max_locals) {
+                                code.accept(code_visitor.as_mut());
+                                code_visitor.visit_end();
+                            }
+                        }
+                        Attribute::Unknown(unknown) => {
+                            method_visitor.visit_attribute(attribute_info.attribute_name_index, &unknown.attribute_name, &unknown.info)
+                        }
+                        _ => {}
+                    }
+                }
+                method_visitor.visit_end();
+            }
+        }
+
+        for attribute_info in &self.attributes {
+            match &attribute_info.attribute {
+                Attribute::BootstrapMethods(bootstrap_methods) => {
+                    visitor.visit_bootstrap_methods(attribute_info.attribute_name_index, &bootstrap_methods.bootstrap_methods)
+                }
+                Attribute::Unknown(unknown) => visitor.visit_attribute(attribute_info.attribute_name_index, &unknown.attribute_name, &unknown.info),
+                _ => {}
+            }
+        }
+        visitor.visit_end();
+    }
+}
+
+impl AttributeCode {
+    pub(crate) fn accept(&self, code_visitor: &mut dyn CodeVisitor) {
+        code_visitor.visit_bytecode(&self.code);
+        for entry in &self.exceptions {
+            code_visitor.visit_exception_table_entry(entry);
+        }
+        for attribute_info in &self.attributes {
+            match &attribute_info.attribute {
+                Attribute::LineNumberTable(line_number_table) => {
+                    code_visitor.visit_line_number_table(attribute_info.attribute_name_index, &line_number_table.line_number_entries)
+                }
+                Attribute::Unknown(unknown) => {
+                    code_visitor.visit_attribute(attribute_info.attribute_name_index, &unknown.attribute_name, &unknown.info)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+struct MethodNames(Vec<String>);
+
+#[cfg(test)]
+impl ClassVisitor for MethodNames {
+    fn visit_method(&mut self, _access_flags: u16, _name_index: usize, name: &str, _descriptor_index: usize, descriptor: &str) -> Option<Box<dyn MethodVisitor + '_>> {
+        self.0.push(format!("{name}{descriptor}"));
+        None
+    }
+}
+
+#[cfg(test)]
+struct RemoveMethod<'a> {
+    name: &'a str,
+    next: &'a mut dyn ClassVisitor,
+}
+
+#[cfg(test)]
+impl ClassVisitor for RemoveMethod<'_> {
+    fn visit_header(&mut self, prelude: u32, minor: u16, major: u16) {
+        self.next.visit_header(prelude, minor, major)
+    }
+
+    fn visit_constant_pool(&mut self, constant_pool_infos: &[ConstantPoolInfo]) {
+        self.next.visit_constant_pool(constant_pool_infos)
+    }
+
+    fn visit_class(&mut self, access_flags: u16, this_class: usize, super_class: usize, interfaces_indexes: &[usize]) {
+        self.next.visit_class(access_flags, this_class, super_class, interfaces_indexes)
+    }
+
+    fn visit_field(&mut self, access_flags: u16, name_index: usize, name: &str, descriptor_index: usize, descriptor: &str) -> Option<Box<dyn FieldVisitor + '_>> {
+        self.next.visit_field(access_flags, name_index, name, descriptor_index, descriptor)
+    }
+
+    fn visit_method(&mut self, access_flags: u16, name_index: usize, name: &str, descriptor_index: usize, descriptor: &str) -> Option<Box<dyn MethodVisitor + '_>> {
+        if name == self.name {
+            None
+        } else {
+            self.next.visit_method(access_flags, name_index, name, descriptor_index, descriptor)
+        }
+    }
+
+    fn visit_bootstrap_methods(&mut self, attribute_name_index: usize, bootstrap_methods: &[BootstrapMethod]) {
+        self.next.visit_bootstrap_methods(attribute_name_index, bootstrap_methods)
+    }
+
+    fn visit_attribute(&mut self, attribute_name_index: usize, attribute_name: &str, info: &[u8]) {
+        self.next.visit_attribute(attribute_name_index, attribute_name, info)
+    }
+
+    fn visit_end(&mut self) {
+        self.next.visit_end()
+    }
+}
+
+#[test]
+fn test_visitor_receives_methods_in_order() {
+    let class_reader = crate::class_reader::ClassReader::from_file("MyClass.class").unwrap();
+    let mut method_names = MethodNames(Vec::new());
+    class_reader.accept(&mut method_names).unwrap();
+    assert_eq!(vec!["<init>()V", "intMethod()I", "staticIntMethod()I", "methodFromA(I)I", "methodFromB()Ljava/lang/String;", "main([Ljava/lang/String;)V"], method_names.0);
+}
+
+#[test]
+fn test_reader_to_writer_round_trips_bytes() {
+    let bytes = std::fs::read("MyClass.class").unwrap();
+    let mut class_writer = crate::class_writer::ClassWriter::new();
+    crate::class_reader::ClassReader::new(bytes.clone()).accept(&mut class_writer).unwrap();
+    assert_eq!(bytes, class_writer.to_bytes());
+
+    let java_class = JavaClass::load_from_file("MyClass.class").unwrap();
+    assert_eq!(bytes, java_class.to_bytes());
+}
+
+#[test]
+fn test_chained_visitor_transforms_class() {
+    let class_reader = crate::class_reader::ClassReader::from_file("MyClass.class").unwrap();
+    let mut class_writer = crate::class_writer::ClassWriter::new();
+    class_reader.accept(&mut RemoveMethod { name: "intMethod", next: &mut class_writer }).unwrap();
+
+    let java_class = JavaClass::from_bytes(class_writer.to_bytes()).unwrap();
+    assert_eq!(5, java_class.methods.len());
+    assert!(java_class.methods.iter().all(|method| method.name != "intMethod"));
+}